@@ -47,6 +47,20 @@
             inherit cargoArtifacts;
           }
         );
+        expClippyNvml = craneLib.cargoClippy (
+          commonArgs
+          // {
+            inherit cargoArtifacts;
+            cargoExtraArgs = "--features nvml";
+          }
+        );
+        expClippyCli = craneLib.cargoClippy (
+          commonArgs
+          // {
+            inherit cargoArtifacts;
+            cargoExtraArgs = "--features cli";
+          }
+        );
       in rec
       {
         packages = {
@@ -55,7 +69,7 @@
         };
 
         checks = {
-          inherit expClippy;
+          inherit expClippy expClippyNvml expClippyCli;
         };
 
         formatter = pkgs.alejandra;
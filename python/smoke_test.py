@@ -0,0 +1,26 @@
+"""Smoke test of the bindings, run by CI after `maturin develop`."""
+
+import asyncio
+import tempfile
+
+import exp
+
+
+class Count(exp.Experiment):
+    def configurations(self):
+        return [{"n": n} for n in [1, 2]]
+
+    async def run(self, configuration, configuration_dir, measurements):
+        measurements.record_scalar("n", configuration["n"])
+        measurements.record_series("n", None, configuration["n"])
+
+
+async def main(results_dir):
+    await exp.run(Count(), results_dir)
+    configurations = exp.configurations(results_dir)
+    assert len(configurations) == 2, configurations
+    assert sorted(configurations["n"]) == [1, 2], configurations
+
+
+with tempfile.TemporaryDirectory() as results_dir:
+    asyncio.run(main(results_dir))
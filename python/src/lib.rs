@@ -0,0 +1,545 @@
+//! Python bindings for running and analysing experiments.
+//!
+//! Experiments subclass `exp.Experiment`, overriding its methods with plain or `async` ones, and
+//! are driven by the same run loop as Rust experiments:
+//!
+//! ```python
+//! import asyncio
+//! import exp
+//!
+//! class Sleep(exp.Experiment):
+//!     def configurations(self):
+//!         return [{"seconds": s} for s in [1, 2]]
+//!
+//!     async def run(self, configuration, configuration_dir, measurements):
+//!         await asyncio.sleep(configuration["seconds"])
+//!         measurements.record_scalar("seconds", configuration["seconds"])
+//!
+//! async def main():
+//!     await exp.run(Sleep(), "results/sleep")
+//!     print(await exp.analyse(Sleep(), "results/sleep"))
+//!
+//! asyncio.run(main())
+//! ```
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use exp::{DynConfiguration, DynExperiment, ExpResult, ExperimentConfiguration};
+use polars::prelude::{AnyValue, DataFrame, DataType, TimeUnit};
+use pyo3::exceptions::{PyNotImplementedError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use serde_json::Value;
+
+/// Base class for experiments written in Python.
+///
+/// Subclasses must override `configurations` and `run`. `pre_run`, `post_run` and `analyse` do
+/// nothing by default. Any of them can be `async`, in which case they are awaited.
+///
+/// Configurations are anything that can be serialized to JSON, such as dicts, and are passed
+/// back deserialized. Subclasses can set `skip_hash_fields` and `schema_version` as
+/// `exp::ExperimentConfiguration::SKIP_HASH_FIELDS` and `SCHEMA_VERSION`.
+#[pyclass(subclass, name = "Experiment")]
+struct Experiment;
+
+#[pymethods]
+impl Experiment {
+    #[new]
+    fn new() -> Self {
+        Experiment
+    }
+
+    /// Top-level configuration fields that don't affect the results, left out of the hash.
+    #[classattr]
+    fn skip_hash_fields() -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The version of the configurations' schema.
+    #[classattr]
+    fn schema_version() -> u32 {
+        0
+    }
+
+    /// The configurations to run.
+    fn configurations(&self) -> PyResult<Vec<PyObject>> {
+        Err(PyNotImplementedError::new_err(
+            "Experiment.configurations must be overridden",
+        ))
+    }
+
+    fn pre_run(&self, _configuration: PyObject) {}
+
+    /// Run a configuration, writing any results to `configuration_dir`.
+    fn run(
+        &self,
+        _configuration: PyObject,
+        _configuration_dir: PathBuf,
+        _measurements: Measurements,
+    ) -> PyResult<()> {
+        Err(PyNotImplementedError::new_err(
+            "Experiment.run must be overridden",
+        ))
+    }
+
+    fn post_run(&self, _configuration: PyObject) {}
+
+    /// Analyse the results of the configurations, given as `(configuration, directory)` pairs,
+    /// returning a summary of them or `None`.
+    fn analyse(
+        &self,
+        _dirs: AnalysisDirs,
+        _environment: PyObject,
+        _configurations: Vec<(PyObject, PathBuf)>,
+    ) -> Option<PyObject> {
+        None
+    }
+}
+
+/// Records the custom measurements of a configuration run, see `exp::Measurements`.
+#[pyclass(name = "Measurements")]
+#[derive(Clone)]
+struct Measurements(exp::Measurements);
+
+#[pymethods]
+impl Measurements {
+    /// Record a single value for the run, replacing any recorded with the same name.
+    fn record_scalar(&self, name: &str, value: f64) {
+        self.0.record_scalar(name, value)
+    }
+
+    /// Record a point of a series at a timezone aware `time`, which has no default: pass `None`
+    /// explicitly to record it at the current time.
+    #[pyo3(signature = (name, time, value))]
+    fn record_series(&self, name: &str, time: Option<DateTime<Utc>>, value: f64) {
+        self.0
+            .record_series(name, time.unwrap_or_else(Utc::now), value)
+    }
+}
+
+/// Where an analysis reads results from and writes its outputs to, see `exp::AnalysisDirs`.
+#[pyclass(name = "AnalysisDirs")]
+#[derive(Clone)]
+struct AnalysisDirs(exp::AnalysisDirs);
+
+#[pymethods]
+impl AnalysisDirs {
+    /// The experiment's results directory.
+    fn experiment_dir(&self) -> PathBuf {
+        self.0.experiment_dir().to_owned()
+    }
+
+    /// Directory for outputs of the analysis covering the whole experiment.
+    fn output_dir(&self) -> PathBuf {
+        self.0.output_dir().to_owned()
+    }
+
+    /// Create and return the directory for outputs of the analysis of a single configuration,
+    /// given its results directory.
+    fn configuration_dir(&self, configuration_dir: PathBuf) -> PyResult<PathBuf> {
+        Ok(self.0.configuration_dir(&configuration_dir)?)
+    }
+}
+
+type PyFuture = Pin<Box<dyn Future<Output = PyResult<PyObject>> + Send>>;
+
+/// Drives a Python `Experiment` from the Rust run loop, as a `DynExperiment`.
+struct PyExperiment {
+    object: PyObject,
+    /// Taken from the Python experiment up front, so errors are raised before running.
+    configurations: Vec<Value>,
+    skip_hash_fields: Vec<String>,
+    schema_version: u32,
+}
+
+impl PyExperiment {
+    fn new(experiment: Bound<'_, Experiment>, configure: bool) -> PyResult<Self> {
+        let configurations = if configure {
+            experiment
+                .call_method0("configurations")?
+                .try_iter()?
+                .map(|c| from_py(&c?))
+                .collect::<PyResult<_>>()?
+        } else {
+            Vec::new()
+        };
+        let skip_hash_fields = experiment.getattr("skip_hash_fields")?.extract()?;
+        let schema_version = experiment.getattr("schema_version")?.extract()?;
+        Ok(PyExperiment {
+            object: experiment.into_any().unbind(),
+            configurations,
+            skip_hash_fields,
+            schema_version,
+        })
+    }
+
+    /// Call a method of the Python experiment, awaiting its result if it is awaitable.
+    async fn call<A>(&self, name: &str, args: A) -> PyResult<PyObject>
+    where
+        A: for<'py> FnOnce(Python<'py>) -> PyResult<Bound<'py, PyTuple>>,
+    {
+        let future = Python::with_gil(|py| -> PyResult<PyFuture> {
+            let result = self.object.bind(py).call_method1(name, args(py)?)?;
+            if result.hasattr("__await__")? {
+                Ok(Box::pin(pyo3_async_runtimes::tokio::into_future(result)?))
+            } else {
+                let result = result.unbind();
+                Ok(Box::pin(async move { Ok(result) }))
+            }
+        })?;
+        future.await
+    }
+}
+
+#[async_trait]
+impl DynExperiment for PyExperiment {
+    fn skip_hash_fields(&self) -> Vec<String> {
+        self.skip_hash_fields.clone()
+    }
+
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn configurations(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.configurations)
+    }
+
+    async fn pre_run(&mut self, configuration: &Value) -> ExpResult<()> {
+        self.call("pre_run", |py| {
+            PyTuple::new(py, [to_py(py, configuration)?])
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Value,
+        configuration_dir: &Path,
+        measurements: &exp::Measurements,
+    ) -> ExpResult<()> {
+        self.call("run", |py| {
+            (
+                to_py(py, configuration)?,
+                configuration_dir,
+                Measurements(measurements.clone()),
+            )
+                .into_pyobject(py)
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, configuration: &Value) -> ExpResult<()> {
+        self.call("post_run", |py| {
+            PyTuple::new(py, [to_py(py, configuration)?])
+        })
+        .await?;
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        dirs: &exp::AnalysisDirs,
+        environment: exp::Environment,
+        configurations: Vec<(Value, PathBuf)>,
+    ) -> ExpResult<Value> {
+        let environment = serde_json::to_value(environment)?;
+        Python::with_gil(|py| {
+            let configurations = configurations
+                .into_iter()
+                .map(|(c, dir)| Ok((to_py(py, &c)?, dir)))
+                .collect::<PyResult<Vec<_>>>()?;
+            let summary = self.object.bind(py).call_method1(
+                "analyse",
+                (
+                    AnalysisDirs(dirs.clone()),
+                    to_py(py, &environment)?,
+                    configurations,
+                ),
+            )?;
+            if summary.is_none() {
+                Ok(Value::Null)
+            } else {
+                Ok(from_py(&summary)?)
+            }
+        })
+    }
+}
+
+/// Run the configurations of an experiment, saving their results in `results_dir`.
+///
+/// Each configuration is run `repeats` times, failing a run if it takes longer than `timeout`
+/// seconds. Returns an awaitable.
+#[pyfunction]
+#[pyo3(signature = (
+    experiment,
+    results_dir,
+    repeats = 1,
+    timeout = None,
+    store_dir = None,
+    force_rerun = false,
+    strict_environment = false,
+))]
+#[allow(clippy::too_many_arguments)]
+fn run(
+    py: Python<'_>,
+    experiment: Bound<'_, Experiment>,
+    results_dir: PathBuf,
+    repeats: usize,
+    timeout: Option<f64>,
+    store_dir: Option<PathBuf>,
+    force_rerun: bool,
+    strict_environment: bool,
+) -> PyResult<PyObject> {
+    let mut experiment: Box<dyn DynExperiment> = Box::new(PyExperiment::new(experiment, true)?);
+    let mut builder = exp::RunConfig::builder()
+        .results_dir(results_dir)
+        .repeats(repeats)
+        .force_rerun(force_rerun)
+        .strict_environment(strict_environment);
+    if let Some(timeout) = timeout {
+        let timeout = Duration::try_from_secs_f64(timeout)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        builder = builder.timeout(timeout);
+    }
+    if let Some(store_dir) = store_dir {
+        builder = builder.store_dir(store_dir);
+    }
+    let config = builder
+        .build()
+        .map_err(|error| PyValueError::new_err(error.to_string()))?;
+    let future = pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        exp::run(&mut experiment, &config)
+            .await
+            .map_err(|error| PyRuntimeError::new_err(error.to_string()))
+    })?;
+    Ok(future.unbind())
+}
+
+/// Analyse the results of an experiment in `results_dir`, returning its summary.
+///
+/// Returns an awaitable.
+#[pyfunction]
+#[pyo3(signature = (experiment, results_dir, output_dir = None, incremental = false))]
+fn analyse(
+    py: Python<'_>,
+    experiment: Bound<'_, Experiment>,
+    results_dir: PathBuf,
+    output_dir: Option<PathBuf>,
+    incremental: bool,
+) -> PyResult<PyObject> {
+    let mut experiment: Box<dyn DynExperiment> = Box::new(PyExperiment::new(experiment, false)?);
+    let config = exp::AnalyseConfig {
+        results_dir,
+        output_dir,
+        incremental,
+        filter: None,
+        incomplete: exp::IncompletePolicy::Skip,
+    };
+    let future = pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        let summary = exp::analyse(&mut experiment, &config)
+            .await
+            .map_err(|error| PyRuntimeError::new_err(error.to_string()))?;
+        Python::with_gil(|py| to_py(py, &summary))
+    })?;
+    Ok(future.unbind())
+}
+
+/// List the configuration runs of an experiment's results directory, as dicts of their `hash`,
+/// `state`, `path` and `configuration`.
+#[pyfunction]
+fn list_results(py: Python<'_>, results_dir: PathBuf) -> PyResult<Vec<PyObject>> {
+    exp::results::list_configurations(&results_dir)?
+        .into_iter()
+        .map(|entry| {
+            let dict = PyDict::new(py);
+            dict.set_item("hash", entry.hash)?;
+            dict.set_item("state", entry.state.to_string())?;
+            dict.set_item("path", entry.path)?;
+            dict.set_item(
+                "configuration",
+                entry.configuration.map(|c| to_py(py, &c)).transpose()?,
+            )?;
+            Ok(dict.into_any().unbind())
+        })
+        .collect()
+}
+
+/// Expand a meta-configuration, a dict or dataclass with lists of options for its fields, into
+/// the concrete configurations of a sweep over them, see `exp::expand`.
+///
+/// Returns a list of `(hash, configuration)` pairs, the hash being that of the results directory
+/// the configuration would be run in.
+#[pyfunction]
+fn expand(py: Python<'_>, meta: Bound<'_, PyAny>) -> PyResult<Vec<(String, PyObject)>> {
+    let dataclasses = py.import("dataclasses")?;
+    let meta = if dataclasses
+        .call_method1("is_dataclass", (&meta,))?
+        .is_truthy()?
+    {
+        dataclasses.call_method1("asdict", (&meta,))?
+    } else {
+        meta
+    };
+    exp::expand(&from_py(&meta)?)
+        .into_iter()
+        .map(|configuration| {
+            let configuration = DynConfiguration::new(configuration);
+            let hash = configuration
+                .hash_serialized()
+                .map_err(|error| PyValueError::new_err(error.to_string()))?;
+            Ok((hash, to_py(py, &configuration.0)?))
+        })
+        .collect()
+}
+
+/// Load the docker stats of a configuration run as a pandas `DataFrame`, see
+/// `exp::data::load_container_stats`.
+#[pyfunction]
+fn load_stats(py: Python<'_>, configuration_dir: PathBuf) -> PyResult<PyObject> {
+    let stats = exp::data::load_container_stats(&configuration_dir)
+        .map_err(|error| PyRuntimeError::new_err(error.to_string()))?;
+    to_pandas(py, &stats)
+}
+
+/// Load the description of an experiment's results directory, as a dict of its `experiment`
+/// metadata, `environment` and `provenance`, each `None` if it wasn't recorded.
+#[pyfunction]
+fn load_manifest(py: Python<'_>, results_dir: PathBuf) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    for (key, file) in [
+        ("experiment", "experiment.json"),
+        ("environment", "environment.json"),
+        ("provenance", "provenance.json"),
+    ] {
+        let path = results_dir.join(file);
+        let value = if path.exists() {
+            let value: Value = serde_json::from_reader(std::fs::File::open(path)?)
+                .map_err(|error| PyValueError::new_err(error.to_string()))?;
+            Some(to_py(py, &value)?)
+        } else {
+            None
+        };
+        dict.set_item(key, value)?;
+    }
+    Ok(dict.into_any().unbind())
+}
+
+/// Load the configurations of an experiment's results directory as a pandas `DataFrame`, with
+/// their `hash`, `state` and `path`, and a column per configuration field named by its dotted
+/// path.
+#[pyfunction]
+fn configurations(py: Python<'_>, results_dir: PathBuf) -> PyResult<PyObject> {
+    let records = exp::results::list_configurations(&results_dir)?
+        .into_iter()
+        .map(|entry| {
+            let mut record = match entry.configuration {
+                Some(Value::Object(fields)) => fields,
+                _ => serde_json::Map::new(),
+            };
+            record.insert("hash".to_owned(), Value::String(entry.hash));
+            record.insert("state".to_owned(), Value::String(entry.state.to_string()));
+            record.insert(
+                "path".to_owned(),
+                Value::String(entry.path.to_string_lossy().into_owned()),
+            );
+            Value::Object(record)
+        })
+        .collect();
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("sep", ".")?;
+    let df = py.import("pandas")?.call_method(
+        "json_normalize",
+        (to_py(py, &Value::Array(records))?,),
+        Some(&kwargs),
+    )?;
+    Ok(df.unbind())
+}
+
+/// Convert a polars `DataFrame` to a pandas one, column by column.
+fn to_pandas(py: Python<'_>, df: &DataFrame) -> PyResult<PyObject> {
+    let pandas = py.import("pandas")?;
+    let columns = PyDict::new(py);
+    for series in df.get_columns() {
+        let column = match series.dtype() {
+            DataType::Datetime(unit, _) => {
+                let values = series
+                    .cast(&DataType::Int64)
+                    .and_then(|s| s.i64().map(|values| values.into_iter().collect::<Vec<_>>()))
+                    .map_err(|error| PyRuntimeError::new_err(error.to_string()))?;
+                let unit = match unit {
+                    TimeUnit::Nanoseconds => "ns",
+                    TimeUnit::Microseconds => "us",
+                    TimeUnit::Milliseconds => "ms",
+                };
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("unit", unit)?;
+                kwargs.set_item("utc", true)?;
+                pandas.call_method("to_datetime", (values,), Some(&kwargs))?
+            }
+            _ => {
+                let values = series
+                    .iter()
+                    .map(|value| any_value_to_py(py, value))
+                    .collect::<PyResult<Vec<_>>>()?;
+                pandas.call_method1("Series", (values,))?
+            }
+        };
+        columns.set_item(series.name(), column)?;
+    }
+    Ok(pandas.call_method1("DataFrame", (columns,))?.unbind())
+}
+
+fn any_value_to_py(py: Python<'_>, value: AnyValue<'_>) -> PyResult<PyObject> {
+    Ok(match value {
+        AnyValue::Null => py.None(),
+        AnyValue::Boolean(v) => v.into_pyobject(py)?.to_owned().into_any().unbind(),
+        AnyValue::Utf8(v) => v.into_pyobject(py)?.into_any().unbind(),
+        AnyValue::UInt32(v) => v.into_pyobject(py)?.into_any().unbind(),
+        AnyValue::UInt64(v) => v.into_pyobject(py)?.into_any().unbind(),
+        AnyValue::Int32(v) => v.into_pyobject(py)?.into_any().unbind(),
+        AnyValue::Int64(v) => v.into_pyobject(py)?.into_any().unbind(),
+        AnyValue::Float32(v) => v.into_pyobject(py)?.into_any().unbind(),
+        AnyValue::Float64(v) => v.into_pyobject(py)?.into_any().unbind(),
+        value => value.to_string().into_pyobject(py)?.into_any().unbind(),
+    })
+}
+
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(py
+        .import("json")?
+        .call_method1("loads", (value.to_string(),))?
+        .unbind())
+}
+
+fn from_py(object: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let json: String = object
+        .py()
+        .import("json")?
+        .call_method1("dumps", (object,))?
+        .extract()?;
+    serde_json::from_str(&json).map_err(|error| PyValueError::new_err(error.to_string()))
+}
+
+#[pymodule]
+#[pyo3(name = "exp")]
+fn exp_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Experiment>()?;
+    m.add_class::<Measurements>()?;
+    m.add_class::<AnalysisDirs>()?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_function(wrap_pyfunction!(analyse, m)?)?;
+    m.add_function(wrap_pyfunction!(list_results, m)?)?;
+    m.add_function(wrap_pyfunction!(load_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(load_manifest, m)?)?;
+    m.add_function(wrap_pyfunction!(configurations, m)?)?;
+    m.add_function(wrap_pyfunction!(expand, m)?)?;
+    Ok(())
+}
@@ -1,15 +1,132 @@
 use std::{
-    fs::File,
+    collections::BTreeMap,
+    error::Error,
+    fs::{create_dir_all, read_dir, File},
+    io::{self, BufReader},
     path::{Path, PathBuf},
 };
 
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
-use tracing::{warn, debug};
+use tracing::{debug, info, warn};
 
-use crate::Experiment;
+use crate::config_file::{self, ConfigFileError};
+use crate::results::ConfigurationState;
+use crate::{Experiment, ExperimentMetadata};
+
+/// Default directory in an experiment's results directory for the outputs of analysis.
+pub const ANALYSIS_DIR: &str = "analysis";
+
+/// File in the analysis directory holding the summary returned by `Experiment::analyse`.
+pub const SUMMARY_FILE: &str = "summary.json";
+
+/// File in the analysis directory recording the results an analysis was made from.
+pub const INPUTS_FILE: &str = "inputs.json";
+
+/// File in the analysis directory caching the configurations read by an incremental analysis.
+pub const CACHE_FILE: &str = "cache.json";
 
 pub struct AnalyseConfig {
     pub results_dir: PathBuf,
+    /// Where to write the outputs of analysis, defaults to `analysis/` in the results directory.
+    pub output_dir: Option<PathBuf>,
+    /// Cache the configurations read from the results in `cache.json` in the analysis directory,
+    /// only reading those whose `configuration.json` has been modified since on later analyses.
+    pub incremental: bool,
+    /// Only analyse the configurations this returns true for, given their `configuration.json`,
+    /// such as `|c| c["nodes"] == 3`.
+    pub filter: Option<ConfigurationFilter>,
+    /// What to do with configuration runs that failed or were interrupted.
+    pub incomplete: IncompletePolicy,
+}
+
+impl AnalyseConfig {
+    /// Load the settings from a TOML or YAML file, as for `RunConfigBuilder::from_file`.
+    ///
+    /// `filter` can't be given in a file so is left unset.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let settings: AnalyseSettings = config_file::read(path.as_ref())?;
+        Ok(AnalyseConfig {
+            results_dir: settings.results_dir,
+            output_dir: settings.output_dir,
+            incremental: settings.incremental,
+            filter: None,
+            incomplete: settings.incomplete,
+        })
+    }
+}
+
+/// The settings of an `AnalyseConfig` that can be loaded from a file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnalyseSettings {
+    results_dir: PathBuf,
+    #[serde(default)]
+    output_dir: Option<PathBuf>,
+    #[serde(default)]
+    incremental: bool,
+    #[serde(default)]
+    incomplete: IncompletePolicy,
+}
+
+/// What to do with configuration runs that failed or were interrupted when analysing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncompletePolicy {
+    /// Leave them out, analysing only the completed runs.
+    #[default]
+    Skip,
+    /// Pass them to `Experiment::analyse` along with the completed runs, so partial sweeps can
+    /// be analysed. `ConfigurationState::of` gives the state of each from its directory. Runs
+    /// that failed before writing their `configuration.json` are still left out.
+    Include,
+    /// Fail the analysis if there are any.
+    Fail,
+}
+
+/// A predicate over the JSON of a configuration, deciding whether it is analysed.
+pub type ConfigurationFilter = Box<dyn Fn(&Value) -> bool + Send + Sync>;
+
+/// The results an analysis was made from, written to `inputs.json` in the analysis directory so
+/// outputs can be traced back to them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisInputs {
+    pub time: DateTime<Utc>,
+    /// Names of the configuration directories passed to `Experiment::analyse`.
+    pub results: Vec<String>,
+}
+
+/// Where an analysis reads results from and writes its outputs to.
+#[derive(Debug, Clone)]
+pub struct AnalysisDirs {
+    experiment_dir: PathBuf,
+    output_dir: PathBuf,
+}
+
+impl AnalysisDirs {
+    /// The experiment's results directory.
+    pub fn experiment_dir(&self) -> &Path {
+        &self.experiment_dir
+    }
+
+    /// Directory for outputs of the analysis covering the whole experiment.
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// Create and return the directory for outputs of the analysis of a single configuration,
+    /// given its results directory.
+    pub fn configuration_dir(&self, configuration_dir: &Path) -> io::Result<PathBuf> {
+        let name = configuration_dir.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "missing configuration hash")
+        })?;
+        let dir = self.output_dir.join(name);
+        create_dir_all(&dir)?;
+        Ok(dir)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -18,40 +135,200 @@ pub enum AnalyseError {
     IoError(#[from] std::io::Error),
     #[error(transparent)]
     SerdeError(#[from] serde_json::Error),
+    #[error("analysis failed: {0}")]
+    Analysis(Box<dyn Error + Send + Sync>),
+    #[error("configuration runs failed or were interrupted: {0:?}")]
+    Incomplete(Vec<String>),
 }
 
+/// Analyse the results of an experiment, returning the summary from `Experiment::analyse`.
+///
+/// Unless it is null, the summary is also written to `summary.json` in the analysis directory,
+/// alongside `inputs.json` recording the results that were analysed.
 pub async fn analyse<E: Experiment>(
     experiment: &mut E,
     config: &AnalyseConfig,
-) -> Result<(), AnalyseError> {
-    analyse_single(experiment, &config.results_dir).await?;
-    Ok(())
+) -> Result<Value, AnalyseError> {
+    let dirs = AnalysisDirs {
+        experiment_dir: config.results_dir.clone(),
+        output_dir: config
+            .output_dir
+            .clone()
+            .unwrap_or_else(|| config.results_dir.join(ANALYSIS_DIR)),
+    };
+    let summary = analyse_single(experiment, &dirs, config).await?;
+    if !summary.is_null() {
+        let summary_file = dirs.output_dir.join(SUMMARY_FILE);
+        debug!(?summary_file, "Writing analysis summary");
+        serde_json::to_writer_pretty(File::create(summary_file)?, &summary)?;
+    }
+    Ok(summary)
 }
 
-async fn analyse_single<E: Experiment>(experiment: &mut E, dir: &Path) -> Result<(), AnalyseError> {
+async fn analyse_single<E: Experiment>(
+    experiment: &mut E,
+    dirs: &AnalysisDirs,
+    config: &AnalyseConfig,
+) -> Result<Value, AnalyseError> {
+    let dir = dirs.experiment_dir();
     if !dir.exists() {
         warn!("No directory for experiment exists");
-        return Ok(());
+        return Ok(Value::Null);
+    }
+    match ExperimentMetadata::from_dir(dir) {
+        Ok(metadata) => info!(
+            name = %metadata.name,
+            version = %metadata.version,
+            tags = ?metadata.tags,
+            "Analysing experiment: {}",
+            metadata.description
+        ),
+        Err(error) => debug!(%error, "No experiment metadata"),
     }
     let env_file = File::open(dir.join("environment.json"))?;
     let env = serde_json::from_reader(env_file)?;
-    let mut configuration_dirs = Vec::new();
-    for entry in std::fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            configuration_dirs.push(path)
+
+    let cache_file = dirs.output_dir().join(CACHE_FILE);
+    let cache = if config.incremental && cache_file.exists() {
+        match serde_json::from_reader(BufReader::new(File::open(&cache_file)?)) {
+            Ok(cache) => Some(cache),
+            Err(error) => {
+                warn!(%error, ?cache_file, "Ignoring invalid analysis cache");
+                None
+            }
         }
-    }
-    configuration_dirs.sort();
+    } else {
+        None
+    };
+    let read = read_configurations(dir, config.incomplete, cache.as_ref())?;
     let mut configurations = Vec::new();
-    for c in configuration_dirs {
-        let config_file_path = c.join("configuration.json");
-        debug!(?config_file_path, "Reading configuration");
-        let config_file = File::open(config_file_path)?;
-        let config: E::Configuration = serde_json::from_reader(config_file)?;
-        configurations.push((config, c));
-    }
-    experiment.analyse(dir, env, configurations);
-    Ok(())
+    for c in &read {
+        if let Some(filter) = &config.filter {
+            if !filter(&c.configuration) {
+                debug!(configuration = %c.name, "Configuration filtered out of analysis");
+                continue;
+            }
+        }
+        configurations.push((
+            E::Configuration::deserialize(&c.configuration)?,
+            dir.join(&c.name),
+        ));
+    }
+    create_dir_all(dirs.output_dir())?;
+    if config.incremental {
+        let cache = read
+            .into_iter()
+            .map(|c| (c.name.clone(), c))
+            .collect::<Cache>();
+        serde_json::to_writer(File::create(&cache_file)?, &cache)?;
+    }
+    let inputs = AnalysisInputs {
+        time: Utc::now(),
+        results: configurations
+            .iter()
+            .filter_map(|(_, c)| c.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect(),
+    };
+    serde_json::to_writer_pretty(File::create(dirs.output_dir().join(INPUTS_FILE))?, &inputs)?;
+
+    experiment
+        .analyse(dirs, env, configurations)
+        .map_err(AnalyseError::Analysis)
+}
+
+/// A configuration read from the results, as cached by incremental analyses.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedConfiguration {
+    /// Name of the configuration directory.
+    name: String,
+    /// When `configuration.json` was last modified.
+    modified: DateTime<Utc>,
+    configuration: Value,
+}
+
+/// Cached configurations by the name of their directory.
+type Cache = BTreeMap<String, CachedConfiguration>;
+
+/// Read the configurations of an experiment's results directory in parallel, sorted by their
+/// directory, using the cached ones that haven't been modified since.
+fn read_configurations(
+    dir: &Path,
+    incomplete: IncompletePolicy,
+    cache: Option<&Cache>,
+) -> Result<Vec<CachedConfiguration>, AnalyseError> {
+    let mut paths = read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    let is_incomplete = |path: &PathBuf| {
+        path.is_dir()
+            && matches!(
+                ConfigurationState::of(path),
+                Some(ConfigurationState::Failed | ConfigurationState::Running)
+            )
+    };
+    match incomplete {
+        IncompletePolicy::Skip => paths.retain(|path| !is_incomplete(path)),
+        IncompletePolicy::Include => {}
+        IncompletePolicy::Fail => {
+            let mut names = paths
+                .iter()
+                .filter(|path| is_incomplete(path))
+                .filter_map(|path| path.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            if !names.is_empty() {
+                names.sort();
+                return Err(AnalyseError::Incomplete(names));
+            }
+        }
+    }
+    let mut configurations = paths
+        .into_par_iter()
+        .filter_map(|path| {
+            let config_file_path = path.join("configuration.json");
+            // skip directories that aren't configurations, such as the analysis directory
+            let metadata = config_file_path.metadata().ok().filter(|m| m.is_file())?;
+            Some(read_configuration(
+                &path,
+                &config_file_path,
+                metadata,
+                cache,
+            ))
+        })
+        .collect::<Result<Vec<_>, AnalyseError>>()?;
+    configurations.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(configurations)
+}
+
+fn read_configuration(
+    path: &Path,
+    config_file_path: &Path,
+    metadata: std::fs::Metadata,
+    cache: Option<&Cache>,
+) -> Result<CachedConfiguration, AnalyseError> {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let modified = DateTime::<Utc>::from(metadata.modified()?);
+    if let Some(cached) = cache
+        .and_then(|cache| cache.get(&name))
+        .filter(|cached| cached.modified == modified)
+    {
+        debug!(?config_file_path, "Using cached configuration");
+        return Ok(CachedConfiguration {
+            name,
+            modified,
+            configuration: cached.configuration.clone(),
+        });
+    }
+    debug!(?config_file_path, "Reading configuration");
+    let configuration = serde_json::from_reader(BufReader::new(File::open(config_file_path)?))?;
+    Ok(CachedConfiguration {
+        name,
+        modified,
+        configuration,
+    })
 }
@@ -0,0 +1,136 @@
+use std::{
+    ffi::OsStr,
+    fs::{create_dir_all, read, read_dir, remove_dir_all, rename, File},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::lock::LOCK_EXTENSION;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeError(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportSummary {
+    /// Hashes of the configurations written to the archive.
+    pub exported: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    /// Hashes of the configurations added to the results directory.
+    pub imported: Vec<String>,
+    /// Hashes of configurations already present with the same configuration, which were skipped.
+    pub duplicates: Vec<String>,
+    /// Hashes already present in the results directory with a different configuration, which
+    /// were skipped.
+    pub collisions: Vec<String>,
+}
+
+/// Export an experiment's results directory as a zstd compressed tar archive.
+///
+/// Experiment level files, such as `environment.json`, are always included, while configuration
+/// directories are only included if `filter` returns true for their configuration. Running
+/// configurations and lock files are never exported.
+pub fn export<F: Fn(&serde_json::Value) -> bool>(
+    results_dir: &Path,
+    archive: &Path,
+    filter: F,
+) -> Result<ExportSummary, ArchiveError> {
+    let encoder = zstd::Encoder::new(File::create(archive)?, 0)?.auto_finish();
+    let mut builder = tar::Builder::new(encoder);
+    let mut summary = ExportSummary::default();
+    for entry in read_dir(results_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        if !path.is_dir() {
+            if path.extension() != Some(OsStr::new(LOCK_EXTENSION)) {
+                builder.append_path_with_name(&path, &name)?;
+            }
+            continue;
+        }
+        if path.extension() == Some(OsStr::new("running")) {
+            debug!(?path, "Skipping running configuration");
+            continue;
+        }
+        let config_file = path.join("configuration.json");
+        if !config_file.exists() {
+            continue;
+        }
+        let config: serde_json::Value = serde_json::from_reader(File::open(config_file)?)?;
+        if filter(&config) {
+            builder.append_dir_all(&name, &path)?;
+            summary.exported.push(name.to_string_lossy().into_owned());
+        }
+    }
+    builder.into_inner()?;
+    info!(
+        exported = summary.exported.len(),
+        archive = ?archive,
+        "Exported results"
+    );
+    Ok(summary)
+}
+
+/// Import an archive created by `export`, merging it into an existing results directory.
+///
+/// Configurations already in the results directory are never overwritten: those with identical
+/// configurations are reported as duplicates and those with differing configurations as
+/// collisions. Experiment level files are only added if they don't already exist.
+pub fn import(archive: &Path, results_dir: &Path) -> Result<ImportSummary, ArchiveError> {
+    create_dir_all(results_dir)?;
+    let unpack_dir = results_dir.join(format!(".import-{}", std::process::id()));
+    let decoder = zstd::Decoder::new(File::open(archive)?)?;
+    tar::Archive::new(decoder).unpack(&unpack_dir)?;
+
+    let mut summary = ImportSummary::default();
+    for entry in read_dir(&unpack_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let target = results_dir.join(&name);
+        if !path.is_dir() {
+            if target.exists() {
+                debug!(?target, "Keeping existing experiment file");
+            } else {
+                rename(&path, &target)?;
+            }
+            continue;
+        }
+
+        let hash = name.to_string_lossy().into_owned();
+        if target.exists() {
+            let existing = read(target.join("configuration.json"))?;
+            let imported = read(path.join("configuration.json"))?;
+            let existing: serde_json::Value = serde_json::from_slice(&existing)?;
+            let imported: serde_json::Value = serde_json::from_slice(&imported)?;
+            if existing == imported {
+                debug!(%hash, "Skipping duplicate configuration");
+                summary.duplicates.push(hash);
+            } else {
+                warn!(%hash, "Configuration hash collision, skipping");
+                summary.collisions.push(hash);
+            }
+        } else {
+            rename(&path, &target)?;
+            summary.imported.push(hash);
+        }
+    }
+    remove_dir_all(&unpack_dir)?;
+    info!(
+        imported = summary.imported.len(),
+        duplicates = summary.duplicates.len(),
+        collisions = summary.collisions.len(),
+        "Imported results"
+    );
+    Ok(summary)
+}
@@ -0,0 +1,133 @@
+use std::{
+    fs::{read_dir, symlink_metadata, File},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// File in each configuration directory with the size of what its run wrote.
+pub const ARTIFACTS_FILE: &str = "artifacts.json";
+
+/// How often to check the size of a running configuration's directory, unless
+/// `ArtifactQuota::interval` is set.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Limit on how much a configuration run may write to its directory, set with
+/// `RunConfig::artifact_quota`, so runaway logs don't fill the disk.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArtifactQuota {
+    pub max_bytes: u64,
+    /// What to do once a run has written more, failing it by default.
+    #[serde(default)]
+    pub action: QuotaAction,
+    /// How often to check the size while running, 5 seconds by default.
+    #[serde(default, with = "crate::run::seconds")]
+    pub interval: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Log a warning once the run has finished.
+    Warn,
+    /// Stop the run as soon as it is over the quota and fail it.
+    #[default]
+    Fail,
+}
+
+impl ArtifactQuota {
+    pub fn new(max_bytes: u64, action: QuotaAction) -> Self {
+        Self {
+            max_bytes,
+            action,
+            interval: None,
+        }
+    }
+
+    /// Resolve once the directory is over the quota, with its size, checking every interval.
+    pub(crate) async fn exceeded(&self, dir: &Path) -> DirSize {
+        loop {
+            tokio::time::sleep(self.interval.unwrap_or(DEFAULT_INTERVAL)).await;
+            // walking a large directory blocks
+            let path = dir.to_owned();
+            let size = tokio::task::spawn_blocking(move || DirSize::of(&path))
+                .await
+                .unwrap_or_else(|error| Err(io::Error::other(error)));
+            match size {
+                Ok(size) if size.bytes > self.max_bytes => return size,
+                Ok(_) => {}
+                Err(error) => warn!(%error, ?dir, "Failed to check size of configuration dir"),
+            }
+        }
+    }
+}
+
+/// The size of a directory, not following symlinks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirSize {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+impl DirSize {
+    /// Add up the sizes of the files under a path.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = symlink_metadata(path)?;
+        if !metadata.is_dir() {
+            return Ok(DirSize {
+                bytes: metadata.len(),
+                files: 1,
+            });
+        }
+        let mut size = DirSize::default();
+        for entry in read_dir(path)? {
+            let entry_size = match DirSize::of(&entry?.path()) {
+                Ok(entry_size) => entry_size,
+                // removed while being counted
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            };
+            size.bytes += entry_size.bytes;
+            size.files += entry_size.files;
+        }
+        Ok(size)
+    }
+
+    /// Read the size recorded for a configuration run.
+    pub fn from_configuration(configuration_dir: &Path) -> io::Result<Self> {
+        let file = File::open(configuration_dir.join(ARTIFACTS_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub(crate) fn write(&self, configuration_dir: &Path) -> io::Result<()> {
+        let file = File::create(configuration_dir.join(ARTIFACTS_FILE))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+/// The size of an entry in a results directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub path: PathBuf,
+    pub size: DirSize,
+}
+
+/// The size of each entry in a results directory, such as each configuration run, largest first,
+/// like `du`.
+///
+/// Configurations linked from a store are links so count for nothing here.
+pub fn disk_usage(results_dir: &Path) -> io::Result<Vec<DiskUsage>> {
+    let mut usage = Vec::new();
+    for entry in read_dir(results_dir)? {
+        let path = entry?.path();
+        let size = DirSize::of(&path)?;
+        usage.push(DiskUsage { path, size });
+    }
+    usage.sort_by(|a, b| b.size.bytes.cmp(&a.size.bytes).then(a.path.cmp(&b.path)));
+    Ok(usage)
+}
@@ -0,0 +1,551 @@
+//! The container operations a `docker_runner::Runner` orchestrates, so runs can be exercised
+//! against a `MockBackend` without a docker daemon.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use bollard::{
+    container::{CreateContainerOptions, LogOutput, RemoveContainerOptions, StopContainerOptions},
+    errors::Error,
+    exec::{CreateExecOptions, StartExecResults},
+    models::{ContainerInspectResponse, ContainerState, Ipam, IpamConfig},
+    network::{CreateNetworkOptions, ListNetworksOptions},
+    volume::{CreateVolumeOptions, RemoveVolumeOptions},
+    Docker,
+};
+use chrono::Utc;
+use futures::StreamExt;
+
+use crate::docker_runner::{pull_image, ContainerConfig, ImagePull, VolumeConfig};
+
+/// Where the containers of a `Runner` run.
+///
+/// Errors are docker's, so a backend fails calls as the daemon would.
+#[async_trait]
+pub trait ContainerBackend: Debug + Send + Sync {
+    async fn network_exists(&self, name: &str) -> Result<bool, Error>;
+
+    async fn create_network(
+        &self,
+        name: &str,
+        subnet: Option<&str>,
+        labels: &HashMap<String, String>,
+    ) -> Result<(), Error>;
+
+    async fn create_volume(
+        &self,
+        config: &VolumeConfig,
+        labels: &HashMap<String, String>,
+    ) -> Result<(), Error>;
+
+    async fn pull_image(&self, image_name: &str, image_tag: &str) -> Result<ImagePull, Error>;
+
+    /// Create a container, giving its id.
+    async fn create_container(
+        &self,
+        config: &ContainerConfig,
+        labels: &HashMap<String, String>,
+    ) -> Result<String, Error>;
+
+    async fn start_container(&self, name: &str) -> Result<(), Error>;
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerInspectResponse, Error>;
+
+    /// Stop a container, killing it after `timeout` seconds.
+    async fn stop_container(&self, name: &str, timeout: i64) -> Result<(), Error>;
+
+    /// Remove a container, even if it is running.
+    async fn remove_container(&self, name: &str) -> Result<(), Error>;
+
+    async fn remove_volume(&self, name: &str) -> Result<(), Error>;
+
+    async fn remove_network(&self, name: &str) -> Result<(), Error>;
+
+    /// Execute a command in a running container, giving its stdout and stderr.
+    async fn exec(
+        &self,
+        container: &str,
+        command: Vec<&str>,
+    ) -> Result<(Vec<String>, Vec<String>), Error>;
+
+    /// The docker client behind the backend, if any.
+    ///
+    /// Capturing logs, stats and processes of containers, and the other features that need
+    /// more of the docker API, only happen with a client.
+    fn docker(&self) -> Option<&Docker> {
+        None
+    }
+}
+
+/// The docker daemon, the backend of `Runner::new`.
+#[derive(Debug, Clone)]
+pub struct DockerBackend {
+    docker: Docker,
+}
+
+impl DockerBackend {
+    pub fn new(docker: Docker) -> Self {
+        Self { docker }
+    }
+}
+
+fn label_refs(labels: &HashMap<String, String>) -> HashMap<&str, &str> {
+    labels
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect()
+}
+
+#[async_trait]
+impl ContainerBackend for DockerBackend {
+    async fn network_exists(&self, name: &str) -> Result<bool, Error> {
+        let mut filters = HashMap::new();
+        filters.insert("name", vec![name]);
+        let networks = self
+            .docker
+            .list_networks(Some(ListNetworksOptions { filters }))
+            .await?;
+        // the name filter also matches networks that contain the name
+        Ok(networks.iter().any(|n| n.name.as_deref() == Some(name)))
+    }
+
+    async fn create_network(
+        &self,
+        name: &str,
+        subnet: Option<&str>,
+        labels: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        let network_config = subnet.map(|subnet| {
+            vec![IpamConfig {
+                subnet: Some(subnet.to_owned()),
+                ..Default::default()
+            }]
+        });
+        self.docker
+            .create_network(CreateNetworkOptions {
+                name,
+                check_duplicate: true,
+                ipam: Ipam {
+                    config: network_config,
+                    ..Default::default()
+                },
+                labels: label_refs(labels),
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn create_volume(
+        &self,
+        config: &VolumeConfig,
+        labels: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        self.docker
+            .create_volume(CreateVolumeOptions {
+                name: config.name.as_str(),
+                driver: config.driver.as_deref().unwrap_or("local"),
+                labels: label_refs(labels),
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn pull_image(&self, image_name: &str, image_tag: &str) -> Result<ImagePull, Error> {
+        pull_image(image_name, image_tag).await
+    }
+
+    async fn create_container(
+        &self,
+        config: &ContainerConfig,
+        labels: &HashMap<String, String>,
+    ) -> Result<String, Error> {
+        let mut create_config = config.to_create_container_config();
+        create_config.labels = Some(labels.clone());
+        let response = self
+            .docker
+            .create_container(
+                Some(CreateContainerOptions { name: &config.name }),
+                create_config,
+            )
+            .await?;
+        Ok(response.id)
+    }
+
+    async fn start_container(&self, name: &str) -> Result<(), Error> {
+        self.docker.start_container::<String>(name, None).await
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerInspectResponse, Error> {
+        self.docker.inspect_container(name, None).await
+    }
+
+    async fn stop_container(&self, name: &str, timeout: i64) -> Result<(), Error> {
+        self.docker
+            .stop_container(name, Some(StopContainerOptions { t: timeout }))
+            .await
+    }
+
+    async fn remove_container(&self, name: &str) -> Result<(), Error> {
+        self.docker
+            .remove_container(
+                name,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+    }
+
+    async fn remove_volume(&self, name: &str) -> Result<(), Error> {
+        self.docker
+            .remove_volume(name, Some(RemoveVolumeOptions { force: true }))
+            .await
+    }
+
+    async fn remove_network(&self, name: &str) -> Result<(), Error> {
+        self.docker.remove_network(name).await
+    }
+
+    async fn exec(
+        &self,
+        container: &str,
+        command: Vec<&str>,
+    ) -> Result<(Vec<String>, Vec<String>), Error> {
+        let exec = self
+            .docker
+            .create_exec(
+                container,
+                CreateExecOptions {
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    cmd: Some(command),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        if let StartExecResults::Attached {
+            mut output,
+            input: _,
+        } = self.docker.start_exec(&exec.id, None).await?
+        {
+            while let Some(Ok(msg)) = output.next().await {
+                match msg {
+                    LogOutput::StdErr { message } => {
+                        err.push(String::from_utf8_lossy(&message).into_owned())
+                    }
+                    LogOutput::StdOut { message } => {
+                        out.push(String::from_utf8_lossy(&message).into_owned())
+                    }
+                    LogOutput::StdIn { message: _ } | LogOutput::Console { message: _ } => {
+                        unreachable!()
+                    }
+                }
+            }
+        }
+        Ok((out, err))
+    }
+
+    fn docker(&self) -> Option<&Docker> {
+        Some(&self.docker)
+    }
+}
+
+/// A call made to a `MockBackend`, by the name of the resource it is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendCall {
+    CreateNetwork(String),
+    CreateVolume(String),
+    /// The image as `name:tag`.
+    PullImage(String),
+    CreateContainer(String),
+    StartContainer(String),
+    StopContainer(String),
+    RemoveContainer(String),
+    RemoveVolume(String),
+    RemoveNetwork(String),
+    /// The container and the command.
+    Exec(String, Vec<String>),
+}
+
+/// A backend that only keeps track of its resources, to test the orchestration of runs, such as
+/// the order of teardown or how failures are handled, without a docker daemon.
+///
+/// Clones share their state, so a test can keep one to inspect the calls the `Runner` made,
+/// make calls fail, or have containers exit while the run goes on.
+#[derive(Debug, Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    calls: Vec<BackendCall>,
+    /// Calls to fail and how many more times to fail them.
+    failures: Vec<(BackendCall, usize)>,
+    networks: BTreeSet<String>,
+    volumes: BTreeSet<String>,
+    containers: HashMap<String, MockContainer>,
+    exec_outputs: HashMap<String, (Vec<String>, Vec<String>)>,
+}
+
+#[derive(Debug, Default)]
+struct MockContainer {
+    id: String,
+    state: ContainerState,
+}
+
+fn not_found(what: &str, name: &str) -> Error {
+    Error::DockerResponseServerError {
+        status_code: 404,
+        message: format!("No such {}: {}", what, name),
+    }
+}
+
+fn conflict(what: &str, name: &str) -> Error {
+    Error::DockerResponseServerError {
+        status_code: 409,
+        message: format!("{} {} already exists", what, name),
+    }
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The calls made so far, in order, including those that failed.
+    pub fn calls(&self) -> Vec<BackendCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Fail the next `times` calls equal to `call` with a server error.
+    pub fn fail(&self, call: BackendCall, times: usize) {
+        self.state.lock().unwrap().failures.push((call, times));
+    }
+
+    /// End a running container, as if its process exited with `exit_code`, or was killed for
+    /// running out of memory with `oom_killed`.
+    pub fn exit_container(&self, name: &str, exit_code: i64, oom_killed: bool) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(container) = state.containers.get_mut(name) {
+            container.state.running = Some(false);
+            container.state.exit_code = Some(exit_code);
+            container.state.oom_killed = Some(oom_killed);
+        }
+    }
+
+    /// Set the stdout and stderr of commands executed in a container, empty by default.
+    pub fn exec_output(&self, container: &str, stdout: Vec<String>, stderr: Vec<String>) {
+        self.state
+            .lock()
+            .unwrap()
+            .exec_outputs
+            .insert(container.to_owned(), (stdout, stderr));
+    }
+
+    /// Names of the containers, volumes and networks that exist, which should all be empty once
+    /// a run has finished.
+    pub fn resources(&self) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let state = self.state.lock().unwrap();
+        let mut containers = state.containers.keys().cloned().collect::<Vec<_>>();
+        containers.sort();
+        (
+            containers,
+            state.volumes.iter().cloned().collect(),
+            state.networks.iter().cloned().collect(),
+        )
+    }
+
+    /// Record a call, failing it if asked to.
+    fn call(&self, call: BackendCall) -> Result<MutexGuard<'_, MockState>, Error> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call.clone());
+        if let Some((_, times)) = state
+            .failures
+            .iter_mut()
+            .find(|(failing, times)| *failing == call && *times > 0)
+        {
+            *times -= 1;
+            return Err(Error::DockerResponseServerError {
+                status_code: 500,
+                message: format!("mock failure of {:?}", call),
+            });
+        }
+        Ok(state)
+    }
+}
+
+#[async_trait]
+impl ContainerBackend for MockBackend {
+    async fn network_exists(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.state.lock().unwrap().networks.contains(name))
+    }
+
+    async fn create_network(
+        &self,
+        name: &str,
+        _subnet: Option<&str>,
+        _labels: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        let mut state = self.call(BackendCall::CreateNetwork(name.to_owned()))?;
+        if !state.networks.insert(name.to_owned()) {
+            return Err(conflict("network", name));
+        }
+        Ok(())
+    }
+
+    async fn create_volume(
+        &self,
+        config: &VolumeConfig,
+        _labels: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        let mut state = self.call(BackendCall::CreateVolume(config.name.clone()))?;
+        // as with docker, creating an existing volume is fine
+        state.volumes.insert(config.name.clone());
+        Ok(())
+    }
+
+    async fn pull_image(&self, image_name: &str, image_tag: &str) -> Result<ImagePull, Error> {
+        let image = format!("{}:{}", image_name, image_tag);
+        drop(self.call(BackendCall::PullImage(image.clone()))?);
+        Ok(ImagePull {
+            image,
+            start: Utc::now(),
+            duration_secs: 0.0,
+            repo_digests: Vec::new(),
+            layers: Vec::new(),
+            log: Vec::new(),
+        })
+    }
+
+    async fn create_container(
+        &self,
+        config: &ContainerConfig,
+        _labels: &HashMap<String, String>,
+    ) -> Result<String, Error> {
+        let mut state = self.call(BackendCall::CreateContainer(config.name.clone()))?;
+        if state.containers.contains_key(&config.name) {
+            return Err(conflict("container", &config.name));
+        }
+        if let Some(network) = config.network.as_ref() {
+            if !state.networks.contains(network) {
+                return Err(not_found("network", network));
+            }
+        }
+        let id = format!("{:064x}", state.calls.len());
+        state.containers.insert(
+            config.name.clone(),
+            MockContainer {
+                id: id.clone(),
+                state: ContainerState {
+                    running: Some(false),
+                    ..Default::default()
+                },
+            },
+        );
+        Ok(id)
+    }
+
+    async fn start_container(&self, name: &str) -> Result<(), Error> {
+        let mut state = self.call(BackendCall::StartContainer(name.to_owned()))?;
+        let container = state
+            .containers
+            .get_mut(name)
+            .ok_or_else(|| not_found("container", name))?;
+        container.state = ContainerState {
+            running: Some(true),
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerInspectResponse, Error> {
+        let state = self.state.lock().unwrap();
+        let container = state
+            .containers
+            .get(name)
+            .ok_or_else(|| not_found("container", name))?;
+        Ok(ContainerInspectResponse {
+            id: Some(container.id.clone()),
+            name: Some(format!("/{}", name)),
+            state: Some(container.state.clone()),
+            ..Default::default()
+        })
+    }
+
+    async fn stop_container(&self, name: &str, _timeout: i64) -> Result<(), Error> {
+        let mut state = self.call(BackendCall::StopContainer(name.to_owned()))?;
+        let container = state
+            .containers
+            .get_mut(name)
+            .ok_or_else(|| not_found("container", name))?;
+        if container.state.running == Some(true) {
+            // killed, as by SIGKILL
+            container.state.running = Some(false);
+            container.state.exit_code = Some(137);
+        }
+        Ok(())
+    }
+
+    async fn remove_container(&self, name: &str) -> Result<(), Error> {
+        let mut state = self.call(BackendCall::RemoveContainer(name.to_owned()))?;
+        state
+            .containers
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| not_found("container", name))
+    }
+
+    async fn remove_volume(&self, name: &str) -> Result<(), Error> {
+        let mut state = self.call(BackendCall::RemoveVolume(name.to_owned()))?;
+        if !state.volumes.remove(name) {
+            return Err(not_found("volume", name));
+        }
+        Ok(())
+    }
+
+    async fn remove_network(&self, name: &str) -> Result<(), Error> {
+        let mut state = self.call(BackendCall::RemoveNetwork(name.to_owned()))?;
+        if !state.networks.remove(name) {
+            return Err(not_found("network", name));
+        }
+        Ok(())
+    }
+
+    async fn exec(
+        &self,
+        container: &str,
+        command: Vec<&str>,
+    ) -> Result<(Vec<String>, Vec<String>), Error> {
+        let state = self.call(BackendCall::Exec(
+            container.to_owned(),
+            command.iter().map(|arg| (*arg).to_owned()).collect(),
+        ))?;
+        let running = state
+            .containers
+            .get(container)
+            .ok_or_else(|| not_found("container", container))?
+            .state
+            .running;
+        if running != Some(true) {
+            return Err(Error::DockerResponseServerError {
+                status_code: 409,
+                message: format!("Container {} is not running", container),
+            });
+        }
+        Ok(state
+            .exec_outputs
+            .get(container)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,220 @@
+//! Manage an experiment's results directory from the shell, without needing the experiment.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand};
+use exp::results::{self, ConfigurationEntry, ConfigurationState};
+use exp::{archive, read_events, CompareConfig, ExperimentMetadata, MatchBy};
+
+#[derive(Debug, Parser)]
+#[command(about)]
+struct Args {
+    /// The experiment's results directory.
+    #[arg(short, long, default_value = "results")]
+    results_dir: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the configurations and their state.
+    List,
+    /// Summarise the experiment and how far through its run it is.
+    Status,
+    /// Remove failed and interrupted configuration runs so they are run again.
+    Clean {
+        /// Only print what would be removed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove stale lock files, unfinished imports and broken links into a store.
+    Gc {
+        /// Only print what would be removed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the size in bytes of each configuration run and other entry, largest first.
+    Du,
+    /// Export the results to a zstd compressed tar archive.
+    Archive { archive: PathBuf },
+    /// Show the fields that differ between two configurations.
+    Diff { a: String, b: String },
+    /// Compare the container stats of matching configurations with another results directory,
+    /// failing if any got worse.
+    Compare {
+        /// Results of the new build to compare against these.
+        other: PathBuf,
+        /// Relative change beyond which a worse result is a regression.
+        #[arg(long, default_value_t = 0.05)]
+        threshold: f64,
+        /// Match configurations by their fields, ignoring these dotted paths, instead of by hash.
+        #[arg(long)]
+        ignore: Vec<String>,
+    },
+    /// Show a configuration and the events of its runs.
+    Show { hash: String },
+    /// Run a SQL query over the configurations and their metrics.
+    #[cfg(feature = "sql")]
+    Query { query: String },
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let dir = &args.results_dir;
+    match args.command {
+        Command::List => {
+            for entry in results::list_configurations(dir)? {
+                let configuration = entry
+                    .configuration
+                    .map(|c| c.to_string())
+                    .unwrap_or_default();
+                println!(
+                    "{} {:9} {}",
+                    results::run_name(&entry.hash, entry.repeat),
+                    entry.state,
+                    configuration
+                );
+            }
+        }
+        Command::Status => status(dir)?,
+        Command::Clean { dry_run } => {
+            for path in results::clean(dir, dry_run)? {
+                println!("removed {}", path.display());
+            }
+        }
+        Command::Gc { dry_run } => {
+            for path in results::gc(dir, dry_run)? {
+                println!("removed {}", path.display());
+            }
+        }
+        Command::Du => {
+            let usage = exp::disk_usage(dir)?;
+            for entry in &usage {
+                let name = entry.path.strip_prefix(dir).unwrap_or(&entry.path);
+                println!("{:>12} {}", entry.size.bytes, name.display());
+            }
+            let total = usage.iter().map(|entry| entry.size.bytes).sum::<u64>();
+            println!("{:>12} total", total);
+        }
+        Command::Archive { archive } => {
+            let summary = archive::export(dir, &archive, |_| true)?;
+            println!(
+                "exported {} configurations to {}",
+                summary.exported.len(),
+                archive.display()
+            );
+        }
+        Command::Diff { a, b } => {
+            let a = find_configuration(dir, &a)?;
+            let b = find_configuration(dir, &b)?;
+            let diff = results::diff_configurations(
+                &a.configuration.unwrap_or_default(),
+                &b.configuration.unwrap_or_default(),
+            );
+            for (field, (a_value, b_value)) in diff {
+                let show = |value: Option<serde_json::Value>| {
+                    value.map_or_else(|| "<missing>".to_owned(), |v| v.to_string())
+                };
+                println!("{}: {} -> {}", field, show(a_value), show(b_value));
+            }
+        }
+        Command::Compare {
+            other,
+            threshold,
+            ignore,
+        } => {
+            let config = CompareConfig {
+                match_by: if ignore.is_empty() {
+                    MatchBy::Hash
+                } else {
+                    MatchBy::Fields(ignore)
+                },
+                threshold,
+                ..CompareConfig::default()
+            };
+            let comparison = exp::compare(dir, &other, &config)?;
+            for delta in comparison.regressions() {
+                println!(
+                    "{} {}: {:.3} -> {:.3} ({:+.1}%)",
+                    delta.hash_a,
+                    delta.metric,
+                    delta.a.mean,
+                    delta.b.mean,
+                    delta.change * 100.0
+                );
+            }
+            for hash in &comparison.only_a {
+                println!("{} only in {}", hash, dir.display());
+            }
+            for hash in &comparison.only_b {
+                println!("{} only in {}", hash, other.display());
+            }
+            let regressions = comparison.regressions().count();
+            if regressions > 0 {
+                return Err(format!("{} regressions", regressions).into());
+            }
+        }
+        Command::Show { hash } => {
+            let entry = find_configuration(dir, &hash)?;
+            println!("{} {}", entry.hash, entry.state);
+            println!("{}", entry.path.display());
+            if let Some(configuration) = &entry.configuration {
+                println!("{}", serde_json::to_string_pretty(configuration)?);
+            }
+            if let Ok(events) = read_events(dir) {
+                // events of every repeat of the configuration
+                for record in events
+                    .iter()
+                    .filter(|r| results::split_run_name(r.event.hash()).0 == entry.hash)
+                {
+                    println!("{} {}", record.time, serde_json::to_string(&record.event)?);
+                }
+            }
+        }
+        #[cfg(feature = "sql")]
+        Command::Query { query } => {
+            println!("{}", exp::query::sql(dir, &query)?);
+        }
+    }
+    Ok(())
+}
+
+fn status(dir: &Path) -> Result<(), Box<dyn Error>> {
+    if let Ok(metadata) = ExperimentMetadata::from_dir(dir) {
+        println!("experiment: {} {}", metadata.name, metadata.version);
+        if !metadata.description.is_empty() {
+            println!("{}", metadata.description);
+        }
+    }
+    if let Some(owner) = results::experiment_lock_owner(dir) {
+        println!("locked by {}", owner);
+    }
+    let entries = results::list_configurations(dir)?;
+    let count = |state| entries.iter().filter(|e| e.state == state).count();
+    println!("completed: {}", count(ConfigurationState::Completed));
+    println!("failed: {}", count(ConfigurationState::Failed));
+    println!("running: {}", count(ConfigurationState::Running));
+    Ok(())
+}
+
+/// Find a configuration by its hash, or a unique prefix of it.
+fn find_configuration(dir: &Path, hash: &str) -> Result<ConfigurationEntry, Box<dyn Error>> {
+    let mut matches = results::list_configurations(dir)?
+        .into_iter()
+        .filter(|entry| entry.hash.starts_with(hash))
+        .collect::<Vec<_>>();
+    // prefer a completed run over failed ones of the same configuration
+    matches.sort_by_key(|entry| entry.state != ConfigurationState::Completed);
+    match matches.len() {
+        0 => Err(format!("no configuration matching {}", hash).into()),
+        _ if matches.iter().any(|entry| entry.hash != matches[0].hash) => {
+            Err(format!("{} matches more than one configuration", hash).into())
+        }
+        _ => Ok(matches.remove(0)),
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    env,
+    fs::read,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How the experiment binary was built, recorded in `environment.json`.
+///
+/// Build with `exp::build_metadata!()` in the experiment crate after calling `exp::build::emit()`
+/// from its build script.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildMetadata {
+    pub package: String,
+    pub package_version: String,
+    pub rustc_version: Option<String>,
+    /// The cargo profile, `debug` or `release`.
+    pub profile: Option<String>,
+    /// Enabled cargo features of the experiment crate.
+    pub features: Vec<String>,
+    /// blake3 hash of the `Cargo.lock` used for the build.
+    pub lockfile_hash: Option<String>,
+}
+
+/// Collect build information for `exp::build_metadata!()`, for calling from a build script.
+pub fn emit() {
+    if let Some(rustc) = env::var_os("RUSTC") {
+        if let Ok(output) = Command::new(rustc).arg("--version").output() {
+            let version = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=EXP_BUILD_RUSTC_VERSION={}", version.trim());
+        }
+    }
+    if let Ok(profile) = env::var("PROFILE") {
+        println!("cargo:rustc-env=EXP_BUILD_PROFILE={}", profile);
+    }
+    let mut features = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+    println!("cargo:rustc-env=EXP_BUILD_FEATURES={}", features.join(","));
+    if let Some(lockfile) = env::var_os("CARGO_MANIFEST_DIR").and_then(|dir| find_lockfile(&dir)) {
+        println!("cargo:rerun-if-changed={}", lockfile.display());
+        if let Ok(contents) = read(&lockfile) {
+            println!(
+                "cargo:rustc-env=EXP_BUILD_LOCKFILE_HASH={}",
+                blake3::hash(&contents).to_hex()
+            );
+        }
+    }
+}
+
+/// Find the `Cargo.lock` for the crate in `dir`, which may be in a workspace root above it.
+fn find_lockfile<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
+    dir.as_ref()
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|lockfile| lockfile.exists())
+}
+
+/// Build the `BuildMetadata` for the crate this is called in.
+#[macro_export]
+macro_rules! build_metadata {
+    () => {
+        $crate::build::BuildMetadata {
+            package: env!("CARGO_PKG_NAME").to_owned(),
+            package_version: env!("CARGO_PKG_VERSION").to_owned(),
+            rustc_version: option_env!("EXP_BUILD_RUSTC_VERSION").map(str::to_owned),
+            profile: option_env!("EXP_BUILD_PROFILE").map(str::to_owned),
+            features: option_env!("EXP_BUILD_FEATURES")
+                .map(|features| {
+                    features
+                        .split(',')
+                        .filter(|feature| !feature.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            lockfile_hash: option_env!("EXP_BUILD_LOCKFILE_HASH").map(str::to_owned),
+        }
+    };
+}
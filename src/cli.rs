@@ -0,0 +1,382 @@
+//! Command line arguments shared by experiment binaries.
+//!
+//! ```no_run
+//! # async fn example<E: exp::Experiment + Send>(mut experiment: E) -> Result<(), exp::cli::CliError>
+//! # where E::Configuration: Send + Sync {
+//! exp::main_helper(&mut experiment).await
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use clap::{Parser, ValueEnum};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::expand::{pointer, set_fields};
+use crate::{
+    analyse, run, run_coordinator, run_worker, AnalyseConfig, AnalyseError, AnalysisDirs,
+    ConfigFileError, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    ExperimentMetadata, Measurements, Requirements, RunConfig, RunConfigBuilder, RunConfigError,
+    RunError,
+};
+
+/// Arguments for running and analysing an experiment, for use as `ExpArgs::parse()` or
+/// flattened into a binary's own arguments with `#[command(flatten)]`.
+#[derive(Debug, Parser)]
+pub struct ExpArgs {
+    /// Whether to run the experiment, analyse its results, or both.
+    #[arg(value_enum, default_value_t = Mode::All)]
+    pub mode: Mode,
+    /// TOML or YAML file of run settings, see `RunConfigBuilder::from_file`.
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// Where to put the results, overriding the settings file. Defaults to `results`.
+    #[arg(short, long)]
+    pub results_dir: Option<PathBuf>,
+    /// How many times to run each configuration.
+    #[arg(long)]
+    pub repeats: Option<usize>,
+    /// Most configurations to run at once, if the experiment has replicas.
+    #[arg(long)]
+    pub max_parallel: Option<usize>,
+    /// Fail a configuration run after this many seconds.
+    #[arg(long, value_parser = parse_seconds)]
+    pub timeout: Option<Duration>,
+    /// Seed to derive the seeds of each configuration run from.
+    #[arg(long)]
+    pub base_seed: Option<u64>,
+    /// Append a quick look at each completed run to `live-results.csv`.
+    #[arg(long)]
+    pub live_results: bool,
+    /// Stop starting configurations after this many seconds.
+    #[arg(long, value_parser = parse_seconds)]
+    pub max_duration: Option<Duration>,
+    /// Sleep this many seconds between configurations.
+    #[arg(long, value_parser = parse_seconds)]
+    pub cooldown: Option<Duration>,
+    /// Only run and analyse configurations whose field, as a dotted path, has this value, such
+    /// as `nodes=3`. Values are parsed as JSON, falling back to a string.
+    #[arg(long, value_parser = parse_field)]
+    pub filter: Vec<(String, Value)>,
+    /// Set a field, as a dotted path, of every configuration before running it, such as
+    /// `--set nodes=5`, for one-off variations. Values are parsed as JSON, falling back to a
+    /// string. Filters apply to the configurations after these are set.
+    #[arg(long, value_parser = parse_field)]
+    pub set: Vec<(String, Value)>,
+    /// Only read the configurations modified since the last analysis.
+    #[arg(long)]
+    pub incremental: bool,
+    /// Hand out configurations to workers on this address to run them in parallel, rather
+    /// than running them here.
+    #[arg(long, conflicts_with = "worker")]
+    pub coordinate: Option<SocketAddr>,
+    /// Run configurations handed out by the coordinator on this address.
+    #[arg(long)]
+    pub worker: Option<SocketAddr>,
+    /// Show a live dashboard of the run, needs the `tui` feature.
+    #[arg(long)]
+    pub tui: bool,
+    /// Write the framework's logs for each configuration to `framework.log` in its directory,
+    /// needs the `FrameworkLog` tracing layer.
+    #[arg(long)]
+    pub framework_log: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    Run,
+    Analyse,
+    All,
+}
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error(transparent)]
+    ConfigFile(#[from] ConfigFileError),
+    #[error(transparent)]
+    Invalid(#[from] RunConfigError),
+    #[error(transparent)]
+    Run(#[from] RunError),
+    #[error(transparent)]
+    Analyse(#[from] AnalyseError),
+    #[error("invalid --set for a configuration: {0}")]
+    Override(String),
+}
+
+impl ExpArgs {
+    /// The run settings from the settings file, if any, with the arguments applied over them.
+    pub fn run_config(&self) -> Result<RunConfig, CliError> {
+        let mut builder = match &self.config {
+            Some(path) => RunConfigBuilder::from_file(path)?,
+            None => RunConfig::builder().results_dir("results"),
+        };
+        if let Some(results_dir) = &self.results_dir {
+            builder = builder.results_dir(results_dir);
+        }
+        if let Some(repeats) = self.repeats {
+            builder = builder.repeats(repeats);
+        }
+        if let Some(max_parallel) = self.max_parallel {
+            builder = builder.max_parallel(max_parallel);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(base_seed) = self.base_seed {
+            builder = builder.base_seed(base_seed);
+        }
+        if let Some(max_duration) = self.max_duration {
+            builder = builder.max_duration(max_duration);
+        }
+        if let Some(cooldown) = self.cooldown {
+            builder = builder.cooldown(cooldown);
+        }
+        if self.tui {
+            builder = builder.tui(true);
+        }
+        if self.framework_log {
+            builder = builder.framework_log(true);
+        }
+        if self.live_results {
+            builder = builder.live_results(true);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// The analysis settings for the results in `results_dir`.
+    pub fn analyse_config(&self, results_dir: &Path) -> AnalyseConfig {
+        let filters = self.filter.clone();
+        AnalyseConfig {
+            results_dir: results_dir.to_owned(),
+            output_dir: None,
+            incremental: self.incremental,
+            filter: if filters.is_empty() {
+                None
+            } else {
+                Some(Box::new(move |configuration| {
+                    matches_filters(&filters, configuration)
+                }))
+            },
+            incomplete: Default::default(),
+        }
+    }
+
+    /// Run and analyse the experiment as the arguments say.
+    pub async fn execute<E>(&self, experiment: &mut E) -> Result<(), CliError>
+    where
+        E: Experiment + Send,
+        E::Configuration: Send + Sync,
+    {
+        let run_config = self.run_config()?;
+        if matches!(self.mode, Mode::Run | Mode::All) {
+            let mut experiment = Adjusted {
+                experiment: Instance::Borrowed(&mut *experiment),
+                overrides: &self.set,
+                filters: &self.filter,
+                invalid: None,
+            };
+            if let Some(listen) = self.coordinate {
+                run_coordinator(&mut experiment, &run_config, listen).await?;
+            } else if let Some(coordinator) = self.worker {
+                run_worker(&mut experiment, &run_config, coordinator).await?;
+            } else {
+                run(&mut experiment, &run_config).await?;
+            }
+            if let Some(error) = experiment.invalid {
+                return Err(CliError::Override(error));
+            }
+        }
+        // workers send their results to the coordinator, which analyses them
+        if matches!(self.mode, Mode::Analyse | Mode::All) && self.worker.is_none() {
+            let summary =
+                analyse(experiment, &self.analyse_config(&run_config.results_dir)).await?;
+            info!(%summary, "Analysed experiment");
+        }
+        Ok(())
+    }
+}
+
+/// Parse the arguments from the command line then run and analyse the experiment as they say,
+/// so experiment binaries can be a single call from `main`.
+pub async fn main_helper<E>(experiment: &mut E) -> Result<(), CliError>
+where
+    E: Experiment + Send,
+    E::Configuration: Send + Sync,
+{
+    ExpArgs::parse().execute(experiment).await
+}
+
+/// Parse a `field=value` argument, the value as JSON or else a string.
+fn parse_field(arg: &str) -> Result<(String, Value), String> {
+    let (field, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected field=value, got {:?}", arg))?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()));
+    Ok((field.to_owned(), value))
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let seconds = arg.parse::<f64>().map_err(|error| error.to_string())?;
+    Duration::try_from_secs_f64(seconds).map_err(|error| error.to_string())
+}
+
+fn matches_filters(filters: &[(String, Value)], configuration: &Value) -> bool {
+    filters
+        .iter()
+        .all(|(field, value)| configuration.pointer(&pointer(field)) == Some(value))
+}
+
+/// Apply the overrides to a configuration, going through its JSON.
+fn override_configuration<C: ExperimentConfiguration>(
+    configuration: &C,
+    overrides: &[(String, Value)],
+) -> Result<C, String> {
+    let mut value = serde_json::to_value(configuration).map_err(|error| error.to_string())?;
+    set_fields(&mut value, overrides)?;
+    serde_json::from_value(value).map_err(|error| error.to_string())
+}
+
+/// An experiment that gives its configurations with the overrides set, keeping only those
+/// matching the filters.
+struct Adjusted<'a, E> {
+    experiment: Instance<'a, E>,
+    overrides: &'a [(String, Value)],
+    filters: &'a [(String, Value)],
+    /// Why the overrides couldn't be applied to a configuration, which is then left out.
+    invalid: Option<String>,
+}
+
+/// The experiment being adjusted, or a replica of it.
+enum Instance<'a, E> {
+    Borrowed(&'a mut E),
+    Replica(E),
+}
+
+impl<'a, E> Deref for Instance<'a, E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        match self {
+            Instance::Borrowed(experiment) => experiment,
+            Instance::Replica(experiment) => experiment,
+        }
+    }
+}
+
+impl<'a, E> DerefMut for Instance<'a, E> {
+    fn deref_mut(&mut self) -> &mut E {
+        match self {
+            Instance::Borrowed(experiment) => experiment,
+            Instance::Replica(experiment) => experiment,
+        }
+    }
+}
+
+#[async_trait]
+impl<'a, E> Experiment for Adjusted<'a, E>
+where
+    E: Experiment + Send,
+    E::Configuration: Send + Sync,
+{
+    type Configuration = E::Configuration;
+
+    fn metadata(&self) -> ExperimentMetadata {
+        self.experiment.metadata()
+    }
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        let mut configurations = self.experiment.configurations();
+        if !self.overrides.is_empty() {
+            let overrides = self.overrides;
+            let invalid = &mut self.invalid;
+            configurations = configurations
+                .iter()
+                .filter_map(|configuration| {
+                    match override_configuration(configuration, overrides) {
+                        Ok(configuration) => Some(configuration),
+                        Err(error) => {
+                            warn!(%error, "Skipping configuration that couldn't be overridden");
+                            invalid.get_or_insert(error);
+                            None
+                        }
+                    }
+                })
+                .collect();
+        }
+        if self.filters.is_empty() {
+            return configurations;
+        }
+        configurations
+            .into_iter()
+            .filter(|configuration| match serde_json::to_value(configuration) {
+                Ok(value) => matches_filters(self.filters, &value),
+                Err(error) => {
+                    warn!(%error, "Skipping configuration that couldn't be serialized");
+                    false
+                }
+            })
+            .collect()
+    }
+
+    fn requirements(&self, configurations: &[Self::Configuration]) -> Requirements {
+        self.experiment.requirements(configurations)
+    }
+
+    fn images(&self, configurations: &[Self::Configuration]) -> Vec<String> {
+        self.experiment.images(configurations)
+    }
+
+    fn replica(&self) -> Option<Self> {
+        Some(Adjusted {
+            experiment: Instance::Replica(self.experiment.replica()?),
+            overrides: self.overrides,
+            filters: self.filters,
+            invalid: None,
+        })
+    }
+
+    async fn pre_run(&mut self, configuration: &Self::Configuration) -> ExpResult<()> {
+        self.experiment.pre_run(configuration).await
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()> {
+        self.experiment
+            .run(configuration, configuration_dir, measurements)
+            .await
+    }
+
+    async fn post_run(&mut self, configuration: &Self::Configuration) -> ExpResult<()> {
+        self.experiment.post_run(configuration).await
+    }
+
+    fn quick_look(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+    ) -> ExpResult<BTreeMap<String, f64>> {
+        self.experiment.quick_look(configuration, configuration_dir)
+    }
+
+    fn analyse(
+        &mut self,
+        dirs: &AnalysisDirs,
+        environment: Environment,
+        configurations: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<Value> {
+        self.experiment.analyse(dirs, environment, configurations)
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+use tracing::info;
+
+use crate::docker_runner::Stats;
+use crate::results::{flatten, list_configurations, ConfigurationState};
+use crate::stats::{t_test, Summary};
+use crate::workload::WorkloadMetric;
+use crate::ExpResult;
+
+/// Measurements of a configuration run, keyed by the name of the metric.
+pub type Metrics = BTreeMap<String, Vec<f64>>;
+
+/// How configurations of two results directories are matched up.
+#[derive(Debug, Clone)]
+pub enum MatchBy {
+    /// Configurations with the same hash.
+    Hash,
+    /// Configurations with the same fields, ignoring those at the given dotted paths and their
+    /// children, such as the image tag of the build under test.
+    Fields(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct CompareConfig {
+    pub match_by: MatchBy,
+    /// Relative change of the mean of a metric, such as `0.05` for 5%, beyond which a worse
+    /// result is a regression.
+    pub threshold: f64,
+    /// p-value below which a change is significant.
+    pub significance: f64,
+    /// Metrics for which higher is better, such as throughput. An increase of any other metric
+    /// is worse.
+    pub higher_is_better: Vec<String>,
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        Self {
+            match_by: MatchBy::Hash,
+            threshold: 0.05,
+            significance: 0.05,
+            higher_is_better: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CompareError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("failed to load metrics of {path:?}: {error}")]
+    Metrics {
+        path: PathBuf,
+        error: Box<dyn Error + Send + Sync>,
+    },
+}
+
+/// The change of a metric between matched configurations.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricDelta {
+    /// The configuration as run in the first results directory.
+    pub configuration: Value,
+    pub hash_a: String,
+    pub hash_b: String,
+    pub metric: String,
+    /// Summary of the mean of each run.
+    pub a: Summary,
+    /// Summary of the mean of each run.
+    pub b: Summary,
+    /// Change of the mean relative to `a`.
+    pub change: f64,
+    /// p-value of Welch's t-test of the means of the runs, `None` with fewer than two runs on
+    /// either side.
+    pub p_value: Option<f64>,
+    /// Whether the change is worse by more than the threshold and significant, or without a
+    /// p-value just worse by more than the threshold.
+    pub regression: bool,
+}
+
+/// The result of comparing two results directories.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Comparison {
+    pub deltas: Vec<MetricDelta>,
+    /// Hashes of the configurations only in the first results directory.
+    pub only_a: Vec<String>,
+    /// Hashes of the configurations only in the second results directory.
+    pub only_b: Vec<String>,
+}
+
+impl Comparison {
+    pub fn regressions(&self) -> impl Iterator<Item = &MetricDelta> {
+        self.deltas.iter().filter(|delta| delta.regression)
+    }
+}
+
+/// Compare the container stats of the completed configurations of two results directories, such
+/// as from two builds of the system under test.
+///
+/// See `compare_with` for comparing other metrics.
+pub fn compare(
+    results_a: &Path,
+    results_b: &Path,
+    config: &CompareConfig,
+) -> Result<Comparison, CompareError> {
+    compare_with(results_a, results_b, config, container_metrics)
+}
+
+/// Compare the completed configurations of two results directories, loading the metrics of each
+/// configuration run with `metrics`.
+///
+/// The measurements of each configuration run are averaged to one value per run, as those of a
+/// single run aren't independent, and the runs of configurations that match the same
+/// configuration on the other side, such as repeats differing only in ignored fields, are merged.
+/// So it takes several repeats of each configuration to tell whether a change is significant.
+pub fn compare_with<F>(
+    results_a: &Path,
+    results_b: &Path,
+    config: &CompareConfig,
+    mut metrics: F,
+) -> Result<Comparison, CompareError>
+where
+    F: FnMut(&Path) -> ExpResult<Metrics>,
+{
+    let groups_a = group(results_a, &config.match_by)?;
+    let mut groups_b = group(results_b, &config.match_by)?;
+    let mut comparison = Comparison::default();
+    for (key, a) in groups_a {
+        let b = match groups_b.remove(&key) {
+            Some(b) => b,
+            None => {
+                comparison.only_a.push(a.hash);
+                continue;
+            }
+        };
+        let metrics_a = load_metrics(&a.dirs, &mut metrics)?;
+        let metrics_b = load_metrics(&b.dirs, &mut metrics)?;
+        for (metric, values_a) in metrics_a {
+            let values_b = match metrics_b.get(&metric) {
+                Some(values_b) => values_b,
+                None => continue,
+            };
+            let (summary_a, summary_b) = match (Summary::of(&values_a), Summary::of(values_b)) {
+                (Some(summary_a), Some(summary_b)) => (summary_a, summary_b),
+                _ => continue,
+            };
+            let change = relative_change(summary_a.mean, summary_b.mean);
+            let p_value = t_test(&values_a, values_b).map(|test| test.p_value);
+            let worse = if config.higher_is_better.contains(&metric) {
+                change < -config.threshold
+            } else {
+                change > config.threshold
+            };
+            let regression = worse && p_value.is_none_or(|p| p < config.significance);
+            comparison.deltas.push(MetricDelta {
+                configuration: a.configuration.clone(),
+                hash_a: a.hash.clone(),
+                hash_b: b.hash.clone(),
+                metric,
+                a: summary_a,
+                b: summary_b,
+                change,
+                p_value,
+                regression,
+            });
+        }
+    }
+    comparison.only_b = groups_b.into_values().map(|b| b.hash).collect();
+    info!(
+        compared = comparison.deltas.len(),
+        regressions = comparison.regressions().count(),
+        "Compared results"
+    );
+    Ok(comparison)
+}
+
+/// The CPU and memory usage of each container of a configuration run, as
+/// `<container>.cpu_percentage` and `<container>.memory_bytes`.
+pub fn container_metrics(configuration_dir: &Path) -> ExpResult<Metrics> {
+    let mut metrics = Metrics::new();
+    for (name, stat) in Stats::from_configuration(configuration_dir)? {
+        if let Some(percentage) = stat.cpu_percentage() {
+            metrics
+                .entry(format!("{}.cpu_percentage", name))
+                .or_default()
+                .push(percentage);
+        }
+        if let Some(usage) = stat.memory_stats_usage {
+            metrics
+                .entry(format!("{}.memory_bytes", name))
+                .or_default()
+                .push(usage as f64);
+        }
+    }
+    Ok(metrics)
+}
+
+/// The metrics of each workload of a configuration run written by a `WorkloadExtractor`, as
+/// `<container>.<metric>` or `<container>.<operation>.<metric>` for those of an operation.
+pub fn workload_metrics(configuration_dir: &Path) -> ExpResult<Metrics> {
+    let mut metrics = Metrics::new();
+    for metric in WorkloadMetric::from_configuration(configuration_dir)? {
+        let name = match &metric.operation {
+            Some(operation) => format!("{}.{}.{}", metric.container, operation, metric.metric),
+            None => format!("{}.{}", metric.container, metric.metric),
+        };
+        metrics.entry(name).or_default().push(metric.value);
+    }
+    Ok(metrics)
+}
+
+/// Completed configuration runs that match each other.
+struct Group {
+    /// Hash of the first configuration of the group.
+    hash: String,
+    configuration: Value,
+    dirs: Vec<PathBuf>,
+}
+
+fn group(experiment_dir: &Path, match_by: &MatchBy) -> std::io::Result<BTreeMap<String, Group>> {
+    let mut groups = BTreeMap::<String, Group>::new();
+    for entry in list_configurations(experiment_dir)? {
+        if entry.state != ConfigurationState::Completed {
+            continue;
+        }
+        let configuration = entry.configuration.unwrap_or_default();
+        let key = match match_by {
+            MatchBy::Hash => entry.hash.clone(),
+            MatchBy::Fields(ignore) => {
+                let mut fields = BTreeMap::new();
+                flatten(&configuration, String::new(), &mut fields);
+                fields.retain(|path, _| {
+                    !ignore.iter().any(|ignored| {
+                        path == ignored || path.starts_with(&format!("{}.", ignored))
+                    })
+                });
+                serde_json::to_string(&fields)?
+            }
+        };
+        let hash = entry.hash;
+        groups
+            .entry(key)
+            .or_insert_with(|| Group {
+                hash,
+                configuration,
+                dirs: Vec::new(),
+            })
+            .dirs
+            .push(entry.path);
+    }
+    Ok(groups)
+}
+
+/// Load the metrics of the runs, as the mean of each run.
+fn load_metrics<F>(dirs: &[PathBuf], metrics: &mut F) -> Result<Metrics, CompareError>
+where
+    F: FnMut(&Path) -> ExpResult<Metrics>,
+{
+    let mut merged = Metrics::new();
+    for dir in dirs {
+        let loaded = metrics(dir).map_err(|error| CompareError::Metrics {
+            path: dir.clone(),
+            error,
+        })?;
+        for (metric, values) in loaded {
+            if let Some(summary) = Summary::of(&values) {
+                merged.entry(metric).or_default().push(summary.mean);
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Change from `a` to `b` relative to `a`.
+fn relative_change(a: f64, b: f64) -> f64 {
+    if a == b {
+        0.0
+    } else if a == 0.0 {
+        (b - a).signum() * f64::INFINITY
+    } else {
+        (b - a) / a.abs()
+    }
+}
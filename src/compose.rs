@@ -0,0 +1,287 @@
+//! Reuse the services of a docker-compose file as the containers of a run.
+
+use std::{
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::docker_runner::{split_image, ContainerConfig, HostPort, Runner};
+
+#[derive(Debug, Error)]
+pub enum ComposeError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("service {0} has no image, building images isn't supported")]
+    MissingImage(String),
+    #[error("service {service} has an invalid port {port}, only single TCP ports are supported")]
+    InvalidPort { service: String, port: String },
+    #[error("service {service} has an invalid volume {volume}, only bind mounts can be read-only")]
+    InvalidVolume { service: String, volume: String },
+    #[error("service {0} has a command with quotes or escapes, give it as a list instead")]
+    QuotedCommand(String),
+    #[error("service {0} joins more than one network, only one is supported")]
+    MultipleNetworks(String),
+}
+
+/// The containers of a docker-compose file, to add to a `Runner` with `Topology::start`.
+///
+/// Only a subset of the compose format is read: the `image`, `container_name`, `hostname`,
+/// `command`, `environment`, `ports`, `volumes` and `networks` of each service. Other keys, such
+/// as `depends_on` or `healthcheck`, are ignored.
+#[derive(Debug)]
+pub struct Topology {
+    /// Configurations of the services, in the order of the file.
+    pub containers: Vec<ContainerConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ComposeFile {
+    #[serde(default)]
+    services: Mapping,
+}
+
+#[derive(Debug, Deserialize)]
+struct Service {
+    image: Option<String>,
+    container_name: Option<String>,
+    hostname: Option<String>,
+    command: Option<Command>,
+    #[serde(default)]
+    environment: Environment,
+    #[serde(default)]
+    ports: Vec<Value>,
+    #[serde(default)]
+    volumes: Vec<String>,
+    #[serde(default)]
+    networks: Networks,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Command {
+    Shell(String),
+    Exec(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Environment {
+    List(Vec<String>),
+    Map(Mapping),
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::List(Vec::new())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Networks {
+    List(Vec<String>),
+    Map(Mapping),
+}
+
+impl Default for Networks {
+    fn default() -> Self {
+        Networks::List(Vec::new())
+    }
+}
+
+impl Topology {
+    /// Read the services of a docker-compose file.
+    ///
+    /// As with compose, services without networks join a `<project>_default` network, named
+    /// after the directory of the file, so they can reach each other by name, and relative bind
+    /// mounts are relative to that directory.
+    pub fn from_compose(path: &Path) -> Result<Self, ComposeError> {
+        debug!(?path, "Reading compose file");
+        let compose: ComposeFile = serde_yaml::from_str(&read_to_string(path)?)?;
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .canonicalize()?;
+        let project = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "exp".to_owned());
+        let default_network = format!("{}_default", project);
+
+        let mut containers = Vec::new();
+        for (name, service) in compose.services {
+            let name = value_string(&name);
+            let service: Service = serde_yaml::from_value(service)?;
+            containers.push(service.into_container(&name, &dir, &default_network)?);
+        }
+        Ok(Self { containers })
+    }
+
+    /// Add the containers to the runner, in order.
+    pub async fn start(&self, runner: &mut Runner) {
+        for container in &self.containers {
+            runner.add_container(container).await;
+        }
+    }
+}
+
+impl Service {
+    fn into_container(
+        self,
+        service: &str,
+        dir: &Path,
+        default_network: &str,
+    ) -> Result<ContainerConfig, ComposeError> {
+        let image = self
+            .image
+            .ok_or_else(|| ComposeError::MissingImage(service.to_owned()))?;
+        let (image_name, image_tag) = split_image(&image);
+
+        let mut networks = match self.networks {
+            Networks::List(networks) => networks,
+            Networks::Map(networks) => networks.keys().map(value_string).collect(),
+        };
+        if networks.len() > 1 {
+            return Err(ComposeError::MultipleNetworks(service.to_owned()));
+        }
+        let network = networks.pop().unwrap_or_else(|| default_network.to_owned());
+
+        let env = match self.environment {
+            Environment::List(env) => env,
+            Environment::Map(env) => env
+                .iter()
+                .map(|(name, value)| format!("{}={}", value_string(name), value_string(value)))
+                .collect(),
+        };
+
+        let ports = self
+            .ports
+            .iter()
+            .map(|port| {
+                parse_port(&value_string(port)).ok_or_else(|| ComposeError::InvalidPort {
+                    service: service.to_owned(),
+                    port: value_string(port),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut volumes = Vec::new();
+        let mut named_volumes = Vec::new();
+        let mut read_only_volumes = Vec::new();
+        for volume in &self.volumes {
+            let mut parts = volume.splitn(3, ':');
+            let source = parts.next().unwrap_or_default();
+            let target = parts.next();
+            let read_only = match parts.next() {
+                None | Some("rw") => false,
+                Some("ro") if target.is_some() && is_path(source) => true,
+                Some(_) => {
+                    return Err(ComposeError::InvalidVolume {
+                        service: service.to_owned(),
+                        volume: volume.clone(),
+                    })
+                }
+            };
+            match target {
+                Some(target) if is_path(source) => {
+                    let source = expand_path(source, dir);
+                    let volume = (source.to_string_lossy().into_owned(), target.to_owned());
+                    if read_only {
+                        read_only_volumes.push(volume);
+                    } else {
+                        volumes.push(volume);
+                    }
+                }
+                Some(target) => named_volumes.push((source.to_owned(), target.to_owned())),
+                // an anonymous volume
+                None => named_volumes.push((
+                    format!("{}-{}", service, named_volumes.len()),
+                    source.to_owned(),
+                )),
+            }
+        }
+
+        let command = match self.command {
+            // without a shell to run them, only commands of plain words can be split
+            Some(Command::Shell(command)) if command.contains(['"', '\'', '\\']) => {
+                return Err(ComposeError::QuotedCommand(service.to_owned()))
+            }
+            Some(Command::Shell(command)) => Some(
+                command
+                    .split_whitespace()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>(),
+            ),
+            Some(Command::Exec(command)) => Some(command),
+            None => None,
+        };
+
+        Ok(ContainerConfig {
+            name: self.container_name.unwrap_or_else(|| service.to_owned()),
+            image_name,
+            image_tag,
+            pull: true,
+            network: Some(network),
+            hostname: self.hostname,
+            command,
+            env: Some(env).filter(|env| !env.is_empty()),
+            ports: Some(ports).filter(|ports| !ports.is_empty()),
+            volumes,
+            read_only_volumes,
+            named_volumes,
+            ..Default::default()
+        })
+    }
+}
+
+/// Parse a short port mapping, `[[ip:]host:]container[/tcp]`, where a missing host port is
+/// picked by docker. Ports are always published on all interfaces, so the IP is ignored.
+///
+/// Ranges of ports, such as `8000-8010:8000-8010`, aren't supported.
+fn parse_port(port: &str) -> Option<(HostPort, String)> {
+    let port = port.strip_suffix("/tcp").unwrap_or(port);
+    if port.contains(['/', '-']) {
+        return None;
+    }
+    let mut parts = port.rsplit(':');
+    let container = parts.next()?.to_owned();
+    let host = match parts.next() {
+        Some("") | None => HostPort::Auto,
+        Some(host) => HostPort::Fixed(host.to_owned()),
+    };
+    Some((host, container))
+}
+
+fn is_path(source: &str) -> bool {
+    source.starts_with('.') || source.starts_with('/') || source.starts_with('~')
+}
+
+fn expand_path(source: &str, dir: &Path) -> PathBuf {
+    match source.strip_prefix("~/") {
+        Some(rest) => std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(rest),
+        None => dir.join(source.strip_prefix("./").unwrap_or(source)),
+    }
+}
+
+fn value_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        value => serde_yaml::to_string(value)
+            .unwrap_or_default()
+            .trim_end()
+            .to_owned(),
+    }
+}
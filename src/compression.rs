@@ -0,0 +1,96 @@
+use std::{
+    ffi::OsStr,
+    fs::{read_dir, remove_file, File},
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Extension given to compressed files.
+pub const COMPRESSED_EXTENSION: &str = "zst";
+
+/// Options for compressing large files in a configuration directory once it has finished
+/// running.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Only compress files at least this many bytes in size.
+    pub threshold: u64,
+    /// Extensions of files to compress.
+    pub extensions: Vec<String>,
+    /// zstd compression level, 0 uses the zstd default.
+    pub level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 1024 * 1024,
+            extensions: vec!["csv".to_owned(), "log".to_owned(), "jsonl".to_owned()],
+            level: 0,
+        }
+    }
+}
+
+/// Compress matching files under `dir` to `<file>.zst`, removing the originals.
+pub fn compress_dir(dir: &Path, config: &CompressionConfig) -> io::Result<()> {
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            compress_dir(&path, config)?;
+            continue;
+        }
+        let matches = path
+            .extension()
+            .is_some_and(|ext| config.extensions.iter().any(|e| ext == e.as_str()));
+        if matches && entry.metadata()?.len() >= config.threshold {
+            compress_file(&path, config.level)?;
+        }
+    }
+    Ok(())
+}
+
+fn compress_file(path: &Path, level: i32) -> io::Result<()> {
+    let compressed_path = compressed_path(path);
+    debug!(?path, "Compressing file");
+    let mut encoder = zstd::Encoder::new(File::create(&compressed_path)?, level)?;
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    remove_file(path)
+}
+
+/// Open a results file for reading, transparently decompressing it.
+///
+/// If `path` does not exist but `<path>.zst` does then that is read instead, so loaders can use
+/// the original file names whether or not the directory has been compressed.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    if path.extension() == Some(OsStr::new(COMPRESSED_EXTENSION)) {
+        return Ok(Box::new(zstd::Decoder::new(File::open(path)?)?));
+    }
+    match File::open(path) {
+        Ok(file) => Ok(Box::new(BufReader::new(file))),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            let compressed_path = compressed_path(path);
+            if compressed_path.exists() {
+                Ok(Box::new(zstd::Decoder::new(File::open(compressed_path)?)?))
+            } else {
+                Err(error)
+            }
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// File name of `path`, without any compressed extension.
+pub(crate) fn uncompressed_name(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.strip_suffix(&format!(".{}", COMPRESSED_EXTENSION))
+        .unwrap_or(&name)
+        .to_owned()
+}
+
+fn compressed_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), COMPRESSED_EXTENSION))
+}
@@ -0,0 +1,67 @@
+use std::{
+    env,
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+};
+
+use regex::{Captures, Regex};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::RunConfigError;
+
+#[derive(Debug, Error)]
+pub enum ConfigFileError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("unknown config file format for {0:?}, expected .toml, .yaml or .yml")]
+    UnknownFormat(PathBuf),
+    #[error("environment variable {0} is not set and has no default")]
+    MissingVariable(String),
+    #[error(transparent)]
+    Invalid(#[from] RunConfigError),
+}
+
+/// Read settings from a TOML or YAML file, chosen by its extension, after interpolating
+/// environment variables into it.
+pub(crate) fn read<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigFileError> {
+    debug!(?path, "Reading config file");
+    let text = interpolate(&read_to_string(path)?)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => Ok(toml::from_str(&text)?),
+        Some("yaml" | "yml") => Ok(serde_yaml::from_str(&text)?),
+        _ => Err(ConfigFileError::UnknownFormat(path.to_owned())),
+    }
+}
+
+/// Replace `${VAR}` with the value of the environment variable `VAR`, or `default` for
+/// `${VAR:-default}` if it isn't set. `$$` is a literal `$`.
+fn interpolate(text: &str) -> Result<String, ConfigFileError> {
+    let pattern =
+        Regex::new(r"\$(?:\$|\{([A-Za-z_][A-Za-z0-9_]*)(?::-([^}]*))?\})").expect("valid regex");
+    let mut missing = None;
+    let interpolated = pattern.replace_all(text, |captures: &Captures| {
+        let name = match captures.get(1) {
+            Some(name) => name.as_str(),
+            None => return "$".to_owned(),
+        };
+        match (env::var(name), captures.get(2)) {
+            (Ok(value), _) => value,
+            (Err(_), Some(default)) => default.as_str().to_owned(),
+            (Err(_), None) => {
+                missing.get_or_insert_with(|| name.to_owned());
+                String::new()
+            }
+        }
+    });
+    match missing {
+        Some(name) => Err(ConfigFileError::MissingVariable(name)),
+        None => Ok(interpolated.into_owned()),
+    }
+}
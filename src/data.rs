@@ -0,0 +1,413 @@
+//! Load the CSVs collected during runs as polars `DataFrame`s, and export them as Parquet.
+
+use std::{
+    fs::{create_dir_all, read_dir, remove_file, File},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use tracing::info;
+
+use crate::compression;
+use crate::power::POWER_FILE;
+use crate::results::{list_configurations, ConfigurationState};
+
+/// The type a CSV column is loaded as.
+#[derive(Debug, Clone, Copy)]
+enum Column {
+    Time,
+    String,
+    U32,
+    U64,
+    F32,
+    F64,
+    /// Nanoseconds since the unix epoch.
+    TimeNanos,
+}
+
+/// Load all docker stats of a configuration run, from `metrics/docker-<name>-stat.csv` including
+/// those in phase subdirectories.
+///
+/// `container` and `phase` columns are added to tell the files apart, `phase` being null for
+/// stats from before any phase was started. `read` and `preread` are UTC datetimes, and the
+/// derived `cpu_percent`, `memory_percent` and `network_*_bytes_per_second` are floats.
+pub fn load_container_stats(configuration_dir: &Path) -> PolarsResult<DataFrame> {
+    load_metrics(
+        configuration_dir,
+        CONTAINER_STATS_FILES,
+        "container",
+        container_stats_column,
+    )
+}
+
+const CONTAINER_STATS_FILES: (&str, &str) = ("docker-", "-stat.csv");
+
+fn container_stats_column(column: &str) -> Column {
+    match column {
+        "read" | "preread" => Column::Time,
+        "networks_name" | "networks_names" => Column::String,
+        "num_procs" => Column::U32,
+        "cpu_percent"
+        | "memory_percent"
+        | "network_rx_bytes_per_second"
+        | "network_tx_bytes_per_second" => Column::F64,
+        _ => Column::U64,
+    }
+}
+
+/// Load the processes running in the containers of a configuration run, from
+/// `metrics/docker-<name>-top.csv` including those in phase subdirectories.
+///
+/// Columns are as from `ps aux`, with `container` and `phase` columns added as for
+/// `load_container_stats`. `timestamp_nanos` is a UTC datetime. See
+/// `docker_runner::TopRecord` for the processes with the columns of other `ps`s normalized.
+pub fn load_container_top(configuration_dir: &Path) -> PolarsResult<DataFrame> {
+    load_metrics(
+        configuration_dir,
+        CONTAINER_TOP_FILES,
+        "container",
+        container_top_column,
+    )
+}
+
+const CONTAINER_TOP_FILES: (&str, &str) = ("docker-", "-top.csv");
+
+fn container_top_column(column: &str) -> Column {
+    match column {
+        "timestamp_nanos" => Column::TimeNanos,
+        "%CPU" | "%MEM" => Column::F32,
+        "PID" | "VSZ" | "RSS" | "RSZ" => Column::U64,
+        _ => Column::String,
+    }
+}
+
+/// Load the measurements of all `ProcessMonitor`s of a configuration run, from
+/// `metrics/process-<program>.csv`.
+///
+/// A `program` column is added to tell the files apart, and a `phase` column as for
+/// `load_container_stats`.
+pub fn load_process_monitors(configuration_dir: &Path) -> PolarsResult<DataFrame> {
+    load_metrics(
+        configuration_dir,
+        PROCESS_MONITOR_FILES,
+        "program",
+        process_monitor_column,
+    )
+}
+
+const PROCESS_MONITOR_FILES: (&str, &str) = ("process-", ".csv");
+
+/// Load the measurements written by a `ProcessMonitor`, either as written or converted to
+/// Parquet by `convert_to_parquet`.
+///
+/// `time` is a UTC datetime.
+pub fn load_process_monitor(path: &Path) -> PolarsResult<DataFrame> {
+    if path.extension().is_some_and(|ext| ext == "parquet") {
+        load_parquet(path)
+    } else {
+        load_csv(path, process_monitor_column)
+    }
+}
+
+fn process_monitor_column(column: &str) -> Column {
+    match column {
+        "time" => Column::Time,
+        "name" => Column::String,
+        "pid" | "parent" => Column::U32,
+        "cpu_usage_percentage" | "cpu_usage_per_core_percentage" => Column::F32,
+        "disk_write_bytes_per_second" | "disk_read_bytes_per_second" => Column::F64,
+        _ => Column::U64,
+    }
+}
+
+/// Load the power samples of a configuration run, from `metrics/power.csv`.
+///
+/// `time` is a UTC datetime.
+pub fn load_power(configuration_dir: &Path) -> PolarsResult<DataFrame> {
+    let mut frames = Vec::new();
+    for (phase, path) in metrics_files(&configuration_dir.join("metrics"))? {
+        if phase.is_none() && compression::uncompressed_name(&path) == POWER_FILE {
+            frames.push(load_csv(&path, |column| match column {
+                "time" => Column::Time,
+                "source" => Column::String,
+                _ => Column::F64,
+            })?);
+        }
+    }
+    stack(frames)
+}
+
+/// Load the measurements written by a `SystemMonitor`.
+///
+/// `time` is a UTC datetime, byte counts are integers and percentages and rates are floats.
+pub fn load_system_monitor(path: &Path) -> PolarsResult<DataFrame> {
+    load_csv(path, |column| match column {
+        "time" => Column::Time,
+        column if column.ends_with("_bytes") => Column::U64,
+        _ => Column::F64,
+    })
+}
+
+/// Loads a kind of metric from a configuration directory.
+pub(crate) type Loader = fn(&Path) -> PolarsResult<DataFrame>;
+
+/// The kinds of metric collected during runs, by the name of their dataset.
+pub(crate) const DATASETS: [(&str, Loader); 4] = [
+    ("container_stats", load_container_stats),
+    ("container_top", load_container_top),
+    ("process_monitor", load_process_monitors),
+    ("power", load_power),
+];
+
+/// Export the metrics of every completed configuration as Parquet, for loading into tools like
+/// pandas or duckdb.
+///
+/// Each kind of metric is a dataset directory in `output_dir`, `container_stats`,
+/// `container_top`, `process_monitor` and `power`, partitioned into a `<hash>/data.parquet` file per
+/// configuration. Every row has a `configuration` column of the configuration hash.
+///
+/// Returns the paths of the files written.
+pub fn export_parquet(experiment_dir: &Path, output_dir: &Path) -> PolarsResult<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for entry in list_configurations(experiment_dir)? {
+        if entry.state != ConfigurationState::Completed {
+            continue;
+        }
+        for (dataset, load) in DATASETS {
+            let mut df = load_configuration(&entry.hash, &entry.path, load)?;
+            if df.height() == 0 {
+                continue;
+            }
+            let dir = output_dir.join(dataset).join(&entry.hash);
+            create_dir_all(&dir)?;
+            let path = dir.join("data.parquet");
+            ParquetWriter::new(File::create(&path)?).finish(&mut df)?;
+            written.push(path);
+        }
+    }
+    info!(
+        files = written.len(),
+        ?output_dir,
+        "Exported metrics to parquet"
+    );
+    Ok(written)
+}
+
+/// The type of each column of a kind of metric, by column name.
+type ColumnTypes = fn(&str) -> Column;
+
+/// The metrics `convert_to_parquet` converts, by their file names.
+const PARQUET_METRICS: [((&str, &str), ColumnTypes); 7] = [
+    (CONTAINER_STATS_FILES, container_stats_column),
+    (("docker-", "-cpu.csv"), container_record_column),
+    (("docker-", "-memory.csv"), container_record_column),
+    (("docker-", "-network.csv"), container_record_column),
+    (("docker-", "-blkio.csv"), container_record_column),
+    (CONTAINER_TOP_FILES, container_top_column),
+    (PROCESS_MONITOR_FILES, process_monitor_column),
+];
+
+/// Columns of the typed records of `docker_runner::ContainerStats`.
+fn container_record_column(column: &str) -> Column {
+    match column {
+        "read" => Column::Time,
+        "interface" => Column::String,
+        "percent" | "rx_bytes_per_second" | "tx_bytes_per_second" => Column::F64,
+        _ => Column::U64,
+    }
+}
+
+/// Convert the container stats, both flattened and split, container processes and process
+/// monitor measurements of a configuration run from CSV to Parquet, alongside them with a
+/// `.parquet` extension, removing the CSVs.
+///
+/// Parquet stores the types of the columns and skips the mostly empty ones of container stats,
+/// so the files are much smaller and faster to load. The loaders in this module read either
+/// format, but those outside it, like `docker_runner::Stats::from_configuration` used by
+/// `exp::compare` and `exp::plot`, only read CSVs and fail on converted runs.
+///
+/// Returns the paths of the files written.
+pub fn convert_to_parquet(configuration_dir: &Path) -> PolarsResult<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for (_, path) in metrics_files(&configuration_dir.join("metrics"))? {
+        let file_name = compression::uncompressed_name(&path);
+        let column_type = PARQUET_METRICS
+            .iter()
+            .find_map(|((prefix, suffix), column_type)| {
+                (file_name.starts_with(prefix) && file_name.ends_with(suffix))
+                    .then_some(column_type)
+            });
+        let column_type = match column_type {
+            Some(column_type) => column_type,
+            None => continue,
+        };
+        let mut df = load_csv(&path, column_type)?;
+        let parquet_path = path.with_file_name(parquet_name(&file_name));
+        ParquetWriter::new(File::create(&parquet_path)?).finish(&mut df)?;
+        remove_file(&path)?;
+        written.push(parquet_path);
+    }
+    info!(
+        files = written.len(),
+        ?configuration_dir,
+        "Converted metrics to parquet"
+    );
+    Ok(written)
+}
+
+/// `<name>.parquet` for a `<name>.csv` file name.
+fn parquet_name(csv_name: &str) -> String {
+    format!("{}.parquet", csv_name.trim_end_matches(".csv"))
+}
+
+fn load_parquet(path: &Path) -> PolarsResult<DataFrame> {
+    ParquetReader::new(File::open(path)?).finish()
+}
+
+/// Load a kind of metric from a configuration directory, adding a `configuration` column of its
+/// hash.
+pub(crate) fn load_configuration(
+    hash: &str,
+    configuration_dir: &Path,
+    load: Loader,
+) -> PolarsResult<DataFrame> {
+    let mut df = load(configuration_dir)?;
+    let height = df.height();
+    if height > 0 {
+        df.with_column(Series::new("configuration", vec![hash.to_owned(); height]))?;
+    }
+    Ok(df)
+}
+
+/// Load the metrics files named `<prefix><name><suffix>`, or converted to Parquet, adding a
+/// `name_column` of their name and a `phase` column.
+fn load_metrics<F: Fn(&str) -> Column>(
+    configuration_dir: &Path,
+    (prefix, suffix): (&str, &str),
+    name_column: &str,
+    column_type: F,
+) -> PolarsResult<DataFrame> {
+    let metrics_dir = configuration_dir.join("metrics");
+    let mut frames = Vec::new();
+    let parquet_suffix = parquet_name(suffix);
+    for (phase, path) in metrics_files(&metrics_dir)? {
+        let file_name = compression::uncompressed_name(&path);
+        let (name, parquet) = match file_name.strip_prefix(prefix).and_then(|name| {
+            name.strip_suffix(suffix)
+                .map(|name| (name, false))
+                .or_else(|| name.strip_suffix(&parquet_suffix).map(|name| (name, true)))
+        }) {
+            Some((name, parquet)) => (name.to_owned(), parquet),
+            None => continue,
+        };
+        let mut df = if parquet {
+            load_parquet(&path)?
+        } else {
+            load_csv(&path, &column_type)?
+        };
+        let height = df.height();
+        df.with_column(Series::new(name_column, vec![name; height]))?;
+        df.with_column(Series::new("phase", vec![phase; height]))?;
+        frames.push(df);
+    }
+    stack(frames)
+}
+
+/// Files in the metrics directory and its phase subdirectories, with their phase.
+fn metrics_files(metrics_dir: &Path) -> PolarsResult<Vec<(Option<String>, PathBuf)>> {
+    let mut files = Vec::new();
+    if !metrics_dir.exists() {
+        return Ok(files);
+    }
+    for entry in read_dir(metrics_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let phase = compression::uncompressed_name(&path);
+            for entry in read_dir(&path)? {
+                files.push((Some(phase.clone()), entry?.path()));
+            }
+        } else {
+            files.push((None, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn load_csv<F: Fn(&str) -> Column>(path: &Path, column_type: F) -> PolarsResult<DataFrame> {
+    let mut reader = csv::Reader::from_reader(compression::open(path)?);
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let mut columns = vec![Vec::new(); headers.len()];
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        for (column, value) in columns.iter_mut().zip(record.iter()) {
+            column.push(value.to_owned());
+        }
+    }
+    let series = headers
+        .iter()
+        .zip(columns)
+        .map(|(name, values)| to_series(name, column_type(name), values))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    DataFrame::new(series)
+}
+
+fn to_series(name: &str, column: Column, values: Vec<String>) -> PolarsResult<Series> {
+    fn parse<T: std::str::FromStr>(name: &str, values: &[String]) -> PolarsResult<Vec<Option<T>>> {
+        values
+            .iter()
+            .map(|value| {
+                if value.is_empty() {
+                    Ok(None)
+                } else {
+                    value.parse().map(Some).map_err(|_| {
+                        polars_err!(ComputeError: "invalid value {:?} in column {}", value, name)
+                    })
+                }
+            })
+            .collect()
+    }
+    Ok(match column {
+        Column::Time => {
+            let millis = parse::<DateTime<Utc>>(name, &values)?
+                .into_iter()
+                .map(|time| time.map(|time| time.timestamp_millis()))
+                .collect::<Vec<_>>();
+            Series::new(name, millis).cast(&DataType::Datetime(
+                TimeUnit::Milliseconds,
+                Some("UTC".to_owned()),
+            ))?
+        }
+        Column::TimeNanos => Series::new(name, parse::<i64>(name, &values)?).cast(
+            &DataType::Datetime(TimeUnit::Nanoseconds, Some("UTC".to_owned())),
+        )?,
+        Column::String => Series::new(
+            name,
+            values
+                .into_iter()
+                .map(|value| (!value.is_empty()).then_some(value))
+                .collect::<Vec<_>>(),
+        ),
+        Column::U32 => Series::new(name, parse::<u32>(name, &values)?),
+        Column::U64 => Series::new(name, parse::<u64>(name, &values)?),
+        Column::F32 => Series::new(name, parse::<f32>(name, &values)?),
+        Column::F64 => Series::new(name, parse::<f64>(name, &values)?),
+    })
+}
+
+pub(crate) fn stack(frames: Vec<DataFrame>) -> PolarsResult<DataFrame> {
+    let mut frames = frames.into_iter();
+    let mut df = match frames.next() {
+        Some(df) => df,
+        None => return Ok(DataFrame::default()),
+    };
+    for other in frames {
+        df.vstack_mut(&other)?;
+    }
+    Ok(df)
+}
+
+fn csv_error(error: csv::Error) -> PolarsError {
+    polars_err!(ComputeError: "failed to read csv: {}", error)
+}
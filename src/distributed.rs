@@ -0,0 +1,482 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{create_dir_all, read_dir, remove_dir_all, rename},
+    io,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Notify,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    events::{self, RunEvent},
+    provenance::collect_provenance,
+    results::run_name,
+    run::{
+        check_requirements, collect_environment_data, create_experiment_dir, lock_experiment_dir,
+        select_configurations, FinishedRun, Session,
+    },
+    schedule::order_configurations,
+    Experiment, ExperimentConfiguration, RunConfig, RunError,
+};
+
+/// How long a worker waits before asking again when no configurations are available.
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Messages sent from a worker to the coordinator, one JSON object per line.
+#[derive(Debug, Serialize, Deserialize)]
+enum Request {
+    /// Ask for the next configuration to run.
+    Next,
+    /// Report the result of a configuration, followed by `files` file frames.
+    Result {
+        /// Name of the run's directory, see `results::run_name`.
+        hash: String,
+        success: bool,
+        files: usize,
+    },
+}
+
+/// Messages sent from the coordinator to a worker, one JSON object per line.
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    Run {
+        hash: String,
+        #[serde(default)]
+        repeat: usize,
+        configuration: serde_json::Value,
+    },
+    /// No configurations are available right now but some are still running and may be
+    /// requeued, ask again later.
+    Wait,
+    /// All configurations have been run.
+    Done,
+}
+
+/// Header for a file frame, followed by `len` raw bytes of the file.
+#[derive(Debug, Serialize, Deserialize)]
+struct FileHeader {
+    path: PathBuf,
+    len: u64,
+}
+
+/// A repeat of a configuration waiting to be run.
+#[derive(Debug, Clone)]
+struct QueuedRun {
+    hash: String,
+    repeat: usize,
+    configuration: serde_json::Value,
+    /// Hashes of the configurations to finish running before handing this out.
+    dependencies: Vec<String>,
+}
+
+/// Runs to hand out, those in flight keyed by their run name.
+#[derive(Debug)]
+struct Queue {
+    experiment_dir: PathBuf,
+    pending: VecDeque<QueuedRun>,
+    in_flight: HashMap<String, QueuedRun>,
+    /// Hashes of configurations with a successful repeat.
+    succeeded: HashSet<String>,
+}
+
+impl Queue {
+    fn new(experiment_dir: PathBuf) -> Self {
+        Self {
+            experiment_dir,
+            pending: VecDeque::new(),
+            in_flight: HashMap::new(),
+            succeeded: HashSet::new(),
+        }
+    }
+
+    fn next(&mut self) -> Response {
+        loop {
+            let ready = self.pending.iter().position(|run| {
+                run.dependencies
+                    .iter()
+                    .all(|dependency| !self.is_outstanding(dependency))
+            });
+            let run = match ready.and_then(|i| self.pending.remove(i)) {
+                Some(run) => run,
+                None if self.in_flight.is_empty() => return Response::Done,
+                None => return Response::Wait,
+            };
+            let name = run_name(&run.hash, run.repeat);
+            let failed_dependency = run
+                .dependencies
+                .iter()
+                .find(|dependency| !self.succeeded.contains(*dependency));
+            if let Some(dependency) = failed_dependency {
+                warn!(hash = %name, %dependency, "Dependency failed, skipping configuration");
+                events::record(
+                    &self.experiment_dir,
+                    RunEvent::ConfigurationSkipped {
+                        hash: name,
+                        reason: format!("dependency {} failed", dependency),
+                    },
+                );
+                continue;
+            }
+            self.in_flight.insert(name, run.clone());
+            return Response::Run {
+                hash: run.hash,
+                repeat: run.repeat,
+                configuration: run.configuration,
+            };
+        }
+    }
+
+    /// Record the result of a run in flight.
+    fn finished(&mut self, name: &str, success: bool) {
+        if let Some(run) = self.in_flight.remove(name) {
+            if success {
+                self.succeeded.insert(run.hash);
+            }
+        }
+    }
+
+    fn requeue(&mut self, name: &str) {
+        if let Some(run) = self.in_flight.remove(name) {
+            self.pending.push_front(run);
+        }
+    }
+
+    /// Whether any repeat of the configuration is still to be run.
+    fn is_outstanding(&self, hash: &str) -> bool {
+        self.pending
+            .iter()
+            .chain(self.in_flight.values())
+            .any(|run| run.hash == hash)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.pending.is_empty() && self.in_flight.is_empty()
+    }
+}
+
+/// Coordinate a run across worker machines.
+///
+/// The coordinator listens on `listen` and hands out the configurations that still need running
+/// to workers started with `run_worker`. Workers send back their result directories which are
+/// stored in the coordinator's results directory as if the configurations had been run locally.
+/// Configurations given to a worker that disconnects before reporting are handed out again, and
+/// those with dependencies are held back until the configurations they depend on have finished.
+pub async fn run_coordinator<E: Experiment>(
+    experiment: &mut E,
+    config: &RunConfig,
+    listen: SocketAddr,
+) -> Result<(), RunError> {
+    let exp_path = create_experiment_dir(&config.results_dir)?;
+    let _lock = lock_experiment_dir(&exp_path)?;
+    info!(dir=%exp_path.display(), %listen, "Coordinating experiment");
+    collect_environment_data(&exp_path, config, None)?;
+    experiment.metadata().write(&exp_path)?;
+    collect_provenance(&exp_path, &config.provenance_repos);
+
+    let configurations = order_configurations(
+        select_configurations(experiment.configurations(), &exp_path, config.repeats)?,
+        &exp_path,
+    )?;
+    let mut queue = Queue::new(exp_path.clone());
+    for scheduled in &configurations {
+        for dependency in &scheduled.dependencies {
+            // dependencies run before this coordinator started
+            if (0..config.repeats)
+                .any(|repeat| exp_path.join(run_name(dependency, repeat)).is_dir())
+            {
+                queue.succeeded.insert(dependency.clone());
+            }
+        }
+    }
+    for scheduled in configurations {
+        let configuration = serde_json::to_value(&scheduled.configuration)?;
+        for repeat in scheduled.repeats {
+            queue.pending.push_back(QueuedRun {
+                hash: scheduled.hash.clone(),
+                repeat,
+                configuration: configuration.clone(),
+                dependencies: scheduled.dependencies.clone(),
+            });
+        }
+    }
+    if queue.is_finished() {
+        return Ok(());
+    }
+
+    let queue = Arc::new(Mutex::new(queue));
+    let done = Arc::new(Notify::new());
+    let listener = TcpListener::bind(listen).await?;
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, worker) = accepted?;
+                info!(%worker, "Worker connected");
+                let queue = Arc::clone(&queue);
+                let done = Arc::clone(&done);
+                let exp_path = exp_path.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = handle_worker(stream, queue, &exp_path, done).await {
+                        warn!(%error, %worker, "Error handling worker");
+                    }
+                    info!(%worker, "Worker disconnected");
+                });
+            }
+            _ = done.notified() => break,
+        }
+    }
+    info!("All configurations completed by workers");
+    Ok(())
+}
+
+async fn handle_worker(
+    stream: TcpStream,
+    queue: Arc<Mutex<Queue>>,
+    experiment_dir: &Path,
+    done: Arc<Notify>,
+) -> Result<(), RunError> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut assigned = None;
+    let result = async {
+        while let Some(request) = read_message(&mut reader).await? {
+            match request {
+                Request::Next => {
+                    let response = queue.lock().unwrap().next();
+                    match &response {
+                        Response::Run { hash, repeat, .. } => {
+                            let name = run_name(hash, *repeat);
+                            debug!(hash = %name, "Assigned configuration to worker");
+                            assigned = Some(name);
+                        }
+                        // the last runs may have been skipped rather than run
+                        Response::Done => done.notify_one(),
+                        Response::Wait => {}
+                    }
+                    write_message(&mut writer, &response).await?;
+                }
+                Request::Result {
+                    hash,
+                    success,
+                    files,
+                } => {
+                    if assigned.as_ref() != Some(&hash) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "worker reported result of {} but was assigned {:?}",
+                                hash, assigned
+                            ),
+                        )
+                        .into());
+                    }
+                    receive_result(&mut reader, experiment_dir, &hash, success, files).await?;
+                    assigned = None;
+                    let finished = {
+                        let mut queue = queue.lock().unwrap();
+                        queue.finished(&hash, success);
+                        queue.is_finished()
+                    };
+                    if finished {
+                        done.notify_one();
+                    }
+                }
+            }
+        }
+        Ok::<_, RunError>(())
+    }
+    .await;
+    if let Some(hash) = assigned {
+        warn!(%hash, "Worker did not complete configuration, requeueing");
+        queue.lock().unwrap().requeue(&hash);
+    }
+    result
+}
+
+async fn receive_result<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    experiment_dir: &Path,
+    hash: &str,
+    success: bool,
+    files: usize,
+) -> Result<(), RunError> {
+    let mut running_dir = experiment_dir.join(hash);
+    running_dir.set_extension("running");
+    create_dir_all(&running_dir)?;
+    for _ in 0..files {
+        let header: FileHeader = read_message(reader)
+            .await?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "missing file header"))?;
+        if !header
+            .path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid result file path {:?}", header.path),
+            )
+            .into());
+        }
+        let path = running_dir.join(&header.path);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut file = tokio::fs::File::create(&path).await?;
+        let copied = tokio::io::copy(&mut reader.take(header.len), &mut file).await?;
+        if copied != header.len {
+            return Err(
+                io::Error::new(io::ErrorKind::UnexpectedEof, "truncated result file").into(),
+            );
+        }
+    }
+
+    let mut final_dir = experiment_dir.join(hash);
+    if !success {
+        final_dir.set_extension("failed");
+        if final_dir.exists() {
+            // replace the failure from a previous attempt
+            remove_dir_all(&final_dir)?;
+        }
+    }
+    rename(running_dir, &final_dir)?;
+    info!(%hash, success, "Received configuration result");
+    Ok(())
+}
+
+/// Run configurations handed out by a coordinator started with `run_coordinator`.
+///
+/// Configurations are run in the worker's own results directory as normal, with the hooks,
+/// cooldown and idle wait of `config`, and then sent to the coordinator, along with the worker's
+/// `environment.json`.
+pub async fn run_worker<E: Experiment>(
+    experiment: &mut E,
+    config: &RunConfig,
+    coordinator: SocketAddr,
+) -> Result<(), RunError> {
+    let exp_path = create_experiment_dir(&config.results_dir)?;
+    // how many configurations this worker gets is up to the coordinator
+    let mut session = Session::start(experiment, &exp_path, config, 0).await?;
+
+    let stream = TcpStream::connect(coordinator).await?;
+    info!(%coordinator, "Connected to coordinator");
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    loop {
+        write_message(&mut writer, &Request::Next).await?;
+        match read_message(&mut reader).await? {
+            None | Some(Response::Done) => break,
+            Some(Response::Wait) => tokio::time::sleep(WAIT_INTERVAL).await,
+            Some(Response::Run {
+                hash,
+                repeat,
+                configuration,
+            }) => {
+                let configuration: E::Configuration = serde_json::from_value(configuration)?;
+                let local_hash = configuration.hash_serialized()?;
+                if local_hash != hash {
+                    return Err(RunError::Other(
+                        format!(
+                            "configuration hash mismatch with coordinator, got {} expected {}",
+                            local_hash, hash
+                        )
+                        .into(),
+                    ));
+                }
+                check_requirements(experiment, &exp_path, std::slice::from_ref(&configuration))
+                    .await?;
+                let hash = run_name(&hash, repeat);
+                info!(%hash, "Running configuration from coordinator");
+                session.wait().await;
+                let FinishedRun { dir, success, .. } = session
+                    .run(experiment, &configuration, repeat)
+                    .await?
+                    .ok_or_else(|| {
+                        RunError::Other(
+                            format!("configuration {} already run or running locally", hash).into(),
+                        )
+                    })?;
+                // runs linked from a store already have the environment they ran in
+                if !dir.join("environment.json").exists() {
+                    std::fs::copy(
+                        exp_path.join("environment.json"),
+                        dir.join("environment.json"),
+                    )?;
+                }
+                send_result(&mut writer, &dir, &hash, success).await?;
+            }
+        }
+    }
+    info!("Coordinator has no more configurations");
+    session.finish().await
+}
+
+async fn send_result<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    dir: &Path,
+    hash: &str,
+    success: bool,
+) -> Result<(), RunError> {
+    let mut files = Vec::new();
+    collect_files(dir, Path::new(""), &mut files)?;
+    write_message(
+        writer,
+        &Request::Result {
+            hash: hash.to_owned(),
+            success,
+            files: files.len(),
+        },
+    )
+    .await?;
+    for path in files {
+        let mut file = tokio::fs::File::open(dir.join(&path)).await?;
+        let len = file.metadata().await?.len();
+        write_message(writer, &FileHeader { path, len }).await?;
+        tokio::io::copy(&mut (&mut file).take(len), writer).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Collect the paths of all files under `dir`, relative to it.
+fn collect_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_files(dir, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+async fn read_message<R: AsyncBufRead + Unpin, T: DeserializeOwned>(
+    reader: &mut R,
+) -> Result<Option<T>, RunError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+async fn write_message<W: AsyncWrite + Unpin, T: Serialize>(
+    writer: &mut W,
+    message: &T,
+) -> Result<(), RunError> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
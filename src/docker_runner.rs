@@ -1,41 +1,102 @@
 use bollard::container::MemoryStatsStats;
-use bollard::exec::StartExecResults;
 use chrono::DateTime;
+use chrono::TimeZone;
 use chrono::Utc;
 use std::{
-    collections::HashMap,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
     fs::{create_dir_all, File},
     io,
     io::{BufRead, ErrorKind, Write},
+    ops::RangeBounds,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
 };
 
 use bollard::{
     container::{
-        Config, CreateContainerOptions, ListContainersOptions, LogsOptions, RemoveContainerOptions,
-        StatsOptions, StopContainerOptions, TopOptions,
+        Config, CreateContainerOptions, DownloadFromContainerOptions, ListContainersOptions,
+        LogsOptions, RemoveContainerOptions, StatsOptions, TopOptions,
     },
     image::CreateImageOptions,
-    models::{HostConfig, Ipam, IpamConfig, Mount, MountTypeEnum, PortBinding},
-    network::{CreateNetworkOptions, ListNetworksOptions},
+    models::{
+        ChangeType, HostConfig, Mount, MountTypeEnum, PortBinding, ResourcesUlimits,
+        RestartPolicyNameEnum, ThrottleDevice,
+    },
+    network::{InspectNetworkOptions, PruneNetworksOptions},
+    system::EventsOptions,
+    volume::PruneVolumesOptions,
     Docker,
 };
 use futures::{future::join_all, stream::StreamExt, TryStreamExt};
-use serde::{Deserialize, Serialize};
-use tokio::task::JoinHandle;
-use tracing::{debug, warn};
+use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+    time::MissedTickBehavior,
+};
+use tracing::{debug, info_span, warn, Span};
+
+use crate::backend::{ContainerBackend, DockerBackend};
+use crate::compression::{self, COMPRESSED_EXTENSION};
+use crate::events::{self, RunEvent};
+use crate::log_capture::{LogCaptureConfig, LogWriter, DOCKER_TIMESTAMP_FIELD, MESSAGE_FIELD};
+use crate::log_metrics::log_files;
+use crate::prometheus::{parse_prometheus, PrometheusRecord, PrometheusScrape};
+
+/// Label applied to all docker resources created by a `Runner`, holding the experiment name.
+pub const EXPERIMENT_LABEL: &str = "exp.experiment";
+/// Label applied to all docker resources created by a `Runner`, holding the configuration hash.
+pub const CONFIG_LABEL: &str = "exp.config";
+
+/// Image used for the helper container when preserving volume contents.
+const PRESERVE_IMAGE_NAME: &str = "busybox";
+const PRESERVE_IMAGE_TAG: &str = "latest";
+/// Runners dropped without being finished, such as when a run times out, waiting for
+/// `finish_abandoned` to tear them down.
+static ABANDONED: Mutex<Vec<Runner>> = Mutex::new(Vec::new());
+
+const TCPDUMP_IMAGE_NAME: &str = "nicolaka/netshoot";
+const TCPDUMP_IMAGE_TAG: &str = "v0.13";
 
 // The docker runner for a particular experiment run
 // handles creation of resources and teardown after
 #[derive(Debug)]
 pub struct Runner {
     containers: Vec<String>,
+    /// Names of the containers by id, shared with monitoring tasks.
+    container_ids: Arc<Mutex<HashMap<String, String>>>,
+    /// Host ports published for each container, by container port.
+    host_ports: HashMap<String, BTreeMap<String, u16>>,
+    /// Containers that ran out of memory and were restarted, shared with their lifecycle tasks.
+    oom_killed: Arc<Mutex<HashSet<String>>>,
+    /// Containers to snapshot the filesystem of when the run finishes.
+    snapshots: HashMap<String, Snapshot>,
+    /// Log lines of each container as they are captured, until its logs end.
+    log_lines: HashMap<String, Weak<broadcast::Sender<String>>>,
+    /// Helper containers, such as traffic captures, stopped after the containers of the run.
+    sidecars: Vec<String>,
     networks: Vec<String>,
-    docker: Docker,
+    volumes: Vec<VolumeConfig>,
+    backend: Arc<dyn ContainerBackend>,
     config_dir: PathBuf,
+    labels: HashMap<String, String>,
     end_tx: tokio::sync::watch::Sender<()>,
     end_rx: tokio::sync::watch::Receiver<()>,
+    phase_tx: tokio::sync::watch::Sender<Option<String>>,
+    phase_rx: tokio::sync::watch::Receiver<Option<String>>,
+    phases_writer: Option<csv::Writer<File>>,
+    /// Open until the next phase starts or the run finishes.
+    phase_span: Option<Span>,
+    /// Open until the containers are stopped.
+    container_spans: Vec<Span>,
     futures: Vec<JoinHandle<()>>,
+    /// Whether the runner has been torn down, otherwise it is abandoned when dropped.
+    finished: bool,
 }
 
 impl Runner {
@@ -53,15 +114,481 @@ impl Runner {
         let info_file = File::create(config_dir.join("docker-info.json"))
             .expect("Failed to create docker info file");
         serde_json::to_writer_pretty(info_file, &info).unwrap();
+        Self::with_backend(config_dir, DockerBackend::new(docker))
+    }
+
+    /// A runner of containers in another backend, such as a `MockBackend` to test the
+    /// orchestration of a run without a docker daemon.
+    ///
+    /// Logs, stats and processes of containers are only captured, and filesystem snapshots,
+    /// traffic captures and volume preservation only happen, when the backend has a docker
+    /// client.
+    pub fn with_backend(config_dir: PathBuf, backend: impl ContainerBackend + 'static) -> Self {
+        Self::from_backend(config_dir, Arc::new(backend))
+    }
+
+    fn from_backend(config_dir: PathBuf, backend: Arc<dyn ContainerBackend>) -> Self {
         let (end_tx, end_rx) = tokio::sync::watch::channel(());
+        let (phase_tx, phase_rx) = tokio::sync::watch::channel(None);
+        let labels = ownership_labels(&config_dir);
         Self {
             containers: Vec::new(),
+            container_ids: Arc::default(),
+            host_ports: HashMap::new(),
+            oom_killed: Arc::default(),
+            snapshots: HashMap::new(),
+            log_lines: HashMap::new(),
+            sidecars: Vec::new(),
             networks: Vec::new(),
-            docker,
+            volumes: Vec::new(),
+            backend,
             config_dir,
+            labels,
             end_tx,
             end_rx,
+            phase_tx,
+            phase_rx,
+            phases_writer: None,
+            phase_span: None,
+            container_spans: Vec::new(),
             futures: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Sample the GPUs of the host, and the containers' use of them, into `metrics/gpu.csv` until
+    /// the run finishes.
+    ///
+    /// Processes are attributed to containers by their cgroup, so this must run on the docker
+    /// host.
+    #[cfg(feature = "nvml")]
+    pub fn monitor_gpus(&mut self, interval: std::time::Duration) {
+        use crate::gpu::{GpuProcesses, GpuSampler, GPU_FILE};
+
+        let metrics_dir =
+            create_metrics_dir(&self.config_dir).expect("Failed to create metrics dir");
+        let mut sampler = match GpuSampler::new(metrics_dir.join(GPU_FILE)) {
+            Ok(sampler) => sampler,
+            Err(error) => {
+                warn!(%error, "Failed to start GPU monitoring");
+                return;
+            }
+        };
+        let container_ids = self.container_ids.clone();
+        let mut end_rx = self.end_rx.clone();
+        self.futures.push(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = end_rx.changed() => break,
+                    _ = interval.tick() => {
+                        let containers = container_ids.lock().unwrap().clone();
+                        if let Err(error) = sampler.sample(GpuProcesses::Containers(&containers)) {
+                            warn!(%error, "Failed to sample GPUs");
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    /// The host port a container's port was published on, such as one picked for
+    /// `HostPort::Auto`.
+    ///
+    /// The ports of each container are also written to `config/docker-<name>-ports.json`.
+    pub fn host_port(&self, container: &str, container_port: &str) -> Option<u16> {
+        self.host_ports.get(container)?.get(container_port).copied()
+    }
+
+    /// The host ports a started container's ports were published on.
+    async fn published_ports(&self, name: &str) -> BTreeMap<String, u16> {
+        let container = self
+            .backend
+            .inspect_container(name)
+            .await
+            .expect("Failed to inspect container");
+        let mut host_ports = BTreeMap::new();
+        let ports = container
+            .network_settings
+            .and_then(|settings| settings.ports)
+            .unwrap_or_default();
+        for (container_port, bindings) in ports {
+            let host_port = bindings
+                .unwrap_or_default()
+                .into_iter()
+                .find_map(|binding| binding.host_port?.parse().ok());
+            if let Some(host_port) = host_port {
+                let container_port = container_port.trim_end_matches("/tcp").to_owned();
+                debug!(container = name, %container_port, host_port, "Published port");
+                host_ports.insert(container_port, host_port);
+            }
+        }
+        host_ports
+    }
+
+    /// Wait until a line of the container's logs matches `pattern`, such as `listening on port`,
+    /// returning the line prefixed with its timestamp.
+    ///
+    /// Lines logged before the call are also checked, so this can be called any time after
+    /// `add_container` to wait for a container to be ready without it needing a health check.
+    pub async fn wait_for_log(
+        &self,
+        container: &str,
+        pattern: &Regex,
+        timeout: Duration,
+    ) -> Result<String, WaitForLogError> {
+        let exited = || WaitForLogError::Exited(container.to_owned());
+        let log_tx = self
+            .log_lines
+            .get(container)
+            .ok_or_else(|| WaitForLogError::UnknownContainer(container.to_owned()))?
+            .upgrade()
+            .ok_or_else(exited)?;
+        // subscribe before reading the past logs so no line is missed in between
+        let mut lines = log_tx.subscribe();
+        drop(log_tx);
+        let matching = async {
+            if let Some(docker) = self.backend.docker() {
+                let mut past = docker.logs(
+                    container,
+                    Some(LogsOptions::<String> {
+                        stdout: true,
+                        stderr: true,
+                        timestamps: true,
+                        ..Default::default()
+                    }),
+                );
+                while let Some(item) = past.next().await {
+                    let line = item?.to_string();
+                    if pattern.is_match(&line) {
+                        return Ok(line);
+                    }
+                }
+            }
+            loop {
+                match lines.recv().await {
+                    Ok(line) if pattern.is_match(&line) => return Ok(line),
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(%container, skipped, "Skipped log lines while waiting for a match")
+                    }
+                    Err(RecvError::Closed) => return Err(exited()),
+                }
+            }
+        };
+        tokio::time::timeout(timeout, matching)
+            .await
+            .map_err(|_| WaitForLogError::Timeout {
+                container: container.to_owned(),
+                timeout,
+            })?
+    }
+
+    /// Capture the traffic on a network of the run into `metrics/tcpdump-<network>.pcap` with
+    /// a tcpdump sidecar, until the run finishes.
+    ///
+    /// The sidecar shares the host's network to capture on the network's bridge, so sees the
+    /// traffic between all containers on it. `filter` is a pcap filter expression, such as
+    /// `tcp port 2379`, to capture only some of the traffic.
+    pub async fn capture_traffic(&mut self, network: &str, filter: Option<&str>) {
+        let docker = match self.backend.docker() {
+            Some(docker) => docker.clone(),
+            None => {
+                warn!(%network, "Traffic capture needs docker, not capturing");
+                return;
+            }
+        };
+        pull_image(TCPDUMP_IMAGE_NAME, TCPDUMP_IMAGE_TAG)
+            .await
+            .expect("Failed to pull tcpdump image");
+        let network_info = docker
+            .inspect_network(network, None::<InspectNetworkOptions<String>>)
+            .await
+            .expect("Failed to inspect network");
+        let interface = network_info
+            .options
+            .as_ref()
+            .and_then(|options| options.get("com.docker.network.bridge.name").cloned())
+            .unwrap_or_else(|| {
+                let id = network_info.id.unwrap_or_default();
+                format!("br-{}", &id[..id.len().min(12)])
+            });
+        let metrics_dir =
+            create_metrics_dir(&self.config_dir).expect("Failed to create metrics dir");
+        let metrics_dir =
+            std::fs::canonicalize(metrics_dir).expect("Failed to resolve metrics dir");
+
+        let mut command = vec![
+            "tcpdump".to_owned(),
+            "-i".to_owned(),
+            interface.clone(),
+            // write each packet as it is captured so nothing is lost when stopped
+            "-U".to_owned(),
+            "-Z".to_owned(),
+            "root".to_owned(),
+            "-w".to_owned(),
+            format!("/capture/tcpdump-{}.pcap", network),
+        ];
+        command.extend(filter.map(str::to_owned));
+
+        let name = helper_name(&self.labels, "tcpdump", network);
+        docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: name.as_str(),
+                }),
+                Config {
+                    image: Some(format!("{}:{}", TCPDUMP_IMAGE_NAME, TCPDUMP_IMAGE_TAG)),
+                    cmd: Some(command),
+                    labels: Some(self.labels.clone()),
+                    host_config: Some(HostConfig {
+                        network_mode: Some("host".to_owned()),
+                        cap_add: Some(vec!["NET_ADMIN".to_owned(), "NET_RAW".to_owned()]),
+                        mounts: Some(vec![Mount {
+                            target: Some("/capture".to_owned()),
+                            source: Some(metrics_dir.to_string_lossy().into_owned()),
+                            typ: Some(MountTypeEnum::BIND),
+                            ..Default::default()
+                        }]),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed to create tcpdump container");
+        docker
+            .start_container::<String>(&name, None)
+            .await
+            .expect("Failed to start tcpdump container");
+        debug!(%network, %interface, ?filter, "Capturing traffic");
+        self.sidecars.push(name);
+    }
+
+    /// Checkpoint a running container with CRIU, which stops it, giving the name of the
+    /// checkpoint to `restore` it from.
+    ///
+    /// Experimental: this needs CRIU installed and docker's experimental features enabled, and
+    /// uses the `docker` CLI as the API client lacks checkpoints. How long it took is appended to
+    /// `metrics/docker-<name>-checkpoints.csv`. The container's logs and stats stop being
+    /// captured when it stops and aren't captured again once restored. Fails when the runner's
+    /// backend isn't docker.
+    pub async fn checkpoint(&self, name: &str) -> io::Result<String> {
+        self.require_docker("checkpoint")?;
+        let checkpoint = format!("{}-{}", name, Utc::now().timestamp_millis());
+        let started = std::time::Instant::now();
+        docker_cli(&["checkpoint", "create", name, &checkpoint]).await?;
+        self.record_checkpoint(name, "checkpoint", &checkpoint, started.elapsed())?;
+        Ok(checkpoint)
+    }
+
+    /// Start a container from a checkpoint made by `Runner::checkpoint`.
+    ///
+    /// How long it took is appended to `metrics/docker-<name>-checkpoints.csv`. Fails when the
+    /// runner's backend isn't docker.
+    pub async fn restore(&self, name: &str, checkpoint: &str) -> io::Result<()> {
+        self.require_docker("restore")?;
+        let started = std::time::Instant::now();
+        docker_cli(&["start", "--checkpoint", checkpoint, name]).await?;
+        self.record_checkpoint(name, "restore", checkpoint, started.elapsed())
+    }
+
+    /// Fail the docker CLI operation when the backend isn't docker, rather than acting on
+    /// whatever docker daemon the CLI reaches.
+    fn require_docker(&self, operation: &str) -> io::Result<()> {
+        match self.backend.docker() {
+            Some(_) => Ok(()),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} needs the docker backend", operation),
+            )),
+        }
+    }
+
+    fn record_checkpoint(
+        &self,
+        name: &str,
+        action: &str,
+        checkpoint: &str,
+        duration: Duration,
+    ) -> io::Result<()> {
+        debug!(
+            container = name,
+            action,
+            checkpoint,
+            ?duration,
+            "Checkpoint"
+        );
+        let metrics_dir = create_metrics_dir(&self.config_dir)?;
+        let path = metrics_dir.join(format!("docker-{}-checkpoints.csv", name));
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let has_headers = file.metadata()?.len() == 0;
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(has_headers)
+            .from_writer(file);
+        writer.serialize(CheckpointRecord {
+            time: Utc::now(),
+            action: action.to_owned(),
+            checkpoint: checkpoint.to_owned(),
+            duration_secs: duration.as_secs_f64(),
+        })?;
+        writer.flush()
+    }
+
+    /// Scrape the Prometheus metrics endpoint of a running container, writing the series
+    /// selected to `metrics/prom-<name>.csv` until the run finishes.
+    ///
+    /// Each phase writes a new file in its subdirectory of `metrics/`, overwriting one from an
+    /// earlier phase with the same name.
+    ///
+    /// Failed scrapes, such as before the container is serving, are logged and skipped.
+    pub async fn scrape_prometheus(&mut self, name: &str, scrape: &PrometheusScrape) {
+        let filter = match scrape.filter() {
+            Ok(filter) => filter,
+            Err(error) => {
+                warn!(%error, container = name, "Invalid Prometheus series pattern");
+                return;
+            }
+        };
+        let address = if scrape.host.is_some() {
+            String::new()
+        } else {
+            let container = self
+                .backend
+                .inspect_container(name)
+                .await
+                .expect("Failed to inspect container");
+            let address = container
+                .network_settings
+                .and_then(|settings| settings.networks)
+                .and_then(|networks| {
+                    networks
+                        .into_values()
+                        .filter_map(|endpoint| endpoint.ip_address)
+                        .find(|address| !address.is_empty())
+                });
+            match address {
+                Some(address) => address,
+                None => {
+                    warn!(container = name, "Container has no address to scrape");
+                    return;
+                }
+            }
+        };
+        let url = scrape.url(&address);
+        debug!(container = name, %url, "Scraping Prometheus metrics");
+        let interval = scrape.interval();
+        let metrics_dir =
+            create_metrics_dir(&self.config_dir).expect("Failed to create metrics dir");
+        let file_name = format!("prom-{}.csv", name);
+        let mut end_rx = self.end_rx.clone();
+        let mut phase_rx = self.phase_rx.clone();
+        self.futures.push(tokio::spawn(async move {
+            let agent = ureq::AgentBuilder::new().timeout(interval).build();
+            let mut phase = phase_rx.borrow().clone();
+            let mut writer =
+                csv::Writer::from_path(phase_dir(&metrics_dir, phase.as_deref()).join(&file_name))
+                    .unwrap();
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = end_rx.changed() => break,
+                    Ok(()) = phase_rx.changed() => {
+                        let new_phase = phase_rx.borrow().clone();
+                        if new_phase != phase {
+                            phase = new_phase;
+                            writer.flush().unwrap();
+                            writer = csv::Writer::from_path(
+                                phase_dir(&metrics_dir, phase.as_deref()).join(&file_name),
+                            )
+                            .unwrap();
+                        }
+                    }
+                    _ = interval.tick() => {
+                        let agent = agent.clone();
+                        let url = url.clone();
+                        let body = tokio::task::spawn_blocking(move || -> Result<String, String> {
+                            agent
+                                .get(&url)
+                                .call()
+                                .map_err(|error| error.to_string())?
+                                .into_string()
+                                .map_err(|error| error.to_string())
+                        })
+                        .await;
+                        match body {
+                            Ok(Ok(body)) => {
+                                let time = Utc::now();
+                                for sample in parse_prometheus(&body) {
+                                    if filter(&sample.name) {
+                                        writer.serialize(PrometheusRecord::new(time, sample)).unwrap();
+                                    }
+                                }
+                                writer.flush().unwrap();
+                            }
+                            Ok(Err(error)) => warn!(%error, "Failed to scrape Prometheus metrics"),
+                            Err(error) => warn!(%error, "Prometheus scrape task failed"),
+                        }
+                    }
+                    else => break,
+                }
+            }
+            writer.flush().unwrap();
+        }));
+    }
+
+    /// Create a named volume for this configuration run.
+    ///
+    /// The volume is removed in `finish`, after optionally preserving its contents into the
+    /// `volumes` directory of the configuration.
+    pub async fn add_volume(&mut self, config: &VolumeConfig) {
+        if self.volumes.iter().any(|v| v.name == config.name) {
+            debug!(volume = %config.name, "Volume already created, skipping");
+            return;
+        }
+        self.backend
+            .create_volume(config, &self.labels)
+            .await
+            .expect("Failed to create volume");
+        self.volumes.push(config.clone());
+    }
+
+    /// Start a new phase of the run, such as `load` or `measure`.
+    ///
+    /// From now on, logs and metrics of all containers are written into `logs/<phase>/` and
+    /// `metrics/<phase>/` respectively, and the start of the phase is recorded in
+    /// `metrics/phases.csv`. Phase names should be unique within a run as files from an earlier
+    /// phase with the same name are overwritten.
+    pub fn phase(&mut self, name: &str) {
+        if self.phases_writer.is_none() {
+            let metrics_dir =
+                create_metrics_dir(&self.config_dir).expect("Failed to create metrics dir");
+            let writer = csv::Writer::from_path(metrics_dir.join("phases.csv"))
+                .expect("Failed to create phases file");
+            self.phases_writer = Some(writer);
+        }
+        let writer = self.phases_writer.as_mut().unwrap();
+        writer
+            .serialize(Phase {
+                name: name.to_owned(),
+                start: Utc::now(),
+            })
+            .expect("Failed to write phase");
+        writer.flush().expect("Failed to flush phases file");
+
+        debug!(phase = name, "Starting phase");
+        // replacing the previous phase's span closes it
+        self.phase_span = Some(info_span!("phase", name));
+        self.record_event(|hash| RunEvent::Phase {
+            hash,
+            name: name.to_owned(),
+        });
+        let r = self.phase_tx.send(Some(name.to_owned()));
+        if let Err(error) = r {
+            warn!(%error, "Error sending phase change to monitoring tasks")
         }
     }
 
@@ -76,65 +603,164 @@ impl Runner {
         serde_json::to_writer_pretty(config_file, &config).expect("Failed to write docker config");
 
         if let Some(network_name) = &config.network {
-            let mut net_filters = HashMap::new();
-            net_filters.insert("name", vec![network_name.as_str()]);
-            let net_count = self
-                .docker
-                .list_networks(Some(ListNetworksOptions {
-                    filters: net_filters,
-                }))
+            let exists = self
+                .backend
+                .network_exists(network_name)
                 .await
-                .expect("Failed to list networks")
-                .iter()
-                .filter(|n| n.name.as_ref() == Some(network_name))
-                .count();
-            if net_count == 0 {
-                let network_config = config.network_subnet.as_ref().map(|subnet| {
-                    vec![IpamConfig {
-                        subnet: Some(subnet.clone()),
-                        ..Default::default()
-                    }]
-                });
-                self.docker
-                    .create_network(CreateNetworkOptions {
-                        name: network_name.as_str(),
-                        check_duplicate: true,
-                        ipam: Ipam {
-                            config: network_config,
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    })
+                .expect("Failed to list networks");
+            if !exists {
+                self.backend
+                    .create_network(network_name, config.network_subnet.as_deref(), &self.labels)
                     .await
                     .expect("Failed to create network");
                 self.networks.push(network_name.clone());
             }
         }
 
-        if config.pull {
-            pull_image(&config.image_name, &config.image_tag)
+        for (volume, _) in &config.named_volumes {
+            self.add_volume(&VolumeConfig {
+                name: volume.clone(),
+                driver: None,
+                preserve: false,
+            })
+            .await;
+        }
+
+        let image = format!("{}:{}", config.image_name, config.image_tag);
+        // images already pulled for the run, such as by `prepull_images`, aren't pulled again
+        if config.pull && !image_pull_path(&config_dir, &image).exists() {
+            let pull = self
+                .backend
+                .pull_image(&config.image_name, &config.image_tag)
                 .await
                 .expect("Failed to pull image");
+            debug!(%image, duration_secs = pull.duration_secs, "Pulled image");
+            write_image_pull(&self.config_dir, &pull).expect("Failed to write docker pull");
         }
 
-        let _create_res = self
-            .docker
-            .create_container(
-                Some(CreateContainerOptions { name: &config.name }),
-                config.to_create_container_config(),
-            )
+        let id = self
+            .backend
+            .create_container(config, &self.labels)
             .await
             .expect("Failed to create container");
 
         self.containers.push(config.name.to_owned());
+        if config.diff || !config.export_paths.is_empty() {
+            self.snapshots.insert(
+                config.name.clone(),
+                Snapshot {
+                    diff: config.diff,
+                    export_paths: config.export_paths.clone(),
+                },
+            );
+        }
+        self.container_ids
+            .lock()
+            .unwrap()
+            .insert(id, config.name.to_owned());
 
-        self.docker
-            .start_container::<String>(&config.name, None)
+        self.backend
+            .start_container(&config.name)
             .await
             .expect("Failed to start container");
+        self.record_event(|hash| RunEvent::ContainerStarted {
+            hash,
+            name: config.name.clone(),
+        });
+        self.container_spans
+            .push(info_span!("container", name = %config.name));
+
+        if config.ports.is_some() {
+            let host_ports = self.published_ports(&config.name).await;
+            let ports_file =
+                File::create(config_dir.join(format!("docker-{}-ports.json", config.name)))
+                    .expect("Failed to create docker ports file");
+            serde_json::to_writer_pretty(ports_file, &host_ports)
+                .expect("Failed to write docker ports");
+            self.host_ports.insert(config.name.clone(), host_ports);
+        }
+
+        let client = match self.backend.docker() {
+            Some(docker) => docker.clone(),
+            None => {
+                debug!(container = %config.name, "Backend has no docker client, not monitoring");
+                return;
+            }
+        };
 
-        let docker = self.docker.clone();
+        if config.restart_policy.is_some() {
+            let docker = client.clone();
+            let name_owned = config.name.to_owned();
+            let metrics_dir_c = metrics_dir.clone();
+            let mut end_rx_clone = self.end_rx.clone();
+            let oom_killed = self.oom_killed.clone();
+            self.futures.push(tokio::spawn(async move {
+                let mut filters = HashMap::new();
+                filters.insert("type", vec!["container"]);
+                filters.insert("container", vec![name_owned.as_str()]);
+                filters.insert("event", vec!["die", "start", "restart", "oom"]);
+                let mut events = docker.events(Some(EventsOptions {
+                    filters,
+                    ..Default::default()
+                }));
+                let restarts_file_name =
+                    metrics_dir_c.join(format!("docker-{}-restarts.csv", name_owned));
+                let mut writer = csv::Writer::from_path(restarts_file_name).unwrap();
+                loop {
+                    tokio::select! {
+                        _ = end_rx_clone.changed() => break,
+                        Some(event) = events.next() => {
+                            match event {
+                                Ok(event) => {
+                                    let time = event
+                                        .time_nano
+                                        .map(|nanos| Utc.timestamp_nanos(nanos))
+                                        .unwrap_or_else(Utc::now);
+                                    let exit_code = event
+                                        .actor
+                                        .and_then(|actor| actor.attributes)
+                                        .and_then(|attributes| attributes.get("exitCode").cloned());
+                                    let action = event.action.unwrap_or_default();
+                                    if action == "oom" {
+                                        // the restart clears the container's OOMKilled state
+                                        oom_killed.lock().unwrap().insert(name_owned.clone());
+                                    }
+                                    let restart_event = ContainerLifecycleEvent {
+                                        time,
+                                        action,
+                                        exit_code,
+                                    };
+                                    writer.serialize(restart_event).unwrap();
+                                    // flush eagerly, restarts are rare and useful while debugging
+                                    writer.flush().unwrap();
+                                }
+                                Err(error) => {
+                                    warn!(%error, "Error getting container events");
+                                }
+                            }
+                        }
+                        else => break,
+                    }
+                }
+                writer.flush().unwrap();
+            }));
+        }
+
+        let docker = client.clone();
         let name_owned = config.name.to_owned();
+        let logs_extension = if config.logs.json { "jsonl" } else { "log" };
+        let logs_file_name = format!("docker-{}.{}", name_owned, logs_extension);
+        let mut phase_rx_clone = self.phase_rx.clone();
+        let mut phase = phase_rx_clone.borrow().clone();
+        let mut log_writer = LogWriter::new(
+            phase_dir(&logs_dir, phase.as_deref()).join(&logs_file_name),
+            &config.logs,
+        )
+        .expect("Invalid log drop pattern");
+        let (log_tx, _) = broadcast::channel(1024);
+        let log_tx = Arc::new(log_tx);
+        self.log_lines
+            .insert(config.name.clone(), Arc::downgrade(&log_tx));
         self.futures.push(tokio::spawn(async move {
             let mut logs = docker.logs(
                 &name_owned,
@@ -146,14 +772,26 @@ impl Runner {
                     ..Default::default()
                 }),
             );
-            let mut logs_file = File::create(logs_dir.join(format!("docker-{}.log", name_owned)))
-                .expect("Failed to create logs file");
             loop {
                 tokio::select! {
+                    Ok(()) = phase_rx_clone.changed() => {
+                        let new_phase = phase_rx_clone.borrow().clone();
+                        if new_phase != phase {
+                            phase = new_phase;
+                            log_writer
+                                .reopen(phase_dir(&logs_dir, phase.as_deref()).join(&logs_file_name))
+                                .unwrap();
+                        }
+                    }
                     Some(item) = logs.next() => {
                         match item {
                             Ok(item) => {
-                                write!(logs_file, "{}", item).unwrap();
+                                let line = item.to_string();
+                                #[cfg(feature = "tui")]
+                                crate::tui::log_line(&name_owned, &line);
+                                log_writer.write_line(&line).unwrap();
+                                // only fails when nobody is waiting for a line
+                                let _ = log_tx.send(line);
                             }
                             Err(error) => {
                                 if let bollard::errors::Error::DockerResponseServerError{status_code: 409, message:_} = error {
@@ -168,32 +806,91 @@ impl Runner {
                     else => break
                 }
             }
+            log_writer.flush().unwrap();
         }));
 
-        let docker = self.docker.clone();
+        let docker = client.clone();
         let name_owned = config.name.to_owned();
         let metrics_dir_c = metrics_dir.clone();
         let mut end_rx_clone = self.end_rx.clone();
+        let mut phase_rx_clone = self.phase_rx.clone();
+        let stats_interval = config.stats_interval;
         self.futures.push(tokio::spawn(async move {
-            let mut stats = docker.stats(
-                &name_owned,
-                Some(StatsOptions {
-                    stream: true,
-                    one_shot: false,
-                }),
-            );
-            let stats_file_name = metrics_dir_c.join(format!("docker-{}-stat.csv", name_owned));
-            let mut writer = csv::Writer::from_path(stats_file_name).unwrap();
+            let mut stats = match stats_interval {
+                None => docker
+                    .stats(
+                        &name_owned,
+                        Some(StatsOptions {
+                            stream: true,
+                            one_shot: false,
+                        }),
+                    )
+                    .boxed(),
+                Some(interval) => {
+                    let mut ticks = tokio::time::interval(interval);
+                    // skip samples rather than falling behind when docker is slow to respond
+                    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    let name = name_owned.clone();
+                    futures::stream::unfold(
+                        (docker, name, ticks),
+                        |(docker, name, mut ticks)| async move {
+                            ticks.tick().await;
+                            let stat = docker
+                                .stats(
+                                    &name,
+                                    Some(StatsOptions {
+                                        stream: false,
+                                        one_shot: true,
+                                    }),
+                                )
+                                .next()
+                                .await?;
+                            Some((stat, (docker, name, ticks)))
+                        },
+                    )
+                    .boxed()
+                }
+            };
+            let mut phase = phase_rx_clone.borrow().clone();
+            let mut writers =
+                StatsWriters::create(&phase_dir(&metrics_dir_c, phase.as_deref()), &name_owned)
+                    .unwrap();
+            // for the rates since the previous sample
+            let mut previous_split: Option<ContainerStats> = None;
             loop {
                 tokio::select! {
                     _ = end_rx_clone.changed() => break,
+                    Ok(()) = phase_rx_clone.changed() => {
+                        let new_phase = phase_rx_clone.borrow().clone();
+                        if new_phase != phase {
+                            phase = new_phase;
+                            writers.flush().unwrap();
+                            writers = StatsWriters::create(
+                                &phase_dir(&metrics_dir_c, phase.as_deref()),
+                                &name_owned,
+                            )
+                            .unwrap();
+                        }
+                    }
                     Some(stat) = stats.next() => {
                         match stat {
                             Ok(stats) => {
-                                let stats = Stats::from_bollard(stats);
-                                for stats in stats {
-                                    writer.serialize(stats).unwrap();
-                                }
+                                let split = ContainerStats::from_bollard(&stats, previous_split.as_ref());
+                                let flat = Stats::flatten(&split, previous_split.as_ref(), &stats.name, &stats.id);
+                                crate::metrics::container_stats(
+                                    &name_owned,
+                                    split.cpu.percent,
+                                    split.memory.usage,
+                                );
+                                #[cfg(feature = "tui")]
+                                crate::tui::container_stats(
+                                    &name_owned,
+                                    split.cpu.percent,
+                                    split.memory.usage,
+                                );
+                                writers.stat.serialize(&flat).unwrap();
+                                writers.write(&split).unwrap();
+                                previous_split = Some(split);
                             }
                             Err(error) => {
                                 if let bollard::errors::Error::DockerResponseServerError{status_code: 409, message:_} = error {
@@ -208,22 +905,39 @@ impl Runner {
                     else => break,
                 }
             }
-            writer.flush().unwrap();
+            writers.flush().unwrap();
         }));
 
-        let docker = self.docker.clone();
+        let docker = client.clone();
         let name_owned = config.name.to_owned();
         let mut end_rx_clone = self.end_rx.clone();
+        let mut phase_rx_clone = self.phase_rx.clone();
         self.futures.push(tokio::spawn(async move {
             let interval = tokio::time::interval(std::time::Duration::from_secs(1));
             tokio::pin!(interval);
 
-            let top_file = metrics_dir.join(format!("docker-{}-top.csv", name_owned));
-            let mut writer = csv::Writer::from_path(top_file).unwrap();
+            let top_file_name = format!("docker-{}-top.csv", name_owned);
+            let mut phase = phase_rx_clone.borrow().clone();
+            let mut writer = csv::Writer::from_path(
+                phase_dir(&metrics_dir, phase.as_deref()).join(&top_file_name),
+            )
+            .unwrap();
             let mut written_header = false;
             loop {
                 tokio::select! {
                     _ = end_rx_clone.changed() => break,
+                    Ok(()) = phase_rx_clone.changed() => {
+                        let new_phase = phase_rx_clone.borrow().clone();
+                        if new_phase != phase {
+                            phase = new_phase;
+                            writer.flush().unwrap();
+                            writer = csv::Writer::from_path(
+                                phase_dir(&metrics_dir, phase.as_deref()).join(&top_file_name),
+                            )
+                            .unwrap();
+                            written_header = false;
+                        }
+                    }
                     _ = interval.tick() => {
                         let top = docker
                             .top_processes(&name_owned, Some(TopOptions { ps_args: "aux" }))
@@ -262,41 +976,161 @@ impl Runner {
         }));
     }
 
-    pub async fn finish(self) {
-        for container in self.containers {
-            let _ = self
-                .docker
-                .stop_container(
-                    &container,
-                    Some(StopContainerOptions {
-                        t: 0, // seconds until kill
-                    }),
-                )
-                .await;
-            let _ = self
-                .docker
-                .remove_container(
-                    &container,
-                    Some(RemoveContainerOptions {
-                        force: true,
-                        ..Default::default()
-                    }),
-                )
-                .await;
+    /// How a container ended, or its state if it is still running.
+    async fn container_exit(&self, name: &str) -> ContainerExit {
+        let state = match self.backend.inspect_container(name).await {
+            Ok(container) => container.state.unwrap_or_default(),
+            Err(error) => {
+                warn!(%error, container = name, "Error inspecting container");
+                Default::default()
+            }
+        };
+        let running = state.running.unwrap_or_default();
+        ContainerExit {
+            name: name.to_owned(),
+            running,
+            exit_code: state.exit_code.filter(|_| !running),
+            oom_killed: state.oom_killed.unwrap_or_default()
+                || self.oom_killed.lock().unwrap().contains(name),
+            error: state.error.filter(|error| !error.is_empty()),
+        }
+    }
+
+    /// Record the files changed in the container and export the requested paths from it.
+    async fn snapshot(&self, container: &str, snapshot: &Snapshot) {
+        let docker = match self.backend.docker() {
+            Some(docker) => docker,
+            None => {
+                warn!(%container, "Snapshots need docker, not snapshotting container");
+                return;
+            }
+        };
+        if snapshot.diff {
+            let r = self.file_changes(docker, container).await;
+            if let Err(error) = r {
+                warn!(%error, %container, "Error recording container filesystem diff");
+            }
+        }
+        for path in &snapshot.export_paths {
+            let r = export_path(docker, &self.config_dir, container, path).await;
+            if let Err(error) = r {
+                warn!(%error, %container, %path, "Error exporting path from container");
+            }
+        }
+    }
+
+    async fn file_changes(
+        &self,
+        docker: &Docker,
+        container: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let changes = docker
+            .container_changes(container)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|change| FileChange {
+                path: change.path,
+                kind: match change.kind {
+                    ChangeType::_0 => FileChangeKind::Modified,
+                    ChangeType::_1 => FileChangeKind::Added,
+                    ChangeType::_2 => FileChangeKind::Deleted,
+                },
+            })
+            .collect::<Vec<_>>();
+        let config_dir = create_config_dir(&self.config_dir)?;
+        let file = File::create(config_dir.join(format!("docker-{}-diff.json", container)))?;
+        serde_json::to_writer_pretty(file, &changes)?;
+        Ok(())
+    }
+
+    /// Record an event in the experiment's event log, given the configuration hash.
+    fn record_event<F: FnOnce(String) -> RunEvent>(&self, event: F) {
+        if let (Some(hash), Some(experiment_dir)) =
+            (self.labels.get(CONFIG_LABEL), self.config_dir.parent())
+        {
+            events::record(experiment_dir, event(hash.clone()));
+        }
+    }
+
+    /// Stop and remove the containers, volumes and networks of the run.
+    ///
+    /// How each container ended, such as whether it ran out of memory, is first written to
+    /// `config/docker-<name>-exit.json`.
+    pub async fn finish(mut self) {
+        let containers = std::mem::take(&mut self.containers);
+        for container in &containers {
+            let exit = self.container_exit(container).await;
+            if exit.oom_killed {
+                warn!(%container, "Container was killed for running out of memory");
+                self.record_event(|hash| RunEvent::ContainerOomKilled {
+                    hash,
+                    name: container.clone(),
+                });
+            }
+            let r = create_config_dir(&self.config_dir).and_then(|config_dir| {
+                let file =
+                    File::create(config_dir.join(format!("docker-{}-exit.json", container)))?;
+                serde_json::to_writer_pretty(file, &exit)?;
+                Ok(())
+            });
+            if let Err(error) = r {
+                warn!(%error, %container, "Error writing container exit");
+            }
+            if let Some(snapshot) = self.snapshots.get(container) {
+                self.snapshot(container, snapshot).await;
+            }
+        }
+        for container in containers {
+            // killed straight away
+            let _ = self.backend.stop_container(&container, 0).await;
+            let _ = self.backend.remove_container(&container).await;
+        }
+        self.container_spans.clear();
+
+        for sidecar in std::mem::take(&mut self.sidecars) {
+            // give tcpdump the chance to finish writing its capture
+            let r = self.backend.stop_container(&sidecar, 5).await;
+            if let Err(error) = r {
+                warn!(%error, %sidecar, "Error stopping sidecar container")
+            }
+            let _ = self.backend.remove_container(&sidecar).await;
         }
 
         let r = self.end_tx.send(());
         if let Err(error) = r {
             warn!(%error, "Error sending shutdown signal to monitoring tasks")
         }
-        join_all(self.futures).await;
+        join_all(std::mem::take(&mut self.futures)).await;
+
+        for volume in std::mem::take(&mut self.volumes) {
+            match self.backend.docker() {
+                Some(docker) if volume.preserve => {
+                    let r =
+                        preserve_volume(docker, &self.config_dir, &self.labels, &volume.name).await;
+                    if let Err(error) = r {
+                        warn!(%error, volume = %volume.name, "Error preserving volume")
+                    }
+                }
+                None if volume.preserve => {
+                    warn!(volume = %volume.name, "Preserving volumes needs docker, not preserving")
+                }
+                _ => {}
+            }
+            let r = self.backend.remove_volume(&volume.name).await;
+            if let Err(error) = r {
+                warn!(%error, volume = %volume.name, "Error removing volume")
+            }
+        }
 
-        for network in self.networks {
-            let r = self.docker.remove_network(&network).await;
+        for network in std::mem::take(&mut self.networks) {
+            let r = self.backend.remove_network(&network).await;
             if let Err(error) = r {
                 warn!(%error, %network, "Error removing network")
             }
         }
+        self.phase_span = None;
+        self.finished = true;
     }
 
     pub async fn execute_command(
@@ -304,83 +1138,414 @@ impl Runner {
         container_name: &str,
         command: Vec<&str>,
     ) -> (Vec<String>, Vec<String>) {
-        let exec = self
-            .docker
-            .create_exec(
-                container_name,
-                bollard::exec::CreateExecOptions {
-                    attach_stdout: Some(true),
-                    attach_stderr: Some(true),
-                    cmd: Some(command),
-                    ..Default::default()
-                },
-            )
-            .await
-            .unwrap();
-        let mut out = Vec::new();
-        let mut err = Vec::new();
-        if let StartExecResults::Attached {
-            mut output,
-            input: _,
-        } = self.docker.start_exec(&exec.id, None).await.unwrap()
-        {
-            while let Some(Ok(msg)) = output.next().await {
-                match msg {
-                    bollard::container::LogOutput::StdErr { message } => {
-                        err.push(String::from_utf8(message.to_vec()).unwrap())
-                    }
-                    bollard::container::LogOutput::StdOut { message } => {
-                        out.push(String::from_utf8(message.to_vec()).unwrap())
-                    }
-                    bollard::container::LogOutput::StdIn { message: _ }
-                    | bollard::container::LogOutput::Console { message: _ } => unreachable!(),
-                }
-            }
+        self.backend.exec(container_name, command).await.unwrap()
+    }
+
+    /// The docker client of the runner, `None` when its backend isn't docker.
+    pub fn docker_client(&self) -> Option<&Docker> {
+        self.backend.docker()
+    }
+}
+
+impl Drop for Runner {
+    /// Keep an unfinished runner, whose containers would otherwise be left running, for
+    /// `finish_abandoned`.
+    fn drop(&mut self) {
+        if self.finished {
+            return;
         }
-        (out, err)
+        let mut placeholder = Runner::from_backend(self.config_dir.clone(), self.backend.clone());
+        placeholder.finished = true;
+        let runner = std::mem::replace(self, placeholder);
+        ABANDONED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(runner);
     }
+}
 
-    pub fn docker_client(&self) -> &Docker {
-        &self.docker
+/// Finish the runners of a configuration run that were dropped without being finished, such as
+/// when its future was dropped on a timeout, stopping their monitoring tasks and removing their
+/// containers, volumes and networks.
+pub(crate) async fn finish_abandoned(config_dir: &Path) {
+    let runners = {
+        let mut abandoned = ABANDONED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (runners, rest) = std::mem::take(&mut *abandoned)
+            .into_iter()
+            .partition::<Vec<_>, _>(|runner| runner.config_dir.starts_with(config_dir));
+        *abandoned = rest;
+        runners
+    };
+    for runner in runners {
+        warn!(config_dir = ?runner.config_dir, "Tearing down runner that wasn't finished");
+        runner.finish().await;
     }
 }
 
+/// Logs captured from a container, with each line's timestamp.
+///
+/// Plain text logs have `String` lines, JSON logs (`LogCaptureConfig::json`) have
+/// `serde_json::Value` lines.
 #[derive(Debug, Clone)]
-pub struct Logs {
+pub struct Logs<L = String> {
     pub container_name: String,
-    pub lines: Vec<(chrono::DateTime<chrono::Utc>, String)>,
+    pub lines: Vec<(chrono::DateTime<chrono::Utc>, L)>,
 }
 
 impl Logs {
+    /// Load plain text logs, decompressing them if they have been compressed.
     pub fn from_file(path: &Path) -> io::Result<Self> {
-        if let Some(file_name) = path.file_stem() {
-            if let Some(name) = file_name.to_string_lossy().strip_prefix("docker-") {
-                let file = File::open(path)?;
-                let mut lines = Vec::new();
-                for line in std::io::BufReader::new(file).lines() {
-                    let line = line.unwrap();
-                    let split = line.splitn(2, ' ').collect::<Vec<_>>();
-                    if let [date, text] = split[..] {
-                        let date = chrono::DateTime::parse_from_rfc3339(date)
-                            .unwrap()
-                            .with_timezone(&chrono::Utc);
-                        lines.push((date, text.to_owned()));
-                    }
-                }
-                Ok(Logs {
-                    container_name: name.to_owned(),
-                    lines,
+        let name = container_name_from_path(path)?;
+        let file = compression::open(path)?;
+        let mut lines = Vec::new();
+        for line in std::io::BufReader::new(file).lines() {
+            let line = line.unwrap();
+            let split = line.splitn(2, ' ').collect::<Vec<_>>();
+            if let [date, text] = split[..] {
+                let date = chrono::DateTime::parse_from_rfc3339(date)
+                    .unwrap()
+                    .with_timezone(&chrono::Utc);
+                lines.push((date, text.to_owned()));
+            }
+        }
+        Ok(Logs {
+            container_name: name,
+            lines,
+        })
+    }
+}
+
+impl Logs<serde_json::Value> {
+    /// Load logs captured with `LogCaptureConfig::json` set.
+    ///
+    /// The `docker_timestamp` field is removed from each object and used as the line timestamp.
+    pub fn from_jsonl(path: &Path) -> io::Result<Self> {
+        let name = container_name_from_path(path)?;
+        let file = compression::open(path)?;
+        let mut lines = Vec::new();
+        for line in std::io::BufReader::new(file).lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let mut value: serde_json::Value = serde_json::from_str(&line)?;
+            let date = value
+                .as_object_mut()
+                .and_then(|object| object.remove(DOCKER_TIMESTAMP_FIELD))
+                .and_then(|date| {
+                    date.as_str()
+                        .and_then(|date| chrono::DateTime::parse_from_rfc3339(date).ok())
                 })
+                .ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, "missing docker timestamp field")
+                })?
+                .with_timezone(&chrono::Utc);
+            lines.push((date, value));
+        }
+        Ok(Logs {
+            container_name: name,
+            lines,
+        })
+    }
+}
+
+impl Logs {
+    /// Load the logs of every container of a configuration run, from `logs/` including those in
+    /// phase subdirectories, sorted by container name.
+    ///
+    /// The logs of a container across phases are combined into one `Logs`. Lines of JSON logs
+    /// are their `message` if they weren't JSON when logged, and the JSON text otherwise.
+    pub fn load_all(repeat_dir: &Path) -> io::Result<Vec<Logs>> {
+        let mut containers = BTreeMap::<String, Vec<_>>::new();
+        for (_, path) in log_files(&repeat_dir.join("logs"))? {
+            let logs = if compression::uncompressed_name(&path).ends_with(".jsonl") {
+                let logs = Logs::from_jsonl(&path)?;
+                Logs {
+                    container_name: logs.container_name,
+                    lines: logs
+                        .lines
+                        .into_iter()
+                        .map(|(time, line)| (time, json_line_text(line)))
+                        .collect(),
+                }
             } else {
-                Err(io::Error::new(
-                    ErrorKind::InvalidInput,
-                    "filename should start with docker-",
-                ))
+                Logs::from_file(&path)?
+            };
+            containers
+                .entry(logs.container_name)
+                .or_default()
+                .extend(logs.lines);
+        }
+        Ok(containers
+            .into_iter()
+            .map(|(container_name, mut lines)| {
+                lines.sort_by_key(|(time, _)| *time);
+                Logs {
+                    container_name,
+                    lines,
+                }
+            })
+            .collect())
+    }
+
+    /// The lines matching `regex`, with their timestamps.
+    pub fn grep<'a>(&'a self, regex: &'a Regex) -> impl Iterator<Item = LogLine<'a>> {
+        self.lines
+            .iter()
+            .filter(move |(_, line)| regex.is_match(line))
+            .map(move |(time, line)| LogLine {
+                container_name: &self.container_name,
+                time: *time,
+                line,
+            })
+    }
+}
+
+impl<L> Logs<L> {
+    /// The lines logged within `range`, such as `start..end` of a phase.
+    pub fn between<R: RangeBounds<DateTime<Utc>>>(
+        &self,
+        range: R,
+    ) -> impl Iterator<Item = LogLine<'_, L>> {
+        self.lines
+            .iter()
+            .filter(move |(time, _)| range.contains(time))
+            .map(move |(time, line)| LogLine {
+                container_name: &self.container_name,
+                time: *time,
+                line,
+            })
+    }
+
+    /// The lines of all of `logs` in the order they were logged, for following what happened
+    /// across containers.
+    ///
+    /// The lines of each `Logs` should be in order, as they are when loaded. Lines logged at the
+    /// same time are in the order of `logs`.
+    pub fn merge(logs: &[Logs<L>]) -> MergedLogs<'_, L> {
+        let mut next = BinaryHeap::new();
+        for (index, logs) in logs.iter().enumerate() {
+            if let Some((time, _)) = logs.lines.first() {
+                next.push(Reverse((*time, index, 0)));
             }
+        }
+        MergedLogs { logs, next }
+    }
+}
+
+/// A line of a container's logs, from `Logs::grep`, `Logs::between` or `Logs::merge`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogLine<'a, L = String> {
+    pub container_name: &'a str,
+    pub time: DateTime<Utc>,
+    pub line: &'a L,
+}
+
+/// The lines of several containers' logs in the order they were logged, from `Logs::merge`.
+#[derive(Debug)]
+pub struct MergedLogs<'a, L> {
+    logs: &'a [Logs<L>],
+    /// Time, index in `logs` and index in its lines of the next line of each `Logs`.
+    next: BinaryHeap<Reverse<(DateTime<Utc>, usize, usize)>>,
+}
+
+impl<'a, L> Iterator for MergedLogs<'a, L> {
+    type Item = LogLine<'a, L>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((time, index, line)) = self.next.pop()?;
+        let logs = &self.logs[index];
+        if let Some((next_time, _)) = logs.lines.get(line + 1) {
+            self.next.push(Reverse((*next_time, index, line + 1)));
+        }
+        Some(LogLine {
+            container_name: &logs.container_name,
+            time,
+            line: &logs.lines[line].1,
+        })
+    }
+}
+
+/// The text of a line of JSON logs, unwrapping lines that weren't JSON when logged.
+fn json_line_text(line: serde_json::Value) -> String {
+    match line {
+        serde_json::Value::Object(object) if object.len() == 1 => match object.get(MESSAGE_FIELD) {
+            Some(serde_json::Value::String(message)) => message.clone(),
+            _ => serde_json::Value::Object(object).to_string(),
+        },
+        line => line.to_string(),
+    }
+}
+
+/// Get the container name from a path of the form `docker-<name>.<ext>`, or `process-<name>.<ext>`
+/// and `ssh-<name>.<ext>` for logs from the process and ssh runners.
+///
+/// A trailing `.zst` from compression is ignored.
+fn container_name_from_path(path: &Path) -> io::Result<String> {
+    let path = if path.extension() == Some(std::ffi::OsStr::new(COMPRESSED_EXTENSION)) {
+        Path::new(path.file_stem().unwrap_or_default())
+    } else {
+        path
+    };
+    if let Some(file_name) = path.file_stem() {
+        let file_name = file_name.to_string_lossy();
+        if let Some(name) = ["docker-", "process-", "ssh-"]
+            .iter()
+            .find_map(|prefix| file_name.strip_prefix(prefix))
+        {
+            Ok(name.to_owned())
         } else {
-            Err(io::Error::new(ErrorKind::NotFound, "missing file_stem"))
+            Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "filename should start with docker-, process- or ssh-",
+            ))
+        }
+    } else {
+        Err(io::Error::new(ErrorKind::NotFound, "missing file_stem"))
+    }
+}
+
+/// The start of a phase of a run, as recorded in `metrics/phases.csv`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    pub start: DateTime<Utc>,
+}
+
+/// How a container of a run ended, written to `config/docker-<name>-exit.json` when the `Runner`
+/// finishes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerExit {
+    pub name: String,
+    /// Whether the container was still running when the run finished, so was stopped.
+    pub running: bool,
+    /// Exit code of the container if it exited before the run finished.
+    pub exit_code: Option<i64>,
+    /// Whether the kernel killed a process of the container for running out of memory, which
+    /// otherwise just looks like metrics ending early.
+    pub oom_killed: bool,
+    /// Error docker reported for the container, such as failing to start it.
+    pub error: Option<String>,
+}
+
+/// Read how the containers of a configuration run ended, from `config/docker-<name>-exit.json`.
+pub fn read_container_exits(configuration_dir: &Path) -> io::Result<Vec<ContainerExit>> {
+    let config_dir = configuration_dir.join("config");
+    let mut exits = Vec::new();
+    if !config_dir.exists() {
+        return Ok(exits);
+    }
+    let mut paths = std::fs::read_dir(&config_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with("docker-") && name.ends_with("-exit.json") {
+            exits.push(serde_json::from_reader(File::open(&path)?)?);
         }
     }
+    Ok(exits)
+}
+
+/// Filesystem snapshot settings of a container, from its `ContainerConfig`.
+#[derive(Debug)]
+struct Snapshot {
+    diff: bool,
+    export_paths: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum WaitForLogError {
+    #[error("no log line of container {container} matched within {timeout:?}")]
+    Timeout {
+        container: String,
+        timeout: Duration,
+    },
+    #[error("logs of container {0} ended before a line matched")]
+    Exited(String),
+    #[error("container {0} has not been added to the runner")]
+    UnknownContainer(String),
+    #[error(transparent)]
+    Docker(#[from] bollard::errors::Error),
+}
+
+/// A file changed in a container, written to `config/docker-<name>-diff.json` when the `Runner`
+/// finishes if `ContainerConfig::diff` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: FileChangeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChangeKind {
+    Modified,
+    Added,
+    Deleted,
+}
+
+/// Read the files changed in a container of a configuration run, from
+/// `config/docker-<name>-diff.json`.
+pub fn read_file_changes(configuration_dir: &Path, container: &str) -> io::Result<Vec<FileChange>> {
+    let path = configuration_dir
+        .join("config")
+        .join(format!("docker-{}-diff.json", container));
+    Ok(serde_json::from_reader(File::open(path)?)?)
+}
+
+/// Path of the archive a path exported from a container is written to,
+/// `exports/<container>/<path>.tar` with the separators of the path replaced by `_`.
+pub fn export_archive_path(configuration_dir: &Path, container: &str, path: &str) -> PathBuf {
+    let name = path.trim_matches('/').replace('/', "_");
+    let name = if name.is_empty() { "root" } else { &name };
+    configuration_dir
+        .join("exports")
+        .join(container)
+        .join(format!("{}.tar", name))
+}
+
+/// A checkpoint or restore of a container, as recorded in
+/// `metrics/docker-<name>-checkpoints.csv`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointRecord {
+    /// When it finished.
+    pub time: DateTime<Utc>,
+    /// Either `checkpoint` or `restore`.
+    pub action: String,
+    pub checkpoint: String,
+    pub duration_secs: f64,
+}
+
+/// Run a `docker` CLI command, failing with its stderr if it fails.
+async fn docker_cli(args: &[&str]) -> io::Result<()> {
+    let output = tokio::process::Command::new("docker")
+        .args(args)
+        .output()
+        .await?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "docker {} failed with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// A lifecycle event of a container, recorded when the container has a restart policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerLifecycleEvent {
+    pub time: DateTime<Utc>,
+    /// The docker event action, one of `die`, `start`, `restart` or `oom`.
+    pub action: String,
+    /// Exit code of the container, only present for `die` events.
+    pub exit_code: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -399,16 +1564,26 @@ pub struct Stats {
     pub network_tx_dropped: Option<u64>,
     pub network_tx_errors: Option<u64>,
     pub network_tx_bytes: Option<u64>,
-    // flattened map from networks
-    pub networks_name: Option<String>,
-    pub networks_rx_dropped: Option<u64>,
-    pub networks_rx_bytes: Option<u64>,
-    pub networks_rx_errors: Option<u64>,
-    pub networks_rx_packets: Option<u64>,
-    pub networks_tx_packets: Option<u64>,
-    pub networks_tx_dropped: Option<u64>,
-    pub networks_tx_errors: Option<u64>,
-    pub networks_tx_bytes: Option<u64>,
+    // summed over the map from networks, read from the single interface columns of older files
+    /// Names of the network interfaces, sorted and joined with `,`.
+    #[serde(alias = "networks_name")]
+    pub networks_names: Option<String>,
+    #[serde(alias = "networks_rx_dropped")]
+    pub networks_total_rx_dropped: Option<u64>,
+    #[serde(alias = "networks_rx_bytes")]
+    pub networks_total_rx_bytes: Option<u64>,
+    #[serde(alias = "networks_rx_errors")]
+    pub networks_total_rx_errors: Option<u64>,
+    #[serde(alias = "networks_rx_packets")]
+    pub networks_total_rx_packets: Option<u64>,
+    #[serde(alias = "networks_tx_packets")]
+    pub networks_total_tx_packets: Option<u64>,
+    #[serde(alias = "networks_tx_dropped")]
+    pub networks_total_tx_dropped: Option<u64>,
+    #[serde(alias = "networks_tx_errors")]
+    pub networks_total_tx_errors: Option<u64>,
+    #[serde(alias = "networks_tx_bytes")]
+    pub networks_total_tx_bytes: Option<u64>,
 
     // v1 memory stats
     pub memory_stats_stats_v1_cache: Option<u64>,
@@ -489,41 +1664,7 @@ pub struct Stats {
     pub memory_stats_commitpeakbytes: Option<u64>,
     pub memory_stats_privateworkingset: Option<u64>,
 
-    // TODO: re-enable this
-    //     pub blkio_stats_index: u32,
-    //     // per blkio_stats_index
-    //     pub blkio_stats_io_service_bytes_recursive_major: Option<u64>,
-    //     pub blkio_stats_io_service_bytes_recursive_minor: Option<u64>,
-    //     pub blkio_stats_io_service_bytes_recursive_op: Option<String>,
-    //     pub blkio_stats_io_service_bytes_recursive_value: Option<u64>,
-    //     pub blkio_stats_io_serviced_recursive_major: Option<u64>,
-    //     pub blkio_stats_io_serviced_recursive_minor: Option<u64>,
-    //     pub blkio_stats_io_serviced_recursive_op: Option<String>,
-    //     pub blkio_stats_io_serviced_recursive_value: Option<u64>,
-    //     pub blkio_stats_io_queue_recursive_major: Option<u64>,
-    //     pub blkio_stats_io_queue_recursive_minor: Option<u64>,
-    //     pub blkio_stats_io_queue_recursive_op: Option<String>,
-    //     pub blkio_stats_io_queue_recursive_value: Option<u64>,
-    //     pub blkio_stats_io_service_time_recursive_major: Option<u64>,
-    //     pub blkio_stats_io_service_time_recursive_minor: Option<u64>,
-    //     pub blkio_stats_io_service_time_recursive_op: Option<String>,
-    //     pub blkio_stats_io_service_time_recursive_value: Option<u64>,
-    //     pub blkio_stats_io_wait_time_recursive_major: Option<u64>,
-    //     pub blkio_stats_io_wait_time_recursive_minor: Option<u64>,
-    //     pub blkio_stats_io_wait_time_recursive_op: Option<String>,
-    //     pub blkio_stats_io_wait_time_recursive_value: Option<u64>,
-    //     pub blkio_stats_io_merged_recursive_major: Option<u64>,
-    //     pub blkio_stats_io_merged_recursive_minor: Option<u64>,
-    //     pub blkio_stats_io_merged_recursive_op: Option<String>,
-    //     pub blkio_stats_io_merged_recursive_value: Option<u64>,
-    //     pub blkio_stats_io_time_recursive_major: Option<u64>,
-    //     pub blkio_stats_io_time_recursive_minor: Option<u64>,
-    //     pub blkio_stats_io_time_recursive_op: Option<String>,
-    //     pub blkio_stats_io_time_recursive_value: Option<u64>,
-    //     pub blkio_stats_sectors_recursive_major: Option<u64>,
-    //     pub blkio_stats_sectors_recursive_minor: Option<u64>,
-    //     pub blkio_stats_sectors_recursive_op: Option<String>,
-    //     pub blkio_stats_sectors_recursive_value: Option<u64>,
+    // per device blkio stats are in `BlkioStats`
     // TODO: re-enable this
     // pub cpu_stats_cpu_usage_percpu_usage: Option<Vec<u64>>,
     pub cpu_stats_cpu_usage_usage_in_usermode: u64,
@@ -556,225 +1697,783 @@ pub struct Stats {
 
     pub name: String,
     pub id: String,
+
+    // derived from the above, missing from stats recorded before they were added
+    /// CPU usage as a percentage of one CPU, as from `cpu_percentage`.
+    #[serde(default)]
+    pub cpu_percent: Option<f64>,
+    /// Memory in use as a percentage of the limit, as from `memory_used`.
+    #[serde(default)]
+    pub memory_percent: Option<f64>,
+    /// Bytes received per second since the previous sample.
+    #[serde(default)]
+    pub network_rx_bytes_per_second: Option<f64>,
+    /// Bytes sent per second since the previous sample.
+    #[serde(default)]
+    pub network_tx_bytes_per_second: Option<f64>,
 }
 
 impl Stats {
-    fn from_bollard(stats: bollard::container::Stats) -> Vec<Stats> {
-        let bollard::container::Stats {
-            read,
-            preread,
-            num_procs,
-            pids_stats,
-            network,
-            networks,
-            memory_stats,
-            blkio_stats: _,
-            cpu_stats,
-            precpu_stats,
-            storage_stats,
-            name,
-            id,
-        } = stats;
-
-        let mut v = Vec::new();
-
-        let memv1 = memory_stats.stats.and_then(|v| {
-            if let MemoryStatsStats::V1(v1) = v {
-                Some(v1)
-            } else {
-                None
+    /// Load the stats recorded for a container, decompressing them if they have been compressed.
+    ///
+    /// The derived columns are calculated for stats recorded before they were added.
+    pub fn from_file(path: &Path) -> Result<Vec<Stats>, csv::Error> {
+        let file = compression::open(path)?;
+        let mut stats = csv::Reader::from_reader(file)
+            .deserialize()
+            .collect::<Result<Vec<Stats>, _>>()?;
+        if stats.iter().all(|s| {
+            s.cpu_percent.is_none()
+                && s.memory_percent.is_none()
+                && s.network_rx_bytes_per_second.is_none()
+                && s.network_tx_bytes_per_second.is_none()
+        }) {
+            for i in 0..stats.len() {
+                let (before, after) = stats.split_at_mut(i);
+                after[0].derive(before.last());
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Load the stats of every container of a configuration run, from
+    /// `metrics/docker-<name>-stat.csv` including those in phase subdirectories, with the name
+    /// of their container.
+    pub fn from_configuration(
+        configuration_dir: &Path,
+    ) -> Result<Vec<(String, Stats)>, csv::Error> {
+        let mut stats = Vec::new();
+        for path in metric_files(&configuration_dir.join("metrics"), "-stat.csv")? {
+            let name = compression::uncompressed_name(&path);
+            let name = name
+                .trim_start_matches("docker-")
+                .trim_end_matches("-stat.csv")
+                .to_owned();
+            for stat in Stats::from_file(&path)? {
+                stats.push((name.clone(), stat));
             }
+        }
+        Ok(stats)
+    }
+
+    /// CPU usage since the previous sample as a percentage of one CPU, calculated as `docker
+    /// stats` does.
+    pub fn cpu_percentage(&self) -> Option<f64> {
+        cpu_percentage(
+            (
+                self.cpu_stats_cpu_usage_total_usage,
+                self.cpu_stats_system_cpu_usage,
+            ),
+            (
+                self.precpu_stats_cpu_usage_total_usage,
+                self.precpu_stats_system_cpu_usage,
+            ),
+            self.cpu_stats_online_cpus,
+        )
+    }
+
+    /// Memory in use, excluding the inactive page cache as `docker stats` does.
+    pub fn memory_used(&self) -> Option<u64> {
+        let usage = self.memory_stats_usage?;
+        let inactive = self
+            .memory_stats_stats_v1_total_inactive_file
+            .or(self.memory_stats_stats_v2_inactive_file)
+            .unwrap_or(0);
+        Some(usage.saturating_sub(inactive))
+    }
+
+    /// Fill in the derived columns, given the previous sample of the same container for the
+    /// network rates and the CPU usage of one-shot samples.
+    pub fn derive(&mut self, previous: Option<&Stats>) {
+        if let Some(previous) = previous {
+            self.precpu_from(previous);
+        }
+        self.cpu_percent = self.cpu_percentage();
+        self.memory_percent = match (self.memory_used(), self.memory_stats_limit) {
+            (Some(used), Some(limit)) if limit > 0 => Some(used as f64 / limit as f64 * 100.0),
+            _ => None,
+        };
+        let rates = previous.and_then(|previous| {
+            let seconds = (self.read - previous.read).num_milliseconds() as f64 / 1000.0;
+            if seconds <= 0.0 {
+                return None;
+            }
+            let (rx, tx) = self.network_bytes()?;
+            let (previous_rx, previous_tx) = previous.network_bytes()?;
+            Some((
+                rx.checked_sub(previous_rx)? as f64 / seconds,
+                tx.checked_sub(previous_tx)? as f64 / seconds,
+            ))
         });
-        let memv2 = memory_stats.stats.and_then(|v| {
-            if let MemoryStatsStats::V2(v2) = v {
-                Some(v2)
-            } else {
+        self.network_rx_bytes_per_second = rates.map(|(rx, _)| rx);
+        self.network_tx_bytes_per_second = rates.map(|(_, tx)| tx);
+    }
+
+    /// Take the previous CPU usage from the previous sample if docker didn't give it, as for
+    /// one-shot samples, so the usage is since then rather than since the container started.
+    fn precpu_from(&mut self, previous: &Stats) {
+        if self.precpu_stats_system_cpu_usage.unwrap_or(0) != 0 {
+            return;
+        }
+        self.precpu_stats_cpu_usage_usage_in_usermode =
+            previous.cpu_stats_cpu_usage_usage_in_usermode;
+        self.precpu_stats_cpu_usage_total_usage = previous.cpu_stats_cpu_usage_total_usage;
+        self.precpu_stats_cpu_usage_usage_in_kernelmode =
+            previous.cpu_stats_cpu_usage_usage_in_kernelmode;
+        self.precpu_stats_system_cpu_usage = previous.cpu_stats_system_cpu_usage;
+        self.precpu_stats_online_cpus = previous.cpu_stats_online_cpus;
+        self.precpu_stats_throttling_data_periods = previous.cpu_stats_throttling_data_periods;
+        self.precpu_stats_throttling_data_throttled_periods =
+            previous.cpu_stats_throttling_data_throttled_periods;
+        self.precpu_stats_throttling_data_throttled_time =
+            previous.cpu_stats_throttling_data_throttled_time;
+    }
+
+    /// Bytes received and sent, over all networks or else from the legacy network stats.
+    fn network_bytes(&self) -> Option<(u64, u64)> {
+        Some((
+            self.networks_total_rx_bytes.or(self.network_rx_bytes)?,
+            self.networks_total_tx_bytes.or(self.network_tx_bytes)?,
+        ))
+    }
+
+    /// Flatten a sample split by `ContainerStats::from_bollard` into a row, given the previous
+    /// split sample of the same container for the `precpu_*` columns.
+    ///
+    /// Only the columns the split records have are filled in. The `networks_total_*`
+    /// columns are summed over the container's network interfaces.
+    pub fn flatten(
+        split: &ContainerStats,
+        previous: Option<&ContainerStats>,
+        name: &str,
+        id: &str,
+    ) -> Stats {
+        let ContainerStats {
+            cpu,
+            memory,
+            networks,
+            blkio: _,
+        } = split;
+        let read = cpu.read;
+        let precpu = previous.map(|previous| &previous.cpu);
+        let v1 = memory.cgroup_version == Some(1);
+        let v2 = memory.cgroup_version == Some(2);
+        let networks_sum = |field: fn(&NetStats) -> u64| {
+            (!networks.is_empty()).then(|| networks.iter().map(field).sum())
+        };
+        // over all interfaces, once each has a rate
+        let rate = |field: fn(&NetStats) -> Option<f64>| {
+            if networks.is_empty() {
                 None
+            } else {
+                networks.iter().map(field).sum()
             }
-        });
+        };
+        Stats {
+            read,
+            preread: previous.map_or(read, |previous| previous.cpu.read),
+            num_procs: 0,
+            pids_stats_current: cpu.pids,
+            pids_stats_limit: cpu.pids_limit,
+            network_rx_dropped: None,
+            network_rx_bytes: None,
+            network_rx_errors: None,
+            network_rx_packets: None,
+            network_tx_packets: None,
+            network_tx_dropped: None,
+            network_tx_errors: None,
+            network_tx_bytes: None,
+            networks_names: (!networks.is_empty()).then(|| {
+                networks
+                    .iter()
+                    .map(|network| network.interface.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            networks_total_rx_dropped: networks_sum(|network| network.rx_dropped),
+            networks_total_rx_bytes: networks_sum(|network| network.rx_bytes),
+            networks_total_rx_errors: networks_sum(|network| network.rx_errors),
+            networks_total_rx_packets: networks_sum(|network| network.rx_packets),
+            networks_total_tx_packets: networks_sum(|network| network.tx_packets),
+            networks_total_tx_dropped: networks_sum(|network| network.tx_dropped),
+            networks_total_tx_errors: networks_sum(|network| network.tx_errors),
+            networks_total_tx_bytes: networks_sum(|network| network.tx_bytes),
+            memory_stats_stats_v1_cache: None,
+            memory_stats_stats_v1_dirty: None,
+            memory_stats_stats_v1_mapped_file: None,
+            memory_stats_stats_v1_total_inactive_file: memory.inactive_file.filter(|_| v1),
+            memory_stats_stats_v1_pgpgout: None,
+            memory_stats_stats_v1_rss: None,
+            memory_stats_stats_v1_total_mapped_file: None,
+            memory_stats_stats_v1_writeback: None,
+            memory_stats_stats_v1_unevictable: None,
+            memory_stats_stats_v1_pgpgin: None,
+            memory_stats_stats_v1_total_unevictable: None,
+            memory_stats_stats_v1_pgmajfault: None,
+            memory_stats_stats_v1_total_rss: memory.anon.filter(|_| v1),
+            memory_stats_stats_v1_total_rss_huge: None,
+            memory_stats_stats_v1_total_writeback: None,
+            memory_stats_stats_v1_total_inactive_anon: None,
+            memory_stats_stats_v1_rss_huge: None,
+            memory_stats_stats_v1_hierarchical_memory_limit: None,
+            memory_stats_stats_v1_total_pgfault: memory.pgfault.filter(|_| v1),
+            memory_stats_stats_v1_total_active_file: memory.active_file.filter(|_| v1),
+            memory_stats_stats_v1_active_anon: None,
+            memory_stats_stats_v1_total_active_anon: None,
+            memory_stats_stats_v1_total_pgpgout: None,
+            memory_stats_stats_v1_total_cache: memory.file.filter(|_| v1),
+            memory_stats_stats_v1_total_dirty: None,
+            memory_stats_stats_v1_inactive_anon: None,
+            memory_stats_stats_v1_active_file: None,
+            memory_stats_stats_v1_pgfault: None,
+            memory_stats_stats_v1_inactive_file: None,
+            memory_stats_stats_v1_total_pgmajfault: memory.pgmajfault.filter(|_| v1),
+            memory_stats_stats_v1_total_pgpgin: None,
+            memory_stats_stats_v1_hierarchical_memsw_limit: None,
+            memory_stats_stats_v1_shmem: None,
+            memory_stats_stats_v1_total_shmem: memory.shmem.filter(|_| v1),
+            memory_stats_stats_v2_anon: memory.anon.filter(|_| v2),
+            memory_stats_stats_v2_file: memory.file.filter(|_| v2),
+            memory_stats_stats_v2_kernel_stack: None,
+            memory_stats_stats_v2_slab: None,
+            memory_stats_stats_v2_sock: None,
+            memory_stats_stats_v2_shmem: memory.shmem.filter(|_| v2),
+            memory_stats_stats_v2_file_mapped: None,
+            memory_stats_stats_v2_file_dirty: None,
+            memory_stats_stats_v2_file_writeback: None,
+            memory_stats_stats_v2_anon_thp: None,
+            memory_stats_stats_v2_inactive_anon: None,
+            memory_stats_stats_v2_active_anon: None,
+            memory_stats_stats_v2_inactive_file: memory.inactive_file.filter(|_| v2),
+            memory_stats_stats_v2_active_file: memory.active_file.filter(|_| v2),
+            memory_stats_stats_v2_unevictable: None,
+            memory_stats_stats_v2_slab_reclaimable: None,
+            memory_stats_stats_v2_slab_unreclaimable: None,
+            memory_stats_stats_v2_pgfault: memory.pgfault.filter(|_| v2),
+            memory_stats_stats_v2_pgmajfault: memory.pgmajfault.filter(|_| v2),
+            memory_stats_stats_v2_workingset_refault: None,
+            memory_stats_stats_v2_workingset_activate: None,
+            memory_stats_stats_v2_workingset_nodereclaim: None,
+            memory_stats_stats_v2_pgrefill: None,
+            memory_stats_stats_v2_pgscan: None,
+            memory_stats_stats_v2_pgsteal: None,
+            memory_stats_stats_v2_pgactivate: None,
+            memory_stats_stats_v2_pgdeactivate: None,
+            memory_stats_stats_v2_pglazyfree: None,
+            memory_stats_stats_v2_pglazyfreed: None,
+            memory_stats_stats_v2_thp_fault_alloc: None,
+            memory_stats_stats_v2_thp_collapse_alloc: None,
+            memory_stats_max_usage: memory.max_usage,
+            memory_stats_usage: memory.usage,
+            memory_stats_failcnt: memory.failcnt,
+            memory_stats_limit: memory.limit,
+            memory_stats_commit: None,
+            memory_stats_commit_peak: None,
+            memory_stats_commitbytes: None,
+            memory_stats_commitpeakbytes: None,
+            memory_stats_privateworkingset: None,
+            cpu_stats_cpu_usage_usage_in_usermode: cpu.usermode_usage,
+            cpu_stats_cpu_usage_total_usage: cpu.total_usage,
+            cpu_stats_cpu_usage_usage_in_kernelmode: cpu.kernelmode_usage,
+            cpu_stats_system_cpu_usage: cpu.system_usage,
+            cpu_stats_online_cpus: cpu.online_cpus,
+            cpu_stats_throttling_data_periods: cpu.throttling_periods,
+            cpu_stats_throttling_data_throttled_periods: cpu.throttled_periods,
+            cpu_stats_throttling_data_throttled_time: cpu.throttled_time,
+            precpu_stats_cpu_usage_usage_in_usermode: precpu
+                .map_or(0, |precpu| precpu.usermode_usage),
+            precpu_stats_cpu_usage_total_usage: precpu.map_or(0, |precpu| precpu.total_usage),
+            precpu_stats_cpu_usage_usage_in_kernelmode: precpu
+                .map_or(0, |precpu| precpu.kernelmode_usage),
+            precpu_stats_system_cpu_usage: precpu.and_then(|precpu| precpu.system_usage),
+            precpu_stats_online_cpus: precpu.and_then(|precpu| precpu.online_cpus),
+            precpu_stats_throttling_data_periods: precpu
+                .map_or(0, |precpu| precpu.throttling_periods),
+            precpu_stats_throttling_data_throttled_periods: precpu
+                .map_or(0, |precpu| precpu.throttled_periods),
+            precpu_stats_throttling_data_throttled_time: precpu
+                .map_or(0, |precpu| precpu.throttled_time),
+            storage_stats_read_count_normalized: None,
+            storage_stats_read_size_bytes: None,
+            storage_stats_write_count_normalized: None,
+            storage_stats_write_size_bytes: None,
+            name: name.to_owned(),
+            id: id.to_owned(),
+            cpu_percent: cpu.percent,
+            memory_percent: memory.percent,
+            network_rx_bytes_per_second: rate(|network| network.rx_bytes_per_second),
+            network_tx_bytes_per_second: rate(|network| network.tx_bytes_per_second),
+        }
+    }
+}
+
+/// CPU usage since the previous sample as a percentage of one CPU, from the `(total, system)`
+/// usage of a sample and the previous one, calculated as `docker stats` does.
+fn cpu_percentage(
+    (total, system): (u64, Option<u64>),
+    (previous_total, previous_system): (u64, Option<u64>),
+    online_cpus: Option<u64>,
+) -> Option<f64> {
+    let cpu_delta = total.checked_sub(previous_total)?;
+    let system_delta = system?.checked_sub(previous_system?)?;
+    if system_delta == 0 {
+        return None;
+    }
+    Some(cpu_delta as f64 / system_delta as f64 * online_cpus.unwrap_or(1) as f64 * 100.0)
+}
 
-        let mut networks = networks.iter().flat_map(|v| v.iter());
-        // let mut blio_stats = blkio_stats.as_mut().unwrap().iter();
+/// A sample of a container's stats split into typed records, each kind written to its own file
+/// in the metrics directory: `docker-<name>-cpu.csv`, `docker-<name>-memory.csv`,
+/// `docker-<name>-network.csv` and `docker-<name>-blkio.csv`.
+///
+/// `Stats::flatten` turns them into a row of `docker-<name>-stat.csv`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerStats {
+    pub cpu: CpuStats,
+    pub memory: MemStats,
+    /// One per network interface, by name.
+    pub networks: Vec<NetStats>,
+    /// One per block device, by device number.
+    pub blkio: Vec<BlkioStats>,
+}
+
+impl ContainerStats {
+    /// Split a sample, given the previous sample of the same container for the network rates
+    /// and the CPU usage of one-shot samples.
+    pub fn from_bollard(
+        stats: &bollard::container::Stats,
+        previous: Option<&ContainerStats>,
+    ) -> Self {
+        let read = stats.read;
+        let cpu_stats = &stats.cpu_stats;
+        // one-shot samples don't have the previous usage
+        let precpu = match (stats.precpu_stats.system_cpu_usage, previous) {
+            (None | Some(0), Some(previous)) => {
+                (previous.cpu.total_usage, previous.cpu.system_usage)
+            }
+            _ => (
+                stats.precpu_stats.cpu_usage.total_usage,
+                stats.precpu_stats.system_cpu_usage,
+            ),
+        };
+        let cpu = CpuStats {
+            read,
+            pids: stats.pids_stats.current,
+            pids_limit: stats.pids_stats.limit,
+            total_usage: cpu_stats.cpu_usage.total_usage,
+            usermode_usage: cpu_stats.cpu_usage.usage_in_usermode,
+            kernelmode_usage: cpu_stats.cpu_usage.usage_in_kernelmode,
+            system_usage: cpu_stats.system_cpu_usage,
+            online_cpus: cpu_stats.online_cpus,
+            throttling_periods: cpu_stats.throttling_data.periods,
+            throttled_periods: cpu_stats.throttling_data.throttled_periods,
+            throttled_time: cpu_stats.throttling_data.throttled_time,
+            percent: cpu_percentage(
+                (cpu_stats.cpu_usage.total_usage, cpu_stats.system_cpu_usage),
+                precpu,
+                cpu_stats.online_cpus,
+            ),
+        };
 
-        let networks = networks.next();
-        let stat = Stats {
+        let memory_stats = &stats.memory_stats;
+        let mut memory = MemStats {
             read,
-            preread,
-            num_procs,
-            pids_stats_current: pids_stats.current,
-            pids_stats_limit: pids_stats.limit,
-            network_rx_dropped: network.map(|v| v.rx_dropped),
-            network_rx_bytes: network.map(|v| v.rx_bytes),
-            network_rx_errors: network.map(|v| v.rx_errors),
-            network_rx_packets: network.map(|v| v.rx_packets),
-            network_tx_packets: network.map(|v| v.tx_packets),
-            network_tx_dropped: network.map(|v| v.tx_dropped),
-            network_tx_errors: network.map(|v| v.tx_errors),
-            network_tx_bytes: network.map(|v| v.tx_bytes),
-
-            networks_name: networks.map(|n| n.0.clone()),
-            networks_rx_dropped: networks.map(|n| n.1.rx_dropped),
-            networks_rx_bytes: networks.map(|n| n.1.rx_bytes),
-            networks_rx_errors: networks.map(|n| n.1.rx_errors),
-            networks_rx_packets: networks.map(|n| n.1.rx_packets),
-            networks_tx_packets: networks.map(|n| n.1.tx_packets),
-            networks_tx_dropped: networks.map(|n| n.1.tx_dropped),
-            networks_tx_errors: networks.map(|n| n.1.tx_errors),
-            networks_tx_bytes: networks.map(|n| n.1.tx_bytes),
-
-            memory_stats_stats_v1_cache: memv1.map(|v| v.cache),
-            memory_stats_stats_v1_dirty: memv1.map(|v| v.dirty),
-            memory_stats_stats_v1_mapped_file: memv1.map(|v| v.mapped_file),
-            memory_stats_stats_v1_total_inactive_file: memv1.map(|v| v.total_inactive_file),
-            memory_stats_stats_v1_pgpgout: memv1.map(|v| v.pgpgout),
-            memory_stats_stats_v1_rss: memv1.map(|v| v.rss),
-            memory_stats_stats_v1_total_mapped_file: memv1.map(|v| v.total_mapped_file),
-            memory_stats_stats_v1_writeback: memv1.map(|v| v.writeback),
-            memory_stats_stats_v1_unevictable: memv1.map(|v| v.unevictable),
-            memory_stats_stats_v1_pgpgin: memv1.map(|v| v.pgpgin),
-            memory_stats_stats_v1_total_unevictable: memv1.map(|v| v.total_unevictable),
-            memory_stats_stats_v1_pgmajfault: memv1.map(|v| v.pgmajfault),
-            memory_stats_stats_v1_total_rss: memv1.map(|v| v.total_rss),
-            memory_stats_stats_v1_total_rss_huge: memv1.map(|v| v.total_rss_huge),
-            memory_stats_stats_v1_total_writeback: memv1.map(|v| v.total_writeback),
-            memory_stats_stats_v1_total_inactive_anon: memv1.map(|v| v.total_inactive_anon),
-            memory_stats_stats_v1_rss_huge: memv1.map(|v| v.rss_huge),
-            memory_stats_stats_v1_hierarchical_memory_limit: memv1
-                .map(|v| v.hierarchical_memory_limit),
-            memory_stats_stats_v1_total_pgfault: memv1.map(|v| v.total_pgfault),
-            memory_stats_stats_v1_total_active_file: memv1.map(|v| v.total_active_file),
-            memory_stats_stats_v1_active_anon: memv1.map(|v| v.active_anon),
-            memory_stats_stats_v1_total_active_anon: memv1.map(|v| v.total_active_anon),
-            memory_stats_stats_v1_total_pgpgout: memv1.map(|v| v.total_pgpgout),
-            memory_stats_stats_v1_total_cache: memv1.map(|v| v.total_cache),
-            memory_stats_stats_v1_total_dirty: memv1.map(|v| v.total_dirty),
-            memory_stats_stats_v1_inactive_anon: memv1.map(|v| v.inactive_anon),
-            memory_stats_stats_v1_active_file: memv1.map(|v| v.active_file),
-            memory_stats_stats_v1_pgfault: memv1.map(|v| v.pgfault),
-            memory_stats_stats_v1_inactive_file: memv1.map(|v| v.inactive_file),
-            memory_stats_stats_v1_total_pgmajfault: memv1.map(|v| v.total_pgmajfault),
-            memory_stats_stats_v1_total_pgpgin: memv1.map(|v| v.total_pgpgin),
-            memory_stats_stats_v1_hierarchical_memsw_limit: memv1
-                .and_then(|v| v.hierarchical_memsw_limit),
-            memory_stats_stats_v1_shmem: memv1.and_then(|v| v.shmem),
-            memory_stats_stats_v1_total_shmem: memv1.and_then(|v| v.total_shmem),
-
-            memory_stats_stats_v2_anon: memv2.map(|v| v.anon),
-            memory_stats_stats_v2_file: memv2.map(|v| v.file),
-            memory_stats_stats_v2_kernel_stack: memv2.map(|v| v.kernel_stack),
-            memory_stats_stats_v2_slab: memv2.map(|v| v.slab),
-            memory_stats_stats_v2_sock: memv2.map(|v| v.sock),
-            memory_stats_stats_v2_shmem: memv2.map(|v| v.shmem),
-            memory_stats_stats_v2_file_mapped: memv2.map(|v| v.file_mapped),
-            memory_stats_stats_v2_file_dirty: memv2.map(|v| v.file_dirty),
-            memory_stats_stats_v2_file_writeback: memv2.map(|v| v.file_writeback),
-            memory_stats_stats_v2_anon_thp: memv2.map(|v| v.anon_thp),
-            memory_stats_stats_v2_inactive_anon: memv2.map(|v| v.inactive_anon),
-            memory_stats_stats_v2_active_anon: memv2.map(|v| v.active_anon),
-            memory_stats_stats_v2_inactive_file: memv2.map(|v| v.inactive_file),
-            memory_stats_stats_v2_active_file: memv2.map(|v| v.active_file),
-            memory_stats_stats_v2_unevictable: memv2.map(|v| v.unevictable),
-            memory_stats_stats_v2_slab_reclaimable: memv2.map(|v| v.slab_reclaimable),
-            memory_stats_stats_v2_slab_unreclaimable: memv2.map(|v| v.slab_unreclaimable),
-            memory_stats_stats_v2_pgfault: memv2.map(|v| v.pgfault),
-            memory_stats_stats_v2_pgmajfault: memv2.map(|v| v.pgmajfault),
-            memory_stats_stats_v2_workingset_refault: memv2.map(|v| v.workingset_refault),
-            memory_stats_stats_v2_workingset_activate: memv2.map(|v| v.workingset_activate),
-            memory_stats_stats_v2_workingset_nodereclaim: memv2.map(|v| v.workingset_nodereclaim),
-            memory_stats_stats_v2_pgrefill: memv2.map(|v| v.pgrefill),
-            memory_stats_stats_v2_pgscan: memv2.map(|v| v.pgscan),
-            memory_stats_stats_v2_pgsteal: memv2.map(|v| v.pgsteal),
-            memory_stats_stats_v2_pgactivate: memv2.map(|v| v.pgactivate),
-            memory_stats_stats_v2_pgdeactivate: memv2.map(|v| v.pgdeactivate),
-            memory_stats_stats_v2_pglazyfree: memv2.map(|v| v.pglazyfree),
-            memory_stats_stats_v2_pglazyfreed: memv2.map(|v| v.pglazyfreed),
-            memory_stats_stats_v2_thp_fault_alloc: memv2.map(|v| v.thp_fault_alloc),
-            memory_stats_stats_v2_thp_collapse_alloc: memv2.map(|v| v.thp_collapse_alloc),
-
-            memory_stats_max_usage: memory_stats.max_usage,
-            memory_stats_usage: memory_stats.usage,
-            memory_stats_failcnt: memory_stats.failcnt,
-            memory_stats_limit: memory_stats.limit,
-            memory_stats_commit: memory_stats.commit,
-            memory_stats_commit_peak: memory_stats.commit_peak,
-            memory_stats_commitbytes: memory_stats.commitbytes,
-            memory_stats_commitpeakbytes: memory_stats.commitpeakbytes,
-            memory_stats_privateworkingset: memory_stats.privateworkingset,
-
-            // blkio_stats_index: todo!(),
-            // blkio_stats_io_service_bytes_recursive_major: todo!(),
-            // blkio_stats_io_service_bytes_recursive_minor: todo!(),
-            // blkio_stats_io_service_bytes_recursive_op: todo!(),
-            // blkio_stats_io_service_bytes_recursive_value: todo!(),
-            // blkio_stats_io_serviced_recursive_major: todo!(),
-            // blkio_stats_io_serviced_recursive_minor: todo!(),
-            // blkio_stats_io_serviced_recursive_op: todo!(),
-            // blkio_stats_io_serviced_recursive_value: todo!(),
-            // blkio_stats_io_queue_recursive_major: todo!(),
-            // blkio_stats_io_queue_recursive_minor: todo!(),
-            // blkio_stats_io_queue_recursive_op: todo!(),
-            // blkio_stats_io_queue_recursive_value: todo!(),
-            // blkio_stats_io_service_time_recursive_major: todo!(),
-            // blkio_stats_io_service_time_recursive_minor: todo!(),
-            // blkio_stats_io_service_time_recursive_op: todo!(),
-            // blkio_stats_io_service_time_recursive_value: todo!(),
-            // blkio_stats_io_wait_time_recursive_major: todo!(),
-            // blkio_stats_io_wait_time_recursive_minor: todo!(),
-            // blkio_stats_io_wait_time_recursive_op: todo!(),
-            // blkio_stats_io_wait_time_recursive_value: todo!(),
-            // blkio_stats_io_merged_recursive_major: todo!(),
-            // blkio_stats_io_merged_recursive_minor: todo!(),
-            // blkio_stats_io_merged_recursive_op: todo!(),
-            // blkio_stats_io_merged_recursive_value: todo!(),
-            // blkio_stats_io_time_recursive_major: todo!(),
-            // blkio_stats_io_time_recursive_minor: todo!(),
-            // blkio_stats_io_time_recursive_op: todo!(),
-            // blkio_stats_io_time_recursive_value: todo!(),
-            // blkio_stats_sectors_recursive_major: todo!(),
-            // blkio_stats_sectors_recursive_minor: todo!(),
-            // blkio_stats_sectors_recursive_op: todo!(),
-            // blkio_stats_sectors_recursive_value: todo!(),
-            // cpu_stats_cpu_usage_percpu_usage: cpu_stats.cpu_usage.percpu_usage,
-            cpu_stats_cpu_usage_usage_in_usermode: cpu_stats.cpu_usage.usage_in_usermode,
-            cpu_stats_cpu_usage_total_usage: cpu_stats.cpu_usage.total_usage,
-            cpu_stats_cpu_usage_usage_in_kernelmode: cpu_stats.cpu_usage.usage_in_kernelmode,
-            cpu_stats_system_cpu_usage: cpu_stats.system_cpu_usage,
-            cpu_stats_online_cpus: cpu_stats.online_cpus,
-            cpu_stats_throttling_data_periods: cpu_stats.throttling_data.periods,
-            cpu_stats_throttling_data_throttled_periods: cpu_stats
-                .throttling_data
-                .throttled_periods,
-            cpu_stats_throttling_data_throttled_time: cpu_stats.throttling_data.throttled_time,
-
-            // precpu_stats_cpu_usage_percpu_usage: precpu_stats.cpu_usage.percpu_usage,
-            precpu_stats_cpu_usage_usage_in_usermode: precpu_stats.cpu_usage.usage_in_usermode,
-            precpu_stats_cpu_usage_total_usage: precpu_stats.cpu_usage.total_usage,
-            precpu_stats_cpu_usage_usage_in_kernelmode: precpu_stats.cpu_usage.usage_in_kernelmode,
-
-            precpu_stats_system_cpu_usage: precpu_stats.system_cpu_usage,
-            precpu_stats_online_cpus: precpu_stats.online_cpus,
-            precpu_stats_throttling_data_periods: precpu_stats.throttling_data.periods,
-            precpu_stats_throttling_data_throttled_periods: precpu_stats
-                .throttling_data
-                .throttled_periods,
-            precpu_stats_throttling_data_throttled_time: precpu_stats
-                .throttling_data
-                .throttled_time,
-
-            storage_stats_read_count_normalized: storage_stats.read_count_normalized,
-            storage_stats_read_size_bytes: storage_stats.read_size_bytes,
-            storage_stats_write_count_normalized: storage_stats.write_count_normalized,
-            storage_stats_write_size_bytes: storage_stats.write_size_bytes,
-            name,
-            id,
+            usage: memory_stats.usage,
+            max_usage: memory_stats.max_usage,
+            limit: memory_stats.limit,
+            failcnt: memory_stats.failcnt,
+            used: None,
+            percent: None,
+            anon: None,
+            file: None,
+            active_file: None,
+            inactive_file: None,
+            shmem: None,
+            pgfault: None,
+            pgmajfault: None,
+            cgroup_version: None,
+        };
+        match memory_stats.stats {
+            Some(MemoryStatsStats::V1(v1)) => {
+                memory.anon = Some(v1.total_rss);
+                memory.file = Some(v1.total_cache);
+                memory.active_file = Some(v1.total_active_file);
+                memory.inactive_file = Some(v1.total_inactive_file);
+                memory.shmem = v1.total_shmem;
+                memory.pgfault = Some(v1.total_pgfault);
+                memory.pgmajfault = Some(v1.total_pgmajfault);
+                memory.cgroup_version = Some(1);
+            }
+            Some(MemoryStatsStats::V2(v2)) => {
+                memory.anon = Some(v2.anon);
+                memory.file = Some(v2.file);
+                memory.active_file = Some(v2.active_file);
+                memory.inactive_file = Some(v2.inactive_file);
+                memory.shmem = Some(v2.shmem);
+                memory.pgfault = Some(v2.pgfault);
+                memory.pgmajfault = Some(v2.pgmajfault);
+                memory.cgroup_version = Some(2);
+            }
+            None => {}
+        }
+        memory.used = memory
+            .usage
+            .map(|usage| usage.saturating_sub(memory.inactive_file.unwrap_or(0)));
+        memory.percent = match (memory.used, memory.limit) {
+            (Some(used), Some(limit)) if limit > 0 => Some(used as f64 / limit as f64 * 100.0),
+            _ => None,
+        };
+
+        let seconds = previous
+            .map(|previous| (read - previous.cpu.read).num_milliseconds() as f64 / 1000.0)
+            .filter(|seconds| *seconds > 0.0);
+        let mut networks = stats
+            .networks
+            .iter()
+            .flatten()
+            .map(|(interface, network)| {
+                let previous = previous.and_then(|previous| {
+                    previous
+                        .networks
+                        .iter()
+                        .find(|previous| &previous.interface == interface)
+                });
+                let rate = |bytes: u64, previous_bytes: fn(&NetStats) -> u64| {
+                    Some(bytes.checked_sub(previous_bytes(previous?))? as f64 / seconds?)
+                };
+                NetStats {
+                    read,
+                    interface: interface.clone(),
+                    rx_bytes: network.rx_bytes,
+                    rx_packets: network.rx_packets,
+                    rx_errors: network.rx_errors,
+                    rx_dropped: network.rx_dropped,
+                    tx_bytes: network.tx_bytes,
+                    tx_packets: network.tx_packets,
+                    tx_errors: network.tx_errors,
+                    tx_dropped: network.tx_dropped,
+                    rx_bytes_per_second: rate(network.rx_bytes, |previous| previous.rx_bytes),
+                    tx_bytes_per_second: rate(network.tx_bytes, |previous| previous.tx_bytes),
+                }
+            })
+            .collect::<Vec<_>>();
+        networks.sort_by(|a, b| a.interface.cmp(&b.interface));
+
+        let mut devices = BTreeMap::new();
+        let blkio_stats = &stats.blkio_stats;
+        for (entries, bytes) in [
+            (&blkio_stats.io_service_bytes_recursive, true),
+            (&blkio_stats.io_serviced_recursive, false),
+        ] {
+            for entry in entries.iter().flatten() {
+                let device = devices
+                    .entry((entry.major, entry.minor))
+                    .or_insert_with(|| BlkioStats {
+                        read,
+                        major: entry.major,
+                        minor: entry.minor,
+                        read_bytes: None,
+                        write_bytes: None,
+                        read_ops: None,
+                        write_ops: None,
+                    });
+                // cgroup v1 also has totals and sync and async splits of the same operations
+                let field = match (entry.op.to_ascii_lowercase().as_str(), bytes) {
+                    ("read", true) => &mut device.read_bytes,
+                    ("write", true) => &mut device.write_bytes,
+                    ("read", false) => &mut device.read_ops,
+                    ("write", false) => &mut device.write_ops,
+                    _ => continue,
+                };
+                *field = Some(field.unwrap_or(0) + entry.value);
+            }
+        }
+
+        Self {
+            cpu,
+            memory,
+            networks,
+            blkio: devices.into_values().collect(),
+        }
+    }
+}
+
+/// A kind of typed stats record, written to `metrics/docker-<name><SUFFIX>`.
+pub trait StatsRecord: Serialize + DeserializeOwned {
+    /// End of the names of the files of the records, such as `-cpu.csv`.
+    const SUFFIX: &'static str;
+
+    /// Load the records of a container, decompressing them if they have been compressed.
+    fn from_file(path: &Path) -> Result<Vec<Self>, csv::Error> {
+        csv::Reader::from_reader(compression::open(path)?)
+            .deserialize()
+            .collect()
+    }
+
+    /// Load the records of every container of a configuration run, including those in phase
+    /// subdirectories, with the name of their container.
+    fn from_configuration(configuration_dir: &Path) -> Result<Vec<(String, Self)>, csv::Error> {
+        let mut records = Vec::new();
+        for path in metric_files(&configuration_dir.join("metrics"), Self::SUFFIX)? {
+            let name = compression::uncompressed_name(&path);
+            let name = name
+                .trim_start_matches("docker-")
+                .trim_end_matches(Self::SUFFIX)
+                .to_owned();
+            for record in Self::from_file(&path)? {
+                records.push((name.clone(), record));
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// CPU usage of a container, with usage in nanoseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CpuStats {
+    pub read: DateTime<Utc>,
+    /// Number of processes and threads in the container.
+    pub pids: Option<u64>,
+    pub pids_limit: Option<u64>,
+    pub total_usage: u64,
+    pub usermode_usage: u64,
+    pub kernelmode_usage: u64,
+    /// Usage of the whole host.
+    pub system_usage: Option<u64>,
+    pub online_cpus: Option<u64>,
+    pub throttling_periods: u64,
+    pub throttled_periods: u64,
+    pub throttled_time: u64,
+    /// Usage since the previous sample as a percentage of one CPU, as `docker stats` gives.
+    pub percent: Option<f64>,
+}
+
+impl StatsRecord for CpuStats {
+    const SUFFIX: &'static str = "-cpu.csv";
+}
+
+/// Memory use of a container in bytes, the same for cgroup v1 and v2.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemStats {
+    pub read: DateTime<Utc>,
+    pub usage: Option<u64>,
+    /// Only on cgroup v1.
+    pub max_usage: Option<u64>,
+    pub limit: Option<u64>,
+    pub failcnt: Option<u64>,
+    /// Usage excluding the inactive page cache, as `docker stats` gives.
+    pub used: Option<u64>,
+    /// `used` as a percentage of `limit`.
+    pub percent: Option<f64>,
+    /// Anonymous memory, `rss` on cgroup v1.
+    pub anon: Option<u64>,
+    /// Page cache, `cache` on cgroup v1.
+    pub file: Option<u64>,
+    pub active_file: Option<u64>,
+    pub inactive_file: Option<u64>,
+    pub shmem: Option<u64>,
+    /// Count of page faults.
+    pub pgfault: Option<u64>,
+    /// Count of page faults that read from disk.
+    pub pgmajfault: Option<u64>,
+    /// Version of the cgroup the stats are from, 1 or 2.
+    pub cgroup_version: Option<u8>,
+}
+
+impl StatsRecord for MemStats {
+    const SUFFIX: &'static str = "-memory.csv";
+}
+
+/// Traffic on a network interface of a container.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetStats {
+    pub read: DateTime<Utc>,
+    pub interface: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+    /// Since the previous sample.
+    pub rx_bytes_per_second: Option<f64>,
+    /// Since the previous sample.
+    pub tx_bytes_per_second: Option<f64>,
+}
+
+impl StatsRecord for NetStats {
+    const SUFFIX: &'static str = "-network.csv";
+}
+
+/// IO of a container to a block device, identified by its `major:minor` device number.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlkioStats {
+    pub read: DateTime<Utc>,
+    pub major: u64,
+    pub minor: u64,
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+    pub read_ops: Option<u64>,
+    pub write_ops: Option<u64>,
+}
+
+impl StatsRecord for BlkioStats {
+    const SUFFIX: &'static str = "-blkio.csv";
+}
+
+/// Writers of the files of a container's stats, both flattened and split.
+struct StatsWriters {
+    stat: csv::Writer<File>,
+    cpu: csv::Writer<File>,
+    memory: csv::Writer<File>,
+    network: csv::Writer<File>,
+    blkio: csv::Writer<File>,
+}
+
+impl StatsWriters {
+    fn create(dir: &Path, name: &str) -> csv::Result<Self> {
+        let writer =
+            |suffix: &str| csv::Writer::from_path(dir.join(format!("docker-{}{}", name, suffix)));
+        Ok(Self {
+            stat: writer("-stat.csv")?,
+            cpu: writer(CpuStats::SUFFIX)?,
+            memory: writer(MemStats::SUFFIX)?,
+            network: writer(NetStats::SUFFIX)?,
+            blkio: writer(BlkioStats::SUFFIX)?,
+        })
+    }
+
+    fn write(&mut self, split: &ContainerStats) -> csv::Result<()> {
+        self.cpu.serialize(&split.cpu)?;
+        self.memory.serialize(&split.memory)?;
+        for network in &split.networks {
+            self.network.serialize(network)?;
+        }
+        for device in &split.blkio {
+            self.blkio.serialize(device)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stat.flush()?;
+        self.cpu.flush()?;
+        self.memory.flush()?;
+        self.network.flush()?;
+        self.blkio.flush()
+    }
+}
+
+/// A process running in a container, parsed from a row of `docker-<name>-top.csv`.
+///
+/// The columns of the file are whatever `ps` in the container printed, so they are matched by
+/// their common names, such as `RSS` or `RSZ`, and those that a `ps` doesn't print, like the
+/// busybox one in alpine images, are `None`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopRecord {
+    pub timestamp: DateTime<Utc>,
+    pub pid: u32,
+    pub user: Option<String>,
+    pub cpu_percent: Option<f32>,
+    pub memory_percent: Option<f32>,
+    /// Resident memory in bytes.
+    pub rss_bytes: Option<u64>,
+    pub command: String,
+}
+
+impl TopRecord {
+    /// Parse a row as returned from `docker top`, given the titles of its columns.
+    ///
+    /// Returns `None` if the row has no pid.
+    pub fn parse<S: AsRef<str>>(titles: &[S], row: &[S], timestamp: DateTime<Utc>) -> Option<Self> {
+        let column = |names: &[&str]| {
+            titles
+                .iter()
+                .position(|title| {
+                    names
+                        .iter()
+                        .any(|name| title.as_ref().trim().eq_ignore_ascii_case(name))
+                })
+                .and_then(|index| row.get(index))
+                .map(|value| value.as_ref().trim())
+                .filter(|value| !value.is_empty() && *value != "-")
         };
-        v.push(stat);
+        Some(Self {
+            timestamp,
+            pid: column(&["PID"])?.parse().ok()?,
+            user: column(&["USER", "UID"]).map(str::to_owned),
+            cpu_percent: column(&["%CPU", "PCPU"]).and_then(|cpu| cpu.parse().ok()),
+            memory_percent: column(&["%MEM", "PMEM"]).and_then(|mem| mem.parse().ok()),
+            rss_bytes: column(&["RSS", "RSZ", "RES"]).and_then(parse_kibibytes),
+            command: column(&["COMMAND", "CMD", "ARGS", "COMM"])
+                .unwrap_or_default()
+                .to_owned(),
+        })
+    }
+
+    /// Load the processes recorded for a container, decompressing them if they have been
+    /// compressed.
+    ///
+    /// Rows without a pid are skipped.
+    pub fn from_file(path: &Path) -> Result<Vec<TopRecord>, csv::Error> {
+        let file = compression::open(path)?;
+        let mut reader = csv::Reader::from_reader(file);
+        let titles = reader.headers()?.clone();
+        let titles = titles.iter().collect::<Vec<_>>();
+        let timestamp = titles.iter().position(|title| *title == "timestamp_nanos");
+        let mut records = Vec::new();
+        for row in reader.records() {
+            let row = row?;
+            let time = timestamp
+                .and_then(|index| row.get(index)?.parse().ok())
+                .map(|nanos| Utc.timestamp_nanos(nanos))
+                .unwrap_or_default();
+            let row = row.iter().collect::<Vec<_>>();
+            match TopRecord::parse(&titles, &row, time) {
+                Some(record) => records.push(record),
+                None => debug!(?row, ?path, "Skipping process without a pid"),
+            }
+        }
+        Ok(records)
+    }
 
-        v
+    /// Load the processes of every container of a configuration run, from
+    /// `metrics/docker-<name>-top.csv` including those in phase subdirectories, with the name
+    /// of their container.
+    pub fn from_configuration(
+        configuration_dir: &Path,
+    ) -> Result<Vec<(String, TopRecord)>, csv::Error> {
+        let mut records = Vec::new();
+        for path in metric_files(&configuration_dir.join("metrics"), "-top.csv")? {
+            let name = compression::uncompressed_name(&path);
+            let name = name
+                .trim_start_matches("docker-")
+                .trim_end_matches("-top.csv")
+                .to_owned();
+            for record in TopRecord::from_file(&path)? {
+                records.push((name.clone(), record));
+            }
+        }
+        Ok(records)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Parse a size from `ps`, in KiB unless it has a unit suffix as busybox prints for large sizes.
+fn parse_kibibytes(size: &str) -> Option<u64> {
+    let (number, multiplier) = match size.char_indices().last()? {
+        (index, 'k' | 'K') => (&size[..index], 1u64 << 10),
+        (index, 'm' | 'M') => (&size[..index], 1 << 20),
+        (index, 'g' | 'G') => (&size[..index], 1 << 30),
+        (index, 't' | 'T') => (&size[..index], 1 << 40),
+        _ => (size, 1 << 10),
+    };
+    let number = number.parse::<f64>().ok()?;
+    Some((number * multiplier as f64) as u64)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ContainerConfig {
     pub name: String,
     pub image_name: String,
@@ -782,30 +2481,239 @@ pub struct ContainerConfig {
     pub pull: bool,
     pub network: Option<String>,
     pub network_subnet: Option<String>,
+    /// Hostname of the container, the start of its id by default.
+    pub hostname: Option<String>,
+    /// DNS servers the container uses instead of docker's.
+    pub dns: Vec<String>,
+    /// Extra entries for `/etc/hosts` in the container, as `(hostname, ip)`, such as for nodes of
+    /// a cluster to address each other by name on networks without docker's DNS.
+    pub extra_hosts: Vec<(String, String)>,
     pub command: Option<Vec<String>>,
     pub env: Option<Vec<String>>,
-    pub ports: Option<Vec<(String, String)>>,
+    /// Container ports to publish on the host, as `(host, container)`.
+    ///
+    /// With `HostPort::Auto` docker picks a free host port, so configurations run in parallel
+    /// don't clash, see `Runner::host_port`.
+    pub ports: Option<Vec<(HostPort, String)>>,
     pub capabilities: Option<Vec<String>>,
+    /// Run the container with all capabilities and access to the host's devices, such as to run
+    /// a container runtime or eBPF tools inside it.
+    pub privileged: bool,
+    /// Mount the container's root filesystem read-only, with `tmpfs` for paths it writes to.
+    pub read_only: bool,
+    /// Security options, such as `seccomp=<profile.json>`, `apparmor=<profile>` or
+    /// `no-new-privileges`.
+    ///
+    /// Seccomp profiles are read from the file, as with `docker run`.
+    pub security_opt: Vec<String>,
     pub cpus: Option<f64>,
+    /// CPUs the container may run on, e.g. `0-3` or `0,2`.
+    pub cpuset_cpus: Option<String>,
+    /// NUMA memory nodes the container may allocate from, e.g. `0`.
+    pub cpuset_mems: Option<String>,
     pub memory: Option<i64>,
+    /// Limit the rate the container reads from devices, as `(device, bytes per second)`, such
+    /// as `("/dev/sda", 10_000_000)` to emulate a slow disk.
+    pub blkio_device_read_bps: Vec<(String, i64)>,
+    /// Limit the rate the container writes to devices, as `(device, bytes per second)`.
+    pub blkio_device_write_bps: Vec<(String, i64)>,
+    /// Limit the read operations of the container on devices, as `(device, IO per second)`.
+    pub blkio_device_read_iops: Vec<(String, i64)>,
+    /// Limit the write operations of the container on devices, as `(device, IO per second)`.
+    pub blkio_device_write_iops: Vec<(String, i64)>,
     /// Mount the given paths as tmpfs directories.
     pub tmpfs: Vec<String>,
     pub volumes: Vec<(String, String)>,
+    /// Bind mount host paths read-only, as `(host, target)`, such as for configuration files.
+    pub read_only_volumes: Vec<(String, String)>,
+    /// Mount named docker volumes, as `(volume, target)`.
+    ///
+    /// Volumes not already added with `Runner::add_volume` are created with default settings.
+    pub named_volumes: Vec<(String, String)>,
+    /// Resource limits to set in the container, e.g. `nofile`.
+    pub ulimits: Vec<Ulimit>,
+    /// Host devices to give the container access to, e.g. `/dev/nvme0n1` or `/dev/kvm`.
+    pub devices: Vec<DeviceMapping>,
+    /// Record the files changed in the container to `config/docker-<name>-diff.json` when the
+    /// run finishes.
+    pub diff: bool,
+    /// Paths in the container to export as tar archives into `exports/<name>/` when the run
+    /// finishes, such as a database's data directory to see its size on disk.
+    pub export_paths: Vec<String>,
+    /// Take a single sample of the container's stats this often, rather than docker's stream of
+    /// a sample about every second, to lighten the load of sampling many containers.
+    ///
+    /// CPU usage is then the mean between samples.
+    #[serde(default, with = "crate::run::seconds")]
+    pub stats_interval: Option<Duration>,
+    /// Size of `/dev/shm` in bytes.
+    pub shm_size: Option<i64>,
+    /// Namespaced kernel parameters to set in the container, e.g. `net.core.somaxconn`.
+    pub sysctls: Vec<(String, String)>,
+    /// Restart policy for the container.
+    ///
+    /// When set, container `die`, `start` and `restart` events are recorded in
+    /// `metrics/docker-<name>-restarts.csv`.
+    pub restart_policy: Option<RestartPolicy>,
+    /// How to write the captured container logs.
+    pub logs: LogCaptureConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RestartPolicy {
+    No,
+    Always,
+    UnlessStopped,
+    OnFailure { max_retries: Option<i64> },
+}
+
+impl RestartPolicy {
+    fn to_bollard(&self) -> bollard::models::RestartPolicy {
+        let (name, maximum_retry_count) = match self {
+            RestartPolicy::No => (RestartPolicyNameEnum::NO, None),
+            RestartPolicy::Always => (RestartPolicyNameEnum::ALWAYS, None),
+            RestartPolicy::UnlessStopped => (RestartPolicyNameEnum::UNLESS_STOPPED, None),
+            RestartPolicy::OnFailure { max_retries } => {
+                (RestartPolicyNameEnum::ON_FAILURE, *max_retries)
+            }
+        };
+        bollard::models::RestartPolicy {
+            name: Some(name),
+            maximum_retry_count,
+        }
+    }
+}
+
+/// A port on the host to publish a container port on.
+///
+/// Written as the port number, or `auto`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum HostPort {
+    /// A free port picked by docker when the container starts.
+    Auto,
+    Fixed(String),
+}
+
+impl From<String> for HostPort {
+    fn from(port: String) -> Self {
+        if port == "auto" {
+            HostPort::Auto
+        } else {
+            HostPort::Fixed(port)
+        }
+    }
+}
+
+impl From<&str> for HostPort {
+    fn from(port: &str) -> Self {
+        HostPort::from(port.to_owned())
+    }
+}
+
+impl From<HostPort> for String {
+    fn from(port: HostPort) -> Self {
+        match port {
+            HostPort::Auto => "auto".to_owned(),
+            HostPort::Fixed(port) => port,
+        }
+    }
+}
+
+fn throttle_devices(limits: &[(String, i64)]) -> Vec<ThrottleDevice> {
+    limits
+        .iter()
+        .map(|(path, rate)| ThrottleDevice {
+            path: Some(path.clone()),
+            rate: Some(*rate),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ulimit {
+    pub name: String,
+    pub soft: i64,
+    pub hard: i64,
+}
+
+/// A host device made available in a container.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceMapping {
+    pub path_on_host: String,
+    /// Path of the device in the container, the same as on the host by default.
+    pub path_in_container: Option<String>,
+    /// Cgroup permissions of the container on the device, a combination of `r`ead, `w`rite and
+    /// `m`knod, `rwm` by default.
+    pub permissions: Option<String>,
+}
+
+impl DeviceMapping {
+    /// Map the device at `path` to the same path in the container, with all permissions.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path_on_host: path.into(),
+            path_in_container: None,
+            permissions: None,
+        }
+    }
+
+    fn to_bollard(&self) -> bollard::models::DeviceMapping {
+        bollard::models::DeviceMapping {
+            path_on_host: Some(self.path_on_host.clone()),
+            path_in_container: Some(
+                self.path_in_container
+                    .clone()
+                    .unwrap_or_else(|| self.path_on_host.clone()),
+            ),
+            cgroup_permissions: Some(self.permissions.clone().unwrap_or_else(|| "rwm".to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeConfig {
+    pub name: String,
+    /// Volume driver to use, defaults to `local`.
+    pub driver: Option<String>,
+    /// Save the contents of the volume as `volumes/<name>.tar` in the configuration directory
+    /// before removing it.
+    pub preserve: bool,
 }
 
 impl ContainerConfig {
-    fn to_create_container_config(&self) -> Config<String> {
+    /// The security options with seccomp profiles read from their files, as the API takes the
+    /// profile itself.
+    fn security_options(&self) -> Vec<String> {
+        self.security_opt
+            .iter()
+            .map(|option| match option.strip_prefix("seccomp=") {
+                Some(profile) if profile != "unconfined" && Path::new(profile).is_file() => {
+                    let profile =
+                        std::fs::read_to_string(profile).expect("Failed to read seccomp profile");
+                    format!("seccomp={}", profile)
+                }
+                _ => option.clone(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn to_create_container_config(&self) -> Config<String> {
         let mut exposed_ports = HashMap::new();
         let mut port_bindings = HashMap::new();
         if let Some(ports) = &self.ports {
             for (i, e) in ports {
                 let e = format!("{}/tcp", e);
                 exposed_ports.insert(e.clone(), HashMap::new());
+                let host_port = match i {
+                    HostPort::Auto => None,
+                    HostPort::Fixed(port) => Some(port.clone()),
+                };
                 port_bindings.insert(
                     e.clone(),
                     Some(vec![PortBinding {
                         host_ip: Some("0.0.0.0".to_owned()),
-                        host_port: Some(i.clone()),
+                        host_port,
                     }]),
                 );
             }
@@ -825,10 +2733,24 @@ impl ContainerConfig {
         let mut volume_mounts = self
             .volumes
             .iter()
-            .map(|(host, target)| Mount {
+            .map(|volume| (volume, false))
+            .chain(self.read_only_volumes.iter().map(|volume| (volume, true)))
+            .map(|((host, target), read_only)| Mount {
                 target: Some(target.clone()),
                 source: Some(host.clone()),
                 typ: Some(MountTypeEnum::BIND),
+                read_only: Some(read_only),
+                ..Default::default()
+            })
+            .collect();
+
+        let mut named_volume_mounts = self
+            .named_volumes
+            .iter()
+            .map(|(volume, target)| Mount {
+                target: Some(target.clone()),
+                source: Some(volume.clone()),
+                typ: Some(MountTypeEnum::VOLUME),
                 ..Default::default()
             })
             .collect();
@@ -836,10 +2758,12 @@ impl ContainerConfig {
         let mut mounts = Vec::new();
         mounts.append(&mut tmpfs_mounts);
         mounts.append(&mut volume_mounts);
+        mounts.append(&mut named_volume_mounts);
 
         Config {
             image: Some(format!("{}:{}", self.image_name, self.image_tag)),
             cmd: self.command.clone(),
+            hostname: self.hostname.clone(),
             exposed_ports: Some(exposed_ports),
             host_config: Some(HostConfig {
                 port_bindings: Some(port_bindings),
@@ -849,11 +2773,41 @@ impl ContainerConfig {
                         .unwrap_or(&"default".to_owned())
                         .to_owned(),
                 ),
+                dns: Some(self.dns.clone()),
+                extra_hosts: Some(
+                    self.extra_hosts
+                        .iter()
+                        .map(|(hostname, ip)| format!("{}:{}", hostname, ip))
+                        .collect(),
+                ),
                 cap_add: self.capabilities.clone(),
+                privileged: Some(self.privileged),
+                readonly_rootfs: Some(self.read_only),
+                security_opt: Some(self.security_options()),
                 cpu_period: self.cpus.map(|_| cpu_period),
                 cpu_quota: self.cpus.map(|cpus| (cpu_period as f64 * cpus) as i64),
+                cpuset_cpus: self.cpuset_cpus.clone(),
+                cpuset_mems: self.cpuset_mems.clone(),
                 memory: self.memory,
+                blkio_device_read_bps: Some(throttle_devices(&self.blkio_device_read_bps)),
+                blkio_device_write_bps: Some(throttle_devices(&self.blkio_device_write_bps)),
+                blkio_device_read_iops: Some(throttle_devices(&self.blkio_device_read_iops)),
+                blkio_device_write_iops: Some(throttle_devices(&self.blkio_device_write_iops)),
                 mounts: Some(mounts),
+                ulimits: Some(
+                    self.ulimits
+                        .iter()
+                        .map(|u| ResourcesUlimits {
+                            name: Some(u.name.clone()),
+                            soft: Some(u.soft),
+                            hard: Some(u.hard),
+                        })
+                        .collect(),
+                ),
+                devices: Some(self.devices.iter().map(DeviceMapping::to_bollard).collect()),
+                shm_size: self.shm_size,
+                sysctls: Some(self.sysctls.iter().cloned().collect()),
+                restart_policy: self.restart_policy.as_ref().map(|r| r.to_bollard()),
                 ..Default::default()
             }),
             env: self.env.clone(),
@@ -862,7 +2816,110 @@ impl ContainerConfig {
     }
 }
 
-fn create_config_dir(parent: &Path) -> Result<PathBuf, io::Error> {
+/// Build the ownership labels for resources created in the given configuration directory.
+///
+/// The configuration directory is expected to be `<experiment>/<hash>[.running]`.
+fn ownership_labels(config_dir: &Path) -> HashMap<String, String> {
+    let mut labels = HashMap::new();
+    if let Some(hash) = config_dir.file_stem() {
+        labels.insert(CONFIG_LABEL.to_owned(), hash.to_string_lossy().into_owned());
+    }
+    if let Some(experiment) = config_dir.parent().and_then(|p| p.file_name()) {
+        labels.insert(
+            EXPERIMENT_LABEL.to_owned(),
+            experiment.to_string_lossy().into_owned(),
+        );
+    }
+    labels
+}
+
+/// Name a helper container of a run, such as a traffic capture, after the configuration run it
+/// belongs to so that runs using resources of the same name don't clash.
+fn helper_name(labels: &HashMap<String, String>, kind: &str, resource: &str) -> String {
+    match labels.get(CONFIG_LABEL) {
+        Some(hash) => format!("exp-{}-{}-{}", kind, hash, resource),
+        None => format!("exp-{}-{}", kind, resource),
+    }
+}
+
+/// Copy the contents of a volume into the configuration directory as a tar archive.
+///
+/// This uses a helper container with the volume mounted, which is removed afterwards.
+async fn preserve_volume(
+    docker: &Docker,
+    config_dir: &Path,
+    labels: &HashMap<String, String>,
+    volume: &str,
+) -> Result<(), bollard::errors::Error> {
+    let volumes_dir = create_volumes_dir(config_dir)?;
+    pull_image(PRESERVE_IMAGE_NAME, PRESERVE_IMAGE_TAG).await?;
+
+    let helper_name = helper_name(labels, "preserve", volume);
+    docker
+        .create_container(
+            Some(CreateContainerOptions {
+                name: helper_name.as_str(),
+            }),
+            Config {
+                image: Some(format!("{}:{}", PRESERVE_IMAGE_NAME, PRESERVE_IMAGE_TAG)),
+                labels: Some(labels.clone()),
+                host_config: Some(HostConfig {
+                    mounts: Some(vec![Mount {
+                        target: Some("/volume".to_owned()),
+                        source: Some(volume.to_owned()),
+                        typ: Some(MountTypeEnum::VOLUME),
+                        read_only: Some(true),
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    let mut archive = docker.download_from_container(
+        &helper_name,
+        Some(DownloadFromContainerOptions { path: "/volume" }),
+    );
+    // download before removing the helper container, even if it fails
+    let result = async {
+        let mut archive_file = File::create(volumes_dir.join(format!("{}.tar", volume)))?;
+        while let Some(chunk) = archive.next().await {
+            archive_file.write_all(&chunk?)?;
+        }
+        Ok::<_, bollard::errors::Error>(())
+    }
+    .await;
+
+    docker
+        .remove_container(
+            &helper_name,
+            Some(RemoveContainerOptions {
+                force: true,
+                ..Default::default()
+            }),
+        )
+        .await?;
+    result
+}
+
+/// Get the directory to write files for the given phase into, creating it if needed.
+fn phase_dir(parent: &Path, phase: Option<&str>) -> PathBuf {
+    match phase {
+        Some(phase) => {
+            let phase_path = parent.join(phase);
+            if !phase_path.exists() {
+                debug!(path = ?phase_path, "Creating phase directory");
+                create_dir_all(&phase_path).expect("Failed to create phase directory");
+            }
+            phase_path
+        }
+        None => parent.to_owned(),
+    }
+}
+
+pub(crate) fn create_config_dir(parent: &Path) -> Result<PathBuf, io::Error> {
     let conf_path = parent.join("config");
     if !conf_path.exists() {
         debug!(path = ?conf_path, "Creating config directory");
@@ -871,7 +2928,7 @@ fn create_config_dir(parent: &Path) -> Result<PathBuf, io::Error> {
     Ok(conf_path)
 }
 
-fn create_logs_dir(parent: &Path) -> Result<PathBuf, io::Error> {
+pub(crate) fn create_logs_dir(parent: &Path) -> Result<PathBuf, io::Error> {
     let logs_path = parent.join("logs");
     if !logs_path.exists() {
         debug!(path = ?logs_path, "Creating logs directory");
@@ -880,7 +2937,35 @@ fn create_logs_dir(parent: &Path) -> Result<PathBuf, io::Error> {
     Ok(logs_path)
 }
 
-fn create_metrics_dir(parent: &Path) -> Result<PathBuf, io::Error> {
+async fn export_path(
+    docker: &Docker,
+    config_dir: &Path,
+    container: &str,
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let archive_path = export_archive_path(config_dir, container, path);
+    if let Some(parent) = archive_path.parent() {
+        create_dir_all(parent)?;
+    }
+    let mut archive =
+        docker.download_from_container(container, Some(DownloadFromContainerOptions { path }));
+    let mut archive_file = File::create(&archive_path)?;
+    while let Some(chunk) = archive.next().await {
+        archive_file.write_all(&chunk?)?;
+    }
+    Ok(())
+}
+
+fn create_volumes_dir(parent: &Path) -> Result<PathBuf, io::Error> {
+    let volumes_path = parent.join("volumes");
+    if !volumes_path.exists() {
+        debug!(path = ?volumes_path, "Creating volumes directory");
+        create_dir_all(&volumes_path)?;
+    }
+    Ok(volumes_path)
+}
+
+pub(crate) fn create_metrics_dir(parent: &Path) -> Result<PathBuf, io::Error> {
     let metrics_path = parent.join("metrics");
     if !metrics_path.exists() {
         debug!(path = ?metrics_path, "Creating metrics directory");
@@ -889,11 +2974,17 @@ fn create_metrics_dir(parent: &Path) -> Result<PathBuf, io::Error> {
     Ok(metrics_path)
 }
 
-pub async fn pull_image(image_name: &str, image_tag: &str) -> Result<(), bollard::errors::Error> {
+/// Pull an image, giving how long it took and the digests of what was pulled.
+pub async fn pull_image(
+    image_name: &str,
+    image_tag: &str,
+) -> Result<ImagePull, bollard::errors::Error> {
     let docker =
         bollard::Docker::connect_with_local_defaults().expect("Failed to connect to docker api");
 
-    docker
+    let start = Utc::now();
+    let started = std::time::Instant::now();
+    let infos = docker
         .create_image(
             Some(CreateImageOptions {
                 from_image: image_name,
@@ -905,19 +2996,144 @@ pub async fn pull_image(image_name: &str, image_tag: &str) -> Result<(), bollard
         )
         .try_collect::<Vec<_>>()
         .await?;
+    let duration = started.elapsed();
+
+    let image = format!("{}:{}", image_name, image_tag);
+    let log = infos
+        .into_iter()
+        // skip the download and extraction progress updates
+        .filter(|info| info.progress_detail.is_none() && info.progress.is_none())
+        .filter_map(|info| match (info.id, info.status) {
+            (Some(id), Some(status)) => Some(format!("{}: {}", id, status)),
+            (None, status) => status,
+            (Some(_), None) => None,
+        })
+        .collect();
+    let inspect = docker.inspect_image(&image).await?;
+    Ok(ImagePull {
+        image,
+        start,
+        duration_secs: duration.as_secs_f64(),
+        repo_digests: inspect.repo_digests.unwrap_or_default(),
+        layers: inspect
+            .root_fs
+            .and_then(|root_fs| root_fs.layers)
+            .unwrap_or_default(),
+        log,
+    })
+}
+
+/// A pull of an image, written to `config/docker-pull-<image>.json` when a container is added
+/// with `ContainerConfig::pull` or the image was pulled by `prepull_images` for the run, with `/`
+/// and `:` in the image replaced by `_`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImagePull {
+    /// The image pulled, as `name:tag`.
+    pub image: String,
+    pub start: DateTime<Utc>,
+    pub duration_secs: f64,
+    /// Digests of the image in its repositories, such as `postgres@sha256:...`, to pin it.
+    pub repo_digests: Vec<String>,
+    /// Digests of the image's layers, from the base up.
+    pub layers: Vec<String>,
+    /// Status messages of the pull, such as whether each layer was downloaded or already
+    /// existed.
+    pub log: Vec<String>,
+}
+
+fn image_pull_path(config_dir: &Path, image: &str) -> PathBuf {
+    config_dir.join(format!(
+        "docker-pull-{}.json",
+        image.replace(['/', ':'], "_")
+    ))
+}
+
+/// Write the pull of an image for a configuration run, to `config/docker-pull-<image>.json`.
+pub(crate) fn write_image_pull(configuration_dir: &Path, pull: &ImagePull) -> io::Result<()> {
+    let config_dir = create_config_dir(configuration_dir)?;
+    let file = File::create(image_pull_path(&config_dir, &pull.image))?;
+    serde_json::to_writer_pretty(file, pull)?;
     Ok(())
 }
 
-pub async fn clean(prefix: &str) -> Result<(), bollard::errors::Error> {
+/// Read the image pulls of a configuration run, from `config/docker-pull-<image>.json`.
+pub fn read_image_pulls(configuration_dir: &Path) -> io::Result<Vec<ImagePull>> {
+    let config_dir = configuration_dir.join("config");
+    let mut pulls = Vec::new();
+    if !config_dir.exists() {
+        return Ok(pulls);
+    }
+    let mut paths = std::fs::read_dir(&config_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with("docker-pull-") && name.ends_with(".json") {
+            pulls.push(serde_json::from_reader(File::open(&path)?)?);
+        }
+    }
+    Ok(pulls)
+}
+
+/// Pull each of the images, such as `postgres:15`, once, showing progress on the terminal.
+///
+/// Writing the pulls to the configuration directories of runs with `write_image_pull` stops
+/// containers of those runs pulling the images again, even with `ContainerConfig::pull`.
+pub async fn prepull_images(
+    images: &[String],
+) -> Result<Vec<ImagePull>, (String, bollard::errors::Error)> {
+    let images = images
+        .iter()
+        .map(|image| split_image(image))
+        .collect::<BTreeSet<_>>();
+    let bar = ProgressBar::new(images.len() as u64);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {bar:40} {pos}/{len} {msg}").unwrap(),
+    );
+    let mut pulls = Vec::new();
+    for (name, tag) in images {
+        let image = format!("{}:{}", name, tag);
+        bar.set_message(format!("pulling {}", image));
+        debug!(%image, "Pulling image");
+        let pull = pull_image(&name, &tag)
+            .await
+            .map_err(|error| (image.clone(), error))?;
+        pulls.push(pull);
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    Ok(pulls)
+}
+
+/// Split an image into its name and tag, `latest` if it has none.
+pub(crate) fn split_image(image: &str) -> (String, String) {
+    // a registry can have a port, so only look for the tag after the last `/`
+    let name_start = image.rfind('/').map_or(0, |index| index + 1);
+    match image[name_start..].rfind(':') {
+        Some(index) => (
+            image[..name_start + index].to_owned(),
+            image[name_start + index + 1..].to_owned(),
+        ),
+        None => (image.to_owned(), "latest".to_owned()),
+    }
+}
+
+/// Remove all docker resources created by a `Runner` for the given experiment.
+///
+/// Containers are matched by their `exp.experiment` label and removed, then any unused networks
+/// and volumes carrying the same label are pruned.
+pub async fn clean(experiment: &str) -> Result<(), bollard::errors::Error> {
     let docker = bollard::Docker::connect_with_local_defaults()?;
+    let label = format!("{}={}", EXPERIMENT_LABEL, experiment);
     let mut filters = HashMap::new();
-    filters.insert("name", vec![prefix]);
+    filters.insert("label", vec![label.as_str()]);
     let containers = docker
         .list_containers(Some(ListContainersOptions {
             all: true,
             limit: None,
             size: false,
-            filters,
+            filters: filters.clone(),
         }))
         .await?;
     for container in containers {
@@ -937,5 +3153,51 @@ pub async fn clean(prefix: &str) -> Result<(), bollard::errors::Error> {
             )
             .await?;
     }
+
+    let pruned_networks = docker
+        .prune_networks(Some(PruneNetworksOptions {
+            filters: filters.clone(),
+        }))
+        .await?;
+    debug!(networks = ?pruned_networks.networks_deleted, "Pruned networks");
+
+    let pruned_volumes = docker
+        .prune_volumes(Some(PruneVolumesOptions { filters }))
+        .await?;
+    debug!(volumes = ?pruned_volumes.volumes_deleted, "Pruned volumes");
     Ok(())
 }
+
+/// Docker metric files, such as `docker-<name>-stat.csv`, in the metrics directory and its phase
+/// subdirectories.
+///
+/// Fails if any have been converted to Parquet by `data::convert_to_parquet`, rather than
+/// silently leaving them out.
+fn metric_files(metrics_dir: &Path, suffix: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !metrics_dir.exists() {
+        return Ok(files);
+    }
+    let parquet_suffix = format!("{}.parquet", suffix.trim_end_matches(".csv"));
+    for entry in std::fs::read_dir(metrics_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(metric_files(&path, suffix)?);
+        } else {
+            let name = compression::uncompressed_name(&path);
+            if name.starts_with("docker-") && name.ends_with(suffix) {
+                files.push(path);
+            } else if name.starts_with("docker-") && name.ends_with(&parquet_suffix) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} has been converted to parquet, load it with exp::data instead",
+                        path.display()
+                    ),
+                ));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
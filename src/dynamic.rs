@@ -0,0 +1,308 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{
+    hash_value, AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    ExperimentMetadata, Measurements, Requirements,
+};
+
+/// The configuration of a `DynExperiment`, as its JSON, with the schema of the experiment it is
+/// from.
+///
+/// Deserialized configurations don't know their experiment, and so have no skipped fields and
+/// schema version 0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DynConfiguration(pub Value, #[serde(skip)] DynSchema);
+
+/// What a `DynExperiment` says about its configurations, rather than their type.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct DynSchema {
+    skip_hash_fields: Vec<String>,
+    version: u32,
+}
+
+impl DynConfiguration {
+    /// A configuration from an unknown experiment.
+    pub fn new(configuration: Value) -> Self {
+        DynConfiguration(configuration, DynSchema::default())
+    }
+
+    /// A configuration of the experiment, hashed without its skipped fields.
+    pub fn of(experiment: &(impl DynExperiment + ?Sized), configuration: Value) -> Self {
+        DynConfiguration(
+            configuration,
+            DynSchema {
+                skip_hash_fields: experiment.skip_hash_fields(),
+                version: experiment.schema_version(),
+            },
+        )
+    }
+}
+
+impl ExperimentConfiguration for DynConfiguration {
+    fn schema_version(&self) -> u32 {
+        self.1.version
+    }
+
+    fn hash_serialized(&self) -> ExpResult<String> {
+        hash_value(self.0.clone(), &self.1.skip_hash_fields)
+    }
+}
+
+/// An object safe `Experiment` with JSON configurations, so experiments can be put together at
+/// runtime, such as from scripts or configuration files, and run as `Box<dyn DynExperiment>`.
+///
+/// Every `Experiment` is a `DynExperiment`, its configurations converted to and from JSON and
+/// hashed the same way, so its results are in the same directories either way.
+#[async_trait]
+pub trait DynExperiment: Send + Sync {
+    fn metadata(&self) -> ExperimentMetadata {
+        ExperimentMetadata::default()
+    }
+
+    /// Top-level configuration fields left out of the hash, as
+    /// `ExperimentConfiguration::SKIP_HASH_FIELDS`.
+    fn skip_hash_fields(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The version of the configurations' schema, as `ExperimentConfiguration::SCHEMA_VERSION`.
+    fn schema_version(&self) -> u32 {
+        0
+    }
+
+    fn configurations(&mut self) -> Vec<Value>;
+
+    fn requirements(&self, configurations: &[Value]) -> Requirements {
+        let _ = configurations;
+        Requirements::default()
+    }
+
+    fn images(&self, configurations: &[Value]) -> Vec<String> {
+        let _ = configurations;
+        Vec::new()
+    }
+
+    /// Another instance of the experiment, as `Experiment::replica`. Experiments that are
+    /// `DynExperiment`s by being an `Experiment` don't have replicas.
+    fn replica(&self) -> Option<Box<dyn DynExperiment>> {
+        None
+    }
+
+    async fn pre_run(&mut self, configuration: &Value) -> ExpResult<()>;
+    async fn run(
+        &mut self,
+        configuration: &Value,
+        configuration_dir: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()>;
+    async fn post_run(&mut self, configuration: &Value) -> ExpResult<()>;
+
+    fn quick_look(
+        &mut self,
+        configuration: &Value,
+        configuration_dir: &Path,
+    ) -> ExpResult<BTreeMap<String, f64>> {
+        let _ = (configuration, configuration_dir);
+        Ok(BTreeMap::new())
+    }
+
+    fn analyse(
+        &mut self,
+        dirs: &AnalysisDirs,
+        environment: Environment,
+        configurations: Vec<(Value, PathBuf)>,
+    ) -> ExpResult<Value>;
+}
+
+#[async_trait]
+impl<E> DynExperiment for E
+where
+    E: Experiment + Send + Sync,
+    E::Configuration: Send + Sync,
+{
+    fn metadata(&self) -> ExperimentMetadata {
+        Experiment::metadata(self)
+    }
+
+    fn skip_hash_fields(&self) -> Vec<String> {
+        E::Configuration::SKIP_HASH_FIELDS
+            .iter()
+            .map(|field| (*field).to_owned())
+            .collect()
+    }
+
+    fn schema_version(&self) -> u32 {
+        E::Configuration::SCHEMA_VERSION
+    }
+
+    fn configurations(&mut self) -> Vec<Value> {
+        Experiment::configurations(self)
+            .into_iter()
+            .filter_map(|configuration| match serde_json::to_value(configuration) {
+                Ok(configuration) => Some(configuration),
+                Err(error) => {
+                    warn!(%error, "Skipping configuration that couldn't be serialized");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn requirements(&self, configurations: &[Value]) -> Requirements {
+        match configurations
+            .iter()
+            .map(E::Configuration::deserialize)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(configurations) => Experiment::requirements(self, &configurations),
+            Err(error) => {
+                warn!(%error, "Invalid configuration, assuming no requirements");
+                Requirements::default()
+            }
+        }
+    }
+
+    fn images(&self, configurations: &[Value]) -> Vec<String> {
+        match configurations
+            .iter()
+            .map(E::Configuration::deserialize)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(configurations) => Experiment::images(self, &configurations),
+            Err(error) => {
+                warn!(%error, "Invalid configuration, assuming no images");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn pre_run(&mut self, configuration: &Value) -> ExpResult<()> {
+        let configuration = E::Configuration::deserialize(configuration)?;
+        Experiment::pre_run(self, &configuration).await
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Value,
+        configuration_dir: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()> {
+        let configuration = E::Configuration::deserialize(configuration)?;
+        Experiment::run(self, &configuration, configuration_dir, measurements).await
+    }
+
+    async fn post_run(&mut self, configuration: &Value) -> ExpResult<()> {
+        let configuration = E::Configuration::deserialize(configuration)?;
+        Experiment::post_run(self, &configuration).await
+    }
+
+    fn quick_look(
+        &mut self,
+        configuration: &Value,
+        configuration_dir: &Path,
+    ) -> ExpResult<BTreeMap<String, f64>> {
+        let configuration = E::Configuration::deserialize(configuration)?;
+        Experiment::quick_look(self, &configuration, configuration_dir)
+    }
+
+    fn analyse(
+        &mut self,
+        dirs: &AnalysisDirs,
+        environment: Environment,
+        configurations: Vec<(Value, PathBuf)>,
+    ) -> ExpResult<Value> {
+        let configurations = configurations
+            .into_iter()
+            .map(|(configuration, dir)| Ok((E::Configuration::deserialize(configuration)?, dir)))
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Experiment::analyse(self, dirs, environment, configurations)
+    }
+}
+
+#[async_trait]
+impl<'a> Experiment for Box<dyn DynExperiment + 'a> {
+    type Configuration = DynConfiguration;
+
+    fn metadata(&self) -> ExperimentMetadata {
+        (**self).metadata()
+    }
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        (**self)
+            .configurations()
+            .into_iter()
+            .map(|configuration| DynConfiguration::of(&**self, configuration))
+            .collect()
+    }
+
+    fn requirements(&self, configurations: &[Self::Configuration]) -> Requirements {
+        let configurations = configurations
+            .iter()
+            .map(|c| c.0.clone())
+            .collect::<Vec<_>>();
+        (**self).requirements(&configurations)
+    }
+
+    fn images(&self, configurations: &[Self::Configuration]) -> Vec<String> {
+        let configurations = configurations
+            .iter()
+            .map(|c| c.0.clone())
+            .collect::<Vec<_>>();
+        (**self).images(&configurations)
+    }
+
+    fn replica(&self) -> Option<Self> {
+        (**self)
+            .replica()
+            .map(|replica| replica as Box<dyn DynExperiment + 'a>)
+    }
+
+    async fn pre_run(&mut self, configuration: &Self::Configuration) -> ExpResult<()> {
+        (**self).pre_run(&configuration.0).await
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()> {
+        (**self)
+            .run(&configuration.0, configuration_dir, measurements)
+            .await
+    }
+
+    async fn post_run(&mut self, configuration: &Self::Configuration) -> ExpResult<()> {
+        (**self).post_run(&configuration.0).await
+    }
+
+    fn quick_look(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+    ) -> ExpResult<BTreeMap<String, f64>> {
+        (**self).quick_look(&configuration.0, configuration_dir)
+    }
+
+    fn analyse(
+        &mut self,
+        dirs: &AnalysisDirs,
+        environment: Environment,
+        configurations: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<Value> {
+        let configurations = configurations
+            .into_iter()
+            .map(|(c, dir)| (c.0, dir))
+            .collect();
+        (**self).analyse(dirs, environment, configurations)
+    }
+}
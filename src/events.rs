@@ -0,0 +1,117 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// File at the root of an experiment's results directory logging run events, one JSON object per
+/// line.
+pub const EVENTS_FILE: &str = "events.jsonl";
+
+/// A lifecycle event of a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RunEvent {
+    /// The configuration will be run.
+    ConfigurationSelected {
+        hash: String,
+    },
+    /// The configuration won't be run.
+    ConfigurationSkipped {
+        hash: String,
+        reason: String,
+    },
+    /// The configuration's completed results were linked from the store rather than run.
+    ConfigurationLinked {
+        hash: String,
+    },
+    ConfigurationStarted {
+        hash: String,
+    },
+    /// A `docker_runner::Runner` started a new phase.
+    Phase {
+        hash: String,
+        name: String,
+    },
+    ContainerStarted {
+        hash: String,
+        name: String,
+    },
+    /// The kernel killed a process of the container for running out of memory.
+    ContainerOomKilled {
+        hash: String,
+        name: String,
+    },
+    ConfigurationFailed {
+        hash: String,
+        error: String,
+    },
+    ConfigurationFinished {
+        hash: String,
+    },
+}
+
+impl RunEvent {
+    /// Hash of the configuration the event is about, suffixed with `-<repeat>` for later repeats
+    /// like the name of its directory, see `results::run_name`.
+    pub fn hash(&self) -> &str {
+        match self {
+            RunEvent::ConfigurationSelected { hash }
+            | RunEvent::ConfigurationSkipped { hash, .. }
+            | RunEvent::ConfigurationLinked { hash }
+            | RunEvent::ConfigurationStarted { hash }
+            | RunEvent::Phase { hash, .. }
+            | RunEvent::ContainerStarted { hash, .. }
+            | RunEvent::ContainerOomKilled { hash, .. }
+            | RunEvent::ConfigurationFailed { hash, .. }
+            | RunEvent::ConfigurationFinished { hash } => hash,
+        }
+    }
+}
+
+/// A `RunEvent` with the time it happened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: RunEvent,
+}
+
+/// Append an event to the experiment's event log.
+///
+/// Failing to record an event shouldn't fail the run so errors are only logged.
+pub(crate) fn record(experiment_dir: &Path, event: RunEvent) {
+    let record = EventRecord {
+        time: Utc::now(),
+        event,
+    };
+    let r = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(experiment_dir.join(EVENTS_FILE))
+        .and_then(|mut file| {
+            let mut line = serde_json::to_vec(&record)?;
+            line.push(b'\n');
+            file.write_all(&line)
+        });
+    if let Err(error) = r {
+        warn!(%error, "Failed to record run event");
+    }
+}
+
+/// Read the event log of an experiment.
+pub fn read_events(experiment_dir: &Path) -> io::Result<Vec<EventRecord>> {
+    let file = File::open(experiment_dir.join(EVENTS_FILE))?;
+    let mut events = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.is_empty() {
+            events.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(events)
+}
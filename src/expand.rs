@@ -0,0 +1,75 @@
+use serde_json::{Map, Value};
+
+/// Expand a meta-configuration into the concrete configurations of a sweep over it.
+///
+/// Each array in the meta-configuration is a list of options for that field, and objects are
+/// expanded field by field, so the result is every combination of the options. Other values are
+/// kept as they are. To use an array as the value of a field, wrap it in another array with it
+/// as the only option.
+///
+/// ```
+/// # use serde_json::json;
+/// let configurations = exp::expand(&json!({"nodes": [1, 3], "workload": {"clients": [1, 10]}}));
+/// assert_eq!(configurations.len(), 4);
+/// assert_eq!(configurations[0], json!({"nodes": 1, "workload": {"clients": 1}}));
+/// ```
+pub fn expand(meta: &Value) -> Vec<Value> {
+    match meta {
+        Value::Array(options) => options.iter().flat_map(expand_option).collect(),
+        Value::Object(fields) => {
+            let mut configurations = vec![Map::new()];
+            for (key, value) in fields {
+                let options = expand(value);
+                configurations = configurations
+                    .into_iter()
+                    .flat_map(|configuration| {
+                        options.iter().map(move |option| {
+                            let mut configuration = configuration.clone();
+                            configuration.insert(key.clone(), option.clone());
+                            configuration
+                        })
+                    })
+                    .collect();
+            }
+            configurations.into_iter().map(Value::Object).collect()
+        }
+        value => vec![value.clone()],
+    }
+}
+
+/// Expand one option of a list, which is used as is if it is itself a list.
+fn expand_option(option: &Value) -> Vec<Value> {
+    match option {
+        Value::Array(_) => vec![option.clone()],
+        option => expand(option),
+    }
+}
+
+/// JSON pointer to the field at a dotted path.
+pub(crate) fn pointer(field: &str) -> String {
+    field.split('.').map(|part| format!("/{}", part)).collect()
+}
+
+/// Set the fields of a configuration's JSON, failing if a field's parent doesn't exist.
+pub(crate) fn set_fields(
+    configuration: &mut Value,
+    overrides: &[(String, Value)],
+) -> Result<(), String> {
+    for (field, value) in overrides {
+        let (parent, key) = match field.rsplit_once('.') {
+            Some((parent, key)) => (pointer(parent), key),
+            None => (String::new(), field.as_str()),
+        };
+        match configuration.pointer_mut(&parent) {
+            Some(Value::Object(map)) => {
+                map.insert(key.to_owned(), value.clone());
+            }
+            Some(Value::Array(values)) => match key.parse::<usize>() {
+                Ok(i) if i < values.len() => values[i] = value.clone(),
+                _ => return Err(format!("no element {} in {}", key, parent)),
+            },
+            _ => return Err(format!("no field {}", field)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use chrono::Utc;
+use tracing::{
+    field::{Field, Visit},
+    span, warn, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// File in each configuration directory with the framework's own logs from running it, when
+/// `RunConfig::framework_log` is set.
+pub const FRAMEWORK_LOG_FILE: &str = "framework.log";
+
+/// Span field holding the directory to write a configuration's framework logs to.
+pub(crate) const LOG_DIR_FIELD: &str = "log_dir";
+
+/// A tracing layer writing the events of each configuration run to `framework.log` in its
+/// directory, for runs with `RunConfig::framework_log` set.
+///
+/// Add it to the subscriber alongside the usual output, such as
+/// `tracing_subscriber::registry().with(fmt::layer()).with(FrameworkLog)`.
+#[derive(Debug, Default)]
+pub struct FrameworkLog;
+
+/// The open `framework.log` of a configuration's span.
+struct LogFile(Mutex<File>);
+
+impl<S> Layer<S> for FrameworkLog
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut visitor = LogDirVisitor::default();
+        attrs.record(&mut visitor);
+        open_log(visitor, id, &ctx);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = LogDirVisitor::default();
+        values.record(&mut visitor);
+        open_log(visitor, id, &ctx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let scope = match ctx.event_scope(event) {
+            Some(scope) => scope,
+            None => return,
+        };
+        for span in scope {
+            if let Some(LogFile(file)) = span.extensions().get::<LogFile>() {
+                let mut visitor = EventVisitor::default();
+                event.record(&mut visitor);
+                let metadata = event.metadata();
+                let mut file = file.lock().unwrap();
+                // logging is best effort, failing to write shouldn't fail the run
+                let _ = writeln!(
+                    file,
+                    "{} {:>5} {}: {}{}",
+                    Utc::now().to_rfc3339(),
+                    metadata.level(),
+                    metadata.target(),
+                    visitor.message,
+                    visitor.fields
+                );
+                return;
+            }
+        }
+    }
+}
+
+fn open_log<S>(visitor: LogDirVisitor, id: &span::Id, ctx: &Context<'_, S>)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let (dir, span) = match (visitor.0, ctx.span(id)) {
+        (Some(dir), Some(span)) => (dir, span),
+        _ => return,
+    };
+    match File::create(dir.join(FRAMEWORK_LOG_FILE)) {
+        Ok(file) => span.extensions_mut().insert(LogFile(Mutex::new(file))),
+        Err(error) => warn!(?dir, %error, "Failed to create framework log"),
+    }
+}
+
+#[derive(Default)]
+struct LogDirVisitor(Option<PathBuf>);
+
+impl Visit for LogDirVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == LOG_DIR_FIELD {
+            self.0 = Some(PathBuf::from(value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == LOG_DIR_FIELD {
+            self.0 = Some(PathBuf::from(format!("{:?}", value)));
+        }
+    }
+}
+
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for EventVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
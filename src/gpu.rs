@@ -0,0 +1,175 @@
+//! Sample NVIDIA GPUs through NVML, for local processes with `ProcessMonitor::gpu` and for
+//! containers with `docker_runner::Runner::monitor_gpus`.
+//!
+//! Each sample writes a row per GPU to `gpu.csv`, with no `pid`, and a row per monitored process
+//! using it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{read_to_string, File},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use nvml_wrapper::{
+    enum_wrappers::device::TemperatureSensor, enums::device::UsedGpuMemory, error::NvmlError, Nvml,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// File in the metrics directory of a configuration with the GPU samples.
+pub const GPU_FILE: &str = "gpu.csv";
+
+#[derive(Debug, Error)]
+pub enum GpuError {
+    #[error(transparent)]
+    Nvml(#[from] NvmlError),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GpuMeasurement {
+    pub time: DateTime<Utc>,
+    /// Index of the GPU.
+    pub gpu: u32,
+    pub uuid: String,
+    pub utilization_percentage: u32,
+    pub memory_utilization_percentage: u32,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+    pub power_milliwatts: u32,
+    pub temperature_celsius: u32,
+    /// Process the row is for, empty for the row of the whole GPU.
+    pub pid: Option<u32>,
+    pub process_memory_used_bytes: Option<u64>,
+    /// Share of the GPU's streaming multiprocessors the process used since the last sample.
+    pub process_sm_utilization_percentage: Option<u32>,
+    /// Container the process is in, with the docker `Runner`.
+    pub container: Option<String>,
+}
+
+/// Which processes on the GPUs to record.
+pub(crate) enum GpuProcesses<'a> {
+    /// Those with these pids.
+    Pids(&'a HashSet<u32>),
+    /// Those in these containers, by id to name.
+    Containers(&'a HashMap<String, String>),
+}
+
+impl GpuProcesses<'_> {
+    /// Whether to record a process, with its container.
+    fn owner(&self, pid: u32) -> Option<Option<String>> {
+        match self {
+            GpuProcesses::Pids(pids) => pids.contains(&pid).then_some(None),
+            GpuProcesses::Containers(containers) => {
+                let cgroup = read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+                cgroup_container(&cgroup, containers).map(|name| Some(name.to_owned()))
+            }
+        }
+    }
+}
+
+/// The name of the container a process is in, from the contents of its `/proc/<pid>/cgroup` and
+/// the containers by id to name.
+///
+/// The container's id is in the path of the cgroup, such as `/docker/<id>` with cgroup v1 or
+/// `/system.slice/docker-<id>.scope` with v2.
+pub fn cgroup_container<'a>(
+    cgroup: &str,
+    containers: &'a HashMap<String, String>,
+) -> Option<&'a str> {
+    containers
+        .iter()
+        .find(|(id, _)| cgroup.contains(id.as_str()))
+        .map(|(_, name)| name.as_str())
+}
+
+/// Writes samples of all GPUs on the host to a csv file.
+pub struct GpuSampler {
+    nvml: Nvml,
+    writer: csv::Writer<File>,
+    /// Timestamp of the latest process utilization sample seen, to only get newer ones.
+    last_seen: u64,
+}
+
+impl std::fmt::Debug for GpuSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuSampler")
+            .field("last_seen", &self.last_seen)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GpuSampler {
+    /// Load NVML, failing if there is no NVIDIA driver.
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<Self, GpuError> {
+        Ok(Self {
+            nvml: Nvml::init()?,
+            writer: csv::Writer::from_path(filename)?,
+            last_seen: 0,
+        })
+    }
+
+    pub(crate) fn sample(&mut self, processes: GpuProcesses) -> Result<(), GpuError> {
+        let time = Utc::now();
+        let mut last_seen = self.last_seen;
+        for index in 0..self.nvml.device_count()? {
+            let device = self.nvml.device_by_index(index)?;
+            let utilization = device.utilization_rates()?;
+            let memory = device.memory_info()?;
+            let gpu = GpuMeasurement {
+                time,
+                gpu: index,
+                uuid: device.uuid()?,
+                utilization_percentage: utilization.gpu,
+                memory_utilization_percentage: utilization.memory,
+                memory_used_bytes: memory.used,
+                memory_total_bytes: memory.total,
+                power_milliwatts: device.power_usage()?,
+                temperature_celsius: device.temperature(TemperatureSensor::Gpu)?,
+                pid: None,
+                process_memory_used_bytes: None,
+                process_sm_utilization_percentage: None,
+                container: None,
+            };
+            self.writer.serialize(&gpu)?;
+
+            // not all devices report per process utilization, and none is reported until a
+            // process has run for a while
+            let mut sm_utilization = HashMap::new();
+            for sample in device
+                .process_utilization_stats(self.last_seen)
+                .unwrap_or_default()
+            {
+                last_seen = last_seen.max(sample.timestamp);
+                sm_utilization.insert(sample.pid, sample.sm_util);
+            }
+            let mut running = device.running_compute_processes()?;
+            running.extend(device.running_graphics_processes()?);
+            let mut seen = HashSet::new();
+            for process in running {
+                if !seen.insert(process.pid) {
+                    continue;
+                }
+                let container = match processes.owner(process.pid) {
+                    Some(container) => container,
+                    None => continue,
+                };
+                self.writer.serialize(GpuMeasurement {
+                    pid: Some(process.pid),
+                    process_memory_used_bytes: match process.used_gpu_memory {
+                        UsedGpuMemory::Used(bytes) => Some(bytes),
+                        UsedGpuMemory::Unavailable => None,
+                    },
+                    process_sm_utilization_percentage: sm_utilization.get(&process.pid).copied(),
+                    container,
+                    ..gpu.clone()
+                })?;
+            }
+        }
+        self.last_seen = last_seen;
+        self.writer.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+}
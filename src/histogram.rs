@@ -0,0 +1,178 @@
+//! Load latency histograms written by workloads in the HdrHistogram formats.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use base64::Engine;
+use hdrhistogram::serialization::interval_log::{IntervalLogIterator, LogEntry, LogIteratorError};
+use hdrhistogram::serialization::{DeserializeError, Deserializer};
+use hdrhistogram::AdditionError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub use hdrhistogram::Histogram;
+
+use crate::compression;
+
+/// Percentiles commonly reported for latencies.
+pub const PERCENTILES: [f64; 6] = [50.0, 90.0, 99.0, 99.9, 99.99, 100.0];
+
+#[derive(Debug, Error)]
+pub enum HistogramError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error("failed to parse interval log {path:?} at byte {offset}")]
+    ParseError { path: PathBuf, offset: usize },
+    #[error("invalid base64 encoded histogram: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("failed to deserialize histogram: {0}")]
+    Deserialize(#[from] DeserializeError),
+    #[error("failed to merge histograms: {0}")]
+    Merge(#[from] AdditionError),
+}
+
+/// A histogram of one interval of an interval log.
+#[derive(Debug, Clone)]
+pub struct IntervalHistogram {
+    pub tag: Option<String>,
+    /// Start of the interval, since the unix epoch if the log has a `BaseTime` or its interval
+    /// timestamps are absolute, otherwise since the start of the log.
+    pub start: Duration,
+    pub duration: Duration,
+    pub histogram: Histogram<u64>,
+}
+
+/// The percentiles of an interval, or of a whole run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub tag: Option<String>,
+    /// Start of the interval in seconds, as in `IntervalHistogram::start`.
+    pub start: f64,
+    /// Length of the interval in seconds.
+    pub duration: f64,
+    pub count: u64,
+    pub mean: f64,
+    /// Pairs of a percentile, such as `99.9`, and its value.
+    pub values: Vec<(f64, u64)>,
+}
+
+/// Load an interval log, as written by `HdrHistogram`'s `HistogramLogWriter` and tools like
+/// wrk2 and YCSB, decompressing it if it has been compressed.
+pub fn load_interval_log(path: &Path) -> Result<Vec<IntervalHistogram>, HistogramError> {
+    let mut contents = Vec::new();
+    compression::open(path)?.read_to_end(&mut contents)?;
+    let mut base_time = None;
+    let mut intervals = Vec::new();
+    for entry in IntervalLogIterator::new(&contents) {
+        match entry.map_err(|LogIteratorError::ParseError { offset }| {
+            HistogramError::ParseError {
+                path: path.to_owned(),
+                offset,
+            }
+        })? {
+            LogEntry::BaseTime(time) => base_time = Some(time),
+            LogEntry::StartTime(_) => {}
+            LogEntry::Interval(interval) => {
+                let histogram = decode(interval.encoded_histogram().as_bytes())?;
+                intervals.push(IntervalHistogram {
+                    tag: interval.tag().map(|tag| tag.as_str().to_owned()),
+                    start: base_time.unwrap_or_default() + interval.start_timestamp(),
+                    duration: interval.duration(),
+                    histogram,
+                });
+            }
+        }
+    }
+    Ok(intervals)
+}
+
+/// Load a single histogram in the V2 encoding, either binary or base64 encoded text.
+pub fn load_histogram(path: &Path) -> Result<Histogram<u64>, HistogramError> {
+    let mut contents = Vec::new();
+    compression::open(path)?.read_to_end(&mut contents)?;
+    match Deserializer::new().deserialize(&mut contents.as_slice()) {
+        Err(DeserializeError::InvalidCookie) => decode(contents.trim_ascii()),
+        result => Ok(result?),
+    }
+}
+
+fn decode(encoded: &[u8]) -> Result<Histogram<u64>, HistogramError> {
+    let bytes = base64::engine::general_purpose::STANDARD.decode(encoded)?;
+    Ok(Deserializer::new().deserialize(&mut bytes.as_slice())?)
+}
+
+/// Merge histograms, such as those of each repeat of a configuration, or `None` if there are
+/// none.
+pub fn merge<'a>(
+    histograms: impl IntoIterator<Item = &'a Histogram<u64>>,
+) -> Result<Option<Histogram<u64>>, HistogramError> {
+    let mut merged: Option<Histogram<u64>> = None;
+    for histogram in histograms {
+        match &mut merged {
+            Some(merged) => merged.add(histogram)?,
+            None => merged = Some(histogram.clone()),
+        }
+    }
+    Ok(merged)
+}
+
+/// Merge the interval logs of repeats of a configuration, aligning their intervals by position
+/// and tag, so the percentiles of each interval are over all repeats.
+///
+/// The start and duration of each merged interval are those of the first repeat with it.
+pub fn merge_intervals(
+    repeats: &[Vec<IntervalHistogram>],
+) -> Result<Vec<IntervalHistogram>, HistogramError> {
+    let mut merged = Vec::<IntervalHistogram>::new();
+    for intervals in repeats {
+        let mut positions = HashMap::<Option<&str>, usize>::new();
+        for interval in intervals {
+            let position = positions.entry(interval.tag.as_deref()).or_default();
+            let existing = merged
+                .iter_mut()
+                .filter(|m| m.tag == interval.tag)
+                .nth(*position);
+            match existing {
+                Some(existing) => existing.histogram.add(&interval.histogram)?,
+                None => merged.push(interval.clone()),
+            }
+            *position += 1;
+        }
+    }
+    Ok(merged)
+}
+
+/// The percentiles of each interval, for plotting latency over a run.
+pub fn interval_percentiles(
+    intervals: &[IntervalHistogram],
+    percentiles: &[f64],
+) -> Vec<Percentiles> {
+    intervals
+        .iter()
+        .map(|interval| Percentiles {
+            tag: interval.tag.clone(),
+            start: interval.start.as_secs_f64(),
+            duration: interval.duration.as_secs_f64(),
+            ..percentiles_of(&interval.histogram, percentiles)
+        })
+        .collect()
+}
+
+/// The percentiles of a histogram, such as the merge of a whole run.
+pub fn percentiles_of(histogram: &Histogram<u64>, percentiles: &[f64]) -> Percentiles {
+    Percentiles {
+        tag: None,
+        start: 0.0,
+        duration: 0.0,
+        count: histogram.len(),
+        mean: histogram.mean(),
+        values: percentiles
+            .iter()
+            .map(|p| (*p, histogram.value_at_percentile(*p)))
+            .collect(),
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use crate::ExpResult;
+
+/// Callbacks around the phases of a run, added with `RunConfigBuilder::hook`, for concerns that
+/// apply to every experiment such as clearing caches or marking power measurements.
+///
+/// Hooks are called in the order they were added. An error from `before_config` or
+/// `after_config` fails the configuration's run, while one from `before_all` or `after_all`
+/// fails the whole run.
+#[async_trait]
+pub trait RunHooks: Send + Sync {
+    /// Before any configurations are run, with the experiment's directory.
+    async fn before_all(&self, experiment_dir: &Path) -> ExpResult<()> {
+        let _ = experiment_dir;
+        Ok(())
+    }
+
+    /// Before each configuration is run, with the run's name and the directory it is running
+    /// in.
+    async fn before_config(&self, hash: &str, configuration_dir: &Path) -> ExpResult<()> {
+        let _ = (hash, configuration_dir);
+        Ok(())
+    }
+
+    /// After each configuration is run, whether or not it succeeded.
+    async fn after_config(
+        &self,
+        hash: &str,
+        configuration_dir: &Path,
+        success: bool,
+    ) -> ExpResult<()> {
+        let _ = (hash, configuration_dir, success);
+        Ok(())
+    }
+
+    /// After a configuration fails, with why. Errors from this are logged and otherwise
+    /// ignored.
+    async fn on_failure(
+        &self,
+        hash: &str,
+        configuration_dir: &Path,
+        error: &(dyn std::error::Error + Send + Sync),
+    ) -> ExpResult<()> {
+        let _ = (hash, configuration_dir, error);
+        Ok(())
+    }
+
+    /// After all configurations have been run.
+    async fn after_all(&self, experiment_dir: &Path) -> ExpResult<()> {
+        let _ = experiment_dir;
+        Ok(())
+    }
+}
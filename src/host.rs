@@ -0,0 +1,188 @@
+use std::{
+    collections::BTreeSet,
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Details of the host that commonly confound benchmark results, read from sysfs and procfs.
+///
+/// Anything that can't be read is left empty rather than failing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct HostDetails {
+    /// Distinct cpufreq scaling governors in use across CPUs.
+    cpu_governors: Vec<String>,
+    /// Whether turbo boost is enabled, if the cpufreq driver exposes it.
+    turbo: Option<bool>,
+    numa_nodes: Vec<NumaNode>,
+    block_devices: Vec<BlockDevice>,
+    mounts: Vec<Mount>,
+    network_interfaces: Vec<NetworkInterface>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NumaNode {
+    id: u32,
+    /// CPUs on the node in cpulist format, e.g. `0-3,8-11`.
+    cpus: String,
+    memory_total_kb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BlockDevice {
+    name: String,
+    model: Option<String>,
+    /// The active IO scheduler.
+    scheduler: Option<String>,
+    rotational: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Mount {
+    device: String,
+    mount_point: String,
+    fs_type: String,
+    options: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NetworkInterface {
+    name: String,
+    /// Link speed in Mbit/s, missing for interfaces that are down or don't report it.
+    speed_mbps: Option<u64>,
+}
+
+impl HostDetails {
+    pub(crate) fn collect() -> Self {
+        Self {
+            cpu_governors: cpu_governors(),
+            turbo: turbo(),
+            numa_nodes: numa_nodes(),
+            block_devices: block_devices(),
+            mounts: mounts(),
+            network_interfaces: network_interfaces(),
+        }
+    }
+}
+
+fn read_trimmed<P: AsRef<Path>>(path: P) -> Option<String> {
+    read_to_string(path).ok().map(|s| s.trim().to_owned())
+}
+
+/// Names of the entries in `dir` that start with `prefix`, sorted.
+fn entries_with_prefix(dir: &str, prefix: &str) -> Vec<String> {
+    let mut names = read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(prefix))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+fn cpu_governors() -> Vec<String> {
+    entries_with_prefix("/sys/devices/system/cpu", "cpu")
+        .iter()
+        .filter_map(|cpu| {
+            read_trimmed(format!(
+                "/sys/devices/system/cpu/{}/cpufreq/scaling_governor",
+                cpu
+            ))
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn turbo() -> Option<bool> {
+    if let Some(no_turbo) = read_trimmed("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+        return Some(no_turbo == "0");
+    }
+    read_trimmed("/sys/devices/system/cpu/cpufreq/boost").map(|boost| boost == "1")
+}
+
+fn numa_nodes() -> Vec<NumaNode> {
+    entries_with_prefix("/sys/devices/system/node", "node")
+        .into_iter()
+        .filter_map(|node| {
+            let id = node.strip_prefix("node")?.parse().ok()?;
+            let dir = Path::new("/sys/devices/system/node").join(&node);
+            let cpus = read_trimmed(dir.join("cpulist"))?;
+            // lines of the form `Node 0 MemTotal:       32768 kB`
+            let memory_total_kb = read_to_string(dir.join("meminfo"))
+                .ok()
+                .and_then(|meminfo| {
+                    meminfo
+                        .lines()
+                        .find(|line| line.contains("MemTotal:"))
+                        .and_then(|line| line.split_whitespace().nth(3))
+                        .and_then(|kb| kb.parse().ok())
+                });
+            Some(NumaNode {
+                id,
+                cpus,
+                memory_total_kb,
+            })
+        })
+        .collect()
+}
+
+fn block_devices() -> Vec<BlockDevice> {
+    entries_with_prefix("/sys/block", "")
+        .into_iter()
+        .filter(|name| !name.starts_with("loop") && !name.starts_with("ram"))
+        .map(|name| {
+            let dir = Path::new("/sys/block").join(&name);
+            // the active scheduler is in brackets, e.g. `mq-deadline [none]`
+            let scheduler = read_trimmed(dir.join("queue/scheduler")).map(|schedulers| {
+                schedulers
+                    .split_whitespace()
+                    .find_map(|s| s.strip_prefix('[').and_then(|s| s.strip_suffix(']')))
+                    .unwrap_or(&schedulers)
+                    .to_owned()
+            });
+            BlockDevice {
+                model: read_trimmed(dir.join("device/model")),
+                scheduler,
+                rotational: read_trimmed(dir.join("queue/rotational")).map(|r| r == "1"),
+                name,
+            }
+        })
+        .collect()
+}
+
+/// Mounts of block devices, skipping virtual filesystems.
+fn mounts() -> Vec<Mount> {
+    read_to_string("/proc/mounts")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            if !device.starts_with("/dev/") {
+                return None;
+            }
+            Some(Mount {
+                device: device.to_owned(),
+                mount_point: fields.next()?.to_owned(),
+                fs_type: fields.next()?.to_owned(),
+                options: fields.next()?.to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn network_interfaces() -> Vec<NetworkInterface> {
+    entries_with_prefix("/sys/class/net", "")
+        .into_iter()
+        .filter(|name| name != "lo")
+        .map(|name| NetworkInterface {
+            speed_mbps: read_trimmed(format!("/sys/class/net/{}/speed", name))
+                .and_then(|speed| speed.parse().ok()),
+            name,
+        })
+        .collect()
+}
@@ -0,0 +1,99 @@
+use std::{
+    fs::{read_dir, remove_dir_all, remove_file, write, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{ExpResult, RunHooks};
+
+/// File in each configuration directory with the free disk space before it ran, when using
+/// `CacheHygiene`.
+pub const DISK_SPACE_FILE: &str = "disk_space.json";
+
+/// Hooks resetting the page cache and disk state before each configuration run, including each
+/// repeat, so storage benchmarks aren't affected by the previous run.
+///
+/// Add with `RunConfigBuilder::hook`. Dropping the page cache needs root.
+#[derive(Debug, Clone, Default)]
+pub struct CacheHygiene {
+    /// Sync and drop the page cache, dentries and inodes.
+    pub drop_caches: bool,
+    /// Directory the experiment writes to, emptied before each run.
+    pub scratch_dir: Option<PathBuf>,
+    /// Record the free space of these filesystems in `disk_space.json`, the scratch directory
+    /// is always included.
+    pub disk_space: Vec<PathBuf>,
+}
+
+/// Free space of a filesystem before a configuration ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskSpace {
+    pub path: PathBuf,
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl DiskSpace {
+    /// Read the disk space recorded in a configuration's directory.
+    pub fn from_configuration(configuration_dir: &Path) -> io::Result<Vec<Self>> {
+        let file = File::open(configuration_dir.join(DISK_SPACE_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    fn of(path: &Path) -> io::Result<Self> {
+        let stat = nix::sys::statvfs::statvfs(path)?;
+        // the field types vary between platforms
+        #[allow(clippy::unnecessary_cast)]
+        let (fragment_size, available, total) = (
+            stat.fragment_size() as u64,
+            stat.blocks_available() as u64,
+            stat.blocks() as u64,
+        );
+        Ok(DiskSpace {
+            path: path.to_owned(),
+            available_bytes: available * fragment_size,
+            total_bytes: total * fragment_size,
+        })
+    }
+}
+
+#[async_trait]
+impl RunHooks for CacheHygiene {
+    async fn before_config(&self, _hash: &str, configuration_dir: &Path) -> ExpResult<()> {
+        if let Some(scratch_dir) = &self.scratch_dir {
+            debug!(?scratch_dir, "Emptying scratch directory");
+            empty_dir(scratch_dir)?;
+        }
+        if self.drop_caches {
+            debug!("Dropping page cache");
+            nix::unistd::sync();
+            write("/proc/sys/vm/drop_caches", "3")?;
+        }
+        let paths = self.scratch_dir.iter().chain(&self.disk_space);
+        let disk_space = paths
+            .map(|path| DiskSpace::of(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        if !disk_space.is_empty() {
+            let file = File::create(configuration_dir.join(DISK_SPACE_FILE))?;
+            serde_json::to_writer_pretty(file, &disk_space)?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove everything in a directory, keeping the directory itself.
+fn empty_dir(dir: &Path) -> io::Result<()> {
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_dir_all(entry.path())?;
+        } else {
+            remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
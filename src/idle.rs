@@ -0,0 +1,84 @@
+use std::{
+    thread::available_parallelism,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use sysinfo::{ComponentExt, System, SystemExt};
+use tracing::{debug, info, warn};
+
+/// How often to check whether the host is idle, unless `IdleWait::interval` is set.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wait for the host to be idle before starting each configuration, set with
+/// `RunConfig::idle_wait`, so one run heating or loading the machine doesn't affect the next.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleWait {
+    /// Highest one minute load average per core to start a configuration at.
+    pub max_load: Option<f64>,
+    /// Highest temperature, in Celsius, of any sensor to start a configuration at.
+    pub max_temperature: Option<f32>,
+    /// How often to check, 5 seconds by default.
+    #[serde(with = "crate::run::seconds")]
+    pub interval: Option<Duration>,
+    /// Start the configuration anyway after waiting this long, rather than waiting forever.
+    #[serde(with = "crate::run::seconds")]
+    pub timeout: Option<Duration>,
+}
+
+impl IdleWait {
+    /// Wait until the host is below the thresholds, or the timeout passes.
+    pub(crate) async fn wait(&self) {
+        let start = Instant::now();
+        let mut sys = System::new();
+        let mut waited = false;
+        loop {
+            let reason = match self.busy(&mut sys) {
+                Some(reason) => reason,
+                None => {
+                    if waited {
+                        info!(waited = ?start.elapsed(), "Host is idle");
+                    }
+                    return;
+                }
+            };
+            if self
+                .timeout
+                .is_some_and(|timeout| start.elapsed() >= timeout)
+            {
+                warn!(%reason, "Host still busy after waiting for it to be idle, continuing");
+                return;
+            }
+            debug!(%reason, "Waiting for host to be idle");
+            waited = true;
+            tokio::time::sleep(self.interval.unwrap_or(DEFAULT_INTERVAL)).await;
+        }
+    }
+
+    /// Why the host isn't idle, if it isn't.
+    fn busy(&self, sys: &mut System) -> Option<String> {
+        if let Some(max_load) = self.max_load {
+            let cores = available_parallelism().map_or(1, |cores| cores.get());
+            let load = sys.load_average().one / cores as f64;
+            if load > max_load {
+                return Some(format!("load {:.2} per core above {}", load, max_load));
+            }
+        }
+        if let Some(max_temperature) = self.max_temperature {
+            sys.refresh_components_list();
+            let hottest = sys
+                .components()
+                .iter()
+                .map(|component| component.temperature())
+                .fold(f32::NEG_INFINITY, f32::max);
+            if hottest > max_temperature {
+                return Some(format!(
+                    "temperature {:.1}C above {}C",
+                    hottest, max_temperature
+                ));
+            }
+        }
+        None
+    }
+}
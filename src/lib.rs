@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -6,22 +7,164 @@ use serde::{de::DeserializeOwned, Serialize};
 use std::error::Error;
 
 mod analyse;
+pub mod archive;
+mod artifacts;
+mod backend;
+pub mod build;
+#[cfg(feature = "cli")]
+pub mod cli;
+mod compare;
+mod compose;
+pub mod compression;
+mod config_file;
+#[cfg(feature = "polars")]
+pub mod data;
+mod distributed;
 pub mod docker_runner;
+mod dynamic;
+mod events;
+mod expand;
+mod framework_log;
+#[cfg(feature = "nvml")]
+pub mod gpu;
+#[cfg(feature = "histogram")]
+pub mod histogram;
+mod hooks;
+mod host;
+mod hygiene;
+mod idle;
+mod live;
+mod lock;
+mod log_capture;
+mod log_metrics;
+mod measurements;
+mod metadata;
+pub mod metrics;
+mod migrate;
 pub mod monitor;
+pub mod notify;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+mod perf;
+#[cfg(feature = "plotters")]
+pub mod plot;
+mod power;
+mod preflight;
+pub mod process_runner;
+pub mod progress;
+mod prometheus;
+mod provenance;
+#[cfg(feature = "sql")]
+pub mod query;
+pub mod results;
 mod run;
+mod schedule;
+pub mod search;
+mod seed;
+pub mod ssh_runner;
+pub mod stats;
+mod stopping;
+mod store;
+mod suite;
+pub mod sync;
+#[cfg(feature = "tui")]
+pub mod tui;
+mod tuning;
+mod workload;
 
-pub use analyse::{analyse, AnalyseConfig, AnalyseError};
-pub use run::{run, Environment, RunConfig, RunError};
+pub use analyse::{
+    analyse, AnalyseConfig, AnalyseError, AnalysisDirs, AnalysisInputs, ConfigurationFilter,
+    IncompletePolicy, ANALYSIS_DIR, CACHE_FILE, INPUTS_FILE, SUMMARY_FILE,
+};
+pub use artifacts::{disk_usage, ArtifactQuota, DirSize, DiskUsage, QuotaAction, ARTIFACTS_FILE};
+pub use backend::{BackendCall, ContainerBackend, DockerBackend, MockBackend};
+#[cfg(feature = "cli")]
+pub use cli::main_helper;
+pub use compare::{
+    compare, compare_with, container_metrics, workload_metrics, CompareConfig, CompareError,
+    Comparison, MatchBy, MetricDelta, Metrics,
+};
+pub use compose::{ComposeError, Topology};
+pub use compression::CompressionConfig;
+pub use config_file::ConfigFileError;
+pub use distributed::{run_coordinator, run_worker};
+pub use dynamic::{DynConfiguration, DynExperiment};
+pub use events::{read_events, EventRecord, RunEvent, EVENTS_FILE};
+pub use expand::expand;
+pub use framework_log::{FrameworkLog, FRAMEWORK_LOG_FILE};
+pub use hooks::RunHooks;
+pub use hygiene::{CacheHygiene, DiskSpace, DISK_SPACE_FILE};
+pub use idle::IdleWait;
+pub use live::{read_live_results, LiveResult, LIVE_RESULTS_FILE};
+pub use lock::LockOwner;
+pub use log_capture::LogCaptureConfig;
+pub use log_metrics::{ExtractError, LogExtractor, LogMetric, LogSample, LogSelector};
+pub use measurements::{Measurements, MeasurementsError, Recorded, Sample, SCALARS_FILE};
+pub use metadata::ExperimentMetadata;
+pub use migrate::{migrate, MigrateSummary};
+pub use power::{
+    read_energy, Energy, PowerMeter, PowerMonitor, PowerSample, ENERGY_FILE, POWER_FILE,
+};
+pub use preflight::Requirements;
+pub use prometheus::{parse_prometheus, PrometheusRecord, PrometheusSample, PrometheusScrape};
+pub use provenance::{Provenance, RepoProvenance};
+pub use run::{
+    run, run_monitored, EnvDiff, Environment, RunConfig, RunConfigBuilder, RunConfigError, RunError,
+};
+pub use seed::{Seed, SEED_FILE};
+pub use stopping::EarlyStopping;
+pub use suite::{run_suite, SuiteConfig, SuiteEntry, SuiteError, SuiteManifest, SUITE_FILE};
+pub use tuning::{HostTuning, TuningSetting};
+pub use workload::{
+    WorkloadError, WorkloadExtractor, WorkloadFormat, WorkloadMetric, WorkloadOutput, WorkloadValue,
+};
 
 pub type ExpResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
 pub trait ExperimentConfiguration: Serialize + DeserializeOwned {
+    /// Version of the configuration's schema, recorded alongside each run.
+    ///
+    /// Bump this when changing the configuration type and implement `migrate` so existing results
+    /// can be moved to their new hash with `exp::migrate`.
+    const SCHEMA_VERSION: u32 = 0;
+
+    /// Upgrade a configuration serialized with an older schema version to this type.
+    fn migrate(old: serde_json::Value, version: u32) -> ExpResult<Self> {
+        let _ = version;
+        Ok(serde_json::from_value(old)?)
+    }
+
+    /// Top-level fields that don't affect the results, such as labels or output verbosity, and
+    /// so are left out of the hash.
+    const SKIP_HASH_FIELDS: &'static [&'static str] = &[];
+
+    /// The schema version of this configuration, `SCHEMA_VERSION` unless the schema is only known
+    /// at runtime.
+    fn schema_version(&self) -> u32 {
+        Self::SCHEMA_VERSION
+    }
+
+    /// Configurations with a higher priority are run before those with a lower one, once their
+    /// dependencies have run.
+    fn priority(&self) -> i64 {
+        0
+    }
+
+    /// Hashes of configurations to run before this one, such as a baseline. If none of a
+    /// dependency's repeats succeed this configuration is skipped.
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     /// Calculate the hash of the serialized version of this config.
+    ///
+    /// The serialized config is canonicalized first, so the hash doesn't depend on the order of
+    /// fields or map entries, or whether whole floats are written as integers.
     fn hash_serialized(&self) -> ExpResult<String> {
         let mut v = Vec::new();
         self.ser(&mut v)?;
-        let config_hash = blake3::hash(&v).to_hex();
-        Ok(config_hash.to_string())
+        hash_value(serde_json::from_slice(&v)?, Self::SKIP_HASH_FIELDS)
     }
 
     fn ser<W: std::io::Write>(&self, w: W) -> ExpResult<()> {
@@ -40,24 +183,117 @@ pub trait ExperimentConfiguration: Serialize + DeserializeOwned {
     }
 }
 
+/// Hash a serialized configuration without its skipped top-level fields, canonicalizing it first.
+pub(crate) fn hash_value<S: AsRef<str>>(
+    mut value: serde_json::Value,
+    skip_hash_fields: &[S],
+) -> ExpResult<String> {
+    if let serde_json::Value::Object(object) = &mut value {
+        for field in skip_hash_fields {
+            object.remove(field.as_ref());
+        }
+    }
+    let value = canonicalize(value);
+    let config_hash = blake3::hash(&serde_json::to_vec(&value)?).to_hex();
+    Ok(config_hash.to_string())
+}
+
+/// Sort object keys and write whole floats as integers.
+fn canonicalize(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::{Number, Value};
+    match value {
+        Value::Object(object) => {
+            let mut entries = object.into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, canonicalize(v)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonicalize).collect()),
+        Value::Number(n) => match n.as_f64() {
+            // integers beyond 2^53 can't be represented exactly as floats so leave them as is
+            Some(f) if n.is_f64() && f.fract() == 0.0 && f.abs() < (1u64 << 53) as f64 => {
+                Value::Number(Number::from(f as i64))
+            }
+            _ => Value::Number(n),
+        },
+        value => value,
+    }
+}
+
 #[async_trait]
 pub trait Experiment {
     type Configuration: ExperimentConfiguration;
 
+    /// Describe the experiment, written to `experiment.json` in the results directory.
+    fn metadata(&self) -> ExperimentMetadata {
+        ExperimentMetadata::default()
+    }
+
     fn configurations(&mut self) -> Vec<Self::Configuration>;
 
+    /// Resources needed to run the given configurations, checked before any are run.
+    fn requirements(&self, configurations: &[Self::Configuration]) -> Requirements {
+        let _ = configurations;
+        Requirements::default()
+    }
+
+    /// Docker images used by the given configurations, such as `postgres:15`, pulled before any
+    /// are run with `RunConfig::prepull`.
+    fn images(&self, configurations: &[Self::Configuration]) -> Vec<String> {
+        let _ = configurations;
+        Vec::new()
+    }
+
+    /// Another instance of the experiment, to run configurations alongside this one with
+    /// `RunConfig::max_parallel`, or `None` if it can't, when configurations are run one at a
+    /// time.
+    fn replica(&self) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
     async fn pre_run(&mut self, configuration: &Self::Configuration) -> ExpResult<()>;
+    /// Run the configuration, writing any results to `configuration_dir`.
+    ///
+    /// Custom measurements recorded in `measurements` are written to the metrics directory once
+    /// this returns, even if it fails.
     async fn run(
         &mut self,
         configuration: &Self::Configuration,
         configuration_dir: &Path,
+        measurements: &Measurements,
     ) -> ExpResult<()>;
     async fn post_run(&mut self, configuration: &Self::Configuration) -> ExpResult<()>;
 
+    /// Take a quick look at a completed run, giving a few scalars such as its mean throughput.
+    ///
+    /// With `RunConfig::live_results` set these are appended to `live-results.csv` after each
+    /// run, to see trends before the sweep finishes. It is called between runs so should be
+    /// cheap.
+    fn quick_look(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+    ) -> ExpResult<BTreeMap<String, f64>> {
+        let _ = (configuration, configuration_dir);
+        Ok(BTreeMap::new())
+    }
+
+    /// Analyse the results of the configurations, returning a summary of them.
+    ///
+    /// Outputs, such as plots, should be written under `dirs`. The summary is written to
+    /// `summary.json` in the analysis directory for use by other tools, such as to compare
+    /// experiments. Return `Value::Null` if there is nothing to summarise.
     fn analyse(
         &mut self,
-        experiment_dir: &Path,
+        dirs: &AnalysisDirs,
         environment: Environment,
         configurations: Vec<(Self::Configuration, PathBuf)>,
-    );
+    ) -> ExpResult<serde_json::Value>;
 }
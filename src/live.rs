@@ -0,0 +1,69 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// File at the root of an experiment's results directory with the `Experiment::quick_look` of
+/// each completed run, when `RunConfig::live_results` is set.
+pub const LIVE_RESULTS_FILE: &str = "live-results.csv";
+
+/// A scalar from the quick look at a run, one row of `live-results.csv`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveResult {
+    pub time: DateTime<Utc>,
+    /// Name of the run, its configuration's hash suffixed with `-<repeat>` for later repeats.
+    pub hash: String,
+    pub repeat: usize,
+    pub name: String,
+    pub value: f64,
+}
+
+/// Append the scalars of a run to the experiment's live results.
+///
+/// The live results are only for watching a run so errors are only logged.
+pub(crate) fn append(
+    experiment_dir: &Path,
+    hash: &str,
+    repeat: usize,
+    scalars: BTreeMap<String, f64>,
+) {
+    if scalars.is_empty() {
+        return;
+    }
+    let path = experiment_dir.join(LIVE_RESULTS_FILE);
+    let write = || -> Result<(), csv::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let has_headers = file.metadata()?.len() == 0;
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(has_headers)
+            .from_writer(file);
+        let time = Utc::now();
+        for (name, value) in scalars {
+            writer.serialize(LiveResult {
+                time,
+                hash: hash.to_owned(),
+                repeat,
+                name,
+                value,
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    };
+    if let Err(error) = write() {
+        warn!(%error, "Failed to append live results");
+    }
+}
+
+/// Read the live results of an experiment.
+pub fn read_live_results(experiment_dir: &Path) -> Result<Vec<LiveResult>, csv::Error> {
+    let file = File::open(experiment_dir.join(LIVE_RESULTS_FILE))?;
+    csv::Reader::from_reader(file)
+        .deserialize()
+        .collect::<Result<_, _>>()
+}
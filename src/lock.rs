@@ -0,0 +1,200 @@
+use std::{
+    fs::{hard_link, metadata, remove_file, File},
+    io::{self, Read, Write},
+    os::unix::{fs::MetadataExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use chrono::{DateTime, Utc};
+use nix::{
+    errno::Errno,
+    fcntl::{flock, FlockArg},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Extension of lock files.
+pub(crate) const LOCK_EXTENSION: &str = "lock";
+
+/// Distinguishes the lock files this process is creating, before they are linked into place.
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Who holds a lock, stored as JSON in the lock file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub hostname: String,
+    pub pid: u32,
+    pub acquired: DateTime<Utc>,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            hostname: hostname(),
+            pid: std::process::id(),
+            acquired: Utc::now(),
+        }
+    }
+}
+
+impl std::fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "pid {} on {} since {}",
+            self.pid, self.hostname, self.acquired
+        )
+    }
+}
+
+/// An advisory lock, held with `flock` on a lock file naming its owner and released by removing
+/// the file on drop.
+///
+/// The kernel releases the `flock` when its holder exits, so lock files left behind by crashed
+/// runs are told apart from held ones without trusting the owner they name.
+#[derive(Debug)]
+pub(crate) struct Lock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl Lock {
+    /// Try to take the lock at `path`.
+    ///
+    /// Returns the current owner if the lock is held by someone else. Lock files that aren't
+    /// held, left behind by processes that have since exited, are removed and taken over.
+    pub(crate) fn acquire(path: PathBuf) -> io::Result<Result<Self, LockOwner>> {
+        loop {
+            // written and locked before it is linked into place, so it is never seen half
+            // written or unlocked
+            let mut pending = path.clone().into_os_string();
+            pending.push(format!(
+                ".{}-{}",
+                std::process::id(),
+                PENDING.fetch_add(1, Ordering::Relaxed)
+            ));
+            let mut file = File::create(&pending)?;
+            let linked = serde_json::to_writer_pretty(&mut file, &LockOwner::current())
+                .map_err(io::Error::from)
+                .and_then(|()| file.flush())
+                .and_then(|()| try_lock(&file, FlockArg::LockExclusiveNonblock))
+                .and_then(|_| hard_link(&pending, &path));
+            remove_file(&pending)?;
+            match linked {
+                Ok(()) => {
+                    debug!(?path, "Acquired lock");
+                    return Ok(Ok(Self { path, _file: file }));
+                }
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(error) => return Err(error),
+            }
+
+            match take_stale(&path)? {
+                Taken::Held(owner) => return Ok(Err(owner)),
+                Taken::Stale(existing) => {
+                    match read_owner_from(&existing) {
+                        Ok(owner) => warn!(?path, %owner, "Removing stale lock"),
+                        Err(error) => warn!(%error, ?path, "Removing unreadable stale lock"),
+                    }
+                    remove_existing(&path)?;
+                }
+                Taken::Gone => {}
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // removed while still locked, so no one else can take over the file being removed
+        if let Err(error) = remove_file(&self.path) {
+            warn!(%error, path = ?self.path, "Failed to remove lock file");
+        }
+    }
+}
+
+/// The state of an existing lock file.
+enum Taken {
+    /// Held by a live process.
+    Held(LockOwner),
+    /// Not held, now locked by us until the file is dropped.
+    Stale(File),
+    /// Removed or replaced since it was seen.
+    Gone,
+}
+
+/// Lock the file at `path` if no one holds it, checking it is still the file at `path` once
+/// locked.
+fn take_stale(path: &Path) -> io::Result<Taken> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Taken::Gone),
+        Err(error) => return Err(error),
+    };
+    if !try_lock(&file, FlockArg::LockExclusiveNonblock)? {
+        return Ok(Taken::Held(read_owner_from(&file)?));
+    }
+    let current = match metadata(path) {
+        Ok(current) => current,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Taken::Gone),
+        Err(error) => return Err(error),
+    };
+    let locked = file.metadata()?;
+    if (locked.dev(), locked.ino()) != (current.dev(), current.ino()) {
+        return Ok(Taken::Gone);
+    }
+    Ok(Taken::Stale(file))
+}
+
+fn remove_existing(path: &Path) -> io::Result<()> {
+    match remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Take a `flock` on the file, giving whether it was free.
+fn try_lock(file: &File, arg: FlockArg) -> io::Result<bool> {
+    match flock(file.as_raw_fd(), arg) {
+        Ok(()) => Ok(true),
+        Err(Errno::EWOULDBLOCK) => Ok(false),
+        Err(errno) => Err(io::Error::from(errno)),
+    }
+}
+
+fn read_owner_from(mut file: &File) -> io::Result<LockOwner> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Who holds the lock at `path`, or `None` if no one does.
+pub(crate) fn holder(path: &Path) -> io::Result<Option<LockOwner>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    if try_lock(&file, FlockArg::LockSharedNonblock)? {
+        return Ok(None);
+    }
+    read_owner_from(&file).map(Some)
+}
+
+/// Remove the lock file at `path` if no one holds it, giving whether it was removed.
+pub(crate) fn remove_stale(path: &Path) -> io::Result<bool> {
+    match take_stale(path)? {
+        Taken::Stale(_locked) => {
+            remove_existing(path)?;
+            Ok(true)
+        }
+        Taken::Held(_) | Taken::Gone => Ok(false),
+    }
+}
+
+fn hostname() -> String {
+    nix::sys::utsname::uname()
+        .map(|utsname| utsname.nodename().to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,144 @@
+use std::{
+    borrow::Cow,
+    fs::{remove_file, rename, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::debug;
+
+/// Field added to each JSON log line holding the timestamp docker recorded for it.
+pub const DOCKER_TIMESTAMP_FIELD: &str = "docker_timestamp";
+/// Field holding the raw line when a log line is not a JSON object.
+pub const MESSAGE_FIELD: &str = "message";
+
+/// Options for how captured logs are written to disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogCaptureConfig {
+    /// Rotate the log file once it would grow beyond this many bytes.
+    ///
+    /// Rotated files are named `<file>.<n>`, with `n` counting up from 1 so the highest number is
+    /// the most recent.
+    pub max_file_size: Option<u64>,
+    /// Gzip rotated log files, giving `<file>.<n>.gz`.
+    pub compress_rotated: bool,
+    /// Drop any lines matching one of these regexes before writing them.
+    pub drop_patterns: Vec<String>,
+    /// Parse each line as a JSON object and write JSONL instead of plain text.
+    ///
+    /// The docker timestamp is added to each object as `docker_timestamp`, lines that are not JSON
+    /// objects are kept in a `message` field.
+    pub json: bool,
+}
+
+/// A writer for log lines that applies a `LogCaptureConfig`.
+#[derive(Debug)]
+pub(crate) struct LogWriter {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    rotations: usize,
+    max_file_size: Option<u64>,
+    compress_rotated: bool,
+    drop_patterns: RegexSet,
+    json: bool,
+}
+
+impl LogWriter {
+    pub(crate) fn new(path: PathBuf, config: &LogCaptureConfig) -> Result<Self, regex::Error> {
+        let drop_patterns = RegexSet::new(&config.drop_patterns)?;
+        let file = File::create(&path).expect("Failed to create logs file");
+        Ok(Self {
+            path,
+            file,
+            written: 0,
+            rotations: 0,
+            max_file_size: config.max_file_size,
+            compress_rotated: config.compress_rotated,
+            drop_patterns,
+            json: config.json,
+        })
+    }
+
+    pub(crate) fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.drop_patterns.is_match(line) {
+            return Ok(());
+        }
+        let line = if self.json {
+            Cow::Owned(to_json_line(line))
+        } else {
+            Cow::Borrowed(line)
+        };
+        let len = line.len() as u64;
+        if let Some(max_file_size) = self.max_file_size {
+            if self.written > 0 && self.written + len > max_file_size {
+                self.rotate()?;
+            }
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.written += len;
+        Ok(())
+    }
+
+    /// Continue writing into a new file at `path`.
+    pub(crate) fn reopen(&mut self, path: PathBuf) -> io::Result<()> {
+        self.file.flush()?;
+        self.file = File::create(&path)?;
+        self.path = path;
+        self.written = 0;
+        self.rotations = 0;
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.rotations += 1;
+        let rotated = PathBuf::from(format!("{}.{}", self.path.display(), self.rotations));
+        debug!(path = ?rotated, "Rotating log file");
+        rename(&self.path, &rotated)?;
+        if self.compress_rotated {
+            compress(&rotated)?;
+        }
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// Convert a timestamped docker log line into a line of JSON.
+fn to_json_line(line: &str) -> String {
+    let (timestamp, text) = line.split_once(' ').unwrap_or(("", line));
+    let text = text.trim_end();
+    let mut object = match serde_json::from_str(text) {
+        Ok(Value::Object(object)) => object,
+        _ => {
+            let mut object = Map::new();
+            object.insert(MESSAGE_FIELD.to_owned(), Value::String(text.to_owned()));
+            object
+        }
+    };
+    object.insert(
+        DOCKER_TIMESTAMP_FIELD.to_owned(),
+        Value::String(timestamp.to_owned()),
+    );
+    let mut json_line = Value::Object(object).to_string();
+    json_line.push('\n');
+    json_line
+}
+
+/// Gzip the file at `path` into `<path>.gz`, removing the original.
+fn compress(path: &Path) -> io::Result<()> {
+    let compressed_path = PathBuf::from(format!("{}.gz", path.display()));
+    let mut encoder = GzEncoder::new(File::create(&compressed_path)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    remove_file(path)
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_dir},
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::compression;
+use crate::docker_runner::Logs;
+use crate::log_capture::MESSAGE_FIELD;
+use crate::measurements::Sample;
+
+#[derive(Debug, Error)]
+pub enum ExtractError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+    #[error("invalid pattern for log metric {name}: {error}")]
+    Regex { name: String, error: regex::Error },
+}
+
+/// How a metric is taken from log lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LogSelector {
+    /// Lines matching a regex.
+    ///
+    /// The value is parsed from the capture group named `value`, or else the first capture group,
+    /// and without capture groups each matching line counts as one. For JSON logs the regex is
+    /// applied to the `message` field of lines that weren't JSON, and to the whole object
+    /// otherwise.
+    Regex(String),
+    /// A field of JSON log lines, by its dotted path such as `stats.pending`.
+    ///
+    /// Numbers, and strings of numbers, are the value, and any other value counts as one. Plain
+    /// text lines are parsed as JSON objects, so this also works for JSON logged without
+    /// `LogCaptureConfig::json`.
+    JsonField(String),
+}
+
+/// A metric extracted from logs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogMetric {
+    /// Name of the metric, used in the file name so should be safe in one.
+    pub name: String,
+    pub selector: LogSelector,
+    /// Sum the values in each second, such as to count compactions per second, instead of
+    /// writing each value.
+    ///
+    /// Seconds without any matching line are written as zero, from the first to the last line
+    /// of the log.
+    pub per_second: bool,
+}
+
+/// A sample of a metric extracted from logs, written the same as a measured series.
+pub type LogSample = Sample;
+
+/// Extracts timestamped metrics from the captured logs of a run, such as counts of log lines
+/// about an event or numbers the system under test logs periodically.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogExtractor {
+    pub metrics: Vec<LogMetric>,
+}
+
+impl LogExtractor {
+    /// Extract the metrics from the logs of a configuration run.
+    ///
+    /// Each metric of the logs of each container is written to
+    /// `metrics/log-<container>-<metric>.csv`, with `time` and `value` columns, and logs of a
+    /// phase in `logs/<phase>/` to `metrics/<phase>/`. This can be done at the end of
+    /// `Experiment::run` or later in `Experiment::analyse`, as the logs are kept.
+    ///
+    /// Returns the paths of the files written.
+    pub fn extract(&self, configuration_dir: &Path) -> Result<Vec<PathBuf>, ExtractError> {
+        let selectors = self
+            .metrics
+            .iter()
+            .map(Selector::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut written = Vec::new();
+        for (phase, path) in log_files(&configuration_dir.join("logs"))? {
+            let logs = if compression::uncompressed_name(&path).ends_with(".jsonl") {
+                Logs::from_jsonl(&path)?
+            } else {
+                let logs = Logs::from_file(&path)?;
+                Logs {
+                    container_name: logs.container_name,
+                    lines: logs
+                        .lines
+                        .into_iter()
+                        .map(|(time, line)| (time, Value::String(line)))
+                        .collect(),
+                }
+            };
+            let mut metrics_dir = configuration_dir.join("metrics");
+            if let Some(phase) = &phase {
+                metrics_dir.push(phase);
+            }
+            create_dir_all(&metrics_dir)?;
+            for (metric, selector) in self.metrics.iter().zip(&selectors) {
+                let mut samples = logs
+                    .lines
+                    .iter()
+                    .filter_map(|(time, line)| {
+                        selector
+                            .value(line)
+                            .map(|value| LogSample { time: *time, value })
+                    })
+                    .collect::<Vec<_>>();
+                if metric.per_second {
+                    samples = per_second(&logs.lines, samples);
+                }
+                let path =
+                    metrics_dir.join(format!("log-{}-{}.csv", logs.container_name, metric.name));
+                let mut writer = csv::Writer::from_path(&path)?;
+                for sample in &samples {
+                    writer.serialize(sample)?;
+                }
+                writer.flush()?;
+                debug!(?path, samples = samples.len(), "Wrote log metric");
+                written.push(path);
+            }
+        }
+        Ok(written)
+    }
+}
+
+enum Selector<'a> {
+    Regex(Regex),
+    JsonField(Vec<&'a str>),
+}
+
+impl<'a> Selector<'a> {
+    fn new(metric: &'a LogMetric) -> Result<Self, ExtractError> {
+        Ok(match &metric.selector {
+            LogSelector::Regex(pattern) => {
+                Selector::Regex(Regex::new(pattern).map_err(|error| ExtractError::Regex {
+                    name: metric.name.clone(),
+                    error,
+                })?)
+            }
+            LogSelector::JsonField(path) => Selector::JsonField(path.split('.').collect()),
+        })
+    }
+
+    /// The value of a log line, a string for plain text logs.
+    fn value(&self, line: &Value) -> Option<f64> {
+        match self {
+            Selector::Regex(regex) => {
+                let text = match line {
+                    Value::String(text) => text.clone(),
+                    Value::Object(object) => match object.get(MESSAGE_FIELD) {
+                        Some(Value::String(message)) if object.len() == 1 => message.clone(),
+                        _ => line.to_string(),
+                    },
+                    _ => line.to_string(),
+                };
+                let captures = regex.captures(&text)?;
+                match captures.name("value").or_else(|| captures.get(1)) {
+                    Some(value) => value.as_str().trim().parse().ok(),
+                    None => Some(1.0),
+                }
+            }
+            Selector::JsonField(path) => {
+                let parsed;
+                let mut value = match line {
+                    Value::String(text) => {
+                        parsed = serde_json::from_str::<Value>(text).ok()?;
+                        &parsed
+                    }
+                    line => line,
+                };
+                for key in path {
+                    value = value.get(key)?;
+                }
+                match value {
+                    Value::Number(number) => number.as_f64(),
+                    Value::String(text) => Some(text.trim().parse().unwrap_or(1.0)),
+                    Value::Null => None,
+                    _ => Some(1.0),
+                }
+            }
+        }
+    }
+}
+
+/// Sum the samples in each second from the first to the last line of the log.
+fn per_second<L>(lines: &[(DateTime<Utc>, L)], samples: Vec<LogSample>) -> Vec<LogSample> {
+    let second = |time: DateTime<Utc>| time.duration_trunc(Duration::seconds(1)).unwrap_or(time);
+    let (first, last) = match (lines.first(), lines.last()) {
+        (Some(first), Some(last)) => (second(first.0), second(last.0)),
+        _ => return Vec::new(),
+    };
+    let mut sums = BTreeMap::new();
+    let mut time = first;
+    while time <= last {
+        sums.insert(time, 0.0);
+        time += Duration::seconds(1);
+    }
+    for sample in samples {
+        *sums.entry(second(sample.time)).or_default() += sample.value;
+    }
+    sums.into_iter()
+        .map(|(time, value)| LogSample { time, value })
+        .collect()
+}
+
+/// Log files in the logs directory and its phase subdirectories, with their phase.
+pub(crate) fn log_files(logs_dir: &Path) -> io::Result<Vec<(Option<String>, PathBuf)>> {
+    let mut files = Vec::new();
+    if !logs_dir.exists() {
+        return Ok(files);
+    }
+    for entry in read_dir(logs_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let phase = compression::uncompressed_name(&path);
+            for entry in read_dir(&path)? {
+                let path = entry?.path();
+                if is_log(&path) {
+                    files.push((Some(phase.clone()), path));
+                }
+            }
+        } else if is_log(&path) {
+            files.push((None, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether the path is a current log file, not a rotated one.
+fn is_log(path: &Path) -> bool {
+    let name = compression::uncompressed_name(path);
+    ["docker-", "process-", "ssh-"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        && (name.ends_with(".log") || name.ends_with(".jsonl"))
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    fs::{read_dir, File},
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::compression;
+use crate::docker_runner::create_metrics_dir;
+
+/// File in the metrics directory holding the scalar measurements of a configuration run.
+pub const SCALARS_FILE: &str = "measurements.json";
+
+/// Prefix of the files in the metrics directory holding measured series, as
+/// `measurement-<name>.csv`.
+const SERIES_PREFIX: &str = "measurement-";
+
+#[derive(Debug, Error)]
+pub enum MeasurementsError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    SerdeError(#[from] serde_json::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+}
+
+/// A point of a measured series, or of a metric extracted from logs by a `LogExtractor`, as
+/// written to its CSV.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub time: DateTime<Utc>,
+    pub value: f64,
+}
+
+impl Sample {
+    /// Load the samples of a series, decompressing them if they have been compressed.
+    pub fn from_file(path: &Path) -> Result<Vec<Sample>, csv::Error> {
+        csv::Reader::from_reader(compression::open(path)?)
+            .deserialize()
+            .collect()
+    }
+}
+
+/// Records the custom measurements of a configuration run, such as the throughput reported by a
+/// workload, so they are stored the same way for every experiment.
+///
+/// Passed to `Experiment::run` and written to the metrics directory once it returns, scalars to
+/// `metrics/measurements.json` and each series to `metrics/measurement-<name>.csv` with `time`
+/// and `value` columns. Load them in `Experiment::analyse` with `Recorded::from_configuration`.
+///
+/// Cloning gives another handle to the same measurements, such as for a spawned task.
+#[derive(Debug, Clone, Default)]
+pub struct Measurements {
+    inner: Arc<Mutex<Recorded>>,
+}
+
+impl Measurements {
+    /// Record a single value for the run, replacing any recorded with the same name.
+    pub fn record_scalar(&self, name: &str, value: f64) {
+        self.inner
+            .lock()
+            .unwrap()
+            .scalars
+            .insert(name.to_owned(), value);
+    }
+
+    /// Record a point of a series, such as the latency over the run.
+    ///
+    /// The name is used in the file name so should be safe in one.
+    pub fn record_series(&self, name: &str, time: DateTime<Utc>, value: f64) {
+        self.inner
+            .lock()
+            .unwrap()
+            .series
+            .entry(name.to_owned())
+            .or_default()
+            .push(Sample { time, value });
+    }
+
+    /// Write the recorded measurements to the metrics directory of the configuration run.
+    pub(crate) fn write(&self, configuration_dir: &Path) -> Result<(), MeasurementsError> {
+        let recorded = self.inner.lock().unwrap();
+        if recorded.scalars.is_empty() && recorded.series.is_empty() {
+            return Ok(());
+        }
+        let metrics_dir = create_metrics_dir(configuration_dir)?;
+        if !recorded.scalars.is_empty() {
+            let path = metrics_dir.join(SCALARS_FILE);
+            debug!(?path, "Writing scalar measurements");
+            serde_json::to_writer_pretty(File::create(path)?, &recorded.scalars)?;
+        }
+        for (name, samples) in &recorded.series {
+            let path = metrics_dir.join(format!("{}{}.csv", SERIES_PREFIX, name));
+            debug!(?path, samples = samples.len(), "Writing measured series");
+            let mut writer = csv::Writer::from_path(path)?;
+            for sample in samples {
+                writer.serialize(sample)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// The measurements recorded for a configuration run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recorded {
+    pub scalars: BTreeMap<String, f64>,
+    pub series: BTreeMap<String, Vec<Sample>>,
+}
+
+impl Recorded {
+    /// Load the measurements recorded for a configuration run, decompressing them if they have
+    /// been compressed.
+    pub fn from_configuration(configuration_dir: &Path) -> Result<Self, MeasurementsError> {
+        let metrics_dir = configuration_dir.join("metrics");
+        let mut recorded = Recorded::default();
+        let scalars = match compression::open(&metrics_dir.join(SCALARS_FILE)) {
+            Ok(file) => Some(file),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+        if let Some(file) = scalars {
+            recorded.scalars = serde_json::from_reader(file)?;
+        }
+        if !metrics_dir.exists() {
+            return Ok(recorded);
+        }
+        for entry in read_dir(&metrics_dir)? {
+            let path = entry?.path();
+            let name = compression::uncompressed_name(&path);
+            if let Some(name) = name
+                .strip_prefix(SERIES_PREFIX)
+                .and_then(|name| name.strip_suffix(".csv"))
+            {
+                recorded
+                    .series
+                    .insert(name.to_owned(), Sample::from_file(&path)?);
+            }
+        }
+        Ok(recorded)
+    }
+}
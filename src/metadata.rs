@@ -0,0 +1,29 @@
+use std::{fs::File, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// File at the root of an experiment's results directory holding its metadata.
+pub const METADATA_FILE: &str = "experiment.json";
+
+/// Description of an experiment, stored with its results.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentMetadata {
+    pub name: String,
+    pub description: String,
+    pub version: String,
+    pub tags: Vec<String>,
+}
+
+impl ExperimentMetadata {
+    /// Load the metadata stored in an experiment's results directory.
+    pub fn from_dir(experiment_dir: &Path) -> io::Result<Self> {
+        let file = File::open(experiment_dir.join(METADATA_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub(crate) fn write(&self, experiment_dir: &Path) -> io::Result<()> {
+        let file = File::create(experiment_dir.join(METADATA_FILE))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    sync::{Mutex, MutexGuard},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+};
+use tracing::{debug, info, warn};
+
+/// Live state of the running experiment, exposed in the Prometheus text format, reset as each
+/// run starts.
+#[derive(Debug)]
+struct Metrics {
+    configurations_total: usize,
+    configurations_completed: usize,
+    configurations_failed: usize,
+    current_configuration: Option<String>,
+    container_cpu_percent: BTreeMap<String, f64>,
+    container_memory_bytes: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Metrics {
+            configurations_total: 0,
+            configurations_completed: 0,
+            configurations_failed: 0,
+            current_configuration: None,
+            container_cpu_percent: BTreeMap::new(),
+            container_memory_bytes: BTreeMap::new(),
+        }
+    }
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+fn metrics() -> MutexGuard<'static, Metrics> {
+    METRICS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Start the metrics of a run of `total` configurations, dropping those of any previous run.
+pub(crate) fn start_run(total: usize) {
+    *metrics() = Metrics {
+        configurations_total: total,
+        ..Metrics::new()
+    };
+}
+
+pub(crate) fn configuration_started(hash: &str) {
+    metrics().current_configuration = Some(hash.to_owned());
+}
+
+pub(crate) fn configuration_finished(success: bool) {
+    let mut metrics = metrics();
+    metrics.configurations_completed += 1;
+    if !success {
+        metrics.configurations_failed += 1;
+    }
+    metrics.current_configuration = None;
+    metrics.container_cpu_percent.clear();
+    metrics.container_memory_bytes.clear();
+}
+
+pub(crate) fn container_stats(name: &str, cpu_percent: Option<f64>, memory_bytes: Option<u64>) {
+    let mut metrics = metrics();
+    if let Some(cpu_percent) = cpu_percent {
+        metrics
+            .container_cpu_percent
+            .insert(name.to_owned(), cpu_percent);
+    }
+    if let Some(memory_bytes) = memory_bytes {
+        metrics
+            .container_memory_bytes
+            .insert(name.to_owned(), memory_bytes);
+    }
+}
+
+/// Render the current metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let metrics = metrics();
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, String)>| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    gauge(
+        "exp_configurations_total",
+        "Configurations to run.",
+        vec![(String::new(), metrics.configurations_total.to_string())],
+    );
+    gauge(
+        "exp_configurations_completed",
+        "Configurations finished, including failures.",
+        vec![(String::new(), metrics.configurations_completed.to_string())],
+    );
+    gauge(
+        "exp_configurations_failed",
+        "Configurations that failed.",
+        vec![(String::new(), metrics.configurations_failed.to_string())],
+    );
+    gauge(
+        "exp_current_configuration",
+        "The configuration being run.",
+        metrics
+            .current_configuration
+            .iter()
+            .map(|hash| (label("hash", hash), "1".to_owned()))
+            .collect(),
+    );
+    gauge(
+        "exp_container_cpu_percent",
+        "CPU usage of each container, as a percentage of one CPU.",
+        metrics
+            .container_cpu_percent
+            .iter()
+            .map(|(name, cpu)| (label("container", name), cpu.to_string()))
+            .collect(),
+    );
+    gauge(
+        "exp_container_memory_bytes",
+        "Memory usage of each container.",
+        metrics
+            .container_memory_bytes
+            .iter()
+            .map(|(name, memory)| (label("container", name), memory.to_string()))
+            .collect(),
+    );
+    out
+}
+
+/// A label set of a single label, escaping its value as the text format requires.
+fn label(name: &str, value: &str) -> String {
+    let value = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("{{{}=\"{}\"}}", name, value)
+}
+
+/// Serve the metrics over HTTP on `addr`, responding to any request with the current metrics.
+pub async fn serve(addr: SocketAddr) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "Serving metrics");
+    Ok(tokio::spawn(async move {
+        loop {
+            let (mut stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    warn!(%error, "Failed to accept metrics connection");
+                    continue;
+                }
+            };
+            debug!(%peer, "Metrics request");
+            tokio::spawn(async move {
+                // the request itself doesn't matter, just read enough of it to be polite
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let body = render();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                if let Err(error) = stream.write_all(response.as_bytes()).await {
+                    debug!(%error, %peer, "Failed to write metrics response");
+                }
+            });
+        }
+    }))
+}
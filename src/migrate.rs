@@ -0,0 +1,102 @@
+use std::{
+    fs::{read_dir, rename, File},
+    io,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{
+    results::{run_name, split_run_name},
+    run::lock_experiment_dir,
+    ExperimentConfiguration, RunError,
+};
+
+/// File in each configuration directory recording the schema version of its configuration.
+pub(crate) const SCHEMA_FILE: &str = "schema.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Schema {
+    version: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MigrateSummary {
+    /// Old and new names of configuration runs that were moved, the hash followed by the repeat
+    /// for repeats after the first.
+    pub migrated: Vec<(String, String)>,
+    /// Names of configuration runs that were already at the current schema version.
+    pub current: Vec<String>,
+    /// Names of configuration runs whose new name already has a result, which were left alone.
+    pub conflicts: Vec<String>,
+}
+
+/// Record the schema version of the configuration run in `dir`.
+pub(crate) fn write_schema_version(dir: &Path, version: u32) -> io::Result<()> {
+    let file = File::create(dir.join(SCHEMA_FILE))?;
+    serde_json::to_writer_pretty(file, &Schema { version })?;
+    Ok(())
+}
+
+/// Read the schema version of the configuration run in `dir`, runs from before schema versions
+/// were recorded are version 0.
+fn read_schema_version(dir: &Path) -> io::Result<u32> {
+    match File::open(dir.join(SCHEMA_FILE)) {
+        Ok(file) => {
+            let schema: Schema = serde_json::from_reader(file)?;
+            Ok(schema.version)
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(error) => Err(error),
+    }
+}
+
+/// Migrate completed configurations in an experiment directory to the current schema version.
+///
+/// Each configuration with an older schema version is upgraded with
+/// `ExperimentConfiguration::migrate` and its directory moved to the new configuration's hash,
+/// keeping its repeat, so the results are reused rather than the configuration being run again.
+pub fn migrate<C: ExperimentConfiguration>(
+    experiment_dir: &Path,
+) -> Result<MigrateSummary, RunError> {
+    let _lock = lock_experiment_dir(experiment_dir)?;
+    let mut summary = MigrateSummary::default();
+    for entry in read_dir(experiment_dir)? {
+        let path = entry?.path();
+        if !path.is_dir() || path.extension().is_some() {
+            continue;
+        }
+        // such as the analysis directory
+        if !path.join("configuration.json").exists() {
+            debug!(?path, "Not a configuration run, skipping");
+            continue;
+        }
+        let old_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let version = read_schema_version(&path)?;
+        if version >= C::SCHEMA_VERSION {
+            debug!(name = %old_name, version, "Configuration is up to date");
+            summary.current.push(old_name);
+            continue;
+        }
+        let (_, repeat) = split_run_name(&old_name);
+
+        let old: serde_json::Value =
+            serde_json::from_reader(File::open(path.join("configuration.json"))?)?;
+        let config = C::migrate(old, version)?;
+        let new_name = run_name(&config.hash_serialized()?, repeat);
+        let new_path = experiment_dir.join(&new_name);
+        if new_name != old_name && new_path.exists() {
+            warn!(%old_name, %new_name, "Migrated configuration already has results, skipping");
+            summary.conflicts.push(old_name);
+            continue;
+        }
+
+        config.ser_pretty(File::create(path.join("configuration.json"))?)?;
+        write_schema_version(&path, C::SCHEMA_VERSION)?;
+        rename(&path, &new_path)?;
+        info!(%old_name, %new_name, from = version, to = C::SCHEMA_VERSION, "Migrated configuration");
+        summary.migrated.push((old_name, new_name));
+    }
+    Ok(summary)
+}
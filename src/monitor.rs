@@ -1,24 +1,167 @@
+use std::collections::HashMap;
+#[cfg(feature = "nvml")]
+use std::collections::HashSet;
 use std::time::Instant;
-use std::{fs::File, path::Path, thread::sleep, time::Duration};
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{available_parallelism, sleep},
+    time::Duration,
+};
 
 use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::Deserialize;
 use serde::Serialize;
 use sysinfo::PidExt;
 use sysinfo::Process;
-use sysinfo::{Pid, ProcessExt, System, SystemExt};
+use sysinfo::{CpuExt, NetworkExt, Pid, ProcessExt, System, SystemExt};
+use thiserror::Error;
+use tokio::{process::Command, sync::watch, task::JoinHandle};
+use tracing::{debug, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[cfg(feature = "nvml")]
+use crate::gpu::{GpuError, GpuProcesses, GpuSampler};
+#[cfg(all(target_os = "linux", feature = "perf"))]
+use crate::perf::TaskCounters;
+
+#[derive(Debug, Error)]
+pub enum MonitorError {
+    #[error("monitor interval {interval:?} is below the minimum of {minimum:?}")]
+    IntervalTooShort {
+        interval: Duration,
+        minimum: Duration,
+    },
+    #[error("no process with a name matching {0}")]
+    NotFound(String),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Stops a `ProcessMonitor` from another thread or task, at its next sample.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+fn check_interval(interval: Duration) -> Result<(), MonitorError> {
+    if interval < System::MINIMUM_CPU_UPDATE_INTERVAL {
+        return Err(MonitorError::IntervalTooShort {
+            interval,
+            minimum: System::MINIMUM_CPU_UPDATE_INTERVAL,
+        });
+    }
+    Ok(())
+}
+
+/// A row written by a `ProcessMonitor`, with only the columns of the metrics it records.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ProcessMonitorMeasurement {
-    time: chrono::DateTime<chrono::Utc>,
+    time: Option<chrono::DateTime<chrono::Utc>>,
     pid: u32,
     parent: u32,
-    cpu_usage_percentage: f32,
-    memory_usage_bytes: u64,
-    virtual_memory_usage_bytes: u64,
-    disk_bytes_written: u64,
-    disk_bytes_read: u64,
+    cpu_usage_percentage: Option<f32>,
+    cpu_usage_per_core_percentage: Option<f32>,
+    memory_usage_bytes: Option<u64>,
+    virtual_memory_usage_bytes: Option<u64>,
+    disk_bytes_written: Option<u64>,
+    disk_bytes_read: Option<u64>,
+    disk_write_bytes_per_second: Option<f64>,
+    disk_read_bytes_per_second: Option<f64>,
+    open_files: Option<u64>,
+    threads: Option<u64>,
     name: String,
+    /// Events of the thread since the last sample, with `ProcessMonitor::perf_counters`.
+    instructions: Option<u64>,
+    cycles: Option<u64>,
+    cache_misses: Option<u64>,
+    context_switches: Option<u64>,
+}
+
+/// What a `ProcessMonitor` can record about each process, each as one or more columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessMetric {
+    /// `cpu_usage_percentage`, where 100 is a whole core.
+    Cpu,
+    /// `cpu_usage_per_core_percentage`, where 100 is every core of the host.
+    CpuPerCore,
+    /// `memory_usage_bytes` and `virtual_memory_usage_bytes`.
+    Memory,
+    /// `disk_bytes_written` and `disk_bytes_read` since the last sample.
+    Disk,
+    /// `disk_write_bytes_per_second` and `disk_read_bytes_per_second` since the last sample.
+    DiskRates,
+    /// `open_files`, the number of open file descriptors (Linux only).
+    OpenFiles,
+    /// `threads` of the process (Linux only).
+    Threads,
+}
+
+impl ProcessMetric {
+    /// Recorded unless `ProcessMonitor::metrics` is set.
+    pub const DEFAULT: [ProcessMetric; 3] = [
+        ProcessMetric::Cpu,
+        ProcessMetric::Memory,
+        ProcessMetric::Disk,
+    ];
+
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            ProcessMetric::Cpu => &["cpu_usage_percentage"],
+            ProcessMetric::CpuPerCore => &["cpu_usage_per_core_percentage"],
+            ProcessMetric::Memory => &["memory_usage_bytes", "virtual_memory_usage_bytes"],
+            ProcessMetric::Disk => &["disk_bytes_written", "disk_bytes_read"],
+            ProcessMetric::DiskRates => {
+                &["disk_write_bytes_per_second", "disk_read_bytes_per_second"]
+            }
+            ProcessMetric::OpenFiles => &["open_files"],
+            ProcessMetric::Threads => &["threads"],
+        }
+    }
+
+    fn values(self, measurement: &ProcessMonitorMeasurement) -> Vec<String> {
+        match self {
+            ProcessMetric::Cpu => vec![optional(measurement.cpu_usage_percentage)],
+            ProcessMetric::CpuPerCore => vec![optional(measurement.cpu_usage_per_core_percentage)],
+            ProcessMetric::Memory => vec![
+                optional(measurement.memory_usage_bytes),
+                optional(measurement.virtual_memory_usage_bytes),
+            ],
+            ProcessMetric::Disk => vec![
+                optional(measurement.disk_bytes_written),
+                optional(measurement.disk_bytes_read),
+            ],
+            ProcessMetric::DiskRates => vec![
+                optional(measurement.disk_write_bytes_per_second),
+                optional(measurement.disk_read_bytes_per_second),
+            ],
+            ProcessMetric::OpenFiles => vec![optional(measurement.open_files)],
+            ProcessMetric::Threads => vec![optional(measurement.threads)],
+        }
+    }
+}
+
+/// Write a missing value as an empty field.
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
 }
 
 /// Monitor a running process.
@@ -27,41 +170,166 @@ pub struct ProcessMonitor {
     pid: Pid,
     writer: csv::Writer<File>,
     interval: Duration,
+    metrics: Vec<ProcessMetric>,
+    written_headers: bool,
+    last_sample: Option<Instant>,
+    /// Also measure the processes started by the process, and those they start.
+    descendants: bool,
+    stop: StopHandle,
+    max_duration: Option<Duration>,
+    #[cfg(feature = "nvml")]
+    gpu: Option<GpuSampler>,
+    /// Counters of each thread by id, once enabled.
+    #[cfg(all(target_os = "linux", feature = "perf"))]
+    perf: Option<HashMap<u32, TaskCounters>>,
 }
 
 impl ProcessMonitor {
-    pub fn new<P: AsRef<Path>>(pid: u32, filename: P, interval: Duration) -> Self {
-        assert!(
-            interval >= System::MINIMUM_CPU_UPDATE_INTERVAL,
-            "process monitor refresh interval too low, should be above {:?} but was {:?}",
-            System::MINIMUM_CPU_UPDATE_INTERVAL,
-            interval
-        );
-        Self {
+    pub fn new<P: AsRef<Path>>(
+        pid: u32,
+        filename: P,
+        interval: Duration,
+    ) -> Result<Self, MonitorError> {
+        check_interval(interval)?;
+        Ok(Self {
             pid: Pid::from_u32(pid),
-            writer: csv::Writer::from_path(filename).unwrap(),
+            writer: csv::Writer::from_path(filename)?,
             interval,
+            metrics: ProcessMetric::DEFAULT.to_vec(),
+            written_headers: false,
+            last_sample: None,
+            descendants: false,
+            stop: StopHandle::default(),
+            max_duration: None,
+            #[cfg(feature = "nvml")]
+            gpu: None,
+            #[cfg(all(target_os = "linux", feature = "perf"))]
+            perf: None,
+        })
+    }
+
+    /// Monitor an already running process whose name matches `pattern`, the longest running if
+    /// there are several.
+    pub fn find_by_name<P: AsRef<Path>>(
+        pattern: &Regex,
+        filename: P,
+        interval: Duration,
+    ) -> Result<Self, MonitorError> {
+        let mut sys = System::new();
+        sys.refresh_processes();
+        let own_pid = std::process::id();
+        let (pid, process) = sys
+            .processes()
+            .iter()
+            .filter(|(pid, process)| pid.as_u32() != own_pid && pattern.is_match(process.name()))
+            .min_by_key(|(pid, process)| (process.start_time(), **pid))
+            .ok_or_else(|| MonitorError::NotFound(pattern.to_string()))?;
+        debug!(pid = %pid, name = process.name(), "Found process to monitor");
+        Self::new(pid.as_u32(), filename, interval)
+    }
+
+    /// Record these metrics rather than `ProcessMetric::DEFAULT`, in this order.
+    pub fn metrics(mut self, metrics: &[ProcessMetric]) -> Self {
+        self.metrics = metrics.to_vec();
+        self
+    }
+
+    /// A handle to stop monitoring before the process exits.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Stop monitoring after this long, even if the process is still running.
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Whether to stop monitoring, having started at `start`.
+    fn should_stop(&self, start: Instant) -> bool {
+        self.stop.is_stopped()
+            || self
+                .max_duration
+                .is_some_and(|max_duration| start.elapsed() >= max_duration)
+    }
+
+    /// Also measure the descendants of the process, such as the workers of a server that forks.
+    pub fn descendants(mut self) -> Self {
+        self.descendants = true;
+        self
+    }
+
+    /// Spawn a command and monitor it and its descendants until it exits, writing to
+    /// `process-<program>.csv` in `metrics_dir`.
+    ///
+    /// Returns the exit status of the command and the path measurements were written to. Errors
+    /// while monitoring are logged rather than losing the exit status.
+    pub async fn spawn_command(
+        mut command: Command,
+        metrics_dir: &Path,
+        interval: Duration,
+    ) -> Result<(ExitStatus, PathBuf), MonitorError> {
+        let program = Path::new(command.as_std().get_program())
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let path = metrics_dir.join(format!("process-{}.csv", program));
+        check_interval(interval)?;
+        let mut child = command.spawn()?;
+        let pid = child.id().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "child exited before monitoring")
+        })?;
+        debug!(pid, %program, "Spawned monitored process");
+
+        let (end_tx, end_rx) = watch::channel(());
+        let monitor = match Self::new(pid, &path, interval) {
+            Ok(monitor) => monitor.descendants().spawn(end_rx),
+            Err(error) => {
+                // don't leave the child running unmonitored
+                let _ = child.kill().await;
+                return Err(error);
+            }
+        };
+
+        let status = child.wait().await;
+        let _ = end_tx.send(());
+        match monitor.await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => warn!(%error, "Failed to monitor process"),
+            Err(error) => warn!(%error, "Process monitor task failed"),
         }
+        Ok((status?, path))
+    }
+
+    /// Also record the instructions, cycles, cache misses and context switches of each thread
+    /// between samples, with `perf_event_open`.
+    ///
+    /// Counting depends on `kernel.perf_event_paranoid` allowing it for the process and hardware
+    /// counters being available, such as not in a VM, leaving the columns empty otherwise.
+    #[cfg(all(target_os = "linux", feature = "perf"))]
+    pub fn perf_counters(mut self) -> Self {
+        self.perf = Some(HashMap::new());
+        self
+    }
+
+    /// Also sample the GPUs, and the process's use of them, into `filename` on each interval.
+    #[cfg(feature = "nvml")]
+    pub fn gpu<P: AsRef<Path>>(mut self, filename: P) -> Result<Self, GpuError> {
+        self.gpu = Some(GpuSampler::new(filename)?);
+        Ok(self)
     }
 
-    pub fn run(&mut self) {
+    /// Monitor the process on this thread until it exits or monitoring is stopped.
+    pub fn run(&mut self) -> Result<(), MonitorError> {
         let mut sys = System::new_all();
-        println!("running");
-        loop {
+        debug!(pid = %self.pid, "Running process monitor");
+        let start = Instant::now();
+        while !self.should_stop(start) {
             let loop_start = Instant::now();
-            let time = Utc::now();
-            sys.refresh_all();
-
-            if let Some(process) = sys.process(self.pid) {
-                println!("found process");
-                self.write_process(time, self.pid, process)
-            } else {
-                println!("found no process");
+            if !self.sample(&mut sys)? {
                 break;
             }
 
-            self.writer.flush().unwrap();
-
             let loop_end = Instant::now();
             let loop_duration = loop_end - loop_start;
             if loop_duration < self.interval {
@@ -69,24 +337,384 @@ impl ProcessMonitor {
                 sleep(sleep_duration)
             }
         }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Run the monitor on the tokio runtime.
+    ///
+    /// Monitoring stops when the process exits, `end_rx` is notified or monitoring is stopped.
+    pub fn spawn(
+        mut self,
+        mut end_rx: watch::Receiver<()>,
+    ) -> JoinHandle<Result<(), MonitorError>> {
+        tokio::spawn(async move {
+            let mut sys = System::new_all();
+            debug!(pid = %self.pid, "Spawned process monitor");
+            let start = Instant::now();
+            let interval = tokio::time::interval(self.interval);
+            tokio::pin!(interval);
+            loop {
+                tokio::select! {
+                    _ = end_rx.changed() => break,
+                    _ = interval.tick() => {
+                        if self.should_stop(start) || !self.sample(&mut sys)? {
+                            break;
+                        }
+                    }
+                }
+            }
+            self.writer.flush()?;
+            Ok(())
+        })
+    }
+
+    /// Take a single measurement of the process, returning whether it still exists.
+    fn sample(&mut self, sys: &mut System) -> Result<bool, MonitorError> {
+        let time = Utc::now();
+        sys.refresh_all();
+        let now = Instant::now();
+        let elapsed = self.last_sample.map(|last| now - last);
+        self.last_sample = Some(now);
+
+        let mut pids = Vec::new();
+        if let Some(process) = sys.process(self.pid) {
+            self.write_process(time, elapsed, self.pid, process, &mut pids)?;
+        } else {
+            debug!(pid = %self.pid, "Process no longer exists");
+            return Ok(false);
+        }
+        if self.descendants {
+            for (pid, process) in sys.processes() {
+                if *pid != self.pid && is_descendant(sys, process, self.pid) {
+                    self.write_process(time, elapsed, *pid, process, &mut pids)?;
+                }
+            }
+        }
+
+        self.writer.flush()?;
+
+        // close the counters of threads that have exited
+        #[cfg(all(target_os = "linux", feature = "perf"))]
+        if let Some(tasks) = &mut self.perf {
+            tasks.retain(|tid, _| pids.contains(tid));
+        }
+
+        #[cfg(feature = "nvml")]
+        if let Some(gpu) = &mut self.gpu {
+            let pids = pids.into_iter().collect::<HashSet<_>>();
+            if let Err(error) = gpu.sample(GpuProcesses::Pids(&pids)) {
+                warn!(%error, "Failed to sample GPUs");
+            }
+        }
+        Ok(true)
     }
 
-    fn write_process(&mut self, time: DateTime<Utc>, pid: Pid, process: &Process) {
+    /// Write a measurement of the process and its tasks, collecting their pids.
+    fn write_process(
+        &mut self,
+        time: DateTime<Utc>,
+        elapsed: Option<Duration>,
+        pid: Pid,
+        process: &Process,
+        pids: &mut Vec<u32>,
+    ) -> Result<(), MonitorError> {
+        pids.push(pid.as_u32());
         let disk_usage = process.disk_usage();
-        let measurement = ProcessMonitorMeasurement {
-            time,
+        let selected = |metric| self.metrics.contains(&metric);
+        let per_second = |bytes: u64| elapsed.map(|elapsed| bytes as f64 / elapsed.as_secs_f64());
+        let cores = available_parallelism().map_or(1, |cores| cores.get());
+        #[allow(unused_mut)]
+        let mut measurement = ProcessMonitorMeasurement {
+            time: Some(time),
             pid: pid.as_u32(),
-            parent: process.parent().unwrap().as_u32(),
-            cpu_usage_percentage: process.cpu_usage(),
-            memory_usage_bytes: process.memory(),
-            virtual_memory_usage_bytes: process.virtual_memory(),
-            disk_bytes_written: disk_usage.written_bytes,
-            disk_bytes_read: disk_usage.read_bytes,
+            parent: process.parent().map_or(0, |parent| parent.as_u32()),
+            cpu_usage_percentage: Some(process.cpu_usage()),
+            cpu_usage_per_core_percentage: Some(process.cpu_usage() / cores as f32),
+            memory_usage_bytes: Some(process.memory()),
+            virtual_memory_usage_bytes: Some(process.virtual_memory()),
+            disk_bytes_written: Some(disk_usage.written_bytes),
+            disk_bytes_read: Some(disk_usage.read_bytes),
+            disk_write_bytes_per_second: per_second(disk_usage.written_bytes),
+            disk_read_bytes_per_second: per_second(disk_usage.read_bytes),
+            open_files: selected(ProcessMetric::OpenFiles)
+                .then(|| open_files(pid))
+                .flatten(),
+            threads: selected(ProcessMetric::Threads)
+                .then(|| threads(pid))
+                .flatten(),
             name: process.name().to_owned(),
+            ..Default::default()
         };
-        self.writer.serialize(measurement).unwrap();
+        #[cfg(all(target_os = "linux", feature = "perf"))]
+        if let Some(tasks) = &mut self.perf {
+            let perf = tasks
+                .entry(pid.as_u32())
+                .or_insert_with(|| TaskCounters::open(pid.as_u32()))
+                .sample();
+            measurement.instructions = perf.instructions;
+            measurement.cycles = perf.cycles;
+            measurement.cache_misses = perf.cache_misses;
+            measurement.context_switches = perf.context_switches;
+        }
+        self.write_measurement(&measurement)?;
         for (pid, process) in &process.tasks {
-            self.write_process(time, *pid, process);
+            self.write_process(time, elapsed, *pid, process, pids)?;
+        }
+        Ok(())
+    }
+}
+
+impl ProcessMonitor {
+    /// Whether the perf counter columns are written.
+    fn perf_columns(&self) -> bool {
+        #[cfg(all(target_os = "linux", feature = "perf"))]
+        return self.perf.is_some();
+        #[cfg(not(all(target_os = "linux", feature = "perf")))]
+        false
+    }
+
+    /// Write a row of the selected columns, after the headers if this is the first.
+    fn write_measurement(
+        &mut self,
+        measurement: &ProcessMonitorMeasurement,
+    ) -> Result<(), csv::Error> {
+        let perf_columns = self.perf_columns();
+        if !self.written_headers {
+            let mut headers = vec!["time", "pid", "parent"];
+            for metric in &self.metrics {
+                headers.extend(metric.columns());
+            }
+            headers.push("name");
+            if perf_columns {
+                headers.extend(["instructions", "cycles", "cache_misses", "context_switches"]);
+            }
+            self.writer.write_record(headers)?;
+            self.written_headers = true;
+        }
+        let mut record = vec![
+            optional(
+                measurement
+                    .time
+                    .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)),
+            ),
+            measurement.pid.to_string(),
+            measurement.parent.to_string(),
+        ];
+        for metric in &self.metrics {
+            record.extend(metric.values(measurement));
+        }
+        record.push(measurement.name.clone());
+        if perf_columns {
+            record.extend([
+                optional(measurement.instructions),
+                optional(measurement.cycles),
+                optional(measurement.cache_misses),
+                optional(measurement.context_switches),
+            ]);
+        }
+        self.writer.write_record(record)
+    }
+}
+
+/// The number of open file descriptors of a process.
+fn open_files(pid: Pid) -> Option<u64> {
+    let fds = std::fs::read_dir(format!("/proc/{}/fd", pid)).ok()?;
+    Some(fds.count() as u64)
+}
+
+/// The number of threads in a process.
+fn threads(pid: Pid) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Threads:"))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Monitor the whole host, for experiments where the interesting activity spans many processes.
+///
+/// Each row has the CPU usage overall and of each core, memory and swap, and the throughput of
+/// each disk (Linux only) and network interface since the last sample. The disks and interfaces
+/// are those present at the first sample.
+#[derive(Debug)]
+pub struct SystemMonitor {
+    writer: csv::Writer<File>,
+    interval: Duration,
+    /// Disks and network interfaces, once the headers are written.
+    devices: Option<(Vec<String>, Vec<String>)>,
+    /// When the last sample was taken, with the bytes read and written of each disk then.
+    last_sample: Option<(Instant, DiskStats)>,
+}
+
+impl SystemMonitor {
+    pub fn new<P: AsRef<Path>>(filename: P, interval: Duration) -> Result<Self, MonitorError> {
+        check_interval(interval)?;
+        Ok(Self {
+            writer: csv::Writer::from_path(filename)?,
+            interval,
+            devices: None,
+            last_sample: None,
+        })
+    }
+
+    /// Run the monitor on the tokio runtime until `end_rx` is notified.
+    pub fn spawn(
+        mut self,
+        mut end_rx: watch::Receiver<()>,
+    ) -> JoinHandle<Result<(), MonitorError>> {
+        tokio::spawn(async move {
+            let mut sys = System::new_all();
+            debug!("Spawned system monitor");
+            let interval = tokio::time::interval(self.interval);
+            tokio::pin!(interval);
+            loop {
+                tokio::select! {
+                    _ = end_rx.changed() => break,
+                    _ = interval.tick() => self.sample(&mut sys)?,
+                }
+            }
+            self.writer.flush()?;
+            Ok(())
+        })
+    }
+
+    fn sample(&mut self, sys: &mut System) -> Result<(), MonitorError> {
+        let time = Utc::now();
+        sys.refresh_cpu();
+        sys.refresh_memory();
+        sys.refresh_networks();
+        let disks = disk_stats();
+        let now = Instant::now();
+        let elapsed = self
+            .last_sample
+            .as_ref()
+            .map(|(last, _)| (now - *last).as_secs_f64());
+
+        if self.devices.is_none() {
+            let mut disk_names = disks.keys().cloned().collect::<Vec<_>>();
+            disk_names.sort();
+            let mut interfaces = sys
+                .networks()
+                .into_iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+            interfaces.sort();
+            let mut headers = vec!["time".to_owned(), "cpu_usage_percentage".to_owned()];
+            for core in 0..sys.cpus().len() {
+                headers.push(format!("cpu{}_usage_percentage", core));
+            }
+            headers.extend(
+                [
+                    "memory_used_bytes",
+                    "memory_total_bytes",
+                    "swap_used_bytes",
+                    "swap_total_bytes",
+                ]
+                .map(String::from),
+            );
+            for disk in &disk_names {
+                headers.push(format!("disk_{}_read_bytes_per_second", disk));
+                headers.push(format!("disk_{}_write_bytes_per_second", disk));
+            }
+            for interface in &interfaces {
+                headers.push(format!("network_{}_received_bytes_per_second", interface));
+                headers.push(format!(
+                    "network_{}_transmitted_bytes_per_second",
+                    interface
+                ));
+            }
+            self.writer.write_record(headers)?;
+            self.devices = Some((disk_names, interfaces));
+        }
+        let (disk_names, interfaces) = self.devices.as_ref().unwrap();
+
+        let mut record = vec![
+            time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            sys.global_cpu_info().cpu_usage().to_string(),
+        ];
+        record.extend(sys.cpus().iter().map(|cpu| cpu.cpu_usage().to_string()));
+        record.extend(
+            [
+                sys.used_memory(),
+                sys.total_memory(),
+                sys.used_swap(),
+                sys.total_swap(),
+            ]
+            .map(|bytes| bytes.to_string()),
+        );
+        let previous_disks = self.last_sample.as_ref().map(|(_, disks)| disks);
+        for disk in disk_names.iter() {
+            let rates = match (
+                elapsed,
+                disks.get(disk),
+                previous_disks.and_then(|d| d.get(disk)),
+            ) {
+                (Some(elapsed), Some((read, written)), Some((last_read, last_written))) => [
+                    Some(read.saturating_sub(*last_read) as f64 / elapsed),
+                    Some(written.saturating_sub(*last_written) as f64 / elapsed),
+                ],
+                _ => [None, None],
+            };
+            record.extend(rates.map(optional));
+        }
+        for interface in interfaces.iter() {
+            let network = sys
+                .networks()
+                .into_iter()
+                .find(|(name, _)| *name == interface);
+            let rates = match (elapsed, network) {
+                (Some(elapsed), Some((_, data))) => [
+                    Some(data.received() as f64 / elapsed),
+                    Some(data.transmitted() as f64 / elapsed),
+                ],
+                _ => [None, None],
+            };
+            record.extend(rates.map(optional));
+        }
+        self.writer.write_record(record)?;
+        self.writer.flush()?;
+        self.last_sample = Some((now, disks));
+        Ok(())
+    }
+}
+
+/// Bytes read and written by each disk, by name.
+type DiskStats = HashMap<String, (u64, u64)>;
+
+/// The bytes read and written by each disk so far, from `/proc/diskstats`, leaving out loop and
+/// ram devices.
+fn disk_stats() -> DiskStats {
+    const SECTOR_BYTES: u64 = 512;
+    let diskstats = std::fs::read_to_string("/proc/diskstats").unwrap_or_default();
+    diskstats
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let name = *fields.get(2)?;
+            if name.starts_with("loop") || name.starts_with("ram") {
+                return None;
+            }
+            let sectors_read = fields.get(5)?.parse::<u64>().ok()?;
+            let sectors_written = fields.get(9)?.parse::<u64>().ok()?;
+            Some((
+                name.to_owned(),
+                (sectors_read * SECTOR_BYTES, sectors_written * SECTOR_BYTES),
+            ))
+        })
+        .collect()
+}
+
+/// Whether a process was started by `ancestor`, or by one of its descendants.
+fn is_descendant(sys: &System, process: &Process, ancestor: Pid) -> bool {
+    let mut parent = process.parent();
+    while let Some(pid) = parent {
+        if pid == ancestor {
+            return true;
         }
+        parent = sys.process(pid).and_then(|process| process.parent());
     }
+    false
 }
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// How long to wait for a webhook to respond before giving up on the notification.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notable points in a run that a `Notifier` is told about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    /// All configurations have been run.
+    Completed {
+        experiment: String,
+        total: usize,
+        failed: usize,
+    },
+    /// The first configuration of the run failed.
+    FirstFailure { experiment: String, hash: String },
+    /// Another `NotifyConfig::every` configurations have finished.
+    Progress {
+        experiment: String,
+        completed: usize,
+        total: usize,
+        failed: usize,
+    },
+    /// All experiments of a suite have been run, from `run_suite`.
+    SuiteCompleted {
+        suite: String,
+        experiments: usize,
+        /// Names of the experiments that failed to run.
+        failed: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for Notification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Notification::Completed {
+                experiment,
+                total,
+                failed,
+            } => write!(
+                f,
+                "{}: completed {} configurations, {} failed",
+                experiment, total, failed
+            ),
+            Notification::FirstFailure { experiment, hash } => {
+                write!(f, "{}: configuration {} failed", experiment, hash)
+            }
+            Notification::Progress {
+                experiment,
+                completed,
+                total,
+                failed,
+            } => write!(
+                f,
+                "{}: {}/{} configurations finished, {} failed",
+                experiment, completed, total, failed
+            ),
+            Notification::SuiteCompleted {
+                suite,
+                experiments,
+                failed,
+            } => write!(
+                f,
+                "{}: ran {} experiments, {} failed",
+                suite,
+                experiments,
+                failed.len()
+            ),
+        }
+    }
+}
+
+/// Something to tell about how a run is going, such as a chat channel.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, notification: &Notification);
+}
+
+/// POSTs each notification as JSON to a URL.
+///
+/// The body is the notification with an added `text` field summarising it, so it can be used
+/// directly with Slack incoming webhooks. Within a tokio runtime the request is sent on a blocking
+/// thread so it doesn't hold up the run.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub url: String,
+}
+
+impl Notifier for Webhook {
+    fn notify(&self, notification: &Notification) {
+        let mut body = serde_json::to_value(notification).unwrap();
+        body["text"] = notification.to_string().into();
+        debug!(url = %self.url, %notification, "Sending webhook notification");
+        let url = self.url.clone();
+        let send = move || {
+            let agent = ureq::AgentBuilder::new().timeout(WEBHOOK_TIMEOUT).build();
+            if let Err(error) = agent.post(&url).send_json(body) {
+                warn!(%error, %url, "Failed to send webhook notification");
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(send);
+            }
+            Err(_) => send(),
+        }
+    }
+}
+
+/// When and who to notify during a run.
+#[derive(Default)]
+pub struct NotifyConfig {
+    pub notifiers: Vec<Box<dyn Notifier>>,
+    /// Send a progress notification every this many finished configurations.
+    pub every: Option<usize>,
+}
+
+impl NotifyConfig {
+    pub(crate) fn notify(&self, notification: Notification) {
+        for notifier in &self.notifiers {
+            notifier.notify(&notification);
+        }
+    }
+}
+
+/// Tracks a run to decide when to send notifications.
+pub(crate) struct Notifications<'a> {
+    config: Option<&'a NotifyConfig>,
+    experiment: String,
+    total: usize,
+    completed: usize,
+    failed: usize,
+}
+
+impl<'a> Notifications<'a> {
+    pub(crate) fn new(config: Option<&'a NotifyConfig>, experiment: String, total: usize) -> Self {
+        Self {
+            config,
+            experiment,
+            total,
+            completed: 0,
+            failed: 0,
+        }
+    }
+
+    pub(crate) fn finished(&mut self, hash: &str, success: bool) {
+        let config = match self.config {
+            Some(config) => config,
+            None => return,
+        };
+        self.completed += 1;
+        if !success {
+            self.failed += 1;
+            if self.failed == 1 {
+                config.notify(Notification::FirstFailure {
+                    experiment: self.experiment.clone(),
+                    hash: hash.to_owned(),
+                });
+            }
+        }
+        if let Some(every) = config.every {
+            if self.completed.is_multiple_of(every) && self.completed < self.total {
+                config.notify(Notification::Progress {
+                    experiment: self.experiment.clone(),
+                    completed: self.completed,
+                    total: self.total,
+                    failed: self.failed,
+                });
+            }
+        }
+    }
+
+    pub(crate) fn completed(&self) {
+        if let Some(config) = self.config {
+            config.notify(Notification::Completed {
+                experiment: self.experiment.clone(),
+                total: self.total,
+                failed: self.failed,
+            });
+        }
+    }
+}
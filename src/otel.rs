@@ -0,0 +1,87 @@
+//! Export the spans of runs over OTLP, such as to Jaeger, to see where the time goes when
+//! orchestrating an experiment alongside the traces of the system under test.
+//!
+//! Runs are traced with an `experiment` span holding a `configuration` span for each repeat of
+//! each configuration, which in turn holds spans for `pre_run`, `run` and `post_run` and, with
+//! the docker `Runner`, for each `phase` and `container`.
+//!
+//! ```no_run
+//! use tracing_subscriber::layer::SubscriberExt;
+//! use tracing_subscriber::util::SubscriberInitExt;
+//!
+//! let exporter = exp::otel::OtelExporter::new("my-experiment", None).unwrap();
+//! tracing_subscriber::registry().with(exporter.layer()).init();
+//! // run the experiment, keeping the exporter alive until it finishes
+//! ```
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    error::OTelSdkError,
+    trace::{SdkTracer, SdkTracerProvider},
+    Resource,
+};
+use thiserror::Error;
+use tracing::{warn, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Debug, Error)]
+pub enum OtelError {
+    #[error(transparent)]
+    Build(#[from] ExporterBuildError),
+    #[error(transparent)]
+    Sdk(#[from] OTelSdkError),
+}
+
+/// Sends spans to an OTLP collector over gRPC in batches, flushing the rest when shut down or
+/// dropped.
+#[derive(Debug)]
+pub struct OtelExporter {
+    provider: SdkTracerProvider,
+}
+
+impl OtelExporter {
+    /// Export spans as from `service_name` to the collector at `endpoint`, by default
+    /// `http://localhost:4317` or the `OTEL_EXPORTER_OTLP_ENDPOINT` environment variable.
+    pub fn new(service_name: &str, endpoint: Option<&str>) -> Result<Self, OtelError> {
+        let mut builder = SpanExporter::builder().with_tonic();
+        if let Some(endpoint) = endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(builder.build()?)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(service_name.to_owned())
+                    .build(),
+            )
+            .build();
+        Ok(Self { provider })
+    }
+
+    /// A tracing layer sending spans to this exporter, to add to the subscriber.
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, SdkTracer>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer("exp"))
+    }
+
+    /// Export any spans not yet sent and stop exporting.
+    pub fn shutdown(self) -> Result<(), OtelError> {
+        self.provider.shutdown()?;
+        Ok(())
+    }
+}
+
+impl Drop for OtelExporter {
+    fn drop(&mut self) {
+        // already shut down by `shutdown`
+        if let Err(error) = self.provider.shutdown() {
+            if !matches!(error, OTelSdkError::AlreadyShutdown) {
+                warn!(%error, "Failed to flush spans to the collector");
+            }
+        }
+    }
+}
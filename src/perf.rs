@@ -0,0 +1,119 @@
+//! Hardware and software counters of monitored threads through `perf_event_open`.
+
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{self, Read},
+    os::unix::io::FromRawFd,
+};
+
+use perf_event_open_sys::{
+    bindings::{
+        perf_event_attr, perf_event_read_format_PERF_FORMAT_TOTAL_TIME_ENABLED,
+        perf_event_read_format_PERF_FORMAT_TOTAL_TIME_RUNNING,
+        perf_hw_id_PERF_COUNT_HW_CACHE_MISSES, perf_hw_id_PERF_COUNT_HW_CPU_CYCLES,
+        perf_hw_id_PERF_COUNT_HW_INSTRUCTIONS, perf_sw_ids_PERF_COUNT_SW_CONTEXT_SWITCHES,
+        perf_type_id_PERF_TYPE_HARDWARE, perf_type_id_PERF_TYPE_SOFTWARE, PERF_FLAG_FD_CLOEXEC,
+    },
+    perf_event_open,
+};
+use tracing::debug;
+
+/// Counts of a thread's events since the last sample.
+#[derive(Debug)]
+pub(crate) struct PerfSample {
+    pub(crate) instructions: Option<u64>,
+    pub(crate) cycles: Option<u64>,
+    pub(crate) cache_misses: Option<u64>,
+    pub(crate) context_switches: Option<u64>,
+}
+
+/// The counters of a thread.
+///
+/// Counters that can't be opened, such as hardware counters in a VM or any when
+/// `perf_event_paranoid` forbids it, are left empty.
+#[derive(Debug)]
+pub(crate) struct TaskCounters {
+    instructions: Option<Counter>,
+    cycles: Option<Counter>,
+    cache_misses: Option<Counter>,
+    context_switches: Option<Counter>,
+}
+
+impl TaskCounters {
+    pub(crate) fn open(tid: u32) -> Self {
+        let hardware = |config| Counter::open(tid, perf_type_id_PERF_TYPE_HARDWARE, config, true);
+        Self {
+            instructions: hardware(perf_hw_id_PERF_COUNT_HW_INSTRUCTIONS.into()),
+            cycles: hardware(perf_hw_id_PERF_COUNT_HW_CPU_CYCLES.into()),
+            cache_misses: hardware(perf_hw_id_PERF_COUNT_HW_CACHE_MISSES.into()),
+            context_switches: Counter::open(
+                tid,
+                perf_type_id_PERF_TYPE_SOFTWARE,
+                perf_sw_ids_PERF_COUNT_SW_CONTEXT_SWITCHES.into(),
+                false,
+            ),
+        }
+    }
+
+    pub(crate) fn sample(&mut self) -> PerfSample {
+        let delta = |counter: &mut Option<Counter>| counter.as_mut()?.delta().ok();
+        PerfSample {
+            instructions: delta(&mut self.instructions),
+            cycles: delta(&mut self.cycles),
+            cache_misses: delta(&mut self.cache_misses),
+            context_switches: delta(&mut self.context_switches),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Counter {
+    file: File,
+    last: u64,
+}
+
+impl Counter {
+    fn open(tid: u32, type_: u32, config: u64, exclude_kernel: bool) -> Option<Self> {
+        let mut attr = perf_event_attr {
+            type_,
+            size: std::mem::size_of::<perf_event_attr>() as u32,
+            config,
+            read_format: (perf_event_read_format_PERF_FORMAT_TOTAL_TIME_ENABLED
+                | perf_event_read_format_PERF_FORMAT_TOTAL_TIME_RUNNING)
+                .into(),
+            ..Default::default()
+        };
+        // counting only in user space needs no privileges
+        attr.set_exclude_kernel(exclude_kernel.into());
+        attr.set_exclude_hv(1);
+        let fd =
+            unsafe { perf_event_open(&mut attr, tid as i32, -1, -1, PERF_FLAG_FD_CLOEXEC.into()) };
+        if fd < 0 {
+            let error = io::Error::last_os_error();
+            debug!(%error, tid, type_, config, "Failed to open perf counter");
+            return None;
+        }
+        Some(Self {
+            file: unsafe { File::from_raw_fd(fd) },
+            last: 0,
+        })
+    }
+
+    /// The events since the last read, scaled up for the time the counter wasn't scheduled when
+    /// there are more counters than the CPU has.
+    fn delta(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 24];
+        self.file.read_exact(&mut buf)?;
+        let [value, enabled, running] = [0, 8, 16]
+            .map(|offset| u64::from_ne_bytes(buf[offset..offset + 8].try_into().unwrap()));
+        let value = if running == 0 {
+            0
+        } else {
+            (value as u128 * enabled as u128 / running as u128) as u64
+        };
+        let delta = value.saturating_sub(self.last);
+        self.last = value;
+        Ok(delta)
+    }
+}
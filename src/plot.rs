@@ -0,0 +1,264 @@
+//! Canned charts for common analyses, written as SVG or PNG.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use plotters::{
+    coord::Shift,
+    prelude::{
+        BitMapBackend, ChartBuilder, Color, DrawingArea, DrawingBackend, ErrorBar, IntoDrawingArea,
+        LineSeries, Palette, Palette99, PathElement, SVGBackend, BLACK, WHITE,
+    },
+};
+use thiserror::Error;
+
+use crate::docker_runner::Stats;
+use crate::stats::Summary;
+use crate::AnalysisDirs;
+
+const SIZE: (u32, u32) = (1024, 768);
+
+#[derive(Debug, Error)]
+pub enum PlotError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+    #[error("failed to draw chart: {0}")]
+    Drawing(String),
+}
+
+/// Image format of a chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    /// Format for the extension of `path`, defaulting to SVG.
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("png") => ImageFormat::Png,
+            _ => ImageFormat::Svg,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "svg",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// Draw a chart into `path` with the backend for its extension.
+macro_rules! draw {
+    ($path:expr, |$root:ident| $body:expr) => {
+        match ImageFormat::of($path) {
+            ImageFormat::Svg => {
+                let $root = SVGBackend::new($path, SIZE).into_drawing_area();
+                $body
+            }
+            ImageFormat::Png => {
+                let $root = BitMapBackend::new($path, SIZE).into_drawing_area();
+                $body
+            }
+        }
+    };
+}
+
+fn drawing_error<E: std::fmt::Debug>(error: E) -> PlotError {
+    PlotError::Drawing(format!("{:?}", error))
+}
+
+/// A line chart of a measurement against a parameter, such as throughput against the number of
+/// clients, with error bars for the 95% confidence interval of each point.
+///
+/// Each series is a name for the legend and its points, as from `exp::stats::summarise`.
+pub fn parameter_chart(
+    path: &Path,
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    series: &[(String, Vec<(f64, Summary)>)],
+) -> Result<(), PlotError> {
+    draw!(path, |root| parameter_chart_on(
+        root, title, x_label, y_label, series
+    ))
+}
+
+fn parameter_chart_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    series: &[(String, Vec<(f64, Summary)>)],
+) -> Result<(), PlotError> {
+    let points = series.iter().flat_map(|(_, points)| points);
+    let (x_range, _) = range(points.clone().map(|(x, _)| *x));
+    let (_, y_max) = range(points.map(|(_, s)| s.ci_high.max(s.max)));
+    root.fill(&WHITE).map_err(drawing_error)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_range, 0.0..y_max)
+        .map_err(drawing_error)?;
+    chart
+        .configure_mesh()
+        .x_desc(x_label)
+        .y_desc(y_label)
+        .draw()
+        .map_err(drawing_error)?;
+    for (i, (name, points)) in series.iter().enumerate() {
+        let color = Palette99::pick(i).mix(1.0);
+        chart
+            .draw_series(LineSeries::new(
+                points.iter().map(|(x, s)| (*x, s.mean)),
+                &color,
+            ))
+            .map_err(drawing_error)?
+            .label(name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        chart
+            .draw_series(points.iter().map(|(x, s)| {
+                ErrorBar::new_vertical(*x, s.ci_low, s.mean, s.ci_high, color.filled(), 10)
+            }))
+            .map_err(drawing_error)?;
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE)
+        .border_style(BLACK)
+        .draw()
+        .map_err(drawing_error)?;
+    root.present().map_err(drawing_error)
+}
+
+/// Cumulative distribution functions of latencies, one line per named series.
+pub fn latency_cdf(
+    path: &Path,
+    title: &str,
+    x_label: &str,
+    series: &[(String, Vec<f64>)],
+) -> Result<(), PlotError> {
+    let cdfs = series
+        .iter()
+        .map(|(name, latencies)| {
+            let mut sorted = latencies.clone();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let n = sorted.len() as f64;
+            let points = sorted
+                .into_iter()
+                .enumerate()
+                .map(|(i, latency)| (latency, (i + 1) as f64 / n))
+                .collect::<Vec<_>>();
+            (name.clone(), points)
+        })
+        .collect::<Vec<_>>();
+    draw!(path, |root| lines_on(
+        root, title, x_label, "fraction", &cdfs
+    ))
+}
+
+/// Plot the CPU and memory usage over time of each container in a configuration run, from its
+/// collected docker stats.
+///
+/// The charts are written as `cpu` and `memory` images into the configuration's analysis
+/// directory, returning their paths.
+pub fn container_stats(
+    dirs: &AnalysisDirs,
+    configuration_dir: &Path,
+    format: ImageFormat,
+) -> Result<Vec<PathBuf>, PlotError> {
+    let mut cpu = BTreeMap::<String, Vec<(f64, f64)>>::new();
+    let mut memory = BTreeMap::<String, Vec<(f64, f64)>>::new();
+    let stats = Stats::from_configuration(configuration_dir)?;
+    let start = stats.iter().map(|(_, stat)| stat.read).min();
+    for (name, stat) in &stats {
+        let time = start.map_or(0.0, |start| {
+            (stat.read - start).num_milliseconds() as f64 / 1000.0
+        });
+        if let Some(percentage) = stat.cpu_percentage() {
+            cpu.entry(name.clone())
+                .or_default()
+                .push((time, percentage));
+        }
+        if let Some(usage) = stat.memory_stats_usage {
+            let mib = usage as f64 / (1024.0 * 1024.0);
+            memory.entry(name.clone()).or_default().push((time, mib));
+        }
+    }
+
+    let output_dir = dirs.configuration_dir(configuration_dir)?;
+    let mut written = Vec::new();
+    for (chart, y_label, series) in [("cpu", "cpu %", cpu), ("memory", "memory MiB", memory)] {
+        let mut series = series.into_iter().collect::<Vec<_>>();
+        for (_, points) in &mut series {
+            points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        let path = output_dir.join(format!("{}.{}", chart, format.extension()));
+        draw!(&path, |root| lines_on(
+            root, chart, "time (s)", y_label, &series
+        ))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn lines_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    series: &[(String, Vec<(f64, f64)>)],
+) -> Result<(), PlotError> {
+    let points = series.iter().flat_map(|(_, points)| points);
+    let (x_range, _) = range(points.clone().map(|(x, _)| *x));
+    let (_, y_max) = range(points.map(|(_, y)| *y));
+    root.fill(&WHITE).map_err(drawing_error)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_range, 0.0..y_max)
+        .map_err(drawing_error)?;
+    chart
+        .configure_mesh()
+        .x_desc(x_label)
+        .y_desc(y_label)
+        .draw()
+        .map_err(drawing_error)?;
+    for (i, (name, points)) in series.iter().enumerate() {
+        let color = Palette99::pick(i).mix(1.0);
+        chart
+            .draw_series(LineSeries::new(points.iter().copied(), &color))
+            .map_err(drawing_error)?
+            .label(name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE)
+        .border_style(BLACK)
+        .draw()
+        .map_err(drawing_error)?;
+    root.present().map_err(drawing_error)
+}
+
+/// The range of the values, padded so it isn't empty, and the maximum value.
+fn range(values: impl Iterator<Item = f64>) -> (std::ops::Range<f64>, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    if min > max {
+        return (0.0..1.0, 1.0);
+    }
+    let max = if max > min { max } else { min + 1.0 };
+    (min..max, max)
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::BTreeMap,
+    fs::{read_dir, read_to_string, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::watch,
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+
+use crate::docker_runner::create_metrics_dir;
+
+/// File in the metrics directory of a configuration with its power samples.
+pub const POWER_FILE: &str = "power.csv";
+
+/// File in each configuration directory with the energy used over its run.
+pub const ENERGY_FILE: &str = "energy.json";
+
+/// How often to sample power, unless `PowerMonitor::interval` is set.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where the kernel exposes RAPL counters, unless `PowerMonitor::rapl_dir` is set.
+const DEFAULT_RAPL_DIR: &str = "/sys/class/powercap";
+
+/// Measure the power used by the host while each configuration runs, set with
+/// `RunConfig::power`.
+///
+/// Samples are written to `metrics/power.csv` and the energy used by each source over the run to
+/// `energy.json`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerMonitor {
+    /// Read the RAPL energy counters of the host's CPU packages and memory, on by default.
+    ///
+    /// The counters are usually only readable by root.
+    pub rapl: bool,
+    /// Powercap directory to find the RAPL zones in, `/sys/class/powercap` by default.
+    pub rapl_dir: Option<PathBuf>,
+    /// An external meter to read the power of the whole machine from.
+    pub meter: Option<PowerMeter>,
+    /// How often to sample, every second by default.
+    #[serde(with = "crate::run::seconds")]
+    pub interval: Option<Duration>,
+}
+
+impl Default for PowerMonitor {
+    fn default() -> Self {
+        Self {
+            rapl: true,
+            rapl_dir: None,
+            meter: None,
+            interval: None,
+        }
+    }
+}
+
+/// An external power meter, reporting watts.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum PowerMeter {
+    /// Get the watts from a url on each sample, either as the whole body or from a JSON body at
+    /// `pointer`, such as `/power/watts`.
+    Http {
+        url: String,
+        pointer: Option<String>,
+    },
+    /// Read the watts as the first number of each line from a serial device, or other file,
+    /// already configured such as with `stty`.
+    Serial { path: PathBuf },
+}
+
+/// A power sample from a source, one row of `metrics/power.csv`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerSample {
+    pub time: DateTime<Utc>,
+    /// The RAPL domain, such as `package-0` or `package-0/dram`, or `meter`.
+    pub source: String,
+    /// Mean power since the last sample.
+    pub watts: f64,
+    /// Energy used by the source since monitoring started.
+    pub joules: f64,
+}
+
+/// The energy a source used over a configuration run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Energy {
+    pub source: String,
+    pub joules: f64,
+    pub mean_watts: f64,
+}
+
+impl PowerMonitor {
+    /// Start sampling into the metrics directory of a configuration until `end_rx` is notified,
+    /// resolving to the energy used by each source.
+    pub(crate) fn spawn(
+        &self,
+        configuration_dir: &Path,
+        mut end_rx: watch::Receiver<()>,
+    ) -> io::Result<JoinHandle<Vec<Energy>>> {
+        let metrics_dir = create_metrics_dir(configuration_dir)?;
+        let mut writer = csv::Writer::from_path(metrics_dir.join(POWER_FILE))?;
+        let mut zones = if self.rapl {
+            let rapl_dir = self
+                .rapl_dir
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_RAPL_DIR));
+            rapl_zones(&rapl_dir)
+        } else {
+            Vec::new()
+        };
+        let (meter, meter_task) = match &self.meter {
+            Some(meter) => {
+                let (reader, task) = MeterReader::new(meter)?;
+                (Some(reader), task)
+            }
+            None => (None, None),
+        };
+        if zones.is_empty() && meter.is_none() {
+            warn!("No power sources to monitor");
+        }
+        let interval = self.interval.unwrap_or(DEFAULT_INTERVAL);
+        Ok(tokio::spawn(async move {
+            let start = Instant::now();
+            let mut last = start;
+            let mut meter = meter.map(|reader| (reader, None));
+            let mut totals = BTreeMap::new();
+            let mut ticks = tokio::time::interval_at((start + interval).into(), interval);
+            loop {
+                let ended = tokio::select! {
+                    _ = end_rx.changed() => true,
+                    _ = ticks.tick() => false,
+                };
+                let now = Instant::now();
+                let time = Utc::now();
+                let elapsed = (now - last).as_secs_f64();
+                last = now;
+                let mut samples = Vec::new();
+                for zone in &mut zones {
+                    match zone.read() {
+                        Ok(joules) => samples.push((zone.source.clone(), joules / elapsed, joules)),
+                        Err(error) => {
+                            warn!(%error, source = %zone.source, "Failed to read RAPL counter")
+                        }
+                    }
+                }
+                if let Some((reader, previous)) = &mut meter {
+                    if let Some(watts) = reader.read().await {
+                        // trapezoidal rule between samples
+                        let joules =
+                            previous.map_or(0., |previous: f64| (previous + watts) / 2. * elapsed);
+                        *previous = Some(watts);
+                        samples.push(("meter".to_owned(), watts, joules));
+                    }
+                }
+                for (source, watts, joules) in samples {
+                    let total = totals.entry(source.clone()).or_insert(0.);
+                    *total += joules;
+                    let sample = PowerSample {
+                        time,
+                        source,
+                        watts,
+                        joules: *total,
+                    };
+                    if let Err(error) = writer.serialize(sample) {
+                        warn!(%error, "Failed to write power sample");
+                    }
+                }
+                let _ = writer.flush();
+                if ended {
+                    break;
+                }
+            }
+            if let Some(task) = meter_task {
+                task.abort();
+            }
+            let duration = start.elapsed().as_secs_f64();
+            totals
+                .into_iter()
+                .map(|(source, joules)| Energy {
+                    source,
+                    joules,
+                    mean_watts: joules / duration,
+                })
+                .collect()
+        }))
+    }
+}
+
+/// Write the energy used over a configuration run.
+pub(crate) fn write_energy(configuration_dir: &Path, energy: &[Energy]) -> io::Result<()> {
+    let file = File::create(configuration_dir.join(ENERGY_FILE))?;
+    serde_json::to_writer_pretty(file, energy)?;
+    Ok(())
+}
+
+/// Read the energy used by each source over a configuration run.
+pub fn read_energy(configuration_dir: &Path) -> io::Result<Vec<Energy>> {
+    let file = File::open(configuration_dir.join(ENERGY_FILE))?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+/// A RAPL domain with the last reading of its energy counter.
+struct RaplZone {
+    source: String,
+    energy_path: PathBuf,
+    max_energy_microjoules: u64,
+    last_microjoules: u64,
+}
+
+impl RaplZone {
+    /// The joules used since the last reading, allowing for the counter wrapping.
+    fn read(&mut self) -> io::Result<f64> {
+        let microjoules = read_u64(&self.energy_path)?;
+        let used = if microjoules >= self.last_microjoules {
+            microjoules - self.last_microjoules
+        } else {
+            self.max_energy_microjoules - self.last_microjoules + microjoules
+        };
+        self.last_microjoules = microjoules;
+        Ok(used as f64 / 1e6)
+    }
+}
+
+/// The readable RAPL zones and subzones, `intel-rapl:<package>[:<subzone>]`, in the powercap
+/// directory.
+fn rapl_zones(rapl_dir: &Path) -> Vec<RaplZone> {
+    let entries = match read_dir(rapl_dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            warn!(%error, ?rapl_dir, "Failed to list RAPL zones");
+            return Vec::new();
+        }
+    };
+    let mut ids = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with("intel-rapl:"))
+        .collect::<Vec<_>>();
+    ids.sort();
+    let zone_name = |id: &str| {
+        read_to_string(rapl_dir.join(id).join("name"))
+            .map(|name| name.trim().to_owned())
+            .unwrap_or_else(|_| id.to_owned())
+    };
+    let mut zones = Vec::new();
+    for id in ids {
+        let dir = rapl_dir.join(&id);
+        // subzones are named within their package, such as `dram`
+        let source = match id.rsplit_once(':') {
+            Some((parent, _)) if parent.contains(':') => {
+                format!("{}/{}", zone_name(parent), zone_name(&id))
+            }
+            _ => zone_name(&id),
+        };
+        let energy_path = dir.join("energy_uj");
+        let reading = read_u64(&energy_path)
+            .and_then(|last| Ok((last, read_u64(&dir.join("max_energy_range_uj"))?)));
+        match reading {
+            Ok((last_microjoules, max_energy_microjoules)) => zones.push(RaplZone {
+                source,
+                energy_path,
+                max_energy_microjoules,
+                last_microjoules,
+            }),
+            Err(error) => warn!(%error, %source, "Failed to read RAPL zone, skipping it"),
+        }
+    }
+    debug!(zones = zones.len(), "Found RAPL zones");
+    zones
+}
+
+fn read_u64(path: &Path) -> io::Result<u64> {
+    read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Reads the current power from a meter.
+enum MeterReader {
+    Http {
+        url: String,
+        pointer: Option<String>,
+    },
+    /// The latest value read from the device.
+    Serial(watch::Receiver<Option<f64>>),
+}
+
+impl MeterReader {
+    /// Create a reader, with the task reading from a serial device.
+    fn new(meter: &PowerMeter) -> io::Result<(Self, Option<JoinHandle<()>>)> {
+        match meter {
+            PowerMeter::Http { url, pointer } => Ok((
+                MeterReader::Http {
+                    url: url.clone(),
+                    pointer: pointer.clone(),
+                },
+                None,
+            )),
+            PowerMeter::Serial { path } => {
+                let file = std::fs::OpenOptions::new().read(true).open(path)?;
+                let (tx, rx) = watch::channel(None);
+                let task = tokio::spawn(async move {
+                    let mut lines = BufReader::new(tokio::fs::File::from_std(file)).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        match parse_watts(&line) {
+                            Some(watts) => {
+                                let _ = tx.send(Some(watts));
+                            }
+                            None => debug!(%line, "Ignoring power meter line"),
+                        }
+                    }
+                });
+                Ok((MeterReader::Serial(rx), Some(task)))
+            }
+        }
+    }
+
+    async fn read(&mut self) -> Option<f64> {
+        match self {
+            MeterReader::Http { url, pointer } => {
+                let url = url.clone();
+                let pointer = pointer.clone();
+                let read = tokio::task::spawn_blocking(move || -> Result<f64, String> {
+                    let body = ureq::get(&url)
+                        .call()
+                        .map_err(|error| error.to_string())?
+                        .into_string()
+                        .map_err(|error| error.to_string())?;
+                    let watts = match pointer {
+                        Some(pointer) => serde_json::from_str::<serde_json::Value>(&body)
+                            .map_err(|error| error.to_string())?
+                            .pointer(&pointer)
+                            .and_then(|value| value.as_f64()),
+                        None => parse_watts(&body),
+                    };
+                    watts.ok_or_else(|| format!("no watts in response {:?}", body))
+                })
+                .await;
+                match read {
+                    Ok(Ok(watts)) => Some(watts),
+                    Ok(Err(error)) => {
+                        warn!(%error, "Failed to read power meter");
+                        None
+                    }
+                    Err(error) => {
+                        warn!(%error, "Power meter task failed");
+                        None
+                    }
+                }
+            }
+            MeterReader::Serial(rx) => *rx.borrow(),
+        }
+    }
+}
+
+/// The first number in a line, such as `123.4` from `123.4 W`.
+fn parse_watts(line: &str) -> Option<f64> {
+    line.split(|c: char| c.is_whitespace() || c == ',')
+        .find_map(|word| word.parse().ok())
+}
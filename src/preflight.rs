@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{System, SystemExt};
+use tracing::{debug, info};
+
+/// Resources an experiment needs, checked before running any configurations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Requirements {
+    /// Whether the docker daemon must be reachable.
+    pub docker: bool,
+    /// Images, as name and tag, that must already be present locally.
+    pub images: Vec<(String, String)>,
+    /// Number of CPUs needed at once.
+    pub cpus: Option<f64>,
+    /// Bytes of memory needed at once.
+    pub memory: Option<u64>,
+    /// Bytes of free disk space needed in the results directory.
+    pub disk_space: Option<u64>,
+}
+
+/// Check the requirements against the host, returning every failure found.
+pub(crate) async fn preflight(results_dir: &Path, requirements: &Requirements) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    if requirements.docker || !requirements.images.is_empty() {
+        match bollard::Docker::connect_with_local_defaults() {
+            Ok(docker) => match docker.ping().await {
+                Ok(_) => {
+                    for (name, tag) in &requirements.images {
+                        let image = format!("{}:{}", name, tag);
+                        if let Err(error) = docker.inspect_image(&image).await {
+                            failures.push(format!("image {} is not available: {}", image, error));
+                        }
+                    }
+                }
+                Err(error) => failures.push(format!("failed to reach docker: {}", error)),
+            },
+            Err(error) => failures.push(format!("failed to connect to docker: {}", error)),
+        }
+    }
+
+    if let Some(disk_space) = requirements.disk_space {
+        match nix::sys::statvfs::statvfs(results_dir) {
+            Ok(stat) => {
+                // the field types vary between platforms
+                #[allow(clippy::unnecessary_cast)]
+                let available = stat.blocks_available() as u64 * stat.fragment_size() as u64;
+                if available < disk_space {
+                    failures.push(format!(
+                        "results directory has {} bytes free but {} are needed",
+                        available, disk_space
+                    ));
+                }
+            }
+            Err(error) => failures.push(format!("failed to check free disk space: {}", error)),
+        }
+    }
+
+    let mut sys = System::new();
+    sys.refresh_cpu();
+    sys.refresh_memory();
+    if let Some(cpus) = requirements.cpus {
+        let available = sys.cpus().len();
+        if cpus > available as f64 {
+            failures.push(format!(
+                "{} cpus are needed but the host has {}",
+                cpus, available
+            ));
+        }
+    }
+    if let Some(memory) = requirements.memory {
+        let available = sys.total_memory();
+        if memory > available {
+            failures.push(format!(
+                "{} bytes of memory are needed but the host has {}",
+                memory, available
+            ));
+        }
+    }
+
+    if failures.is_empty() {
+        info!("Preflight checks passed");
+    } else {
+        debug!(?failures, "Preflight checks failed");
+    }
+    failures
+}
@@ -0,0 +1,242 @@
+use std::{
+    ffi::CString,
+    fs::{create_dir_all, remove_dir, write, File},
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use chrono::{SecondsFormat, Utc};
+use futures::future::join_all;
+use nix::{fcntl::OFlag, sys::stat::Mode};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, Command},
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    docker_runner::{create_config_dir, create_logs_dir, create_metrics_dir},
+    log_capture::{LogCaptureConfig, LogWriter},
+    monitor::ProcessMonitor,
+};
+
+/// Root of the cgroup v2 hierarchy that process cgroups are created under.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Interval at which spawned processes are monitored.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(1);
+
+// The process runner for a particular experiment run, running processes directly on the host
+// handles spawning of processes and teardown after
+#[derive(Debug)]
+pub struct Runner {
+    processes: Vec<(String, Child)>,
+    cgroups: Vec<PathBuf>,
+    config_dir: PathBuf,
+    end_tx: tokio::sync::watch::Sender<()>,
+    end_rx: tokio::sync::watch::Receiver<()>,
+    futures: Vec<JoinHandle<()>>,
+}
+
+impl Runner {
+    pub async fn new(config_dir: PathBuf) -> Self {
+        let (end_tx, end_rx) = tokio::sync::watch::channel(());
+        Self {
+            processes: Vec::new(),
+            cgroups: Vec::new(),
+            config_dir,
+            end_tx,
+            end_rx,
+            futures: Vec::new(),
+        }
+    }
+
+    pub async fn add_process(&mut self, config: &ProcessConfig) {
+        let config_dir =
+            create_config_dir(&self.config_dir).expect("Failed to create process config dir");
+        let logs_dir = create_logs_dir(&self.config_dir).expect("Failed to create logs dir");
+        let metrics_dir =
+            create_metrics_dir(&self.config_dir).expect("Failed to create metrics dir");
+        let config_file = File::create(config_dir.join(format!("process-{}.json", config.name)))
+            .expect("Failed to create process config file");
+        serde_json::to_writer_pretty(config_file, &config).expect("Failed to write process config");
+
+        let cgroup = if config.cpus.is_some() || config.memory.is_some() {
+            let cgroup = create_cgroup(&self.config_dir, config);
+            self.cgroups.push(cgroup.clone());
+            Some(cgroup)
+        } else {
+            None
+        };
+
+        let mut command = Command::new(&config.binary);
+        command
+            .args(&config.args)
+            .envs(config.env.iter().cloned())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(working_dir) = &config.working_dir {
+            command.current_dir(working_dir);
+        }
+        if let Some(cgroup) = &cgroup {
+            let procs = CString::new(cgroup.join("cgroup.procs").as_os_str().as_bytes())
+                .expect("Invalid cgroup path");
+            // the child moves itself into the cgroup before exec, so it is limited from the start
+            // and the pid can't be reused in between
+            unsafe {
+                command.pre_exec(move || join_cgroup(&procs));
+            }
+        }
+        let mut child = command.spawn().expect("Failed to spawn process");
+        let pid = child.id().expect("Process exited before being monitored");
+        debug!(name = %config.name, pid, "Spawned process");
+
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let logs_extension = if config.logs.json { "jsonl" } else { "log" };
+        let mut log_writer = LogWriter::new(
+            logs_dir.join(format!("process-{}.{}", config.name, logs_extension)),
+            &config.logs,
+        )
+        .expect("Invalid log drop pattern");
+        self.futures.push(tokio::spawn(async move {
+            let mut stdout = BufReader::new(stdout).lines();
+            let mut stderr = BufReader::new(stderr).lines();
+            loop {
+                // timestamp lines the same as docker so the logs can be loaded the same way
+                tokio::select! {
+                    Ok(Some(line)) = stdout.next_line() => {
+                        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+                        log_writer.write_line(&format!("{} {}\n", now, line)).unwrap();
+                    }
+                    Ok(Some(line)) = stderr.next_line() => {
+                        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+                        log_writer.write_line(&format!("{} {}\n", now, line)).unwrap();
+                    }
+                    else => break,
+                }
+            }
+            log_writer.flush().unwrap();
+        }));
+
+        let monitor = ProcessMonitor::new(
+            pid,
+            metrics_dir.join(format!("process-{}.csv", config.name)),
+            MONITOR_INTERVAL,
+        )
+        .expect("Failed to create process monitor")
+        .spawn(self.end_rx.clone());
+        let name = config.name.clone();
+        self.futures.push(tokio::spawn(async move {
+            match monitor.await {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => warn!(%error, %name, "Failed to monitor process"),
+                Err(error) => warn!(%error, %name, "Process monitor task failed"),
+            }
+        }));
+
+        self.processes.push((config.name.clone(), child));
+    }
+
+    pub async fn finish(self) {
+        for (name, mut child) in self.processes {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    debug!(%name, %status, "Process already exited");
+                }
+                Ok(None) => {
+                    if let Err(error) = child.kill().await {
+                        warn!(%error, %name, "Error killing process")
+                    }
+                }
+                Err(error) => {
+                    warn!(%error, %name, "Error checking process status")
+                }
+            }
+        }
+
+        let r = self.end_tx.send(());
+        if let Err(error) = r {
+            warn!(%error, "Error sending shutdown signal to monitoring tasks")
+        }
+        join_all(self.futures).await;
+
+        for cgroup in self.cgroups {
+            if let Err(error) = remove_dir(&cgroup) {
+                warn!(%error, ?cgroup, "Error removing cgroup")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessConfig {
+    pub name: String,
+    pub binary: PathBuf,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub working_dir: Option<PathBuf>,
+    /// Limit the process to this many CPUs, using a cgroup v2 `cpu.max`.
+    pub cpus: Option<f64>,
+    /// Limit the process to this many bytes of memory, using a cgroup v2 `memory.max`.
+    pub memory: Option<i64>,
+    /// How to write the captured stdout and stderr.
+    pub logs: LogCaptureConfig,
+}
+
+/// The cgroup of a process of the configuration run in `config_dir`, named after the run so
+/// concurrent runs of the same process don't share one.
+pub fn cgroup_path(config_dir: &Path, name: &str) -> PathBuf {
+    // without the .running extension
+    let run = config_dir
+        .file_stem()
+        .map(|run| run.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    PathBuf::from(CGROUP_ROOT).join(format!("exp-{}-{}", run, name))
+}
+
+/// The cgroup v2 interface files to write the limits of the process to, and their contents.
+pub fn cgroup_limits(config: &ProcessConfig) -> Vec<(&'static str, String)> {
+    let mut limits = Vec::new();
+    if let Some(cpus) = config.cpus {
+        let cpu_period = 100000;
+        let cpu_quota = (cpu_period as f64 * cpus) as i64;
+        limits.push(("cpu.max", format!("{} {}", cpu_quota, cpu_period)));
+    }
+    if let Some(memory) = config.memory {
+        limits.push(("memory.max", memory.to_string()));
+    }
+    limits
+}
+
+/// Create a cgroup for the process with the configured limits.
+///
+/// This requires write access to the cgroup v2 hierarchy, e.g. by running as root.
+fn create_cgroup(config_dir: &Path, config: &ProcessConfig) -> PathBuf {
+    let cgroup = cgroup_path(config_dir, &config.name);
+    debug!(path = ?cgroup, "Creating cgroup");
+    create_dir_all(&cgroup).expect("Failed to create cgroup");
+    for (file, limit) in cgroup_limits(config) {
+        write(cgroup.join(file), limit).expect("Failed to set cgroup limit");
+    }
+    cgroup
+}
+
+/// Move the calling process into the cgroup with the given `cgroup.procs`, from a forked child.
+///
+/// Only makes async-signal-safe calls.
+fn join_cgroup(procs: &CString) -> io::Result<()> {
+    let fd = nix::fcntl::open(procs.as_c_str(), OFlag::O_WRONLY, Mode::empty())?;
+    // 0 is the writing process
+    let written = nix::unistd::write(fd, b"0");
+    let _ = nix::unistd::close(fd);
+    written?;
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Lifecycle events of configurations during a run.
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    Started {
+        hash: String,
+    },
+    Finished {
+        hash: String,
+        duration: Duration,
+    },
+    Failed {
+        hash: String,
+        duration: Duration,
+    },
+    /// The configuration was locked or completed by another run.
+    Skipped {
+        hash: String,
+    },
+}
+
+/// Overall progress through the configurations of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    /// Number of configurations to run.
+    pub total: usize,
+    /// Number of configurations finished, including failures and skips.
+    pub completed: usize,
+    pub failed: usize,
+    /// Estimate of the time left, from the mean duration of the configurations run so far.
+    pub eta: Option<Duration>,
+}
+
+/// Receives progress events from `run`, set with `RunConfig::progress`.
+pub trait ProgressReporter: Send + Sync {
+    fn report(&self, event: &ProgressEvent, progress: &Progress);
+}
+
+/// Tracks progress through a run and passes events to a reporter.
+pub(crate) struct ProgressTracker<'a> {
+    reporter: Option<&'a dyn ProgressReporter>,
+    progress: Progress,
+    run: usize,
+    run_time: Duration,
+    started: Option<Instant>,
+}
+
+impl<'a> ProgressTracker<'a> {
+    pub(crate) fn new(reporter: Option<&'a dyn ProgressReporter>, total: usize) -> Self {
+        Self {
+            reporter,
+            progress: Progress {
+                total,
+                ..Default::default()
+            },
+            run: 0,
+            run_time: Duration::default(),
+            started: None,
+        }
+    }
+
+    pub(crate) fn started(&mut self, hash: &str) {
+        self.started = Some(Instant::now());
+        self.report(ProgressEvent::Started {
+            hash: hash.to_owned(),
+        });
+    }
+
+    /// Record the outcome of the last started configuration, `None` if it was skipped.
+    pub(crate) fn finished(&mut self, hash: &str, success: Option<bool>) {
+        let duration = self
+            .started
+            .take()
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        self.progress.completed += 1;
+        let hash = hash.to_owned();
+        let event = match success {
+            Some(success) => {
+                self.run += 1;
+                self.run_time += duration;
+                if success {
+                    ProgressEvent::Finished { hash, duration }
+                } else {
+                    self.progress.failed += 1;
+                    ProgressEvent::Failed { hash, duration }
+                }
+            }
+            None => ProgressEvent::Skipped { hash },
+        };
+        if self.run > 0 {
+            let remaining = self.progress.total.saturating_sub(self.progress.completed) as u32;
+            self.progress.eta = Some(self.run_time / self.run as u32 * remaining);
+        }
+        self.report(event);
+    }
+
+    fn report(&self, event: ProgressEvent) {
+        if let Some(reporter) = self.reporter {
+            reporter.report(&event, &self.progress);
+        }
+    }
+}
+
+/// A progress bar on the terminal.
+#[derive(Debug)]
+pub struct TerminalProgress {
+    bar: ProgressBar,
+}
+
+impl Default for TerminalProgress {
+    fn default() -> Self {
+        let bar = ProgressBar::new(0);
+        bar.set_style(
+            ProgressStyle::with_template("[{elapsed_precise}] {bar:40} {pos}/{len} {msg}").unwrap(),
+        );
+        Self { bar }
+    }
+}
+
+impl ProgressReporter for TerminalProgress {
+    fn report(&self, event: &ProgressEvent, progress: &Progress) {
+        self.bar.set_length(progress.total as u64);
+        self.bar.set_position(progress.completed as u64);
+        let eta = progress
+            .eta
+            .map(|eta| format!(", eta {}s", eta.as_secs()))
+            .unwrap_or_default();
+        let failed = if progress.failed > 0 {
+            format!(", {} failed", progress.failed)
+        } else {
+            String::new()
+        };
+        match event {
+            ProgressEvent::Started { hash } => self
+                .bar
+                .set_message(format!("running {}{}{}", hash, failed, eta)),
+            ProgressEvent::Finished { .. }
+            | ProgressEvent::Failed { .. }
+            | ProgressEvent::Skipped { .. } => {
+                self.bar.set_message(
+                    format!("{}{}", failed, eta)
+                        .trim_start_matches(", ")
+                        .to_owned(),
+                );
+                if progress.completed == progress.total {
+                    self.bar.finish();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Scrape the metrics containers expose in the Prometheus text format, see
+//! `docker_runner::Runner::scrape_prometheus`.
+
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::compression;
+
+/// Scraping of a container's Prometheus metrics endpoint.
+///
+/// Samples are written to `metrics/prom-<name>.csv`, a new file in a phase subdirectory for
+/// each phase.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrometheusScrape {
+    /// Port in the container the metrics are served on.
+    pub port: u16,
+    /// Path of the metrics endpoint, `/metrics` by default.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Host to scrape, the container's address on its network by default. Set to `localhost`
+    /// with the port published when the containers aren't reachable from the host, such as with
+    /// Docker Desktop.
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Patterns of the names of the series to keep, such as `^http_requests_total$`, all by
+    /// default.
+    #[serde(default)]
+    pub series: Vec<String>,
+    /// How often to scrape, every 5 seconds by default.
+    #[serde(default, with = "crate::run::seconds")]
+    pub interval: Option<Duration>,
+}
+
+impl PrometheusScrape {
+    pub(crate) fn url(&self, host: &str) -> String {
+        format!(
+            "http://{}:{}{}",
+            self.host.as_deref().unwrap_or(host),
+            self.port,
+            self.path.as_deref().unwrap_or("/metrics")
+        )
+    }
+
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval.unwrap_or(Duration::from_secs(5))
+    }
+
+    /// Whether to keep the series with this name.
+    pub(crate) fn filter(&self) -> Result<impl Fn(&str) -> bool, regex::Error> {
+        let series = self
+            .series
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(move |name: &str| series.is_empty() || series.iter().any(|regex| regex.is_match(name)))
+    }
+}
+
+/// A sample of a series in the Prometheus text format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrometheusSample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+}
+
+/// A scraped sample, as written to `metrics/prom-<name>.csv`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrometheusRecord {
+    pub time: DateTime<Utc>,
+    pub metric: String,
+    /// Labels of the series as in the exposition format, sorted by name, such as
+    /// `code="200",method="get"`.
+    pub labels: String,
+    pub value: f64,
+}
+
+impl PrometheusRecord {
+    /// The record of a sample scraped at `time`.
+    pub fn new(time: DateTime<Utc>, sample: PrometheusSample) -> Self {
+        let labels = sample
+            .labels
+            .iter()
+            .map(|(name, value)| {
+                let value = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                format!("{}=\"{}\"", name, value)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        Self {
+            time,
+            metric: sample.name,
+            labels,
+            value: sample.value,
+        }
+    }
+
+    /// Load the samples scraped from a container, decompressing them if they have been
+    /// compressed.
+    pub fn from_file(path: &Path) -> Result<Vec<PrometheusRecord>, csv::Error> {
+        csv::Reader::from_reader(compression::open(path)?)
+            .deserialize()
+            .collect()
+    }
+}
+
+/// Parse the Prometheus text exposition format, skipping comments and lines that aren't samples.
+///
+/// Timestamps of samples are ignored.
+pub fn parse_prometheus(text: &str) -> Vec<PrometheusSample> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(parse_sample)
+        .collect()
+}
+
+fn parse_sample(line: &str) -> Option<PrometheusSample> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .unwrap_or(line.len());
+    let name = &line[..name_end];
+    let mut rest = &line[name_end..];
+    let mut labels = BTreeMap::new();
+    if let Some(label_text) = rest.strip_prefix('{') {
+        let (parsed, after) = parse_labels(label_text)?;
+        labels = parsed;
+        rest = after;
+    }
+    let value = rest.split_whitespace().next()?;
+    let value = match value {
+        "+Inf" => f64::INFINITY,
+        "-Inf" => f64::NEG_INFINITY,
+        value => value.parse().ok()?,
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(PrometheusSample {
+        name: name.to_owned(),
+        labels,
+        value,
+    })
+}
+
+/// Parse `name="value",...}`, returning the labels and the text after the closing brace.
+fn parse_labels(mut text: &str) -> Option<(BTreeMap<String, String>, &str)> {
+    let mut labels = BTreeMap::new();
+    loop {
+        text = text.trim_start().trim_start_matches(',').trim_start();
+        if let Some(rest) = text.strip_prefix('}') {
+            return Some((labels, rest));
+        }
+        let (name, rest) = text.split_once('=')?;
+        let rest = rest.trim_start().strip_prefix('"')?;
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (index, '"') => break index,
+                (_, '\\') => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    escaped => value.push(escaped),
+                },
+                (_, c) => value.push(c),
+            }
+        };
+        labels.insert(name.trim().to_owned(), value);
+        text = &rest[end + 1..];
+    }
+}
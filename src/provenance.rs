@@ -0,0 +1,77 @@
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Where the code used for a run came from, written to `provenance.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The repository containing the experiment, found from the current directory.
+    pub experiment: Option<RepoProvenance>,
+    /// Other repositories configured in `RunConfig::provenance_repos`.
+    pub repos: Vec<RepoProvenance>,
+}
+
+/// The state of a git repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoProvenance {
+    pub path: PathBuf,
+    pub commit: String,
+    /// Whether there were uncommitted changes to tracked files.
+    pub dirty: bool,
+    /// The uncommitted changes, as given by `git diff HEAD`.
+    pub diff: String,
+}
+
+impl RepoProvenance {
+    fn collect(path: &Path) -> io::Result<Self> {
+        let path = git(path, &["rev-parse", "--show-toplevel"])?;
+        let path = PathBuf::from(path.trim());
+        let commit = git(&path, &["rev-parse", "HEAD"])?.trim().to_owned();
+        let diff = git(&path, &["diff", "HEAD"])?;
+        Ok(Self {
+            path,
+            commit,
+            dirty: !diff.is_empty(),
+            diff,
+        })
+    }
+}
+
+pub(crate) fn collect_provenance(path: &Path, repos: &[PathBuf]) {
+    let experiment = match RepoProvenance::collect(Path::new(".")) {
+        Ok(repo) => Some(repo),
+        Err(error) => {
+            debug!(%error, "Experiment is not in a git repository");
+            None
+        }
+    };
+    let repos = repos
+        .iter()
+        .filter_map(|repo| match RepoProvenance::collect(repo) {
+            Ok(repo) => Some(repo),
+            Err(error) => {
+                warn!(%error, ?repo, "Failed to collect provenance");
+                None
+            }
+        })
+        .collect();
+    let provenance = Provenance { experiment, repos };
+    let provenance_file = File::create(path.join("provenance.json")).unwrap();
+    serde_json::to_writer_pretty(provenance_file, &provenance).unwrap();
+}
+
+fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
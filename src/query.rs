@@ -0,0 +1,127 @@
+//! Query a results directory with SQL.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use serde_json::Value;
+
+use crate::data::{load_configuration, stack, DATASETS};
+use crate::results::{flatten, list_configurations, ConfigurationState};
+
+/// Run a SQL query over the results of an experiment.
+///
+/// The tables are:
+/// - `configurations`: a row per configuration run, with its `hash`, `state` and `path`, and a
+///   column per configuration field, named by its dotted path (e.g. `"workload.clients"`).
+/// - `container_stats`, `container_top`, `process_monitor` and `power`: the metrics of the
+///   completed runs, as from `exp::data`, with a `configuration` column of their hash to join on.
+///
+/// ```ignore
+/// exp::query::sql(
+///     results_dir,
+///     "SELECT nodes, AVG(memory_stats_usage) FROM container_stats \
+///      JOIN configurations ON container_stats.configuration = configurations.hash \
+///      GROUP BY nodes",
+/// )?;
+/// ```
+pub fn sql(results_dir: &Path, query: &str) -> PolarsResult<DataFrame> {
+    let mut context = context(results_dir)?;
+    context.execute(query)?.collect()
+}
+
+/// A SQL context with the tables of a results directory registered, for running several queries.
+pub fn context(results_dir: &Path) -> PolarsResult<SQLContext> {
+    let entries = list_configurations(results_dir)?;
+    let mut context = SQLContext::new();
+
+    let mut hashes = Vec::new();
+    let mut states = Vec::new();
+    let mut paths = Vec::new();
+    let mut fields = Vec::new();
+    for entry in &entries {
+        hashes.push(entry.hash.clone());
+        states.push(entry.state.to_string());
+        paths.push(entry.path.to_string_lossy().into_owned());
+        let mut flat = BTreeMap::new();
+        if let Some(configuration) = &entry.configuration {
+            flatten(configuration, String::new(), &mut flat);
+        }
+        fields.push(flat);
+    }
+    let mut columns = vec![
+        Series::new("hash", hashes),
+        Series::new("state", states),
+        Series::new("path", paths),
+    ];
+    let names = fields
+        .iter()
+        .flat_map(|f| f.keys().cloned())
+        .collect::<BTreeSet<_>>();
+    for name in names {
+        let values = fields
+            .iter()
+            .map(|f| f.get(&name).filter(|v| !v.is_null()))
+            .collect::<Vec<_>>();
+        columns.push(field_series(&name, &values));
+    }
+    context.register("configurations", DataFrame::new(columns)?.lazy());
+
+    for (dataset, load) in DATASETS {
+        let frames = entries
+            .iter()
+            .filter(|entry| entry.state == ConfigurationState::Completed)
+            .map(|entry| load_configuration(&entry.hash, &entry.path, load))
+            .filter(|df| df.as_ref().map_or(true, |df| df.height() > 0))
+            .collect::<PolarsResult<Vec<_>>>()?;
+        context.register(dataset, stack(frames)?.lazy());
+    }
+    Ok(context)
+}
+
+/// A column of a configuration field, typed by its values: integers, floats and booleans keep
+/// their type and anything else is a string of its JSON. Missing fields and nulls are null.
+fn field_series(name: &str, values: &[Option<&Value>]) -> Series {
+    let present = || values.iter().flatten();
+    if present().all(|v| v.is_i64()) {
+        Series::new(
+            name,
+            values
+                .iter()
+                .map(|v| v.and_then(Value::as_i64))
+                .collect::<Vec<_>>(),
+        )
+    } else if present().all(|v| v.is_number()) {
+        Series::new(
+            name,
+            values
+                .iter()
+                .map(|v| v.and_then(Value::as_f64))
+                .collect::<Vec<_>>(),
+        )
+    } else if present().all(|v| v.is_boolean()) {
+        Series::new(
+            name,
+            values
+                .iter()
+                .map(|v| v.and_then(Value::as_bool))
+                .collect::<Vec<_>>(),
+        )
+    } else {
+        Series::new(
+            name,
+            values
+                .iter()
+                .map(|v| {
+                    v.map(|v| match v {
+                        Value::String(s) => s.clone(),
+                        v => v.to_string(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs::{read_dir, remove_dir_all, remove_file, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::lock::{holder, remove_stale, LockOwner, LOCK_EXTENSION};
+use crate::run::EXPERIMENT_LOCK_FILE;
+
+/// What happened to a configuration run in a results directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigurationState {
+    Completed,
+    Failed,
+    /// Still running, or interrupted if its lock is no longer held.
+    Running,
+}
+
+impl std::fmt::Display for ConfigurationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            ConfigurationState::Completed => "completed",
+            ConfigurationState::Failed => "failed",
+            ConfigurationState::Running => "running",
+        })
+    }
+}
+
+impl ConfigurationState {
+    /// The state of a configuration run from the name of its directory, or `None` if the name
+    /// isn't that of a configuration run.
+    ///
+    /// Use this in `Experiment::analyse` to tell failed runs apart when they are included with
+    /// `IncompletePolicy::Include`.
+    pub fn of(configuration_dir: &Path) -> Option<Self> {
+        match configuration_dir.extension().and_then(OsStr::to_str) {
+            None => Some(ConfigurationState::Completed),
+            Some("failed") => Some(ConfigurationState::Failed),
+            Some("running") => Some(ConfigurationState::Running),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Name of the directory of a repeat of a configuration run, the configuration's hash for the
+/// first repeat and `<hash>-<repeat>` for later ones.
+pub fn run_name(hash: &str, repeat: usize) -> String {
+    if repeat == 0 {
+        hash.to_owned()
+    } else {
+        format!("{}-{}", hash, repeat)
+    }
+}
+
+/// Split the name of a configuration run's directory, without any state extension, into the
+/// configuration's hash and the repeat.
+pub fn split_run_name(name: &str) -> (&str, usize) {
+    name.rsplit_once('-')
+        .and_then(|(hash, repeat)| Some((hash, repeat.parse().ok()?)))
+        .unwrap_or((name, 0))
+}
+
+/// A configuration directory in a results directory.
+#[derive(Debug, Clone)]
+pub struct ConfigurationEntry {
+    pub hash: String,
+    /// Which repeat of the configuration this is, from 0.
+    pub repeat: usize,
+    pub state: ConfigurationState,
+    pub path: PathBuf,
+    /// The contents of `configuration.json`, if it has been written.
+    pub configuration: Option<Value>,
+}
+
+/// List the configuration directories of an experiment's results directory, sorted by hash.
+pub fn list_configurations(experiment_dir: &Path) -> io::Result<Vec<ConfigurationEntry>> {
+    let mut entries = Vec::new();
+    for entry in read_dir(experiment_dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let (hash, repeat) = match path.file_stem() {
+            Some(name) => {
+                let name = name.to_string_lossy();
+                let (hash, repeat) = split_run_name(&name);
+                (hash.to_owned(), repeat)
+            }
+            None => continue,
+        };
+        let state = match ConfigurationState::of(&path) {
+            Some(state) => state,
+            None => continue,
+        };
+        let config_file = path.join("configuration.json");
+        let configuration = if config_file.exists() {
+            Some(serde_json::from_reader(File::open(config_file)?)?)
+        } else if state == ConfigurationState::Completed {
+            // not a configuration, e.g. the analysis directory
+            continue;
+        } else {
+            None
+        };
+        entries.push(ConfigurationEntry {
+            hash,
+            repeat,
+            state,
+            path,
+            configuration,
+        });
+    }
+    entries.sort_by(|a, b| {
+        a.hash
+            .cmp(&b.hash)
+            .then(a.repeat.cmp(&b.repeat))
+            .then(a.path.cmp(&b.path))
+    });
+    Ok(entries)
+}
+
+/// Who is running the experiment, if anyone.
+pub fn experiment_lock_owner(experiment_dir: &Path) -> Option<LockOwner> {
+    holder(&experiment_dir.join(EXPERIMENT_LOCK_FILE))
+        .ok()
+        .flatten()
+}
+
+/// Whether the lock file at `path` may be held by a live process, only false if it is
+/// verifiably not held.
+fn lock_held(path: &Path) -> bool {
+    !matches!(holder(path), Ok(None))
+}
+
+/// Remove failed configuration runs and running ones that were interrupted, so they are run
+/// again.
+///
+/// Returns the removed paths, or those that would be removed if `dry_run` is set.
+pub fn clean(experiment_dir: &Path, dry_run: bool) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for entry in list_configurations(experiment_dir)? {
+        match entry.state {
+            ConfigurationState::Completed => continue,
+            ConfigurationState::Failed => {}
+            ConfigurationState::Running => {
+                let lock_path = experiment_dir.join(format!(
+                    "{}.{}",
+                    run_name(&entry.hash, entry.repeat),
+                    LOCK_EXTENSION
+                ));
+                if lock_held(&lock_path) {
+                    debug!(path = ?entry.path, "Configuration is still running, keeping");
+                    continue;
+                }
+            }
+        }
+        if !dry_run {
+            remove_dir_all(&entry.path)?;
+        }
+        removed.push(entry.path);
+    }
+    info!(
+        removed = removed.len(),
+        dry_run, "Cleaned results directory"
+    );
+    Ok(removed)
+}
+
+/// Remove leftovers that are no longer of use: stale lock files, unfinished imports and links to
+/// configurations that have been removed from the store.
+///
+/// Returns the removed paths, or those that would be removed if `dry_run` is set.
+pub fn gc(experiment_dir: &Path, dry_run: bool) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for entry in read_dir(experiment_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let garbage = if file_type.is_symlink() {
+            // follows the link, so is false if the target is gone
+            !path.exists()
+        } else if file_type.is_dir() {
+            entry.file_name().to_string_lossy().starts_with(".import-")
+        } else if path.extension() == Some(OsStr::new(LOCK_EXTENSION)) {
+            // removed while locked, so a lock taken in the meantime isn't
+            if dry_run {
+                !lock_held(&path)
+            } else {
+                remove_stale(&path)?
+            }
+        } else {
+            false
+        };
+        if !garbage {
+            continue;
+        }
+        if !dry_run {
+            if file_type.is_dir() {
+                remove_dir_all(&path)?;
+            } else if file_type.is_symlink() {
+                remove_file(&path)?;
+            }
+        }
+        removed.push(path);
+    }
+    removed.sort();
+    info!(removed = removed.len(), dry_run, "Collected garbage");
+    Ok(removed)
+}
+
+/// Fields that differ between two configurations, keyed by their dotted path.
+///
+/// A field missing from one of the configurations is `None` on that side.
+pub fn diff_configurations(
+    a: &Value,
+    b: &Value,
+) -> BTreeMap<String, (Option<Value>, Option<Value>)> {
+    let mut a_fields = BTreeMap::new();
+    let mut b_fields = BTreeMap::new();
+    flatten(a, String::new(), &mut a_fields);
+    flatten(b, String::new(), &mut b_fields);
+    let mut diff = BTreeMap::new();
+    for (path, a_value) in &a_fields {
+        let b_value = b_fields.get(path);
+        if b_value != Some(a_value) {
+            diff.insert(path.clone(), (Some(a_value.clone()), b_value.cloned()));
+        }
+    }
+    for (path, b_value) in b_fields {
+        if !a_fields.contains_key(&path) {
+            diff.insert(path, (None, Some(b_value)));
+        }
+    }
+    diff
+}
+
+pub(crate) fn flatten(value: &Value, path: String, fields: &mut BTreeMap<String, Value>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                flatten(value, join(key), fields);
+            }
+        }
+        Value::Array(values) if !values.is_empty() => {
+            for (i, value) in values.iter().enumerate() {
+                flatten(value, join(&i.to_string()), fields);
+            }
+        }
+        value => {
+            fields.insert(path, value.clone());
+        }
+    }
+}
@@ -1,16 +1,55 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashSet,
     error::Error,
-    fs::{create_dir_all, rename, File},
+    fs::{create_dir_all, remove_dir_all, rename, File},
+    future::Future,
     io,
+    net::SocketAddr,
     path::{Path, PathBuf},
+    process::ExitStatus,
+    time::{Duration, Instant},
 };
 
+use futures::stream::{FuturesUnordered, StreamExt};
+#[cfg(target_os = "linux")]
 use procfs::{kernel_config, ConfigSetting, CpuInfo, Meminfo};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{debug, info};
+use tokio::process::Command;
+use tracing::{debug, field, field::display, info, info_span, warn, Instrument, Span};
 
+use crate::artifacts::{ArtifactQuota, DirSize, QuotaAction};
+use crate::build::BuildMetadata;
+use crate::compression::{compress_dir, CompressionConfig};
+use crate::config_file::{self, ConfigFileError};
+use crate::docker_runner::{
+    create_metrics_dir, finish_abandoned, prepull_images, read_container_exits, write_image_pull,
+    ImagePull,
+};
+use crate::events::{self, RunEvent};
+use crate::framework_log::LOG_DIR_FIELD;
+use crate::hooks::RunHooks;
+use crate::host::HostDetails;
+use crate::idle::IdleWait;
+use crate::live;
+use crate::lock::{Lock, LockOwner, LOCK_EXTENSION};
+use crate::measurements::Measurements;
+use crate::metrics;
+use crate::migrate::write_schema_version;
+use crate::monitor::{MonitorError, ProcessMonitor};
+use crate::notify::{Notifications, NotifyConfig};
+use crate::power::{self, PowerMonitor};
+use crate::preflight::preflight;
+use crate::progress::{ProgressReporter, ProgressTracker};
+use crate::provenance::collect_provenance;
+use crate::results::run_name;
+use crate::schedule::order_configurations;
+use crate::seed::Seed;
+use crate::stopping::EarlyStopping;
+use crate::store::{add_to_store, link_from_store};
+#[cfg(feature = "tui")]
+use crate::tui::{self, ConfigurationStatus};
+use crate::tuning::{HostTuning, TunedHost, TuningSetting};
 use crate::ExpResult;
 use crate::Experiment;
 use crate::ExperimentConfiguration;
@@ -21,102 +60,1180 @@ pub enum RunError {
     IoError(#[from] std::io::Error),
     #[error(transparent)]
     SerdeError(#[from] serde_json::Error),
+    #[error("environment differs from the previous run: {0}")]
+    EnvironmentMismatch(EnvDiff),
+    #[error("preflight checks failed: {}", .0.join("; "))]
+    Preflight(Vec<String>),
+    #[error("configurations depend on each other in a cycle: {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+    #[error("{path:?} is locked by {owner}")]
+    Locked { path: PathBuf, owner: LockOwner },
+    #[error("run timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("run wrote {bytes} bytes, over the artifact quota of {max_bytes}")]
+    QuotaExceeded { bytes: u64, max_bytes: u64 },
+    #[error("failed to pull image {0}: {1}")]
+    Pull(String, bollard::errors::Error),
+    #[error("containers ran out of memory: {}", .0.join(", "))]
+    OomKilled(Vec<String>),
+    #[error("{0} hook failed: {1}")]
+    Hook(&'static str, Box<dyn Error + Send + Sync>),
+    #[error("failed to tune host setting {0:?}: {1}")]
+    Tuning(PathBuf, io::Error),
     #[error(transparent)]
     Other(#[from] Box<dyn Error + Send + Sync>),
 }
 
+/// Name of the lock file held in the experiment directory while running.
+pub(crate) const EXPERIMENT_LOCK_FILE: &str = "exp.lock";
+
 pub struct RunConfig {
     pub results_dir: PathBuf,
+    /// How many times to run each configuration, each repeat in its own directory.
+    ///
+    /// The first repeat is in the configuration's hash directory as usual and later ones in
+    /// `<hash>-<repeat>`, see `results::run_name`.
+    pub repeats: usize,
+    /// Most configuration runs to have running at once, each with its own instance of the
+    /// experiment from `Experiment::replica`.
+    ///
+    /// The dependencies of a configuration finish before it starts, and `cooldown` and
+    /// `idle_wait` apply before starting each run.
+    pub max_parallel: usize,
+    /// Fail a configuration run if `Experiment::run` takes longer than this.
+    pub timeout: Option<Duration>,
+    /// Skip the remaining repeats of a configuration once a metric has been measured precisely
+    /// enough, treating `repeats` as the most to run.
+    pub early_stopping: Option<EarlyStopping>,
+    /// Seed the seeds of each configuration run are derived from, see `Seed`.
+    pub base_seed: u64,
+    /// Stop starting configurations once the run has taken this long, letting the one running
+    /// finish. Those left are recorded as skipped in `events.jsonl` and run by the next run.
+    pub max_duration: Option<Duration>,
+    /// Sleep this long after each configuration run, letting the machine return to idle
+    /// before the next.
+    pub cooldown: Duration,
+    /// Wait for the host's load or temperature to drop before starting each configuration.
+    pub idle_wait: Option<IdleWait>,
+    /// Tune the host, such as pinning the CPU frequency, while running configurations.
+    pub tuning: Option<HostTuning>,
+    /// Limit how much each configuration run may write to its directory. The size written is
+    /// recorded in `artifacts.json` either way.
+    pub artifact_quota: Option<ArtifactQuota>,
+    /// Measure the power used by the host while each configuration runs, into
+    /// `metrics/power.csv` with the energy used in `energy.json`.
+    pub power: Option<PowerMonitor>,
+    /// Compress large files in each configuration directory once it has finished running.
+    pub compression: Option<CompressionConfig>,
+    /// Directory of completed configuration runs shared between experiments, keyed by
+    /// configuration hash.
+    ///
+    /// Configurations with a completed run in the store are linked to it instead of being run
+    /// again, and newly completed configurations are moved into the store.
+    pub store_dir: Option<PathBuf>,
+    /// Run configurations missing from the experiment directory even if the store has a
+    /// completed run for them, replacing it.
+    pub force_rerun: bool,
+    /// Repositories, other than the experiment's own, to record the git state of in
+    /// `provenance.json`.
+    pub provenance_repos: Vec<PathBuf>,
+    /// How the experiment was built, usually from `exp::build_metadata!()`, recorded in
+    /// `environment.json`.
+    pub build_metadata: Option<BuildMetadata>,
+    /// Fail instead of warning when the environment differs from a previous run in the same
+    /// results directory.
+    pub strict_environment: bool,
+    /// Receives progress events as configurations are run, such as a `TerminalProgress` bar.
+    pub progress: Option<Box<dyn ProgressReporter>>,
+    /// Where to send notifications about the run, such as when it completes.
+    pub notify: Option<NotifyConfig>,
+    /// Serve Prometheus metrics about the run over HTTP on this address.
+    pub metrics_addr: Option<SocketAddr>,
+    /// Show a live dashboard of the run on the terminal, needs the `tui` feature.
+    pub tui: bool,
+    /// Write the framework's own logs from running each configuration to `framework.log` in
+    /// its directory, needs the `FrameworkLog` layer in the tracing subscriber.
+    pub framework_log: bool,
+    /// Append the `Experiment::quick_look` of each completed run to `live-results.csv` in the
+    /// results directory.
+    pub live_results: bool,
+    /// Fail configuration runs in which a container of a `docker_runner::Runner` ran out of
+    /// memory, as their results are likely incomplete.
+    pub fail_on_oom: bool,
+    /// Convert the container stats, container processes and process monitor measurements of
+    /// each configuration run to Parquet once it finishes, see `data::convert_to_parquet`. Needs
+    /// the `polars` feature.
+    pub parquet_metrics: bool,
+    /// Pull the images given by `Experiment::images` once before running any configurations,
+    /// rather than when each run adds its containers, so pulls don't count towards the first
+    /// repeat's timings.
+    pub prepull: bool,
+    /// Called around the phases of the run, in order.
+    pub hooks: Vec<Box<dyn RunHooks>>,
+}
+
+impl RunConfig {
+    pub fn builder() -> RunConfigBuilder {
+        RunConfigBuilder::default()
+    }
+
+    /// Load the settings from a TOML or YAML file, see `RunConfigBuilder::from_file`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        Ok(RunConfigBuilder::from_file(path)?.build()?)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RunConfigError {
+    #[error("no results directory given")]
+    MissingResultsDir,
+    #[error("repeats must be at least 1")]
+    NoRepeats,
+    #[error("max_parallel must be at least 1")]
+    NoParallelism,
+    #[error("timeout must be more than zero")]
+    ZeroTimeout,
+    #[error("max_duration must be more than zero")]
+    ZeroMaxDuration,
+    #[error("early stopping tolerance must be more than zero")]
+    NonPositiveTolerance,
+    #[error("force_rerun needs a store_dir to rerun configurations from")]
+    ForceRerunWithoutStore,
+}
+
+/// Builds a `RunConfig`, checking the settings make sense together.
+///
+/// Settings that aren't code, everything but `progress`, `notify`, `build_metadata` and
+/// `hooks`, can also be deserialized, such as from a settings file, with `timeout` in seconds.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfigBuilder {
+    results_dir: Option<PathBuf>,
+    repeats: Option<usize>,
+    max_parallel: Option<usize>,
+    #[serde(with = "seconds")]
+    timeout: Option<Duration>,
+    early_stopping: Option<EarlyStopping>,
+    base_seed: u64,
+    #[serde(with = "seconds")]
+    max_duration: Option<Duration>,
+    #[serde(with = "seconds")]
+    cooldown: Option<Duration>,
+    idle_wait: Option<IdleWait>,
+    tuning: Option<HostTuning>,
+    artifact_quota: Option<ArtifactQuota>,
+    power: Option<PowerMonitor>,
+    compression: Option<CompressionConfig>,
+    store_dir: Option<PathBuf>,
+    force_rerun: bool,
+    provenance_repos: Vec<PathBuf>,
+    #[serde(skip)]
+    build_metadata: Option<BuildMetadata>,
+    strict_environment: bool,
+    #[serde(skip)]
+    progress: Option<Box<dyn ProgressReporter>>,
+    #[serde(skip)]
+    notify: Option<NotifyConfig>,
+    metrics_addr: Option<SocketAddr>,
+    tui: bool,
+    framework_log: bool,
+    live_results: bool,
+    fail_on_oom: bool,
+    parquet_metrics: bool,
+    prepull: bool,
+    #[serde(skip)]
+    hooks: Vec<Box<dyn RunHooks>>,
+}
+
+impl RunConfigBuilder {
+    /// Start from the settings in a TOML or YAML file, chosen by its extension, so they can be
+    /// changed without rebuilding the experiment.
+    ///
+    /// Environment variables are interpolated into the file first, `${VAR}` or
+    /// `${VAR:-default}`, with `$$` for a literal `$`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        config_file::read(path.as_ref())
+    }
+
+    pub fn results_dir(mut self, results_dir: impl Into<PathBuf>) -> Self {
+        self.results_dir = Some(results_dir.into());
+        self
+    }
+
+    pub fn repeats(mut self, repeats: usize) -> Self {
+        self.repeats = Some(repeats);
+        self
+    }
+
+    pub fn max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = Some(max_parallel);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn base_seed(mut self, base_seed: u64) -> Self {
+        self.base_seed = base_seed;
+        self
+    }
+
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn early_stopping(mut self, early_stopping: EarlyStopping) -> Self {
+        self.early_stopping = Some(early_stopping);
+        self
+    }
+
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    pub fn idle_wait(mut self, idle_wait: IdleWait) -> Self {
+        self.idle_wait = Some(idle_wait);
+        self
+    }
+
+    pub fn artifact_quota(mut self, artifact_quota: ArtifactQuota) -> Self {
+        self.artifact_quota = Some(artifact_quota);
+        self
+    }
+
+    pub fn power(mut self, power: PowerMonitor) -> Self {
+        self.power = Some(power);
+        self
+    }
+
+    pub fn tuning(mut self, tuning: HostTuning) -> Self {
+        self.tuning = Some(tuning);
+        self
+    }
+
+    pub fn compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn store_dir(mut self, store_dir: impl Into<PathBuf>) -> Self {
+        self.store_dir = Some(store_dir.into());
+        self
+    }
+
+    pub fn force_rerun(mut self, force_rerun: bool) -> Self {
+        self.force_rerun = force_rerun;
+        self
+    }
+
+    pub fn provenance_repo(mut self, repo: impl Into<PathBuf>) -> Self {
+        self.provenance_repos.push(repo.into());
+        self
+    }
+
+    pub fn build_metadata(mut self, build_metadata: BuildMetadata) -> Self {
+        self.build_metadata = Some(build_metadata);
+        self
+    }
+
+    pub fn strict_environment(mut self, strict_environment: bool) -> Self {
+        self.strict_environment = strict_environment;
+        self
+    }
+
+    pub fn progress(mut self, progress: impl ProgressReporter + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub fn notify(mut self, notify: NotifyConfig) -> Self {
+        self.notify = Some(notify);
+        self
+    }
+
+    pub fn metrics_addr(mut self, metrics_addr: SocketAddr) -> Self {
+        self.metrics_addr = Some(metrics_addr);
+        self
+    }
+
+    pub fn tui(mut self, tui: bool) -> Self {
+        self.tui = tui;
+        self
+    }
+
+    pub fn framework_log(mut self, framework_log: bool) -> Self {
+        self.framework_log = framework_log;
+        self
+    }
+
+    pub fn live_results(mut self, live_results: bool) -> Self {
+        self.live_results = live_results;
+        self
+    }
+
+    pub fn fail_on_oom(mut self, fail_on_oom: bool) -> Self {
+        self.fail_on_oom = fail_on_oom;
+        self
+    }
+
+    pub fn parquet_metrics(mut self, parquet_metrics: bool) -> Self {
+        self.parquet_metrics = parquet_metrics;
+        self
+    }
+
+    pub fn prepull(mut self, prepull: bool) -> Self {
+        self.prepull = prepull;
+        self
+    }
+
+    /// Add hooks to call around the phases of the run, after any already added.
+    pub fn hook(mut self, hooks: impl RunHooks + 'static) -> Self {
+        self.hooks.push(Box::new(hooks));
+        self
+    }
+
+    pub fn build(self) -> Result<RunConfig, RunConfigError> {
+        let results_dir = self.results_dir.ok_or(RunConfigError::MissingResultsDir)?;
+        let repeats = self.repeats.unwrap_or(1);
+        if repeats == 0 {
+            return Err(RunConfigError::NoRepeats);
+        }
+        let max_parallel = self.max_parallel.unwrap_or(1);
+        if max_parallel == 0 {
+            return Err(RunConfigError::NoParallelism);
+        }
+        if self.timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(RunConfigError::ZeroTimeout);
+        }
+        if self
+            .max_duration
+            .is_some_and(|max_duration| max_duration.is_zero())
+        {
+            return Err(RunConfigError::ZeroMaxDuration);
+        }
+        if self.early_stopping.as_ref().is_some_and(|early_stopping| {
+            early_stopping.tolerance.is_nan() || early_stopping.tolerance <= 0.0
+        }) {
+            return Err(RunConfigError::NonPositiveTolerance);
+        }
+        if self.force_rerun && self.store_dir.is_none() {
+            return Err(RunConfigError::ForceRerunWithoutStore);
+        }
+        Ok(RunConfig {
+            results_dir,
+            repeats,
+            max_parallel,
+            timeout: self.timeout,
+            base_seed: self.base_seed,
+            early_stopping: self.early_stopping,
+            max_duration: self.max_duration,
+            cooldown: self.cooldown.unwrap_or_default(),
+            idle_wait: self.idle_wait,
+            tuning: self.tuning,
+            artifact_quota: self.artifact_quota,
+            power: self.power,
+            compression: self.compression,
+            store_dir: self.store_dir,
+            force_rerun: self.force_rerun,
+            provenance_repos: self.provenance_repos,
+            build_metadata: self.build_metadata,
+            strict_environment: self.strict_environment,
+            progress: self.progress,
+            notify: self.notify,
+            metrics_addr: self.metrics_addr,
+            tui: self.tui,
+            framework_log: self.framework_log,
+            live_results: self.live_results,
+            fail_on_oom: self.fail_on_oom,
+            parquet_metrics: self.parquet_metrics,
+            prepull: self.prepull,
+            hooks: self.hooks,
+        })
+    }
+}
+
+/// Durations as a number of seconds.
+pub(crate) mod seconds {
+    use std::time::Duration;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        duration
+            .map(|duration| duration.as_secs_f64())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|seconds| Duration::try_from_secs_f64(seconds).map_err(D::Error::custom))
+            .transpose()
+    }
 }
 
 pub async fn run<E: Experiment>(experiment: &mut E, config: &RunConfig) -> Result<(), RunError> {
-    let exp_path = create_experiment_dir(&config.results_dir)?;
+    monitored(
+        config,
+        run_experiment(experiment, &config.results_dir, config),
+    )
+    .await
+}
+
+/// Run an experiment in the given directory, rather than `RunConfig::results_dir`.
+pub(crate) async fn run_experiment<E: Experiment>(
+    experiment: &mut E,
+    results_dir: &Path,
+    config: &RunConfig,
+) -> Result<(), RunError> {
+    let exp_path = create_experiment_dir(results_dir)?;
+    let _lock = lock_experiment_dir(&exp_path)?;
     info!(dir=%exp_path.display(), "Running experiment");
 
-    run_single(experiment, &exp_path).await?;
-    Ok(())
+    experiment.metadata().write(&exp_path)?;
+    collect_provenance(&exp_path, &config.provenance_repos);
+    run_single(experiment, &exp_path, config)
+        .instrument(info_span!("experiment", dir = %exp_path.display()))
+        .await
+}
+
+/// Serve metrics and show the dashboard, as configured, while running.
+pub(crate) async fn monitored<T, E: From<io::Error>>(
+    config: &RunConfig,
+    running: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let metrics_server = match config.metrics_addr {
+        Some(addr) => Some(metrics::serve(addr).await?),
+        None => None,
+    };
+    #[cfg(feature = "tui")]
+    let dashboard = if config.tui {
+        Some(crate::tui::Dashboard::start()?)
+    } else {
+        None
+    };
+    #[cfg(not(feature = "tui"))]
+    if config.tui {
+        warn!("Built without the tui feature, not showing the dashboard");
+    }
+    let result = running.await;
+    #[cfg(feature = "tui")]
+    drop(dashboard);
+    if let Some(metrics_server) = metrics_server {
+        metrics_server.abort();
+    }
+    result
 }
 
 async fn run_single<E: Experiment>(
     experiment: &mut E,
     experiment_dir: &Path,
+    run_config: &RunConfig,
 ) -> Result<(), RunError> {
-    collect_environment_data(experiment_dir);
-
     let configurations = experiment.configurations();
+    let scheduled = order_configurations(
+        select_configurations(configurations, experiment_dir, run_config.repeats)?,
+        experiment_dir,
+    )?;
+    let (configurations, schedule): (Vec<_>, Vec<_>) = scheduled
+        .into_iter()
+        .map(|s| (s.configuration, (s.hash, s.repeats, s.dependencies)))
+        .unzip();
+    if !configurations.is_empty() {
+        check_requirements(experiment, experiment_dir, &configurations).await?;
+    }
+    let mut runs_to_do = Vec::new();
+    for (config, (hash, repeats, dependencies)) in configurations.iter().zip(&schedule) {
+        for &repeat in repeats {
+            runs_to_do.push((
+                config,
+                hash.clone(),
+                repeat,
+                run_name(hash, repeat),
+                dependencies,
+            ));
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    tui::set_configurations(
+        runs_to_do
+            .iter()
+            .map(|(_, _, _, name, _)| name.clone())
+            .collect(),
+    );
+    let mut session =
+        Session::start(experiment, experiment_dir, run_config, runs_to_do.len()).await?;
+    if run_config.prepull && !configurations.is_empty() {
+        session.prepull(&experiment.images(&configurations)).await?;
+    }
+    let prepulled = session.prepulled.clone();
+    let mut replicas = Vec::new();
+    while replicas.len() + 1 < run_config.max_parallel {
+        match experiment.replica() {
+            Some(replica) => replicas.push(replica),
+            None => {
+                warn!(
+                    max_parallel = run_config.max_parallel,
+                    "Experiment has no replicas, running configurations one at a time"
+                );
+                break;
+            }
+        }
+    }
+    // instances of the experiment not running a configuration
+    let mut idle = std::iter::once(experiment)
+        .chain(replicas.iter_mut())
+        .collect::<Vec<_>>();
+    let mut running = FuturesUnordered::new();
+    let mut running_hashes = Vec::new();
+    let run_started = Instant::now();
+    for (i, (config, config_hash, repeat, hash, dependencies)) in runs_to_do.iter().enumerate() {
+        // wait for an instance to run it on, and for its dependencies to finish
+        while idle.is_empty()
+            || running_hashes
+                .iter()
+                .any(|running| dependencies.contains(running))
+        {
+            let (instance, config, config_hash, repeat, result): (
+                &mut E,
+                &E::Configuration,
+                &String,
+                usize,
+                Result<_, RunError>,
+            ) = running.next().await.expect("configurations are running");
+            let position = running_hashes
+                .iter()
+                .position(|running| running == config_hash)
+                .expect("running configuration is tracked");
+            running_hashes.swap_remove(position);
+            session.finished(&mut *instance, config, config_hash, repeat, result?);
+            idle.push(instance);
+        }
+        let failed_dependency = dependencies.iter().find(|dependency| {
+            !(0..run_config.repeats)
+                .any(|repeat| experiment_dir.join(run_name(dependency, repeat)).is_dir())
+        });
+        if let Some(dependency) = failed_dependency {
+            warn!(%hash, %dependency, "Dependency failed, skipping configuration");
+            session.skip(hash, format!("dependency {} failed", dependency));
+            continue;
+        }
+        if let Some(early_stopping) = &run_config.early_stopping {
+            if early_stopping.is_satisfied(experiment_dir, config_hash, run_config.repeats) {
+                info!(
+                    %hash,
+                    metric = %early_stopping.metric,
+                    "Metric within tolerance, skipping repeat"
+                );
+                session.skip(hash, "confidence interval within tolerance".to_owned());
+                continue;
+            }
+        }
+        session.wait().await;
+        if let Some(max_duration) = run_config.max_duration {
+            if run_started.elapsed() >= max_duration {
+                let remaining = &runs_to_do[i..];
+                warn!(
+                    ?max_duration,
+                    remaining = remaining.len(),
+                    "Time budget exhausted, not starting the remaining configurations"
+                );
+                for (_, _, _, hash, _) in remaining {
+                    session.skip(hash, "time budget exhausted".to_owned());
+                }
+                break;
+            }
+        }
+        let instance = idle.pop().expect("an instance is idle");
+        let index = session.started(config_hash, *repeat);
+        let (config, repeat) = (*config, *repeat);
+        let span = configuration_span(config_hash, Some(index), repeat);
+        running_hashes.push(config_hash.clone());
+        let prepulled = &prepulled;
+        running.push(async move {
+            let result = run_in_dir(
+                &mut *instance,
+                experiment_dir,
+                config,
+                repeat,
+                run_config,
+                prepulled,
+            )
+            .instrument(span)
+            .await;
+            (instance, config, config_hash, repeat, result)
+        });
+    }
+    while let Some((instance, config, config_hash, repeat, result)) = running.next().await {
+        session.finished(instance, config, config_hash, repeat, result?);
+    }
+    session.finish().await
+}
+
+/// The configuration runs of one run of an experiment, between its `before_all` and `after_all`
+/// hooks, with the host tuned and progress, metrics and notifications kept across them.
+pub(crate) struct Session<'a> {
+    experiment_dir: &'a Path,
+    run_config: &'a RunConfig,
+    /// Restores the host's settings when the session ends.
+    _tuned: Option<TunedHost>,
+    progress: ProgressTracker<'a>,
+    notifications: Notifications<'a>,
+    total: usize,
+    /// Number of configuration runs started or skipped so far.
+    index: usize,
+    ran_previous: bool,
+    /// Images pulled for the session, recorded in each of its configuration runs.
+    prepulled: Vec<ImagePull>,
+}
+
+impl<'a> Session<'a> {
+    /// Tune the host, record its environment and call the `before_all` hooks, for a session of
+    /// up to `total` configuration runs.
+    pub(crate) async fn start<E: Experiment>(
+        experiment: &E,
+        experiment_dir: &'a Path,
+        run_config: &'a RunConfig,
+        total: usize,
+    ) -> Result<Session<'a>, RunError> {
+        let tuned = tune_host(run_config)?;
+        collect_environment_data(experiment_dir, run_config, tuned.as_ref())?;
+        metrics::start_run(total);
+        let mut experiment_name = experiment.metadata().name;
+        if experiment_name.is_empty() {
+            experiment_name = experiment_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        let session = Session {
+            experiment_dir,
+            run_config,
+            _tuned: tuned,
+            progress: ProgressTracker::new(run_config.progress.as_deref(), total),
+            notifications: Notifications::new(run_config.notify.as_ref(), experiment_name, total),
+            total,
+            index: 0,
+            ran_previous: false,
+            prepulled: Vec::new(),
+        };
+        for hooks in &run_config.hooks {
+            hooks
+                .before_all(experiment_dir)
+                .await
+                .map_err(|error| RunError::Hook("before_all", error))?;
+        }
+        Ok(session)
+    }
+
+    /// Pull the images once for the configuration runs of the session, which then don't pull
+    /// them again.
+    pub(crate) async fn prepull(&mut self, images: &[String]) -> Result<(), RunError> {
+        let pulls = prepull_images(images)
+            .await
+            .map_err(|(image, error)| RunError::Pull(image, error))?;
+        self.prepulled.extend(pulls);
+        Ok(())
+    }
 
-    // for each configuration, build the directories they would make
-    // if the directories exist then skip this dir
+    /// Cool down after the last configuration run and wait for the host to be idle, before
+    /// running the next.
+    pub(crate) async fn wait(&self) {
+        let run_config = self.run_config;
+        if self.ran_previous && !run_config.cooldown.is_zero() {
+            debug!(cooldown = ?run_config.cooldown, "Cooling down before next configuration");
+            tokio::time::sleep(run_config.cooldown).await;
+        }
+        if let Some(idle_wait) = &run_config.idle_wait {
+            idle_wait.wait().await;
+        }
+    }
+
+    /// Run a repeat of a configuration, giving the finished run or `None` if it was skipped.
+    pub(crate) async fn run<E: Experiment>(
+        &mut self,
+        experiment: &mut E,
+        config: &E::Configuration,
+        repeat: usize,
+    ) -> Result<Option<FinishedRun>, RunError> {
+        let config_hash = config.hash_serialized()?;
+        let index = self.started(&config_hash, repeat);
+        let result = run_in_dir(
+            experiment,
+            self.experiment_dir,
+            config,
+            repeat,
+            self.run_config,
+            &self.prepulled,
+        )
+        .instrument(configuration_span(&config_hash, Some(index), repeat))
+        .await?;
+        Ok(self.finished(experiment, config, &config_hash, repeat, result))
+    }
+
+    /// Record that a repeat of a configuration has started running, giving its index in the
+    /// session.
+    pub(crate) fn started(&mut self, config_hash: &str, repeat: usize) -> usize {
+        let hash = run_name(config_hash, repeat);
+        let index = self.index;
+        self.index += 1;
+        // distributed workers don't know how many configurations the coordinator has
+        if self.total == 0 {
+            info!(%hash, "Running configuration {}", index + 1);
+        } else {
+            info!(
+                %hash,
+                "Running configuration {}/{}",
+                index + 1,
+                self.total,
+            );
+        }
+        self.progress.started(&hash);
+        metrics::configuration_started(&hash);
+        #[cfg(feature = "tui")]
+        tui::configuration_status(&hash, ConfigurationStatus::Running);
+        index
+    }
+
+    /// Record the result of a repeat of a configuration `run_in_dir` ran, giving it back.
+    pub(crate) fn finished<E: Experiment>(
+        &mut self,
+        experiment: &mut E,
+        config: &E::Configuration,
+        config_hash: &str,
+        repeat: usize,
+        result: Option<FinishedRun>,
+    ) -> Option<FinishedRun> {
+        let run_config = self.run_config;
+        let hash = run_name(config_hash, repeat);
+        // skipped runs and runs linked from the store don't need cooling down after
+        if result.as_ref().is_some_and(|run| !run.linked) {
+            self.ran_previous = true;
+        }
+        let success = result.as_ref().map(|run| run.success);
+        self.progress.finished(&hash, success);
+        metrics::configuration_finished(success.unwrap_or(true));
+        #[cfg(feature = "tui")]
+        tui::configuration_status(
+            &hash,
+            match success {
+                Some(true) => ConfigurationStatus::Finished,
+                Some(false) => ConfigurationStatus::Failed,
+                None => ConfigurationStatus::Skipped,
+            },
+        );
+        if let Some(success) = success {
+            self.notifications.finished(&hash, success);
+        }
+        if run_config.live_results && success == Some(true) {
+            match experiment.quick_look(config, &self.experiment_dir.join(&hash)) {
+                Ok(scalars) => live::append(self.experiment_dir, &hash, repeat, scalars),
+                Err(error) => warn!(%error, %hash, "Failed to take a quick look at run"),
+            }
+        }
+        result
+    }
+
+    /// Skip a configuration run without running it.
+    pub(crate) fn skip(&mut self, hash: &str, reason: String) {
+        events::record(
+            self.experiment_dir,
+            RunEvent::ConfigurationSkipped {
+                hash: hash.to_owned(),
+                reason,
+            },
+        );
+        self.index += 1;
+        self.progress.finished(hash, None);
+        #[cfg(feature = "tui")]
+        tui::configuration_status(hash, ConfigurationStatus::Skipped);
+    }
+
+    /// Call the `after_all` hooks and notify that the run completed.
+    pub(crate) async fn finish(self) -> Result<(), RunError> {
+        for hooks in &self.run_config.hooks {
+            hooks
+                .after_all(self.experiment_dir)
+                .await
+                .map_err(|error| RunError::Hook("after_all", error))?;
+        }
+        self.notifications.completed();
+        Ok(())
+    }
+}
+
+/// Check the experiment's requirements for running the configurations are met by the host.
+pub(crate) async fn check_requirements<E: Experiment>(
+    experiment: &E,
+    experiment_dir: &Path,
+    configurations: &[E::Configuration],
+) -> Result<(), RunError> {
+    let failures = preflight(experiment_dir, &experiment.requirements(configurations)).await;
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(RunError::Preflight(failures))
+    }
+}
+
+/// Filter out duplicate configurations and repeats that have already been run, giving the
+/// repeats left to run of each configuration.
+pub(crate) fn select_configurations<C: ExperimentConfiguration>(
+    configurations: Vec<C>,
+    experiment_dir: &Path,
+    repeats: usize,
+) -> Result<Vec<(C, Vec<usize>)>, RunError> {
+    // for each repeat of a configuration, build the directory it would make
+    // if the directory exists then skip that repeat
     let mut seen_configuration_hashes = HashSet::new();
     let mut configurations_to_run = Vec::new();
     let mut duplicate_configurations = 0;
-    let mut skipped_configurations = 0;
+    let mut skipped_runs = 0;
+    let mut remaining_runs = 0;
     for configuration in configurations {
         let config_hash = configuration.hash_serialized()?;
-        if !seen_configuration_hashes.insert(config_hash) {
+        if !seen_configuration_hashes.insert(config_hash.clone()) {
             duplicate_configurations += 1;
             continue;
         }
-        let config_path = build_config_dir(experiment_dir, &configuration)?;
-        if config_path.exists() {
-            debug!(?config_path, "Config directory exists, skipping config");
-            skipped_configurations += 1;
-            continue;
+        let mut repeats_to_run = Vec::new();
+        for repeat in 0..repeats {
+            let name = run_name(&config_hash, repeat);
+            let config_path = experiment_dir.join(&name);
+            if config_path.exists() {
+                debug!(?config_path, "Config directory exists, skipping run");
+                skipped_runs += 1;
+                events::record(
+                    experiment_dir,
+                    RunEvent::ConfigurationSkipped {
+                        hash: name,
+                        reason: "already completed".to_owned(),
+                    },
+                );
+                continue;
+            }
+            events::record(
+                experiment_dir,
+                RunEvent::ConfigurationSelected { hash: name },
+            );
+            repeats_to_run.push(repeat);
+        }
+        if !repeats_to_run.is_empty() {
+            remaining_runs += repeats_to_run.len();
+            configurations_to_run.push((configuration, repeats_to_run));
         }
-        configurations_to_run.push(configuration);
     }
 
     info!(
-        skipped = skipped_configurations,
+        skipped = skipped_runs,
         duplicates = duplicate_configurations,
-        remaining = configurations_to_run.len(),
+        remaining = remaining_runs,
         "Finished skipping pre-completed configurations, running remaining"
     );
+    Ok(configurations_to_run)
+}
 
-    for (i, config) in configurations_to_run.iter().enumerate() {
-        let config_dir = build_config_dir(experiment_dir, config)?;
-        // set up dir for running in, in case of a failure
-        let mut running_dir = config_dir.clone();
-        running_dir.set_extension("running");
+/// Span around running a configuration, `index` being its position in the run if known.
+pub(crate) fn configuration_span(hash: &str, index: Option<usize>, repeat: usize) -> Span {
+    info_span!(
+        "configuration",
+        %hash,
+        index,
+        repeat,
+        log_dir = field::Empty,
+    )
+}
 
-        debug!(path = ?running_dir, "Creating running dir");
-        create_dir_all(&running_dir)?;
+/// Take the lock on an experiment directory, failing if another run holds it.
+pub(crate) fn lock_experiment_dir(experiment_dir: &Path) -> Result<Lock, RunError> {
+    let path = experiment_dir.join(EXPERIMENT_LOCK_FILE);
+    Lock::acquire(path.clone())?.map_err(|owner| RunError::Locked { path, owner })
+}
 
-        info!(
-            hash = %config.hash_serialized().unwrap(),
-            "Running configuration {}/{}",
-            i + 1,
-            configurations_to_run.len(),
-        );
-        match run_configuration(&running_dir, experiment, config).await {
-            Ok(()) => {
-                // successfully run this experiment, move it to a finished dir
-                rename(running_dir, config_dir)?;
+/// A repeat of a configuration that `run_in_dir` finished.
+pub(crate) struct FinishedRun {
+    /// Its final directory, or its `.failed` directory if it failed.
+    pub(crate) dir: PathBuf,
+    pub(crate) success: bool,
+    /// Whether it was linked from the store rather than run.
+    pub(crate) linked: bool,
+}
+
+/// Run a single repeat of a configuration in a `.running` directory, moving it to its final
+/// directory on success or a `.failed` directory on failure.
+///
+/// Returns the finished run, or `None` if the run was skipped because another run has it locked
+/// or has already completed it.
+pub(crate) async fn run_in_dir<E: Experiment>(
+    experiment: &mut E,
+    experiment_dir: &Path,
+    config: &E::Configuration,
+    repeat: usize,
+    run_config: &RunConfig,
+    prepulled: &[ImagePull],
+) -> Result<Option<FinishedRun>, RunError> {
+    let config_hash = config.hash_serialized()?;
+    let hash = run_name(&config_hash, repeat);
+    let config_dir = experiment_dir.join(&hash);
+    let skipped = |reason: &str| {
+        events::record(
+            experiment_dir,
+            RunEvent::ConfigurationSkipped {
+                hash: hash.clone(),
+                reason: reason.to_owned(),
+            },
+        )
+    };
+    let mut lock_path = config_dir.clone();
+    lock_path.set_extension(LOCK_EXTENSION);
+    let _lock = match Lock::acquire(lock_path)? {
+        Ok(lock) => lock,
+        Err(owner) => {
+            warn!(?config_dir, %owner, "Configuration is locked by another run, skipping");
+            skipped(&format!("locked by {}", owner));
+            return Ok(None);
+        }
+    };
+    if config_dir.exists() {
+        debug!(?config_dir, "Config directory exists, skipping config");
+        skipped("already completed");
+        return Ok(None);
+    }
+    if let Some(store_dir) = &run_config.store_dir {
+        if !run_config.force_rerun && link_from_store(store_dir, &config_dir)? {
+            info!(?config_dir, "Linked completed configuration from store");
+            events::record(
+                experiment_dir,
+                RunEvent::ConfigurationLinked { hash: hash.clone() },
+            );
+            return Ok(Some(FinishedRun {
+                dir: config_dir,
+                success: true,
+                linked: true,
+            }));
+        }
+    }
+    // set up dir for running in, in case of a failure
+    let mut running_dir = config_dir.clone();
+    running_dir.set_extension("running");
+
+    debug!(path = ?running_dir, "Creating running dir");
+    create_dir_all(&running_dir)?;
+    if run_config.framework_log {
+        Span::current().record(LOG_DIR_FIELD, display(running_dir.display()));
+    }
+    Seed::derive(run_config.base_seed, &config_hash, repeat).write(&running_dir)?;
+    for pull in prepulled {
+        write_image_pull(&running_dir, pull)?;
+    }
+
+    events::record(
+        experiment_dir,
+        RunEvent::ConfigurationStarted { hash: hash.clone() },
+    );
+    let mut result = run_hooked(&running_dir, &hash, experiment, config, run_config).await;
+    match DirSize::of(&running_dir) {
+        Ok(size) => {
+            size.write(&running_dir)?;
+            let quota = run_config.artifact_quota.as_ref();
+            if let Some(quota) = quota.filter(|quota| size.bytes > quota.max_bytes) {
+                warn!(bytes = size.bytes, quota.max_bytes, %hash, "Run is over its artifact quota");
+                if quota.action == QuotaAction::Fail && result.is_ok() {
+                    result = Err(RunError::QuotaExceeded {
+                        bytes: size.bytes,
+                        max_bytes: quota.max_bytes,
+                    }
+                    .into());
+                }
             }
-            Err(_) => {
-                // unsuccessfully run this experiment, move it to an error dir
-                let mut error_dir = config_dir.clone();
-                error_dir.set_extension("failed");
-                rename(running_dir, error_dir)?;
+        }
+        Err(error) => warn!(%error, %hash, "Failed to measure size of configuration dir"),
+    }
+    if run_config.parquet_metrics {
+        #[cfg(feature = "polars")]
+        if let Err(error) = crate::data::convert_to_parquet(&running_dir) {
+            warn!(%error, %hash, "Failed to convert metrics to parquet");
+        }
+        #[cfg(not(feature = "polars"))]
+        warn!("Built without the polars feature, not converting metrics to parquet");
+    }
+    if let Some(compression) = &run_config.compression {
+        compress_dir(&running_dir, compression)?;
+    }
+    match result {
+        Ok(()) => {
+            // successfully run this experiment, move it to a finished dir
+            rename(running_dir, &config_dir)?;
+            events::record(experiment_dir, RunEvent::ConfigurationFinished { hash });
+            if let Some(store_dir) = &run_config.store_dir {
+                add_to_store(store_dir, &config_dir)?;
+            }
+            Ok(Some(FinishedRun {
+                dir: config_dir,
+                success: true,
+                linked: false,
+            }))
+        }
+        Err(error) => {
+            warn!(%error, %hash, "Configuration failed");
+            events::record(
+                experiment_dir,
+                RunEvent::ConfigurationFailed {
+                    hash,
+                    error: error.to_string(),
+                },
+            );
+            // unsuccessfully run this experiment, move it to an error dir
+            let mut error_dir = config_dir.clone();
+            error_dir.set_extension("failed");
+            if error_dir.exists() {
+                // replace the failure from a previous attempt
+                remove_dir_all(&error_dir)?;
             }
+            rename(running_dir, &error_dir)?;
+            Ok(Some(FinishedRun {
+                dir: error_dir,
+                success: false,
+                linked: false,
+            }))
         }
     }
-    Ok(())
+}
+
+/// Run a configuration between the `before_config` and `after_config` hooks, calling the
+/// `on_failure` hooks if any of them fail.
+async fn run_hooked<E: Experiment>(
+    dir: &Path,
+    hash: &str,
+    experiment: &mut E,
+    config: &E::Configuration,
+    run_config: &RunConfig,
+) -> ExpResult<()> {
+    let mut result = Ok(());
+    for hooks in &run_config.hooks {
+        result = hooks.before_config(hash, dir).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        result = run_configuration(dir, experiment, config, run_config).await;
+    }
+    for hooks in &run_config.hooks {
+        let after = hooks.after_config(hash, dir, result.is_ok()).await;
+        if result.is_ok() {
+            result = after;
+        }
+    }
+    if let Err(error) = &result {
+        for hooks in &run_config.hooks {
+            if let Err(hook_error) = hooks.on_failure(hash, dir, error.as_ref()).await {
+                warn!(error = %hook_error, %hash, "Failure hook failed");
+            }
+        }
+    }
+    result
 }
 
 async fn run_configuration<E: Experiment>(
     dir: &Path,
     experiment: &mut E,
     config: &E::Configuration,
+    run_config: &RunConfig,
 ) -> ExpResult<()> {
     let mut config_file = File::create(dir.join("configuration.json"))?;
     config.ser_pretty(&mut config_file)?;
-    experiment.pre_run(config).await?;
-    experiment.run(config, dir).await?;
-    experiment.post_run(config).await?;
+    write_schema_version(dir, config.schema_version())?;
+    experiment
+        .pre_run(config)
+        .instrument(info_span!("pre_run"))
+        .await?;
+    let measurements = Measurements::default();
+    let (power_end_tx, power_end_rx) = tokio::sync::watch::channel(());
+    let power_monitor = match &run_config.power {
+        Some(power) => Some(power.spawn(dir, power_end_rx)?),
+        None => None,
+    };
+    let running = experiment
+        .run(config, dir, &measurements)
+        .instrument(info_span!("run"));
+    let quota = run_config
+        .artifact_quota
+        .as_ref()
+        .filter(|quota| quota.action == QuotaAction::Fail);
+    let running = async {
+        match quota {
+            Some(quota) => tokio::select! {
+                result = running => result,
+                size = quota.exceeded(dir) => Err(RunError::QuotaExceeded {
+                    bytes: size.bytes,
+                    max_bytes: quota.max_bytes,
+                }
+                .into()),
+            },
+            None => running.await,
+        }
+    };
+    let result = match run_config.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, running)
+            .await
+            .unwrap_or_else(|_| Err(RunError::Timeout(timeout).into())),
+        None => running.await,
+    };
+    // a run cut short drops its runners without finishing them
+    finish_abandoned(dir).await;
+    if let Some(power_monitor) = power_monitor {
+        let _ = power_end_tx.send(());
+        match power_monitor.await {
+            Ok(energy) => power::write_energy(dir, &energy)?,
+            Err(error) => warn!(%error, "Power monitor task failed"),
+        }
+    }
+    measurements.write(dir)?;
+    result?;
+    if run_config.fail_on_oom {
+        let oom_killed = read_container_exits(dir)?
+            .into_iter()
+            .filter(|exit| exit.oom_killed)
+            .map(|exit| exit.name)
+            .collect::<Vec<_>>();
+        if !oom_killed.is_empty() {
+            return Err(RunError::OomKilled(oom_killed).into());
+        }
+    }
+    experiment
+        .post_run(config)
+        .instrument(info_span!("post_run"))
+        .await?;
     Ok(())
 }
 
+/// Spawn a local command and monitor it and its descendants until it exits.
+///
+/// Measurements are written to `metrics/process-<program>.csv` in the configuration directory,
+/// for experiments that run processes directly rather than in docker.
+pub async fn run_monitored(
+    command: Command,
+    configuration_dir: &Path,
+    interval: Duration,
+) -> io::Result<ExitStatus> {
+    let metrics_dir = create_metrics_dir(configuration_dir)?;
+    match ProcessMonitor::spawn_command(command, &metrics_dir, interval).await {
+        Ok((status, _)) => Ok(status),
+        Err(MonitorError::Io(error)) => Err(error),
+        Err(error) => Err(io::Error::other(error)),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Environment {
     hostname: String,
@@ -127,42 +1244,224 @@ pub struct Environment {
     cpu_model_name: String,
     cpu_vendor_id: String,
     cpu_cores: usize,
-    mem_info: Meminfo,
-    kernel_config: HashMap<String, ConfigSetting>,
-}
-
-fn collect_environment_data(path: &Path) {
-    let utsname = nix::sys::utsname::uname().unwrap();
-    let cpuinfo = CpuInfo::new().unwrap();
-    let meminfo = Meminfo::new().unwrap();
-    let env = Environment {
-        hostname: utsname.nodename().to_string_lossy().to_string(),
-        os: utsname.sysname().to_string_lossy().to_string(),
-        release: utsname.release().to_string_lossy().to_string(),
-        version: utsname.version().to_string_lossy().to_string(),
-        architecture: utsname.machine().to_string_lossy().to_string(),
-        cpu_model_name: cpuinfo.model_name(0).unwrap().to_owned(),
-        cpu_vendor_id: cpuinfo.vendor_id(0).unwrap().to_owned(),
-        cpu_cores: cpuinfo.num_cores(),
-        mem_info: meminfo,
-        kernel_config: kernel_config().unwrap_or_default(),
-    };
-    let env_file = File::create(path.join("environment.json")).unwrap();
-    serde_json::to_writer_pretty(env_file, &env).unwrap();
+    #[serde(default)]
+    mem_total_bytes: u64,
+    #[cfg(target_os = "linux")]
+    #[serde(default)]
+    mem_info: Option<Meminfo>,
+    #[cfg(target_os = "linux")]
+    #[serde(default)]
+    kernel_config: std::collections::HashMap<String, ConfigSetting>,
+    #[serde(default)]
+    host: HostDetails,
+    #[serde(default)]
+    build: Option<BuildMetadata>,
+    /// Host settings changed for the run.
+    #[serde(default)]
+    tuning: Vec<TuningSetting>,
+}
+
+impl Environment {
+    #[cfg(target_os = "linux")]
+    fn collect(build: Option<&BuildMetadata>) -> Self {
+        let utsname = nix::sys::utsname::uname().unwrap();
+        let cpuinfo = CpuInfo::new().ok();
+        let meminfo = Meminfo::new().ok();
+        Environment {
+            hostname: utsname.nodename().to_string_lossy().to_string(),
+            os: utsname.sysname().to_string_lossy().to_string(),
+            release: utsname.release().to_string_lossy().to_string(),
+            version: utsname.version().to_string_lossy().to_string(),
+            architecture: utsname.machine().to_string_lossy().to_string(),
+            cpu_model_name: cpuinfo
+                .as_ref()
+                .and_then(|cpuinfo| cpuinfo.model_name(0))
+                .unwrap_or_default()
+                .to_owned(),
+            cpu_vendor_id: cpuinfo
+                .as_ref()
+                .and_then(|cpuinfo| cpuinfo.vendor_id(0))
+                .unwrap_or_default()
+                .to_owned(),
+            cpu_cores: cpuinfo.map_or(0, |cpuinfo| cpuinfo.num_cores()),
+            mem_total_bytes: meminfo.as_ref().map_or(0, |meminfo| meminfo.mem_total),
+            mem_info: meminfo,
+            kernel_config: kernel_config().unwrap_or_default(),
+            host: HostDetails::collect(),
+            build: build.cloned(),
+            tuning: Vec::new(),
+        }
+    }
+
+    /// Collect what is available portably, for platforms without procfs.
+    #[cfg(not(target_os = "linux"))]
+    fn collect(build: Option<&BuildMetadata>) -> Self {
+        use sysinfo::{CpuExt, System, SystemExt};
+        let mut sys = System::new();
+        sys.refresh_cpu();
+        sys.refresh_memory();
+        let cpu = sys.cpus().first();
+        Environment {
+            hostname: sys.host_name().unwrap_or_default(),
+            os: sys.name().unwrap_or_default(),
+            release: sys.kernel_version().unwrap_or_default(),
+            version: sys.os_version().unwrap_or_default(),
+            architecture: std::env::consts::ARCH.to_owned(),
+            cpu_model_name: cpu.map(|cpu| cpu.brand().to_owned()).unwrap_or_default(),
+            cpu_vendor_id: cpu
+                .map(|cpu| cpu.vendor_id().to_owned())
+                .unwrap_or_default(),
+            cpu_cores: sys.physical_core_count().unwrap_or_default(),
+            mem_total_bytes: sys.total_memory(),
+            host: HostDetails::collect(),
+            build: build.cloned(),
+            tuning: Vec::new(),
+        }
+    }
+
+    /// Compare the machine details of two environments, ignoring build metadata.
+    pub fn diff(&self, other: &Environment) -> EnvDiff {
+        let mut changes = Vec::new();
+        let mut compare = |field: &str, old: String, new: String| {
+            if old != new {
+                changes.push((field.to_owned(), old, new));
+            }
+        };
+        compare("hostname", self.hostname.clone(), other.hostname.clone());
+        compare("os", self.os.clone(), other.os.clone());
+        compare("release", self.release.clone(), other.release.clone());
+        compare("version", self.version.clone(), other.version.clone());
+        compare(
+            "architecture",
+            self.architecture.clone(),
+            other.architecture.clone(),
+        );
+        compare(
+            "cpu_model_name",
+            self.cpu_model_name.clone(),
+            other.cpu_model_name.clone(),
+        );
+        compare(
+            "cpu_vendor_id",
+            self.cpu_vendor_id.clone(),
+            other.cpu_vendor_id.clone(),
+        );
+        compare(
+            "cpu_cores",
+            self.cpu_cores.to_string(),
+            other.cpu_cores.to_string(),
+        );
+        // environments recorded before the total memory was recorded portably have it as 0
+        if self.mem_total_bytes != 0 && other.mem_total_bytes != 0 {
+            compare(
+                "mem_total_bytes",
+                self.mem_total_bytes.to_string(),
+                other.mem_total_bytes.to_string(),
+            );
+        }
+        #[cfg(target_os = "linux")]
+        self.diff_kernel_config(other, &mut compare);
+        EnvDiff { changes }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn diff_kernel_config(
+        &self,
+        other: &Environment,
+        compare: &mut impl FnMut(&str, String, String),
+    ) {
+        let kernel_config =
+            |env: &Environment| serde_json::to_value(&env.kernel_config).unwrap_or_default();
+        let (old, new) = (kernel_config(self), kernel_config(other));
+        if old != new {
+            let changed = old
+                .as_object()
+                .into_iter()
+                .chain(new.as_object())
+                .flat_map(|config| config.keys())
+                .filter(|key| old.get(key) != new.get(key))
+                .collect::<HashSet<_>>();
+            compare(
+                "kernel_config",
+                String::new(),
+                format!("{} settings differ", changed.len()),
+            );
+        }
+    }
+}
+
+/// Differences between two environments.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvDiff {
+    /// The field name, old value and new value of each difference.
+    pub changes: Vec<(String, String, String)>,
 }
 
-fn create_experiment_dir(results_dir: &Path) -> Result<PathBuf, io::Error> {
+impl EnvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl std::fmt::Display for EnvDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (field, old, new)) in self.changes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} changed from {:?} to {:?}", field, old, new)?;
+        }
+        Ok(())
+    }
+}
+
+/// Apply the host tuning, if any, until the returned guard is dropped.
+pub(crate) fn tune_host(config: &RunConfig) -> Result<Option<TunedHost>, RunError> {
+    config
+        .tuning
+        .as_ref()
+        .map(|tuning| tuning.apply())
+        .transpose()
+        .map_err(|(path, error)| RunError::Tuning(path, error))
+}
+
+/// Record the environment in `environment.json`, first comparing it against the environment of
+/// any previous run in the same directory.
+///
+/// Differences are warned about, or returned as an error if `RunConfig::strict_environment` is
+/// set.
+pub(crate) fn collect_environment_data(
+    path: &Path,
+    config: &RunConfig,
+    tuned: Option<&TunedHost>,
+) -> Result<(), RunError> {
+    let mut env = Environment::collect(config.build_metadata.as_ref());
+    env.tuning = tuned
+        .map(|tuned| tuned.settings().to_vec())
+        .unwrap_or_default();
+    let env_path = path.join("environment.json");
+    if let Ok(previous) = File::open(&env_path) {
+        match serde_json::from_reader::<_, Environment>(previous) {
+            Ok(previous) => {
+                let diff = previous.diff(&env);
+                if !diff.is_empty() {
+                    if config.strict_environment {
+                        return Err(RunError::EnvironmentMismatch(diff));
+                    }
+                    warn!(%diff, "Environment differs from the previous run");
+                }
+            }
+            Err(error) => warn!(%error, "Failed to read previous environment"),
+        }
+    }
+    let env_file = File::create(env_path)?;
+    serde_json::to_writer_pretty(env_file, &env)?;
+    Ok(())
+}
+
+pub(crate) fn create_experiment_dir(results_dir: &Path) -> Result<PathBuf, io::Error> {
     let exp_path = results_dir.to_owned();
     debug!(path = ?exp_path, "Creating experiments directory");
     create_dir_all(&exp_path)?;
     Ok(exp_path)
 }
-
-fn build_config_dir<C: ExperimentConfiguration>(
-    parent: &Path,
-    configuration: &C,
-) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
-    let config_hash = configuration.hash_serialized()?;
-    let config_path = parent.join(config_hash);
-    Ok(config_path)
-}
@@ -0,0 +1,96 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use tracing::warn;
+
+use crate::{ExperimentConfiguration, RunError};
+
+/// A configuration to run, in the order it should be run.
+pub(crate) struct Scheduled<C> {
+    pub(crate) configuration: C,
+    pub(crate) hash: String,
+    pub(crate) repeats: Vec<usize>,
+    /// Hashes of the configurations being run that this one depends on.
+    pub(crate) dependencies: Vec<String>,
+}
+
+/// Order configurations so each runs after those it depends on and, where free to choose, those
+/// with a higher priority run first, otherwise keeping the order they were given in.
+///
+/// Dependencies on configurations that aren't being run are ignored, warning if they haven't
+/// been run before either.
+pub(crate) fn order_configurations<C: ExperimentConfiguration>(
+    configurations: Vec<(C, Vec<usize>)>,
+    experiment_dir: &Path,
+) -> Result<Vec<Scheduled<C>>, RunError> {
+    let mut scheduled = Vec::new();
+    for (configuration, repeats) in configurations {
+        let hash = configuration.hash_serialized()?;
+        scheduled.push(Scheduled {
+            dependencies: configuration.dependencies(),
+            configuration,
+            hash,
+            repeats,
+        });
+    }
+    let index = scheduled
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.hash.clone(), i))
+        .collect::<HashMap<_, _>>();
+    let mut dependents = vec![Vec::new(); scheduled.len()];
+    let mut waiting_on = vec![0; scheduled.len()];
+    for (i, s) in scheduled.iter_mut().enumerate() {
+        let hash = &s.hash;
+        s.dependencies
+            .retain(|dependency| match index.get(dependency) {
+                Some(&j) => {
+                    dependents[j].push(i);
+                    waiting_on[i] += 1;
+                    true
+                }
+                None => {
+                    if !experiment_dir.join(dependency).is_dir() {
+                        warn!(%hash, %dependency, "Dependency has not been run");
+                    }
+                    false
+                }
+            });
+    }
+
+    // highest priority first, then earliest given
+    let priorities = scheduled
+        .iter()
+        .map(|s| s.configuration.priority())
+        .collect::<Vec<_>>();
+    let mut ready = (0..scheduled.len())
+        .filter(|&i| waiting_on[i] == 0)
+        .map(|i| (Reverse(priorities[i]), i))
+        .collect::<BTreeSet<_>>();
+    let mut order = Vec::new();
+    while let Some((_, i)) = ready.pop_first() {
+        order.push(i);
+        for &dependent in &dependents[i] {
+            waiting_on[dependent] -= 1;
+            if waiting_on[dependent] == 0 {
+                ready.insert((Reverse(priorities[dependent]), dependent));
+            }
+        }
+    }
+    if order.len() < scheduled.len() {
+        let cycle = (0..scheduled.len())
+            .filter(|&i| waiting_on[i] > 0)
+            .map(|i| scheduled[i].hash.clone())
+            .collect();
+        return Err(RunError::DependencyCycle(cycle));
+    }
+
+    let mut scheduled = scheduled.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(order
+        .into_iter()
+        .filter_map(|i| scheduled[i].take())
+        .collect())
+}
@@ -0,0 +1,642 @@
+//! Search the configuration space of an experiment for the best configuration, proposing each
+//! configuration to run from the objectives of those already run rather than running a fixed
+//! list.
+//!
+//! ```no_run
+//! # use exp::search::{search, Goal, Parameter, SearchConfig, SearchExperiment, SearchSpace, Strategy};
+//! # async fn example<E: SearchExperiment + Send>(mut experiment: E, run_config: exp::RunConfig)
+//! # where E::Configuration: Send + Sync {
+//! let space = SearchSpace::new(serde_json::json!({"nodes": 3, "batch_size": 1}))
+//!     .parameter("nodes", Parameter::Choice(vec![1.into(), 3.into(), 5.into()]))
+//!     .parameter("batch_size", Parameter::Int { low: 1, high: 1000 });
+//! let config = SearchConfig::new(space, Strategy::Tpe { startup_trials: 10 }, 50)
+//!     .goal(Goal::Maximise);
+//! let result = search(&mut experiment, &run_config, &config).await.unwrap();
+//! println!("{:?}", result.best());
+//! # }
+//! ```
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    error::Error,
+    f64::consts::PI,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::expand::set_fields;
+use crate::provenance::collect_provenance;
+use crate::results::run_name;
+use crate::run::{
+    check_requirements, create_experiment_dir, lock_experiment_dir, monitored, Session,
+};
+use crate::stats::Rng;
+use crate::{ExpResult, Experiment, ExperimentConfiguration, RunConfig, RunError};
+
+/// File at the root of the results directory with the trials of the last search.
+pub const SEARCH_FILE: &str = "search.json";
+
+/// How many candidates TPE draws for each parameter before picking the most promising.
+const TPE_CANDIDATES: usize = 24;
+/// Fraction of the trials TPE treats as good.
+const TPE_GAMMA: f64 = 0.25;
+/// How many proposals in a row may be configurations the search has already tried before it
+/// gives up, such as once a small space has been covered.
+const MAX_DUPLICATE_PROPOSALS: usize = 100;
+
+/// An experiment with an objective to optimise.
+pub trait SearchExperiment: Experiment {
+    /// Extract the objective from a completed run of a configuration, such as its throughput.
+    ///
+    /// With repeats the objective of a configuration is the mean over its completed repeats.
+    fn objective(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+    ) -> ExpResult<f64>;
+}
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    Run(#[from] RunError),
+    #[error("failed to set parameter: {0}")]
+    InvalidParameter(String),
+    #[error("proposed configuration is invalid: {0}")]
+    InvalidConfiguration(serde_json::Error),
+    #[error("grid search needs discrete parameters but {0} is a float")]
+    NotDiscrete(String),
+    #[error("parameter {0} has no choices")]
+    NoChoices(String),
+    #[error("parameter {0} has a low bound above its high bound")]
+    InvalidRange(String),
+    #[error("failed to get the objective of {0}: {1}")]
+    Objective(String, Box<dyn Error + Send + Sync>),
+}
+
+/// The values a parameter of the search can take.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Parameter {
+    /// One of a list of values.
+    Choice(Vec<Value>),
+    /// An integer in an inclusive range.
+    Int { low: i64, high: i64 },
+    /// A float in an inclusive range, searched on a log scale if `log` is set, for parameters
+    /// spanning orders of magnitude.
+    Float { low: f64, high: f64, log: bool },
+}
+
+/// The configurations to search: a base configuration with some of its fields as parameters.
+#[derive(Debug, Clone)]
+pub struct SearchSpace {
+    base: Value,
+    parameters: Vec<(String, Parameter)>,
+}
+
+impl SearchSpace {
+    /// A space around the serialized base configuration, which gives the fields that aren't
+    /// searched.
+    pub fn new(base: Value) -> Self {
+        Self {
+            base,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Search a field, given like `--set`, with nested fields separated by dots.
+    pub fn parameter(mut self, field: impl Into<String>, parameter: Parameter) -> Self {
+        self.parameters.push((field.into(), parameter));
+        self
+    }
+
+    /// The configuration with the parameters set to the given points.
+    fn configuration(&self, point: &[Point]) -> Result<Value, SearchError> {
+        let fields = self
+            .parameters
+            .iter()
+            .zip(point)
+            .map(|((field, parameter), point)| (field.clone(), point.value(parameter)))
+            .collect::<Vec<_>>();
+        let mut configuration = self.base.clone();
+        set_fields(&mut configuration, &fields).map_err(SearchError::InvalidParameter)?;
+        Ok(configuration)
+    }
+}
+
+/// How to propose the next configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Every combination of the parameters in turn, which must all be discrete.
+    Grid,
+    /// Sample each parameter uniformly.
+    Random,
+    /// Tree-structured Parzen estimator: sample randomly for the first `startup_trials`, then
+    /// favour values more likely among the best trials than the rest.
+    Tpe { startup_trials: usize },
+}
+
+/// Whether to look for the lowest or highest objective.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Goal {
+    #[default]
+    Minimise,
+    Maximise,
+}
+
+pub struct SearchConfig {
+    pub space: SearchSpace,
+    pub strategy: Strategy,
+    /// Most configurations to run, fewer for a grid with fewer points.
+    pub trials: usize,
+    pub goal: Goal,
+}
+
+impl SearchConfig {
+    pub fn new(space: SearchSpace, strategy: Strategy, trials: usize) -> Self {
+        Self {
+            space,
+            strategy,
+            trials,
+            goal: Goal::default(),
+        }
+    }
+
+    pub fn goal(mut self, goal: Goal) -> Self {
+        self.goal = goal;
+        self
+    }
+}
+
+/// A configuration run by the search.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trial {
+    pub configuration: Value,
+    pub hash: String,
+    /// `None` if no repeat of the configuration completed.
+    pub objective: Option<f64>,
+}
+
+/// The trials of a search, written to `search.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub goal: Goal,
+    pub trials: Vec<Trial>,
+}
+
+impl SearchResult {
+    /// Read the result of the last search in a results directory.
+    pub fn from_dir(results_dir: &Path) -> io::Result<Self> {
+        let file = File::open(results_dir.join(SEARCH_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// The trial with the best objective.
+    pub fn best(&self) -> Option<&Trial> {
+        self.trials
+            .iter()
+            .filter(|trial| trial.objective.is_some())
+            .min_by(|a, b| {
+                let (a, b) = (a.objective.unwrap(), b.objective.unwrap());
+                match self.goal {
+                    Goal::Minimise => a.total_cmp(&b),
+                    Goal::Maximise => b.total_cmp(&a),
+                }
+            })
+    }
+}
+
+/// Run the configurations proposed by the search strategy, up to `SearchConfig::trials` of
+/// them, in `RunConfig::results_dir`.
+///
+/// The trials are run in one session, as the configurations of `run` are, with their repeats.
+/// Configurations that have already been run by another search are trials without being run
+/// again, and don't count towards `SearchConfig::trials`, and proposals of configurations this
+/// search has already tried are skipped. Random sampling is seeded by `RunConfig::base_seed`.
+pub async fn search<E>(
+    experiment: &mut E,
+    run_config: &RunConfig,
+    config: &SearchConfig,
+) -> Result<SearchResult, SearchError>
+where
+    E: SearchExperiment + Send,
+    E::Configuration: Send + Sync,
+{
+    monitored(run_config, search_trials(experiment, run_config, config)).await
+}
+
+async fn search_trials<E>(
+    experiment: &mut E,
+    run_config: &RunConfig,
+    config: &SearchConfig,
+) -> Result<SearchResult, SearchError>
+where
+    E: SearchExperiment + Send,
+    E::Configuration: Send + Sync,
+{
+    let parameters = &config.space.parameters;
+    check_parameters(parameters)?;
+    let mut grid = match config.strategy {
+        Strategy::Grid => Some(grid(parameters)?.into_iter()),
+        _ => None,
+    };
+    let experiment_dir = create_experiment_dir(&run_config.results_dir)?;
+    let _lock = lock_experiment_dir(&experiment_dir)?;
+    experiment.metadata().write(&experiment_dir)?;
+    collect_provenance(&experiment_dir, &run_config.provenance_repos);
+    let mut session = Session::start(
+        experiment,
+        &experiment_dir,
+        run_config,
+        config.trials * run_config.repeats,
+    )
+    .await?;
+
+    let mut rng = Rng::new(run_config.base_seed);
+    let mut observed: Vec<(Vec<Point>, f64)> = Vec::new();
+    let mut result = SearchResult {
+        goal: config.goal,
+        trials: Vec::new(),
+    };
+    let mut tried = HashSet::new();
+    let mut duplicates = 0;
+    let mut trial = 0;
+    while trial < config.trials {
+        let point = match (&mut grid, config.strategy) {
+            (Some(grid), _) => match grid.next() {
+                Some(point) => point,
+                None => break,
+            },
+            (None, Strategy::Tpe { startup_trials }) if observed.len() >= startup_trials.max(1) => {
+                tpe(parameters, &observed, &mut rng)
+            }
+            _ => parameters
+                .iter()
+                .map(|(_, parameter)| Point::sample(parameter, &mut rng))
+                .collect(),
+        };
+        let configuration = config.space.configuration(&point)?;
+        let parsed: E::Configuration = serde_json::from_value(configuration.clone())
+            .map_err(SearchError::InvalidConfiguration)?;
+        let hash = parsed
+            .hash_serialized()
+            .map_err(|error| SearchError::Run(error.into()))?;
+        if !tried.insert(hash.clone()) {
+            duplicates += 1;
+            if duplicates >= MAX_DUPLICATE_PROPOSALS {
+                warn!(
+                    trial,
+                    "Search keeps proposing configurations it has tried, stopping"
+                );
+                break;
+            }
+            continue;
+        }
+        duplicates = 0;
+
+        let repeats = (0..run_config.repeats)
+            .filter(|repeat| !experiment_dir.join(run_name(&hash, *repeat)).exists())
+            .collect::<Vec<_>>();
+        if repeats.is_empty() {
+            info!(%hash, "Configuration already run, not counting it as a trial");
+        } else {
+            info!(trial, %hash, "Running search trial");
+            check_requirements(experiment, &experiment_dir, std::slice::from_ref(&parsed)).await?;
+            for repeat in repeats {
+                session.wait().await;
+                session.run(experiment, &parsed, repeat).await?;
+            }
+            trial += 1;
+        }
+
+        let objective = objective(
+            experiment,
+            &parsed,
+            &experiment_dir,
+            &hash,
+            run_config.repeats,
+        )?;
+        match objective {
+            Some(objective) => {
+                info!(%hash, objective, "Search trial finished");
+                let loss = match config.goal {
+                    Goal::Minimise => objective,
+                    Goal::Maximise => -objective,
+                };
+                observed.push((point, loss));
+            }
+            None => warn!(%hash, "Search trial had no completed runs"),
+        }
+        result.trials.push(Trial {
+            configuration,
+            hash,
+            objective,
+        });
+        let file = File::create(experiment_dir.join(SEARCH_FILE))?;
+        serde_json::to_writer_pretty(file, &result).map_err(io::Error::from)?;
+    }
+    session.finish().await?;
+    Ok(result)
+}
+
+/// Check each parameter has values to search.
+fn check_parameters(parameters: &[(String, Parameter)]) -> Result<(), SearchError> {
+    for (field, parameter) in parameters {
+        match parameter {
+            Parameter::Choice(values) if values.is_empty() => {
+                return Err(SearchError::NoChoices(field.clone()))
+            }
+            Parameter::Int { low, high } if low > high => {
+                return Err(SearchError::InvalidRange(field.clone()))
+            }
+            Parameter::Float { low, high, .. } if low > high => {
+                return Err(SearchError::InvalidRange(field.clone()))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The mean objective over the completed repeats of a configuration.
+fn objective<E: SearchExperiment>(
+    experiment: &mut E,
+    configuration: &E::Configuration,
+    results_dir: &Path,
+    hash: &str,
+    repeats: usize,
+) -> Result<Option<f64>, SearchError> {
+    let mut objectives = Vec::new();
+    for repeat in 0..repeats {
+        let dir: PathBuf = results_dir.join(run_name(hash, repeat));
+        if !dir.is_dir() {
+            continue;
+        }
+        let objective = experiment
+            .objective(configuration, &dir)
+            .map_err(|error| SearchError::Objective(run_name(hash, repeat), error))?;
+        objectives.push(objective);
+    }
+    if objectives.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        objectives.iter().sum::<f64>() / objectives.len() as f64,
+    ))
+}
+
+/// The value of a parameter in a trial, an index for choices and otherwise the number, in log
+/// space for log scaled floats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Point {
+    Index(usize),
+    Number(f64),
+}
+
+impl Point {
+    fn sample(parameter: &Parameter, rng: &mut Rng) -> Self {
+        match parameter {
+            Parameter::Choice(values) => Point::Index(rng.next() as usize % values.len()),
+            _ => {
+                let (low, high) = bounds(parameter);
+                Point::Number(low + rng.next_f64() * (high - low))
+            }
+        }
+    }
+
+    fn value(&self, parameter: &Parameter) -> Value {
+        match (self, parameter) {
+            (Point::Index(i), Parameter::Choice(values)) => {
+                values.get(*i).cloned().unwrap_or(Value::Null)
+            }
+            (Point::Number(x), Parameter::Int { low, high }) => {
+                Value::from((x.round() as i64).clamp(*low, *high))
+            }
+            (Point::Number(x), Parameter::Float { log: true, .. }) => Value::from(x.exp()),
+            (Point::Number(x), _) => Value::from(*x),
+            (Point::Index(_), _) => Value::Null,
+        }
+    }
+}
+
+/// The range of a numeric parameter, in log space for log scaled floats.
+fn bounds(parameter: &Parameter) -> (f64, f64) {
+    match parameter {
+        Parameter::Choice(_) => (0.0, 0.0),
+        Parameter::Int { low, high } => (*low as f64, *high as f64),
+        Parameter::Float {
+            low,
+            high,
+            log: true,
+        } => (low.ln(), high.ln()),
+        Parameter::Float { low, high, .. } => (*low, *high),
+    }
+}
+
+/// Every combination of the discrete parameters, varying the last fastest.
+fn grid(parameters: &[(String, Parameter)]) -> Result<Vec<Vec<Point>>, SearchError> {
+    let mut points = vec![Vec::new()];
+    for (field, parameter) in parameters {
+        let options = match parameter {
+            Parameter::Choice(values) => (0..values.len()).map(Point::Index).collect::<Vec<_>>(),
+            Parameter::Int { low, high } => {
+                (*low..=*high).map(|i| Point::Number(i as f64)).collect()
+            }
+            Parameter::Float { .. } => return Err(SearchError::NotDiscrete(field.clone())),
+        };
+        points = points
+            .into_iter()
+            .flat_map(|point| {
+                options.iter().map(move |option| {
+                    let mut point = point.clone();
+                    point.push(*option);
+                    point
+                })
+            })
+            .collect();
+    }
+    Ok(points)
+}
+
+/// Propose a point by TPE, treating the parameters as independent.
+fn tpe(
+    parameters: &[(String, Parameter)],
+    observed: &[(Vec<Point>, f64)],
+    rng: &mut Rng,
+) -> Vec<Point> {
+    let mut sorted = observed.iter().collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let n_good = ((sorted.len() as f64 * TPE_GAMMA).ceil() as usize).max(1);
+    let (good, bad) = sorted.split_at(n_good);
+    parameters
+        .iter()
+        .enumerate()
+        .map(|(i, (_, parameter))| {
+            let good = good.iter().map(|(point, _)| point[i]).collect::<Vec<_>>();
+            let bad = bad.iter().map(|(point, _)| point[i]).collect::<Vec<_>>();
+            let good = Parzen::new(parameter, &good);
+            let bad = Parzen::new(parameter, &bad);
+            let candidates = (0..TPE_CANDIDATES)
+                .map(|_| good.sample(rng))
+                .collect::<BTreeSet<_>>();
+            candidates
+                .into_iter()
+                .map(|candidate| {
+                    let score = good.density(candidate.0) / bad.density(candidate.0);
+                    (candidate, score)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(candidate, _)| candidate.point(parameter))
+                .unwrap_or_else(|| Point::sample(parameter, rng))
+        })
+        .collect()
+}
+
+/// A candidate value of a parameter, ordered so candidates can be deduplicated.
+#[derive(Debug, Clone, Copy)]
+struct Candidate(f64);
+
+impl Candidate {
+    fn point(&self, parameter: &Parameter) -> Point {
+        match parameter {
+            Parameter::Choice(_) => Point::Index(self.0 as usize),
+            _ => Point::Number(self.0),
+        }
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.total_cmp(&other.0).is_eq()
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A density over a parameter's values estimated from observed values, mixed with a uniform
+/// prior so unobserved values keep some weight.
+enum Parzen {
+    /// Smoothed frequencies of each choice.
+    Categorical(Vec<f64>),
+    /// Gaussians around each observation.
+    Kernel {
+        low: f64,
+        high: f64,
+        means: Vec<f64>,
+        bandwidth: f64,
+    },
+}
+
+impl Parzen {
+    fn new(parameter: &Parameter, points: &[Point]) -> Self {
+        match parameter {
+            Parameter::Choice(values) => {
+                let mut weights = vec![1.0; values.len()];
+                for point in points {
+                    if let Point::Index(i) = point {
+                        if let Some(weight) = weights.get_mut(*i) {
+                            *weight += 1.0;
+                        }
+                    }
+                }
+                let total = weights.iter().sum::<f64>();
+                Parzen::Categorical(weights.into_iter().map(|w| w / total).collect())
+            }
+            _ => {
+                let (low, high) = bounds(parameter);
+                let means = points
+                    .iter()
+                    .filter_map(|point| match point {
+                        Point::Number(x) => Some(*x),
+                        Point::Index(_) => None,
+                    })
+                    .collect::<Vec<_>>();
+                let bandwidth = ((high - low) / (means.len() + 1) as f64).max(f64::EPSILON);
+                Parzen::Kernel {
+                    low,
+                    high,
+                    means,
+                    bandwidth,
+                }
+            }
+        }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> Candidate {
+        match self {
+            Parzen::Categorical(weights) => {
+                let mut target = rng.next_f64();
+                for (i, weight) in weights.iter().enumerate() {
+                    if target < *weight {
+                        return Candidate(i as f64);
+                    }
+                    target -= weight;
+                }
+                Candidate(weights.len().saturating_sub(1) as f64)
+            }
+            Parzen::Kernel {
+                low,
+                high,
+                means,
+                bandwidth,
+            } => {
+                // the prior is one more component
+                let component = rng.next() as usize % (means.len() + 1);
+                let x = match means.get(component) {
+                    Some(mean) => mean + bandwidth * normal(rng),
+                    None => low + rng.next_f64() * (high - low),
+                };
+                Candidate(x.clamp(*low, *high))
+            }
+        }
+    }
+
+    fn density(&self, x: f64) -> f64 {
+        match self {
+            Parzen::Categorical(weights) => weights.get(x as usize).copied().unwrap_or(0.0),
+            Parzen::Kernel {
+                low,
+                high,
+                means,
+                bandwidth,
+            } => {
+                let prior = 1.0 / (high - low).max(f64::EPSILON);
+                let kernels = means
+                    .iter()
+                    .map(|mean| {
+                        let z = (x - mean) / bandwidth;
+                        (-0.5 * z * z).exp() / (bandwidth * (2.0 * PI).sqrt())
+                    })
+                    .sum::<f64>();
+                (prior + kernels) / (means.len() + 1) as f64
+            }
+        }
+    }
+}
+
+/// A standard normal sample, by the Box-Muller transform.
+fn normal(rng: &mut Rng) -> f64 {
+    let u1 = rng.next_f64().max(f64::MIN_POSITIVE);
+    let u2 = rng.next_f64();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
@@ -0,0 +1,45 @@
+use std::{fs::File, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// File in each configuration directory with the seed for its run.
+pub const SEED_FILE: &str = "seed.json";
+
+/// The seed for a run of a configuration, derived from `RunConfig::base_seed`, the
+/// configuration's hash and the repeat, so runs are reproducible and repeats are independent.
+///
+/// Written to `seed.json` before the run, read it in `Experiment::run` with
+/// `Seed::from_configuration(configuration_dir)` to seed random number generators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seed {
+    pub base_seed: u64,
+    pub repeat: usize,
+    pub seed: u64,
+}
+
+impl Seed {
+    pub fn derive(base_seed: u64, hash: &str, repeat: usize) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&base_seed.to_le_bytes());
+        hasher.update(hash.as_bytes());
+        hasher.update(&(repeat as u64).to_le_bytes());
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&hasher.finalize().as_bytes()[..8]);
+        Seed {
+            base_seed,
+            repeat,
+            seed: u64::from_le_bytes(seed),
+        }
+    }
+
+    /// Read the seed a configuration was run with.
+    pub fn from_configuration(configuration_dir: &Path) -> io::Result<Self> {
+        let file = File::open(configuration_dir.join(SEED_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub(crate) fn write(&self, configuration_dir: &Path) -> io::Result<()> {
+        let file = File::create(configuration_dir.join(SEED_FILE))?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+}
@@ -0,0 +1,480 @@
+use std::{
+    fs::{create_dir_all, File},
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    docker_runner::{create_config_dir, create_logs_dir, create_metrics_dir},
+    log_capture::{LogCaptureConfig, LogWriter},
+};
+
+/// Interval at which remote processes are sampled with `pidstat`.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(1);
+
+/// File in the `config` directory of a configuration with the clock offset of each remote host.
+pub const CLOCK_OFFSETS_FILE: &str = "clock-offsets.json";
+
+/// Number of timestamp exchanges with a host when measuring its clock offset.
+const CLOCK_SAMPLES: usize = 8;
+
+/// A remote host reachable over ssh.
+///
+/// Connections use the local `ssh` and `scp` binaries so the user's ssh config and agent apply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshHost {
+    /// Name for the host, used to separate downloaded files.
+    pub name: String,
+    /// Address to connect to, e.g. `user@host`.
+    pub address: String,
+    pub port: Option<u16>,
+    pub identity_file: Option<PathBuf>,
+}
+
+impl SshHost {
+    fn ssh_command(&self) -> Command {
+        let mut command = Command::new("ssh");
+        command.args(["-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if let Some(identity_file) = &self.identity_file {
+            command.arg("-i").arg(identity_file);
+        }
+        command.arg(&self.address).arg("--");
+        command
+    }
+
+    fn scp_command(&self) -> Command {
+        let mut command = Command::new("scp");
+        command.args(["-o", "BatchMode=yes", "-r", "-q"]);
+        if let Some(port) = self.port {
+            command.arg("-P").arg(port.to_string());
+        }
+        if let Some(identity_file) = &self.identity_file {
+            command.arg("-i").arg(identity_file);
+        }
+        command
+    }
+}
+
+// The ssh runner for a particular experiment run, running processes on remote hosts
+// handles spawning of remote processes and teardown after
+#[derive(Debug)]
+pub struct Runner {
+    processes: Vec<RemoteProcess>,
+    config_dir: PathBuf,
+    end_tx: tokio::sync::watch::Sender<()>,
+    end_rx: tokio::sync::watch::Receiver<()>,
+    futures: Vec<JoinHandle<()>>,
+    clock_offsets: Vec<ClockOffset>,
+}
+
+#[derive(Debug)]
+struct RemoteProcess {
+    name: String,
+    host: SshHost,
+    pid: u32,
+    child: Child,
+}
+
+impl Runner {
+    pub async fn new(config_dir: PathBuf) -> Self {
+        let (end_tx, end_rx) = tokio::sync::watch::channel(());
+        Self {
+            processes: Vec::new(),
+            config_dir,
+            end_tx,
+            end_rx,
+            futures: Vec::new(),
+            clock_offsets: Vec::new(),
+        }
+    }
+
+    /// Start a long running process on a remote host.
+    ///
+    /// Its output is captured to `logs/ssh-<name>.log` and, if `monitor` is set, it is sampled
+    /// with `pidstat` into `metrics/ssh-<name>-pidstat.csv`.
+    ///
+    /// The clock offset of the host is measured the first time a process is started on it.
+    pub async fn add_process(&mut self, config: &RemoteProcessConfig) {
+        if !self
+            .clock_offsets
+            .iter()
+            .any(|offset| offset.host == config.host.name)
+        {
+            if let Err(error) = self.measure_clock_offset(&config.host).await {
+                warn!(%error, host = %config.host.name, "Failed to measure clock offset");
+            }
+        }
+
+        let config_dir =
+            create_config_dir(&self.config_dir).expect("Failed to create ssh config dir");
+        let logs_dir = create_logs_dir(&self.config_dir).expect("Failed to create logs dir");
+        let metrics_dir =
+            create_metrics_dir(&self.config_dir).expect("Failed to create metrics dir");
+        let config_file = File::create(config_dir.join(format!("ssh-{}.json", config.name)))
+            .expect("Failed to create ssh config file");
+        serde_json::to_writer_pretty(config_file, &config).expect("Failed to write ssh config");
+
+        let mut child = config
+            .host
+            .ssh_command()
+            .arg(config.script())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("Failed to spawn ssh");
+
+        let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+        let stderr = BufReader::new(child.stderr.take().unwrap()).lines();
+        let pid = stdout
+            .next_line()
+            .await
+            .expect("Failed to read remote pid")
+            .and_then(|line| line.trim().parse::<u32>().ok())
+            .expect("Failed to parse remote pid");
+        debug!(name = %config.name, host = %config.host.name, pid, "Started remote process");
+
+        let logs_extension = if config.logs.json { "jsonl" } else { "log" };
+        let mut log_writer = LogWriter::new(
+            logs_dir.join(format!("ssh-{}.{}", config.name, logs_extension)),
+            &config.logs,
+        )
+        .expect("Invalid log drop pattern");
+        self.futures.push(tokio::spawn(async move {
+            let mut stdout = stdout;
+            let mut stderr = stderr;
+            loop {
+                tokio::select! {
+                    Ok(Some(line)) = stdout.next_line() => {
+                        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+                        log_writer.write_line(&format!("{} {}\n", now, line)).unwrap();
+                    }
+                    Ok(Some(line)) = stderr.next_line() => {
+                        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+                        log_writer.write_line(&format!("{} {}\n", now, line)).unwrap();
+                    }
+                    else => break,
+                }
+            }
+            log_writer.flush().unwrap();
+        }));
+
+        if config.monitor {
+            let host = config.host.clone();
+            let pidstat_file = metrics_dir.join(format!("ssh-{}-pidstat.csv", config.name));
+            let end_rx = self.end_rx.clone();
+            self.futures.push(tokio::spawn(async move {
+                monitor_remote(&host, pid, &pidstat_file, end_rx).await;
+            }));
+        }
+
+        self.processes.push(RemoteProcess {
+            name: config.name.clone(),
+            host: config.host.clone(),
+            pid,
+            child,
+        });
+    }
+
+    /// Run a command on a remote host to completion, returning its stdout and stderr lines.
+    pub async fn execute_command(
+        &self,
+        host: &SshHost,
+        command: &str,
+    ) -> (Vec<String>, Vec<String>) {
+        let output = host
+            .ssh_command()
+            .arg(command)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .expect("Failed to run ssh");
+        let out = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_owned())
+            .collect();
+        let err = String::from_utf8(output.stderr)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_owned())
+            .collect();
+        (out, err)
+    }
+
+    /// Measure how far the clock of a remote host is ahead of the local one, recording it in
+    /// `config/clock-offsets.json`.
+    ///
+    /// Timestamps are exchanged over a single ssh session and the exchange with the shortest
+    /// round trip is kept, taking the remote time to be at its midpoint. The offset reported by
+    /// `chronyc tracking` on the host is recorded too when chrony is running there.
+    pub async fn measure_clock_offset(&mut self, host: &SshHost) -> io::Result<ClockOffset> {
+        let mut child = host
+            .ssh_command()
+            .arg("while read -r _; do date +%s%N; done")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut exchanges = Vec::with_capacity(CLOCK_SAMPLES);
+        for _ in 0..CLOCK_SAMPLES {
+            let sent = Utc::now().timestamp_nanos();
+            stdin.write_all(b"\n").await?;
+            stdin.flush().await?;
+            let line = stdout.next_line().await?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "ssh exited during clock exchange",
+                )
+            })?;
+            let received = Utc::now().timestamp_nanos();
+            let remote = line.trim().parse::<i64>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid remote timestamp {:?}", line),
+                )
+            })?;
+            exchanges.push((sent, remote, received));
+        }
+        drop(stdin);
+        let _ = child.wait().await;
+        let (round_trip_nanos, offset_nanos) = clock_offset(&exchanges).unwrap();
+
+        let (out, _) = self
+            .execute_command(host, "chronyc -c tracking 2>/dev/null")
+            .await;
+        let chrony_offset_seconds = out
+            .first()
+            .and_then(|line| line.split(',').nth(4))
+            .and_then(|field| field.parse().ok());
+
+        let offset = ClockOffset {
+            host: host.name.clone(),
+            offset_nanos,
+            round_trip_nanos,
+            measured_at: Utc::now(),
+            chrony_offset_seconds,
+        };
+        debug!(?offset, "Measured clock offset");
+        self.clock_offsets.push(offset.clone());
+        let config_dir = create_config_dir(&self.config_dir)?;
+        let file = File::create(config_dir.join(CLOCK_OFFSETS_FILE))?;
+        serde_json::to_writer_pretty(file, &self.clock_offsets)?;
+        Ok(offset)
+    }
+
+    /// Copy a local file or directory to the remote host.
+    pub async fn upload(&self, host: &SshHost, local: &Path, remote: &str) -> io::Result<()> {
+        let status = host
+            .scp_command()
+            .arg(local)
+            .arg(format!("{}:{}", host.address, remote))
+            .status()
+            .await?;
+        check_status(status, "scp upload")
+    }
+
+    /// Copy a remote file or directory into `data/<host>/` in the configuration directory.
+    pub async fn download(&self, host: &SshHost, remote: &str) -> io::Result<PathBuf> {
+        let data_dir = self.config_dir.join("data").join(&host.name);
+        create_dir_all(&data_dir)?;
+        let status = host
+            .scp_command()
+            .arg(format!("{}:{}", host.address, remote))
+            .arg(&data_dir)
+            .status()
+            .await?;
+        check_status(status, "scp download")?;
+        let file_name = Path::new(remote).file_name().unwrap_or_default();
+        Ok(data_dir.join(file_name))
+    }
+
+    pub async fn finish(self) {
+        for mut process in self.processes {
+            let output = process
+                .host
+                .ssh_command()
+                .arg(format!("kill {}", process.pid))
+                .stdin(Stdio::null())
+                .output()
+                .await;
+            match output {
+                Ok(output) if !output.status.success() => {
+                    debug!(name = %process.name, "Remote process may have already exited")
+                }
+                Ok(_) => {}
+                Err(error) => warn!(%error, name = %process.name, "Error killing remote process"),
+            }
+            if let Err(error) = process.child.wait().await {
+                warn!(%error, name = %process.name, "Error waiting for ssh to exit")
+            }
+        }
+
+        let r = self.end_tx.send(());
+        if let Err(error) = r {
+            warn!(%error, "Error sending shutdown signal to monitoring tasks")
+        }
+        join_all(self.futures).await;
+    }
+}
+
+/// How far the clock of a remote host was from the local one, to align the timestamps in its logs
+/// and metrics with local ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClockOffset {
+    /// Name of the host.
+    pub host: String,
+    /// Remote time minus local time.
+    pub offset_nanos: i64,
+    /// Round trip of the exchange the offset was taken from, bounding its error to half of this.
+    pub round_trip_nanos: i64,
+    /// Local time of the measurement.
+    pub measured_at: DateTime<Utc>,
+    /// Offset of the host's clock from NTP time reported by chrony, if it was running.
+    pub chrony_offset_seconds: Option<f64>,
+}
+
+impl ClockOffset {
+    /// Convert a timestamp taken on the remote host to local time.
+    pub fn to_local(&self, remote: DateTime<Utc>) -> DateTime<Utc> {
+        remote - chrono::Duration::nanoseconds(self.offset_nanos)
+    }
+}
+
+/// The round trip and offset of the remote clock, in nanoseconds, from the exchange of
+/// timestamps with the shortest round trip, taking the remote time to be at its midpoint.
+///
+/// Exchanges are `(sent, remote, received)`, with `sent` and `received` the local times around
+/// the `remote` one. `None` if there are none.
+pub fn clock_offset(exchanges: &[(i64, i64, i64)]) -> Option<(i64, i64)> {
+    exchanges
+        .iter()
+        .map(|(sent, remote, received)| {
+            let round_trip = received - sent;
+            (round_trip, remote - (sent + round_trip / 2))
+        })
+        .min_by_key(|(round_trip, _)| *round_trip)
+}
+
+/// Read the clock offsets recorded for the hosts of a configuration run.
+pub fn read_clock_offsets(configuration_dir: &Path) -> io::Result<Vec<ClockOffset>> {
+    let file = File::open(configuration_dir.join("config").join(CLOCK_OFFSETS_FILE))?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteProcessConfig {
+    pub name: String,
+    pub host: SshHost,
+    /// Shell command to run on the host.
+    pub command: String,
+    pub env: Vec<(String, String)>,
+    pub working_dir: Option<String>,
+    /// Sample the process with `pidstat`, which must be installed on the host.
+    pub monitor: bool,
+    /// How to write the captured stdout and stderr.
+    pub logs: LogCaptureConfig,
+}
+
+impl RemoteProcessConfig {
+    /// The shell script run over ssh to start the process.
+    ///
+    /// It prints the pid of the shell before replacing it with the command, so the process can be
+    /// monitored and stopped later.
+    pub fn script(&self) -> String {
+        let mut script = String::new();
+        if let Some(working_dir) = &self.working_dir {
+            script.push_str(&format!("cd {} || exit 1; ", shell_quote(working_dir)));
+        }
+        script.push_str("echo $$; exec env");
+        for (key, value) in &self.env {
+            script.push_str(&format!(" {}", shell_quote(&format!("{}={}", key, value))));
+        }
+        script.push_str(&format!(" sh -c {}", shell_quote(&self.command)));
+        script
+    }
+}
+
+/// Sample a remote process with `pidstat`, writing each sample as a csv row.
+///
+/// The columns are those reported by `pidstat` along with a `timestamp_nanos` column, like the
+/// docker top output.
+async fn monitor_remote(
+    host: &SshHost,
+    pid: u32,
+    path: &Path,
+    mut end_rx: tokio::sync::watch::Receiver<()>,
+) {
+    let mut child = host
+        .ssh_command()
+        .arg(format!(
+            "LC_ALL=C pidstat -h -u -r -d -p {} {}",
+            pid,
+            MONITOR_INTERVAL.as_secs()
+        ))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .expect("Failed to spawn ssh for pidstat");
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut writer = csv::Writer::from_path(path).unwrap();
+    let mut written_header = false;
+    loop {
+        tokio::select! {
+            _ = end_rx.changed() => break,
+            Ok(Some(line)) = lines.next_line() => {
+                if let Some(header) = line.strip_prefix('#') {
+                    if !written_header {
+                        let mut titles = header.split_whitespace().collect::<Vec<_>>();
+                        titles.push("timestamp_nanos");
+                        writer.write_record(titles).unwrap();
+                        written_header = true;
+                    }
+                } else if written_header && !line.trim().is_empty() {
+                    let now = Utc::now().timestamp_nanos().to_string();
+                    let mut values = line.split_whitespace().collect::<Vec<_>>();
+                    values.push(&now);
+                    writer.write_record(values).unwrap();
+                    writer.flush().unwrap();
+                }
+            }
+            else => break,
+        }
+    }
+    writer.flush().unwrap();
+    let _ = child.kill().await;
+}
+
+fn check_status(status: std::process::ExitStatus, what: &str) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed with {}", what, status)))
+    }
+}
+
+/// Quote a string for use as a single word in a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -0,0 +1,457 @@
+//! Summarise repeated measurements of configurations and test whether they differ, for use in
+//! `Experiment::analyse`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::results::flatten;
+
+/// Two-sided 95% critical values of Student's t-distribution for 1 to 30 degrees of freedom.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+/// Critical value of the normal distribution, used for more than 30 degrees of freedom.
+const Z_95: f64 = 1.960;
+
+/// Descriptive statistics of a set of measurements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation, zero for a single measurement.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Lower bound of the 95% confidence interval of the mean.
+    pub ci_low: f64,
+    /// Upper bound of the 95% confidence interval of the mean.
+    pub ci_high: f64,
+}
+
+impl Summary {
+    /// Summarise the measurements, ignoring NaNs, or `None` if there are none.
+    pub fn of(measurements: &[f64]) -> Option<Self> {
+        let mut sorted = measurements
+            .iter()
+            .copied()
+            .filter(|m| !m.is_nan())
+            .collect::<Vec<_>>();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let median = if count.is_multiple_of(2) {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+        } else {
+            sorted[count / 2]
+        };
+        let stddev = if count > 1 {
+            let variance =
+                sorted.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / (count - 1) as f64;
+            variance.sqrt()
+        } else {
+            0.0
+        };
+        let critical = T_95.get(count.saturating_sub(2)).copied().unwrap_or(Z_95);
+        let margin = critical * stddev / (count as f64).sqrt();
+        Some(Self {
+            count,
+            mean,
+            median,
+            stddev,
+            min: sorted[0],
+            max: sorted[count - 1],
+            ci_low: mean - margin,
+            ci_high: mean + margin,
+        })
+    }
+}
+
+/// The result of a test of whether two sets of measurements differ.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TestResult {
+    /// The test statistic, `t` for the t-test and `U` of the first set for Mann-Whitney.
+    pub statistic: f64,
+    /// Two-sided p-value.
+    pub p_value: f64,
+}
+
+impl TestResult {
+    /// Whether the difference is significant at level `alpha`, such as `0.05`.
+    pub fn is_significant(&self, alpha: f64) -> bool {
+        self.p_value < alpha
+    }
+}
+
+/// Welch's t-test of whether two sets of measurements have the same mean, not assuming equal
+/// variances, or `None` if either has fewer than two measurements.
+pub fn t_test(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    let (a, b) = (Summary::of(a)?, Summary::of(b)?);
+    if a.count < 2 || b.count < 2 {
+        return None;
+    }
+    let a_variance = a.stddev.powi(2) / a.count as f64;
+    let b_variance = b.stddev.powi(2) / b.count as f64;
+    let variance = a_variance + b_variance;
+    if variance == 0.0 {
+        let (statistic, p_value) = if a.mean == b.mean {
+            (0.0, 1.0)
+        } else {
+            ((a.mean - b.mean).signum() * f64::INFINITY, 0.0)
+        };
+        return Some(TestResult { statistic, p_value });
+    }
+    let t = (a.mean - b.mean) / variance.sqrt();
+    let degrees_of_freedom = variance.powi(2)
+        / (a_variance.powi(2) / (a.count - 1) as f64 + b_variance.powi(2) / (b.count - 1) as f64);
+    Some(TestResult {
+        statistic: t,
+        p_value: incomplete_beta(
+            degrees_of_freedom / 2.0,
+            0.5,
+            degrees_of_freedom / (degrees_of_freedom + t * t),
+        ),
+    })
+}
+
+/// The Mann-Whitney U test of whether measurements of one set tend to be larger than those of
+/// the other, for measurements that aren't normally distributed such as latencies, or `None` if
+/// either set is empty.
+///
+/// The p-value uses the normal approximation with corrections for ties and continuity, so is
+/// approximate for fewer than about ten measurements in each set.
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    let a = a
+        .iter()
+        .copied()
+        .filter(|m| !m.is_nan())
+        .collect::<Vec<_>>();
+    let b = b
+        .iter()
+        .copied()
+        .filter(|m| !m.is_nan())
+        .collect::<Vec<_>>();
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let mut all = a
+        .iter()
+        .map(|m| (*m, true))
+        .chain(b.iter().map(|m| (*m, false)))
+        .collect::<Vec<_>>();
+    all.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // rank sum of the first set, averaging the ranks of ties
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < all.len() {
+        let end = start
+            + all[start..]
+                .iter()
+                .take_while(|m| m.0 == all[start].0)
+                .count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        rank_sum += rank * all[start..end].iter().filter(|m| m.1).count() as f64;
+        let tied = (end - start) as f64;
+        ties += tied.powi(3) - tied;
+        start = end;
+    }
+
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let n = n_a + n_b;
+    let u = rank_sum - n_a * (n_a + 1.0) / 2.0;
+    let mean = n_a * n_b / 2.0;
+    let variance = n_a * n_b / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    let p_value = if variance > 0.0 {
+        let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+        erfc(z / std::f64::consts::SQRT_2)
+    } else {
+        1.0
+    };
+    Some(TestResult {
+        statistic: u,
+        p_value: p_value.min(1.0),
+    })
+}
+
+/// A confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Interval {
+    pub fn contains(&self, value: f64) -> bool {
+        self.low <= value && value <= self.high
+    }
+}
+
+/// The mean of the measurements, for use as the statistic of a bootstrap.
+pub fn mean(measurements: &[f64]) -> f64 {
+    measurements.iter().sum::<f64>() / measurements.len() as f64
+}
+
+/// Confidence interval of a statistic of the measurements, such as the `mean` or a percentile,
+/// by the percentile bootstrap with `resamples` resamples, or `None` if there are no
+/// measurements.
+///
+/// Resampling is seeded so the interval is the same on every analysis of the same measurements.
+pub fn bootstrap_ci<F: Fn(&[f64]) -> f64>(
+    measurements: &[f64],
+    statistic: F,
+    confidence: f64,
+    resamples: usize,
+) -> Option<Interval> {
+    if measurements.is_empty() {
+        return None;
+    }
+    let mut rng = Rng::default();
+    let estimates = (0..resamples)
+        .map(|_| statistic(&rng.resample(measurements)))
+        .collect();
+    percentile_interval(estimates, confidence)
+}
+
+/// Confidence interval of the difference of a statistic between two sets of measurements,
+/// `statistic(b) - statistic(a)`, by the percentile bootstrap. The difference is significant if
+/// the interval doesn't contain zero.
+pub fn bootstrap_difference_ci<F: Fn(&[f64]) -> f64>(
+    a: &[f64],
+    b: &[f64],
+    statistic: F,
+    confidence: f64,
+    resamples: usize,
+) -> Option<Interval> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let mut rng = Rng::default();
+    let estimates = (0..resamples)
+        .map(|_| statistic(&rng.resample(b)) - statistic(&rng.resample(a)))
+        .collect();
+    percentile_interval(estimates, confidence)
+}
+
+fn percentile_interval(mut estimates: Vec<f64>, confidence: f64) -> Option<Interval> {
+    estimates.retain(|e| !e.is_nan());
+    if estimates.is_empty() {
+        return None;
+    }
+    estimates.sort_by(|a, b| a.total_cmp(b));
+    let tail = (1.0 - confidence) / 2.0;
+    let at = |q: f64| estimates[((estimates.len() - 1) as f64 * q).round() as usize];
+    Some(Interval {
+        low: at(tail),
+        high: at(1.0 - tail),
+    })
+}
+
+/// A small deterministic random number generator (splitmix64) for resampling.
+pub(crate) struct Rng(u64);
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self(0x5eed)
+    }
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A float uniformly distributed in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Sample as many measurements as there are, with replacement.
+    fn resample(&mut self, measurements: &[f64]) -> Vec<f64> {
+        (0..measurements.len())
+            .map(|_| measurements[(self.next() % measurements.len() as u64) as usize])
+            .collect()
+    }
+}
+
+/// The complementary error function, with a fractional error below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+/// The regularized incomplete beta function `I_x(a, b)`, from its continued fraction.
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    } else if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // the continued fraction converges quickly on this side of the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Evaluate the continued fraction of the incomplete beta function with Lentz's method.
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let nonzero = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.0;
+    let mut d = 1.0 / nonzero(1.0 - (a + b) * x / (a + 1.0));
+    let mut fraction = d;
+    for m in 1..=300 {
+        let m = f64::from(m);
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / nonzero(1.0 + even * d);
+        c = nonzero(1.0 + even / c);
+        fraction *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / nonzero(1.0 + odd * d);
+        c = nonzero(1.0 + odd / c);
+        fraction *= d * c;
+        if (d * c - 1.0).abs() < 1e-14 {
+            break;
+        }
+    }
+    fraction
+}
+
+/// Natural log of the gamma function, by the Lanczos approximation, for `x >= 0.5`.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + (i + 1) as f64)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// A row of a summary table: the fields of a configuration and the statistics of its
+/// measurements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SummaryRow {
+    /// Fields of the configuration, keyed by their dotted path.
+    pub configuration: BTreeMap<String, Value>,
+    /// Position in the time series, `None` for scalar measurements.
+    pub index: Option<usize>,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+/// Configuration fields keyed by their dotted path.
+type Fields = BTreeMap<String, Value>;
+
+/// Group measurements by configuration, merging those of equal configurations, in order of first
+/// appearance.
+fn group<C: Serialize, M>(
+    measurements: impl IntoIterator<Item = (C, M)>,
+) -> Result<Vec<(Fields, Vec<M>)>, serde_json::Error> {
+    let mut groups: Vec<(Fields, Vec<M>)> = Vec::new();
+    for (configuration, measurement) in measurements {
+        let mut fields = BTreeMap::new();
+        flatten(
+            &serde_json::to_value(configuration)?,
+            String::new(),
+            &mut fields,
+        );
+        match groups.iter_mut().find(|(f, _)| *f == fields) {
+            Some((_, group)) => group.push(measurement),
+            None => groups.push((fields, vec![measurement])),
+        }
+    }
+    Ok(groups)
+}
+
+/// Summarise scalar measurements of configurations, such as the throughput of each repeated run,
+/// into one row per configuration.
+pub fn summarise<C: Serialize>(
+    measurements: impl IntoIterator<Item = (C, f64)>,
+) -> Result<Vec<SummaryRow>, serde_json::Error> {
+    Ok(group(measurements)?
+        .into_iter()
+        .filter_map(|(configuration, values)| {
+            Summary::of(&values).map(|summary| SummaryRow {
+                configuration,
+                index: None,
+                summary,
+            })
+        })
+        .collect())
+}
+
+/// Summarise time series of configurations, such as the latency over each repeated run, into one
+/// row per configuration and position in the series.
+///
+/// Series of the same configuration are aligned by position, shorter ones contributing to fewer
+/// rows.
+pub fn summarise_series<C: Serialize>(
+    series: impl IntoIterator<Item = (C, Vec<f64>)>,
+) -> Result<Vec<SummaryRow>, serde_json::Error> {
+    let mut rows = Vec::new();
+    for (configuration, series) in group(series)? {
+        let length = series.iter().map(Vec::len).max().unwrap_or_default();
+        for index in 0..length {
+            let values = series
+                .iter()
+                .filter_map(|s| s.get(index).copied())
+                .collect::<Vec<_>>();
+            if let Some(summary) = Summary::of(&values) {
+                rows.push(SummaryRow {
+                    configuration: configuration.clone(),
+                    index: Some(index),
+                    summary,
+                });
+            }
+        }
+    }
+    Ok(rows)
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::measurements::Recorded;
+use crate::results::run_name;
+use crate::stats::Summary;
+
+/// Stop repeating a configuration once the mean of a metric is known precisely enough, set with
+/// `RunConfig::early_stopping`, with `RunConfig::repeats` as the most repeats to run.
+///
+/// The metric is a scalar recorded with `Measurements::record_scalar`. Before each repeat the
+/// 95% confidence interval of its mean over the completed repeats is computed and the remaining
+/// repeats are skipped once the interval's half width is within `tolerance` of the mean.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EarlyStopping {
+    /// Name of the scalar to check.
+    pub metric: String,
+    /// Largest half width of the confidence interval to accept, relative to the mean, such as
+    /// `0.05` for 5%.
+    pub tolerance: f64,
+    /// Fewest repeats to run before stopping, at least 2.
+    #[serde(default)]
+    pub min_repeats: Option<usize>,
+}
+
+impl EarlyStopping {
+    pub fn new(metric: impl Into<String>, tolerance: f64) -> Self {
+        Self {
+            metric: metric.into(),
+            tolerance,
+            min_repeats: None,
+        }
+    }
+
+    /// Whether the completed repeats of a configuration measure the metric precisely enough to
+    /// stop repeating it.
+    pub(crate) fn is_satisfied(
+        &self,
+        experiment_dir: &Path,
+        config_hash: &str,
+        repeats: usize,
+    ) -> bool {
+        let mut values = Vec::new();
+        for repeat in 0..repeats {
+            let dir = experiment_dir.join(run_name(config_hash, repeat));
+            if !dir.is_dir() {
+                continue;
+            }
+            match Recorded::from_configuration(&dir) {
+                Ok(recorded) => values.extend(recorded.scalars.get(&self.metric)),
+                Err(error) => warn!(%error, ?dir, "Failed to read measurements for early stopping"),
+            }
+        }
+        let min_repeats = self.min_repeats.unwrap_or(2).max(2);
+        if values.len() < min_repeats {
+            return false;
+        }
+        let summary = match Summary::of(&values) {
+            Some(summary) => summary,
+            None => return false,
+        };
+        let half_width = (summary.ci_high - summary.ci_low) / 2.0;
+        debug!(
+            metric = %self.metric,
+            count = summary.count,
+            mean = summary.mean,
+            half_width,
+            "Checked early stopping"
+        );
+        half_width <= self.tolerance * summary.mean.abs()
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    fs::{copy, create_dir_all, read_dir, remove_dir_all, rename},
+    io,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+use tracing::{debug, warn};
+
+use crate::lock::{Lock, LockOwner, LOCK_EXTENSION};
+
+/// Path in the store for the configuration directory `config_dir`, keyed by its hash.
+fn store_path(store_dir: &Path, config_dir: &Path) -> io::Result<PathBuf> {
+    let hash = config_dir
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing config hash"))?;
+    Ok(store_dir.canonicalize()?.join(hash))
+}
+
+/// Take the lock on an entry of the store, so it isn't linked to while being added, giving who
+/// holds it otherwise.
+fn lock_entry(stored: &Path) -> io::Result<Result<Lock, LockOwner>> {
+    let mut path = stored.to_owned();
+    path.set_extension(LOCK_EXTENSION);
+    Lock::acquire(path)
+}
+
+/// Link `config_dir` to a completed run of the same configuration in the store, if there is one.
+///
+/// Returns whether a link was made.
+pub(crate) fn link_from_store(store_dir: &Path, config_dir: &Path) -> io::Result<bool> {
+    create_dir_all(store_dir)?;
+    let stored = store_path(store_dir, config_dir)?;
+    let _lock = match lock_entry(&stored)? {
+        Ok(lock) => lock,
+        Err(owner) => {
+            debug!(?stored, %owner, "Configuration is being added to the store, not linking");
+            return Ok(false);
+        }
+    };
+    if !stored.is_dir() {
+        return Ok(false);
+    }
+    debug!(
+        ?stored,
+        ?config_dir,
+        "Linking completed configuration from store"
+    );
+    symlink(&stored, config_dir)?;
+    Ok(true)
+}
+
+/// Move a completed configuration directory into the store, leaving a link in its place.
+///
+/// Any existing run of the configuration in the store is replaced. If another run is adding the
+/// same configuration the directory is left where it is.
+pub(crate) fn add_to_store(store_dir: &Path, config_dir: &Path) -> io::Result<()> {
+    create_dir_all(store_dir)?;
+    let stored = store_path(store_dir, config_dir)?;
+    let _lock = match lock_entry(&stored)? {
+        Ok(lock) => lock,
+        Err(owner) => {
+            warn!(?stored, %owner, "Configuration is being added to the store by another run, not adding");
+            return Ok(());
+        }
+    };
+    if stored.exists() {
+        remove_dir_all(&stored)?;
+    }
+    debug!(?stored, ?config_dir, "Adding configuration to store");
+    if rename(config_dir, &stored).is_err() {
+        // the store may be on a different filesystem
+        copy_dir(config_dir, &stored)?;
+        remove_dir_all(config_dir)?;
+    }
+    symlink(&stored, config_dir)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    create_dir_all(to)?;
+    for entry in read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::HashSet,
+    fs::{create_dir_all, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::notify::Notification;
+use crate::run::{monitored, run_experiment};
+use crate::{DynExperiment, RunConfig, RunError};
+
+/// File at the root of a suite's results recording the experiments run in it.
+pub const SUITE_FILE: &str = "suite.json";
+
+/// How to run a suite of experiments.
+pub struct SuiteConfig {
+    /// Settings for running each experiment. `results_dir` is the root of the suite, with each
+    /// experiment's results in a subdirectory named after it.
+    ///
+    /// Progress and notifications cover each experiment in turn, followed by a
+    /// `Notification::SuiteCompleted` for the whole suite.
+    pub run: RunConfig,
+    /// Run the rest of the experiments after one fails, instead of stopping.
+    pub keep_going: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum SuiteError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    SerdeError(#[from] serde_json::Error),
+    #[error("more than one experiment is named {0:?}")]
+    DuplicateName(String),
+    #[error("experiment {experiment} failed: {error}")]
+    Run { experiment: String, error: RunError },
+    #[error("experiments failed: {0:?}")]
+    Failed(Vec<String>),
+}
+
+/// The experiments run in a suite, written to `suite.json` at its root.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SuiteManifest {
+    pub experiments: Vec<SuiteEntry>,
+}
+
+impl SuiteManifest {
+    pub fn from_dir(suite_dir: &Path) -> io::Result<Self> {
+        let file = File::open(suite_dir.join(SUITE_FILE))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    fn write(&self, suite_dir: &Path) -> io::Result<()> {
+        let file = File::create(suite_dir.join(SUITE_FILE))?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+}
+
+/// An experiment run in a suite.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuiteEntry {
+    pub name: String,
+    /// Directory of the experiment's results, relative to the suite's.
+    pub dir: PathBuf,
+    pub started: DateTime<Utc>,
+    /// When the experiment finished running, `None` if it is still running or was interrupted.
+    pub finished: Option<DateTime<Utc>>,
+    /// Why the experiment failed to run.
+    pub error: Option<String>,
+}
+
+/// Run several experiments one after another, each in a subdirectory of the suite's results
+/// directory named after it.
+///
+/// Experiments are named by their metadata, or `experiment-<index>` if they don't have a name.
+pub async fn run_suite(
+    mut experiments: Vec<Box<dyn DynExperiment>>,
+    config: &SuiteConfig,
+) -> Result<SuiteManifest, SuiteError> {
+    let suite_dir = &config.run.results_dir;
+    let mut names = Vec::new();
+    let mut seen = HashSet::new();
+    for (i, experiment) in experiments.iter().enumerate() {
+        let mut name = experiment.metadata().name;
+        if name.is_empty() {
+            name = format!("experiment-{}", i);
+        }
+        if !seen.insert(name.clone()) {
+            return Err(SuiteError::DuplicateName(name));
+        }
+        names.push(name);
+    }
+    create_dir_all(suite_dir)?;
+
+    let total = experiments.len();
+    let mut manifest = SuiteManifest::default();
+    let mut failed = Vec::new();
+    let result = monitored(&config.run, async {
+        for (i, (experiment, name)) in experiments.iter_mut().zip(&names).enumerate() {
+            info!(experiment = %name, "Running experiment {}/{} of suite", i + 1, total);
+            manifest.experiments.push(SuiteEntry {
+                name: name.clone(),
+                dir: PathBuf::from(name),
+                started: Utc::now(),
+                finished: None,
+                error: None,
+            });
+            manifest.write(suite_dir)?;
+            let result = run_experiment(experiment, &suite_dir.join(name), &config.run).await;
+            if let Some(entry) = manifest.experiments.last_mut() {
+                entry.finished = Some(Utc::now());
+                entry.error = result.as_ref().err().map(ToString::to_string);
+            }
+            manifest.write(suite_dir)?;
+            if let Err(error) = result {
+                warn!(experiment = %name, %error, "Experiment failed");
+                failed.push(name.clone());
+                if !config.keep_going {
+                    return Err(SuiteError::Run {
+                        experiment: name.clone(),
+                        error,
+                    });
+                }
+            }
+        }
+        Ok(())
+    });
+    let result = result.await;
+
+    if let Some(notify) = &config.run.notify {
+        notify.notify(Notification::SuiteCompleted {
+            suite: suite_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            experiments: manifest.experiments.len(),
+            failed: failed.clone(),
+        });
+    }
+    result?;
+    if failed.is_empty() {
+        Ok(manifest)
+    } else {
+        Err(SuiteError::Failed(failed))
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs::{create_dir_all, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::lock::LOCK_EXTENSION;
+
+/// Name of the manifest file stored at the root of synchronised results directories.
+pub const MANIFEST_FILE: &str = ".exp-sync-manifest.json";
+
+/// Mapping from file paths, relative to the results directory, to their blake3 hash.
+pub type Manifest = BTreeMap<String, String>;
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeError(#[from] serde_json::Error),
+    #[error("{command} failed with {status}")]
+    CommandFailed { command: String, status: ExitStatus },
+    #[error("integrity check failed for {path}: expected hash {expected} but got {actual}")]
+    IntegrityError {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Where to synchronise results to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Remote {
+    /// A directory on this machine, such as a mounted shared filesystem.
+    Local { path: PathBuf },
+    /// A `[user@]host:path` destination, using `rsync` over ssh.
+    Rsync { destination: String },
+    /// A bucket and key prefix in S3-compatible object storage, using the `aws` cli.
+    S3 {
+        bucket: String,
+        prefix: String,
+        /// Endpoint for non-AWS object storage, e.g. a minio server.
+        endpoint_url: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncSummary {
+    /// Number of files that were transferred.
+    pub transferred: usize,
+    /// Number of files that already matched and were skipped.
+    pub unchanged: usize,
+}
+
+/// Upload the results directory to the remote, skipping files the remote already has.
+///
+/// A manifest of the blake3 hash of every file is stored alongside the results, both locally and
+/// remotely, and only files whose hash differs from the remote manifest are transferred. The
+/// uploaded manifest is the remote one updated with the local files, so files pushed from other
+/// machines stay listed. Machines shouldn't push at the same time, as the last manifest uploaded
+/// wins.
+pub async fn push(results_dir: &Path, remote: &Remote) -> Result<SyncSummary, SyncError> {
+    let local = build_manifest(results_dir)?;
+    let mut remote_manifest = fetch_remote_manifest(remote).await?;
+    let changed = local
+        .iter()
+        .filter(|(path, hash)| remote_manifest.get(*path) != Some(hash))
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    info!(
+        changed = changed.len(),
+        total = local.len(),
+        "Pushing results"
+    );
+
+    upload(results_dir, remote, &changed).await?;
+    let unchanged = local.len() - changed.len();
+    remote_manifest.extend(local);
+    write_manifest(results_dir, &remote_manifest)?;
+    upload(results_dir, remote, &[MANIFEST_FILE.to_owned()]).await?;
+
+    Ok(SyncSummary {
+        transferred: changed.len(),
+        unchanged,
+    })
+}
+
+/// Download results from the remote into the results directory, skipping files that are
+/// already present with the same content.
+///
+/// Downloaded files are checked against the hashes in the remote manifest.
+pub async fn pull(results_dir: &Path, remote: &Remote) -> Result<SyncSummary, SyncError> {
+    create_dir_all(results_dir)?;
+    let remote_manifest = fetch_remote_manifest(remote).await?;
+    let local = build_manifest(results_dir)?;
+    let changed = remote_manifest
+        .iter()
+        .filter(|(path, hash)| local.get(*path) != Some(hash))
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    info!(
+        changed = changed.len(),
+        total = remote_manifest.len(),
+        "Pulling results"
+    );
+
+    download(results_dir, remote, &changed).await?;
+    for path in &changed {
+        let actual = hash_file(&results_dir.join(path))?;
+        let expected = &remote_manifest[path];
+        if &actual != expected {
+            return Err(SyncError::IntegrityError {
+                path: path.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+    write_manifest(results_dir, &remote_manifest)?;
+
+    Ok(SyncSummary {
+        transferred: changed.len(),
+        unchanged: remote_manifest.len() - changed.len(),
+    })
+}
+
+/// Build the manifest of all files in the results directory.
+///
+/// Configurations that are still running and lock files are skipped.
+pub fn build_manifest(results_dir: &Path) -> io::Result<Manifest> {
+    let mut manifest = Manifest::new();
+    if results_dir.exists() {
+        add_to_manifest(results_dir, Path::new(""), &mut manifest)?;
+    }
+    Ok(manifest)
+}
+
+fn add_to_manifest(results_dir: &Path, relative: &Path, manifest: &mut Manifest) -> io::Result<()> {
+    for entry in std::fs::read_dir(results_dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        // follow links to configurations in a store
+        if results_dir.join(&path).is_dir() {
+            if path.extension() == Some(OsStr::new("running")) {
+                debug!(?path, "Skipping running configuration");
+                continue;
+            }
+            add_to_manifest(results_dir, &path, manifest)?;
+        } else if path != Path::new(MANIFEST_FILE)
+            && path.extension() != Some(OsStr::new(LOCK_EXTENSION))
+        {
+            let hash = hash_file(&results_dir.join(&path))?;
+            manifest.insert(path.to_string_lossy().into_owned(), hash);
+        }
+    }
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn write_manifest(results_dir: &Path, manifest: &Manifest) -> Result<(), SyncError> {
+    let manifest_file = File::create(results_dir.join(MANIFEST_FILE))?;
+    serde_json::to_writer_pretty(manifest_file, manifest)?;
+    Ok(())
+}
+
+/// Fetch the manifest from the remote, treating a missing manifest as an empty remote.
+///
+/// Other failures, such as the remote being unreachable, are errors rather than an empty remote,
+/// which would have the remote's manifest replaced.
+async fn fetch_remote_manifest(remote: &Remote) -> Result<Manifest, SyncError> {
+    let tmp = temp_path("exp-sync");
+    create_dir_all(&tmp)?;
+    let r = download_manifest(&tmp, remote).await;
+    let manifest = match r {
+        Ok(true) => {
+            serde_json::from_reader(File::open(tmp.join(MANIFEST_FILE))?).map_err(Into::into)
+        }
+        Ok(false) => {
+            debug!("No remote manifest, assuming remote is empty");
+            Ok(Manifest::new())
+        }
+        Err(error) => Err(error),
+    };
+    std::fs::remove_dir_all(&tmp)?;
+    manifest
+}
+
+/// Download the remote's manifest into `dir`, giving whether the remote has one.
+async fn download_manifest(dir: &Path, remote: &Remote) -> Result<bool, SyncError> {
+    let target = dir.join(MANIFEST_FILE);
+    let (command, not_found): (_, fn(&str) -> bool) = match remote {
+        Remote::Local { path } => {
+            if !path.join(MANIFEST_FILE).exists() {
+                return Ok(false);
+            }
+            download(dir, remote, &[MANIFEST_FILE.to_owned()]).await?;
+            return Ok(true);
+        }
+        Remote::Rsync { destination } => {
+            let mut command = Command::new("rsync");
+            command
+                .arg("-a")
+                .arg(format!("{}/{}", destination, MANIFEST_FILE))
+                .arg(&target);
+            (command, |stderr| {
+                stderr.contains("No such file or directory")
+            })
+        }
+        Remote::S3 { .. } => {
+            let mut command = aws_command(remote);
+            command
+                .args(["s3", "cp", "--only-show-errors"])
+                .arg(s3_url(remote, MANIFEST_FILE))
+                .arg(&target);
+            (command, |stderr| {
+                stderr.contains("(404)") || stderr.contains("Not Found")
+            })
+        }
+    };
+    let program = format!("{:?}", command.as_std().get_program());
+    let output = command_output(command).await?;
+    if output.status.success() {
+        Ok(true)
+    } else if not_found(&String::from_utf8_lossy(&output.stderr)) {
+        Ok(false)
+    } else {
+        warn!(
+            stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+            "Failed to download remote manifest"
+        );
+        Err(SyncError::CommandFailed {
+            command: program,
+            status: output.status,
+        })
+    }
+}
+
+async fn upload(results_dir: &Path, remote: &Remote, paths: &[String]) -> Result<(), SyncError> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    match remote {
+        Remote::Local { path: destination } => copy_files(results_dir, destination, paths),
+        Remote::Rsync { destination } => {
+            let files_from = write_files_from(paths)?;
+            let mut command = Command::new("rsync");
+            command
+                .arg("-a")
+                .arg(format!("--files-from={}", files_from.display()))
+                .arg(format!("{}/", results_dir.display()))
+                .arg(format!("{}/", destination));
+            let r = run_command(command).await;
+            std::fs::remove_file(files_from)?;
+            r
+        }
+        Remote::S3 { .. } => {
+            for path in paths {
+                let mut command = aws_command(remote);
+                command
+                    .args(["s3", "cp", "--only-show-errors"])
+                    .arg(results_dir.join(path))
+                    .arg(s3_url(remote, path));
+                run_command(command).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn download(results_dir: &Path, remote: &Remote, paths: &[String]) -> Result<(), SyncError> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    match remote {
+        Remote::Local { path: source } => copy_files(source, results_dir, paths),
+        Remote::Rsync { destination } => {
+            let files_from = write_files_from(paths)?;
+            let mut command = Command::new("rsync");
+            command
+                .arg("-a")
+                .arg(format!("--files-from={}", files_from.display()))
+                .arg(format!("{}/", destination))
+                .arg(format!("{}/", results_dir.display()));
+            let r = run_command(command).await;
+            std::fs::remove_file(files_from)?;
+            r
+        }
+        Remote::S3 { .. } => {
+            for path in paths {
+                let mut command = aws_command(remote);
+                command
+                    .args(["s3", "cp", "--only-show-errors"])
+                    .arg(s3_url(remote, path))
+                    .arg(results_dir.join(path));
+                run_command(command).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Copy the files at `paths` relative to `from` to the same paths relative to `to`.
+fn copy_files(from: &Path, to: &Path, paths: &[String]) -> Result<(), SyncError> {
+    for path in paths {
+        let target = to.join(path);
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        std::fs::copy(from.join(path), target)?;
+    }
+    Ok(())
+}
+
+/// A path in the temporary directory unique to this sync, as syncs can run concurrently.
+fn temp_path(name: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "{}-{}-{}",
+        name,
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Write the list of paths to a file for use with rsync's `--files-from`.
+fn write_files_from(paths: &[String]) -> io::Result<PathBuf> {
+    let path = temp_path("exp-sync-files");
+    let mut file = File::create(&path)?;
+    for p in paths {
+        writeln!(file, "{}", p)?;
+    }
+    Ok(path)
+}
+
+fn aws_command(remote: &Remote) -> Command {
+    let mut command = Command::new("aws");
+    if let Remote::S3 {
+        endpoint_url: Some(endpoint_url),
+        ..
+    } = remote
+    {
+        command.arg("--endpoint-url").arg(endpoint_url);
+    }
+    command
+}
+
+fn s3_url(remote: &Remote, path: &str) -> String {
+    match remote {
+        Remote::S3 { bucket, prefix, .. } => {
+            let prefix = prefix.trim_matches('/');
+            if prefix.is_empty() {
+                format!("s3://{}/{}", bucket, path)
+            } else {
+                format!("s3://{}/{}/{}", bucket, prefix, path)
+            }
+        }
+        Remote::Local { .. } | Remote::Rsync { .. } => unreachable!("s3 url for non-s3 remote"),
+    }
+}
+
+async fn command_output(mut command: Command) -> Result<Output, SyncError> {
+    debug!(?command, "Running sync command");
+    Ok(command.output().await?)
+}
+
+async fn run_command(mut command: Command) -> Result<(), SyncError> {
+    debug!(?command, "Running sync command");
+    let status = command.status().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(SyncError::CommandFailed {
+            command: format!("{:?}", command.as_std().get_program()),
+            status,
+        })
+    }
+}
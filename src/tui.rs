@@ -0,0 +1,305 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Stdout},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Row, Sparkline, Table},
+    Frame, Terminal,
+};
+use tracing::warn;
+
+/// Number of stats samples kept for each container's sparklines.
+const STATS_HISTORY: usize = 120;
+/// Number of log lines kept for the log tail.
+const LOG_TAIL: usize = 200;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Status of a configuration in the dashboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigurationStatus {
+    Pending,
+    Running,
+    Finished,
+    Failed,
+    Skipped,
+}
+
+impl ConfigurationStatus {
+    fn style(self) -> Style {
+        let color = match self {
+            ConfigurationStatus::Pending => Color::Gray,
+            ConfigurationStatus::Running => Color::Yellow,
+            ConfigurationStatus::Finished => Color::Green,
+            ConfigurationStatus::Failed => Color::Red,
+            ConfigurationStatus::Skipped => Color::DarkGray,
+        };
+        Style::default().fg(color)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ContainerHistory {
+    cpu_percent: VecDeque<u64>,
+    memory_bytes: VecDeque<u64>,
+}
+
+/// What the dashboard shows, fed from the run as it goes.
+#[derive(Debug)]
+struct State {
+    configurations: Vec<(String, ConfigurationStatus)>,
+    containers: BTreeMap<String, ContainerHistory>,
+    logs: VecDeque<String>,
+}
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+static STATE: Mutex<State> = Mutex::new(State {
+    configurations: Vec::new(),
+    containers: BTreeMap::new(),
+    logs: VecDeque::new(),
+});
+
+fn state() -> MutexGuard<'static, State> {
+    STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn push_bounded<T>(queue: &mut VecDeque<T>, value: T, limit: usize) {
+    if queue.len() == limit {
+        queue.pop_front();
+    }
+    queue.push_back(value);
+}
+
+pub(crate) fn set_configurations(hashes: Vec<String>) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    state().configurations = hashes
+        .into_iter()
+        .map(|hash| (hash, ConfigurationStatus::Pending))
+        .collect();
+}
+
+pub(crate) fn configuration_status(hash: &str, status: ConfigurationStatus) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut state = state();
+    if let Some((_, s)) = state.configurations.iter_mut().find(|(h, _)| h == hash) {
+        *s = status;
+    }
+    if status == ConfigurationStatus::Running {
+        state.containers.clear();
+        state.logs.clear();
+    }
+}
+
+pub(crate) fn container_stats(name: &str, cpu_percent: Option<f64>, memory_bytes: Option<u64>) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut state = state();
+    let history = state.containers.entry(name.to_owned()).or_default();
+    push_bounded(
+        &mut history.cpu_percent,
+        cpu_percent.unwrap_or_default().round() as u64,
+        STATS_HISTORY,
+    );
+    push_bounded(
+        &mut history.memory_bytes,
+        memory_bytes.unwrap_or_default(),
+        STATS_HISTORY,
+    );
+}
+
+pub(crate) fn log_line(name: &str, line: &str) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    push_bounded(
+        &mut state().logs,
+        format!("{}: {}", name, line.trim_end()),
+        LOG_TAIL,
+    );
+}
+
+/// A live dashboard of the run on the terminal, drawn from a background thread.
+///
+/// Started by `run` when `RunConfig::tui` is set, and restores the terminal when dropped.
+/// Tracing output to the terminal should be disabled while it is shown.
+/// Pressing `q` or `ctrl-c` interrupts the run.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Dashboard {
+    pub fn start() -> io::Result<Self> {
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        enable_raw_mode()?;
+        execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+        ACTIVE.store(true, Ordering::Relaxed);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_c = Arc::clone(&stop);
+        let thread = std::thread::spawn(move || {
+            let result = draw_loop(&mut terminal, &stop_c);
+            if let Err(error) = restore(&mut terminal) {
+                warn!(%error, "Failed to restore terminal");
+            }
+            match result {
+                // raw mode swallows ctrl-c so pass it on now the terminal is usable again
+                Ok(true) => {
+                    let _ = nix::sys::signal::raise(nix::sys::signal::Signal::SIGINT);
+                }
+                Ok(false) => {}
+                Err(error) => warn!(%error, "Failed to draw dashboard"),
+            }
+        });
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        ACTIVE.store(false, Ordering::Relaxed);
+    }
+}
+
+fn restore(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()
+}
+
+/// Redraw until stopped, returning whether the user asked to interrupt the run.
+fn draw_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    stop: &AtomicBool,
+) -> io::Result<bool> {
+    while !stop.load(Ordering::Relaxed) {
+        terminal.draw(|frame| draw(frame, &state()))?;
+        if event::poll(REDRAW_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                let interrupt = key.code == KeyCode::Char('q')
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL));
+                if key.kind == KeyEventKind::Press && interrupt {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn draw(frame: &mut Frame, state: &State) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(30),
+            Constraint::Length(3 * state.containers.len().max(1) as u16),
+            Constraint::Min(5),
+        ])
+        .split(frame.size());
+
+    let completed = state
+        .configurations
+        .iter()
+        .filter(|(_, status)| {
+            !matches!(
+                status,
+                ConfigurationStatus::Pending | ConfigurationStatus::Running
+            )
+        })
+        .count();
+    let rows = state.configurations.iter().map(|(hash, status)| {
+        Row::new(vec![hash.clone(), format!("{:?}", status)]).style(status.style())
+    });
+    let widths = [Constraint::Length(66), Constraint::Length(10)];
+    let table = Table::new(rows)
+        .header(Row::new(vec!["configuration", "status"]))
+        .widths(&widths)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Configurations {}/{}",
+            completed,
+            state.configurations.len()
+        )));
+    frame.render_widget(table, chunks[0]);
+
+    draw_containers(frame, state, chunks[1]);
+
+    let height = chunks[2].height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = state
+        .logs
+        .iter()
+        .skip(state.logs.len().saturating_sub(height))
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    let logs = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Logs"));
+    frame.render_widget(logs, chunks[2]);
+}
+
+fn draw_containers(frame: &mut Frame, state: &State, area: Rect) {
+    if state.containers.is_empty() {
+        let empty = Paragraph::new("no containers running")
+            .block(Block::default().borders(Borders::ALL).title("Containers"));
+        frame.render_widget(empty, area);
+        return;
+    }
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(3); state.containers.len()])
+        .split(area);
+    for ((name, history), row) in state.containers.iter().zip(rows.iter()) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(*row);
+        let cpu: Vec<u64> = history.cpu_percent.iter().copied().collect();
+        let memory: Vec<u64> = history.memory_bytes.iter().copied().collect();
+        let cpu_sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                "{} cpu {}%",
+                name,
+                cpu.last().copied().unwrap_or_default()
+            )))
+            .data(&cpu)
+            .style(Style::default().fg(Color::Cyan));
+        let memory_sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                "{} memory {} MiB",
+                name,
+                memory.last().copied().unwrap_or_default() / (1024 * 1024)
+            )))
+            .data(&memory)
+            .style(Style::default().fg(Color::Magenta));
+        frame.render_widget(cpu_sparkline, columns[0]);
+        frame.render_widget(memory_sparkline, columns[1]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    fs::{read_dir, read_to_string, write},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Settings to tune the host with for the duration of a run, set with `RunConfig::tuning`, so
+/// CPU frequency scaling doesn't add noise to the results.
+///
+/// Applying them needs permission to write to sysfs, usually root. The previous settings are
+/// restored once the run finishes and what was changed is recorded in `environment.json`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HostTuning {
+    /// CPU frequency governor to set on every CPU, such as `performance`.
+    pub governor: Option<String>,
+    /// Turn off turbo boost, through `intel_pstate` or `cpufreq`, whichever is available.
+    pub disable_turbo: bool,
+    /// Turn off simultaneous multithreading, taking the sibling threads offline.
+    pub disable_smt: bool,
+    /// Where sysfs is mounted, `/sys` by default.
+    pub sysfs: Option<PathBuf>,
+}
+
+/// A host setting changed for a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TuningSetting {
+    pub path: PathBuf,
+    pub previous: String,
+    pub applied: String,
+}
+
+/// Settings applied to the host, restored when dropped.
+#[derive(Debug)]
+pub(crate) struct TunedHost {
+    settings: Vec<TuningSetting>,
+}
+
+impl HostTuning {
+    /// Apply the settings, restoring those already applied if any fail.
+    pub(crate) fn apply(&self) -> Result<TunedHost, (PathBuf, io::Error)> {
+        let sysfs = self.sysfs.clone().unwrap_or_else(|| PathBuf::from("/sys"));
+        let cpu_dir = sysfs.join("devices/system/cpu");
+        let mut tuned = TunedHost {
+            settings: Vec::new(),
+        };
+        if let Some(governor) = &self.governor {
+            for cpu in cpus(&cpu_dir).map_err(|error| (cpu_dir.clone(), error))? {
+                let path = cpu.join("cpufreq/scaling_governor");
+                if path.exists() {
+                    tuned.set(path, governor)?;
+                }
+            }
+        }
+        if self.disable_turbo {
+            let intel = cpu_dir.join("intel_pstate/no_turbo");
+            let boost = cpu_dir.join("cpufreq/boost");
+            if intel.exists() {
+                tuned.set(intel, "1")?;
+            } else {
+                tuned.set(boost, "0")?;
+            }
+        }
+        if self.disable_smt {
+            tuned.set(cpu_dir.join("smt/control"), "off")?;
+        }
+        if !tuned.settings.is_empty() {
+            info!(settings = tuned.settings.len(), "Tuned host for the run");
+        }
+        Ok(tuned)
+    }
+}
+
+impl TunedHost {
+    /// The settings that were changed, in the order they were applied.
+    pub(crate) fn settings(&self) -> &[TuningSetting] {
+        &self.settings
+    }
+
+    fn set(&mut self, path: PathBuf, value: &str) -> Result<(), (PathBuf, io::Error)> {
+        let previous = match read_to_string(&path) {
+            Ok(previous) => previous.trim().to_owned(),
+            Err(error) => return Err((path, error)),
+        };
+        if previous == value {
+            return Ok(());
+        }
+        if let Err(error) = write(&path, value) {
+            return Err((path, error));
+        }
+        self.settings.push(TuningSetting {
+            path,
+            previous,
+            applied: value.to_owned(),
+        });
+        Ok(())
+    }
+}
+
+impl Drop for TunedHost {
+    fn drop(&mut self) {
+        // restore in reverse, bringing cpus back online before restoring their governors
+        for setting in self.settings.iter().rev() {
+            if let Err(error) = write(&setting.path, &setting.previous) {
+                warn!(%error, path = ?setting.path, "Failed to restore host setting");
+            }
+        }
+    }
+}
+
+/// The `cpuN` directories in sysfs.
+fn cpus(cpu_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut cpus = Vec::new();
+    for entry in read_dir(cpu_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name
+            .strip_prefix("cpu")
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        {
+            cpus.push(entry.path());
+        }
+    }
+    cpus.sort();
+    Ok(cpus)
+}
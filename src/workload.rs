@@ -0,0 +1,466 @@
+//! Parse the results benchmark tools print at the end of a run, such as YCSB or wrk, from the
+//! captured logs of the container that ran them into metrics with the same names whatever the
+//! tool.
+
+use std::{
+    fs::{create_dir_all, read_dir},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::compression;
+use crate::docker_runner::Logs;
+use crate::results::split_run_name;
+
+#[derive(Debug, Error)]
+pub enum WorkloadError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+    #[error("invalid fio output: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no {format:?} results in the logs of {container}")]
+    NoResults {
+        container: String,
+        format: WorkloadFormat,
+    },
+}
+
+/// The output format of a benchmark tool.
+///
+/// Each is parsed into these metrics, where the tool reports them:
+///
+/// - `throughput_ops_per_second` and `throughput_bytes_per_second`
+/// - `operations`, the number completed, and `errors`
+/// - `latency_mean_ms`, `latency_min_ms`, `latency_max_ms` and percentiles such as
+///   `latency_p99_ms` or `latency_p99_9_ms`
+/// - `runtime_seconds`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkloadFormat {
+    /// The summary YCSB prints, with a metric per operation such as `READ`.
+    Ycsb,
+    /// The summary of `sysbench run`, with the throughput of queries as the `queries` operation.
+    Sysbench,
+    /// The summary of wrk or wrk2, run with `--latency` for percentiles.
+    Wrk,
+    /// fio run with `--output-format=json`, with a metric per direction such as `read`, or
+    /// `<job>.read` when there are several jobs.
+    Fio,
+}
+
+impl WorkloadFormat {
+    /// Parse the output of the tool, ignoring anything else in it such as progress lines.
+    pub fn parse(&self, output: &str) -> Result<Vec<WorkloadValue>, WorkloadError> {
+        Ok(match self {
+            WorkloadFormat::Ycsb => parse_ycsb(output),
+            WorkloadFormat::Sysbench => parse_sysbench(output),
+            WorkloadFormat::Wrk => parse_wrk(output),
+            WorkloadFormat::Fio => parse_fio(output)?,
+        })
+    }
+}
+
+/// A metric parsed from the output of a benchmark tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadValue {
+    /// The operation the metric is of, `None` for the whole workload.
+    pub operation: Option<String>,
+    pub metric: String,
+    pub value: f64,
+}
+
+/// A metric of a workload, as written to its CSV.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkloadMetric {
+    /// Hash of the configuration.
+    pub configuration: String,
+    pub repeat: usize,
+    pub container: String,
+    /// The operation the metric is of, empty for the whole workload.
+    pub operation: Option<String>,
+    pub metric: String,
+    pub value: f64,
+}
+
+impl WorkloadMetric {
+    /// Load the metrics written by a `WorkloadExtractor`, decompressing them if they have been
+    /// compressed.
+    pub fn from_file(path: &Path) -> Result<Vec<WorkloadMetric>, csv::Error> {
+        csv::Reader::from_reader(compression::open(path)?)
+            .deserialize()
+            .collect()
+    }
+
+    /// Load the metrics of every workload of a configuration run, from
+    /// `metrics/workload-<container>.csv`.
+    pub fn from_configuration(configuration_dir: &Path) -> Result<Vec<WorkloadMetric>, csv::Error> {
+        let metrics_dir = configuration_dir.join("metrics");
+        let mut paths = Vec::new();
+        if metrics_dir.exists() {
+            for entry in read_dir(&metrics_dir)? {
+                let path = entry?.path();
+                let name = compression::uncompressed_name(&path);
+                if name.starts_with("workload-") && name.ends_with(".csv") {
+                    paths.push(path);
+                }
+            }
+        }
+        paths.sort();
+        let mut metrics = Vec::new();
+        for path in paths {
+            metrics.extend(WorkloadMetric::from_file(&path)?);
+        }
+        Ok(metrics)
+    }
+}
+
+/// A container whose logs have the output of a benchmark tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadOutput {
+    pub container: String,
+    pub format: WorkloadFormat,
+}
+
+/// Extracts the results of benchmark tools from the captured logs of a run, so that analysis
+/// can compare workloads run with different tools.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkloadExtractor {
+    pub outputs: Vec<WorkloadOutput>,
+}
+
+impl WorkloadExtractor {
+    /// Extract the results from the logs of a configuration run.
+    ///
+    /// The results of each container are written to `metrics/workload-<container>.csv`, with a
+    /// row per metric keyed by the configuration's hash and repeat. This can be done at the end
+    /// of `Experiment::run` or later in `Experiment::analyse`, as the logs are kept.
+    ///
+    /// Returns the paths of the files written.
+    pub fn extract(&self, configuration_dir: &Path) -> Result<Vec<PathBuf>, WorkloadError> {
+        let logs = Logs::load_all(configuration_dir)?;
+        let run_name = configuration_dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let (hash, repeat) = split_run_name(&run_name);
+        let metrics_dir = configuration_dir.join("metrics");
+        create_dir_all(&metrics_dir)?;
+        let mut written = Vec::new();
+        for output in &self.outputs {
+            let text = logs
+                .iter()
+                .find(|logs| logs.container_name == output.container)
+                .map(|logs| {
+                    logs.lines
+                        .iter()
+                        .map(|(_, line)| line.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            let values = output.format.parse(&text)?;
+            if values.is_empty() {
+                return Err(WorkloadError::NoResults {
+                    container: output.container.clone(),
+                    format: output.format,
+                });
+            }
+            let path = metrics_dir.join(format!("workload-{}.csv", output.container));
+            let mut writer = csv::Writer::from_path(&path)?;
+            for value in &values {
+                writer.serialize(WorkloadMetric {
+                    configuration: hash.to_owned(),
+                    repeat,
+                    container: output.container.clone(),
+                    operation: value.operation.clone(),
+                    metric: value.metric.clone(),
+                    value: value.value,
+                })?;
+            }
+            writer.flush()?;
+            debug!(?path, metrics = values.len(), "Wrote workload metrics");
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+fn value(operation: Option<&str>, metric: impl Into<String>, value: f64) -> WorkloadValue {
+    WorkloadValue {
+        operation: operation.map(str::to_owned),
+        metric: metric.into(),
+        value,
+    }
+}
+
+/// The name of a latency percentile metric, such as `latency_p99_9_ms` for `99.900`.
+fn percentile_metric(percentile: &str) -> Option<String> {
+    let percentile = percentile
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .ok()?;
+    Some(format!(
+        "latency_p{}_ms",
+        percentile.to_string().replace('.', "_")
+    ))
+}
+
+/// Parse a duration printed by wrk, such as `635.91us` or `1.00m`, in milliseconds.
+fn parse_millis(duration: &str) -> Option<f64> {
+    let split = duration.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = duration.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let millis = match unit {
+        "us" => 0.001,
+        "ms" => 1.0,
+        "s" => 1000.0,
+        "m" => 60_000.0,
+        "h" => 3_600_000.0,
+        _ => return None,
+    };
+    Some(number * millis)
+}
+
+/// Parse a size printed by wrk, such as `302.60MB`, in bytes.
+fn parse_bytes(size: &str) -> Option<f64> {
+    let split = size
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let power = match unit {
+        "" | "B" => 0,
+        "KB" => 1,
+        "MB" => 2,
+        "GB" => 3,
+        "TB" => 4,
+        _ => return None,
+    };
+    Some(number * 1024f64.powi(power))
+}
+
+/// Lines like `[READ], 95thPercentileLatency(us), 300`.
+fn parse_ycsb(output: &str) -> Vec<WorkloadValue> {
+    let mut values = Vec::new();
+    for line in output.lines() {
+        let fields = line.splitn(3, ',').map(str::trim).collect::<Vec<_>>();
+        let (section, name, number) = match fields[..] {
+            [section, name, number] => (section, name, number),
+            _ => continue,
+        };
+        let section = match section
+            .strip_prefix('[')
+            .and_then(|section| section.strip_suffix(']'))
+        {
+            Some(section) => section,
+            None => continue,
+        };
+        let number = match number.parse::<f64>() {
+            Ok(number) => number,
+            Err(_) => continue,
+        };
+        let operation = Some(section).filter(|section| *section != "OVERALL");
+        match name {
+            "Throughput(ops/sec)" => {
+                values.push(value(operation, "throughput_ops_per_second", number))
+            }
+            "RunTime(ms)" => values.push(value(operation, "runtime_seconds", number / 1000.0)),
+            "Operations" => values.push(value(operation, "operations", number)),
+            _ => {
+                let metric = match name.strip_suffix("Latency(us)") {
+                    Some("Average") => "latency_mean_ms".to_owned(),
+                    Some("Min") => "latency_min_ms".to_owned(),
+                    Some("Max") => "latency_max_ms".to_owned(),
+                    Some(stat) => match stat
+                        .strip_suffix("Percentile")
+                        .and_then(|percentile| percentile_metric(percentile.trim_end_matches("th")))
+                    {
+                        Some(metric) => metric,
+                        None => continue,
+                    },
+                    None => continue,
+                };
+                values.push(value(operation, metric, number / 1000.0));
+            }
+        }
+    }
+    values
+}
+
+/// The statistics sysbench prints after a run, like `transactions: 10000 (333.28 per sec.)`.
+fn parse_sysbench(output: &str) -> Vec<WorkloadValue> {
+    let first_number = |text: &str| text.split_whitespace().next()?.parse::<f64>().ok();
+    let per_second = |text: &str| {
+        let (_, rate) = text.split_once('(')?;
+        first_number(rate)
+    };
+    let mut values = Vec::new();
+    let mut in_latency = false;
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            in_latency = line.trim_start().starts_with("Latency (ms)");
+            continue;
+        }
+        let (key, text) = match line.split_once(':') {
+            Some((key, text)) => (key.trim(), text.trim()),
+            None => continue,
+        };
+        if in_latency {
+            let metric = match key {
+                "min" => "latency_min_ms".to_owned(),
+                "avg" => "latency_mean_ms".to_owned(),
+                "max" => "latency_max_ms".to_owned(),
+                key => match key
+                    .strip_suffix(" percentile")
+                    .and_then(|percentile| percentile_metric(percentile.trim_end_matches("th")))
+                {
+                    Some(metric) => metric,
+                    None => continue,
+                },
+            };
+            if let Some(number) = first_number(text) {
+                values.push(value(None, metric, number));
+            }
+            continue;
+        }
+        let (operation, metric, number) = match key {
+            "transactions" => (None, "throughput_ops_per_second", per_second(text)),
+            "events per second" => (None, "throughput_ops_per_second", first_number(text)),
+            "queries" => (
+                Some("queries"),
+                "throughput_ops_per_second",
+                per_second(text),
+            ),
+            "ignored errors" => (None, "errors", first_number(text)),
+            "total number of events" => (None, "operations", first_number(text)),
+            "total time" => (
+                None,
+                "runtime_seconds",
+                text.trim_end_matches('s').parse().ok(),
+            ),
+            _ => continue,
+        };
+        if let Some(number) = number {
+            values.push(value(operation, metric, number));
+        }
+    }
+    values
+}
+
+/// The summary wrk and wrk2 print, with the latency distribution from `--latency`.
+fn parse_wrk(output: &str) -> Vec<WorkloadValue> {
+    let mut values = Vec::new();
+    // the first distribution, as wrk2 can also print the uncorrected one
+    let mut in_distribution = false;
+    let mut seen_distribution = false;
+    for line in output.lines() {
+        let line = line.trim();
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if in_distribution {
+            match fields[..] {
+                [percentile, latency] if percentile.ends_with('%') => {
+                    if let (Some(metric), Some(latency)) =
+                        (percentile_metric(percentile), parse_millis(latency))
+                    {
+                        values.push(value(None, metric, latency));
+                    }
+                    continue;
+                }
+                _ => in_distribution = false,
+            }
+        }
+        if line.starts_with("Latency Distribution") {
+            in_distribution = !seen_distribution;
+            seen_distribution = true;
+        } else if let ["Latency", mean, _, max, ..] = fields[..] {
+            if let (Some(mean), Some(max)) = (parse_millis(mean), parse_millis(max)) {
+                values.push(value(None, "latency_mean_ms", mean));
+                values.push(value(None, "latency_max_ms", max));
+            }
+        } else if let Some(rate) = line.strip_prefix("Requests/sec:") {
+            if let Ok(rate) = rate.trim().parse() {
+                values.push(value(None, "throughput_ops_per_second", rate));
+            }
+        } else if let Some(rate) = line.strip_prefix("Transfer/sec:") {
+            if let Some(rate) = parse_bytes(rate.trim()) {
+                values.push(value(None, "throughput_bytes_per_second", rate));
+            }
+        } else if let Some(errors) = line.strip_prefix("Non-2xx or 3xx responses:") {
+            if let Ok(errors) = errors.trim().parse() {
+                values.push(value(None, "errors", errors));
+            }
+        } else if let [requests, "requests", "in", duration, ..] = fields[..] {
+            if let Ok(requests) = requests.parse() {
+                values.push(value(None, "operations", requests));
+            }
+            if let Some(duration) = parse_millis(duration.trim_end_matches(',')) {
+                values.push(value(None, "runtime_seconds", duration / 1000.0));
+            }
+        }
+    }
+    values
+}
+
+/// The JSON fio prints with `--output-format=json`, after anything printed before it.
+fn parse_fio(output: &str) -> Result<Vec<WorkloadValue>, WorkloadError> {
+    let start = match output.find('{') {
+        Some(start) => start,
+        None => return Ok(Vec::new()),
+    };
+    let report = match serde_json::Deserializer::from_str(&output[start..])
+        .into_iter::<Value>()
+        .next()
+    {
+        Some(report) => report?,
+        None => return Ok(Vec::new()),
+    };
+    let jobs = match report["jobs"].as_array() {
+        Some(jobs) => jobs,
+        None => return Ok(Vec::new()),
+    };
+    let mut values = Vec::new();
+    for job in jobs {
+        for direction in ["read", "write", "trim"] {
+            let stats = &job[direction];
+            if stats["io_bytes"].as_u64().unwrap_or(0) == 0 {
+                continue;
+            }
+            let operation = if jobs.len() == 1 {
+                direction.to_owned()
+            } else {
+                format!(
+                    "{}.{}",
+                    job["jobname"].as_str().unwrap_or_default(),
+                    direction
+                )
+            };
+            let mut push = |metric: &str, number: Option<f64>| {
+                if let Some(number) = number {
+                    values.push(value(Some(&operation), metric, number));
+                }
+            };
+            push("throughput_ops_per_second", stats["iops"].as_f64());
+            push("throughput_bytes_per_second", stats["bw_bytes"].as_f64());
+            push("operations", stats["total_ios"].as_f64());
+            let millis = |nanos: &Value| nanos.as_f64().map(|nanos| nanos / 1e6);
+            push("latency_mean_ms", millis(&stats["lat_ns"]["mean"]));
+            push("latency_min_ms", millis(&stats["lat_ns"]["min"]));
+            push("latency_max_ms", millis(&stats["lat_ns"]["max"]));
+            if let Some(percentiles) = stats["clat_ns"]["percentile"].as_object() {
+                for (percentile, latency) in percentiles {
+                    if let Some(metric) = percentile_metric(percentile) {
+                        push(&metric, millis(latency));
+                    }
+                }
+            }
+        }
+    }
+    Ok(values)
+}
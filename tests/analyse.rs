@@ -0,0 +1,213 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all, write, File},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use exp::results::ConfigurationState;
+use exp::{
+    AnalyseError, AnalysisDirs, AnalysisInputs, Environment, ExpResult, Experiment,
+    ExperimentConfiguration, IncompletePolicy, Measurements,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        Vec::new()
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(&mut self, _: &Self::Configuration, _: &Path, _: &Measurements) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        dirs: &AnalysisDirs,
+        _environment: Environment,
+        configurations: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        for (_, dir) in &configurations {
+            write(dirs.configuration_dir(dir)?.join("plot.svg"), "")?;
+        }
+        let total: u32 = configurations.iter().map(|(c, _)| c.n).sum();
+        Ok(serde_json::json!({ "total": total }))
+    }
+}
+
+/// Create a results directory with configurations of `n` 1 and 2, named `a` and `b`.
+async fn results(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = remove_dir_all(&dir);
+    let results_dir = dir.join("results");
+    // running without any configurations collects the environment
+    let run_config = exp::RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+    for (hash, n) in [("a", 1), ("b", 2)] {
+        create_dir_all(results_dir.join(hash)).unwrap();
+        write(
+            results_dir.join(hash).join("configuration.json"),
+            format!(r#"{{"n":{}}}"#, n),
+        )
+        .unwrap();
+    }
+    results_dir
+}
+
+#[tokio::test]
+async fn analyse_records_outputs_and_inputs() {
+    let results_dir = results("exp-analyse-test").await;
+    let output_dir = results_dir.parent().unwrap().join("plots");
+
+    let config = exp::AnalyseConfig {
+        results_dir: results_dir.clone(),
+        output_dir: Some(output_dir.clone()),
+        incremental: false,
+        filter: None,
+        incomplete: exp::IncompletePolicy::Skip,
+    };
+    let summary = exp::analyse(&mut Exp, &config).await.unwrap();
+    assert_eq!(summary["total"], 3);
+    assert!(output_dir.join(exp::SUMMARY_FILE).exists());
+    assert!(output_dir.join("a").join("plot.svg").exists());
+    let inputs: AnalysisInputs =
+        serde_json::from_reader(File::open(output_dir.join(exp::INPUTS_FILE)).unwrap()).unwrap();
+    assert_eq!(inputs.results, vec!["a", "b"]);
+
+    // the default analysis directory is in the results directory and isn't a configuration
+    let config = exp::AnalyseConfig {
+        results_dir: results_dir.clone(),
+        output_dir: None,
+        incremental: false,
+        filter: None,
+        incomplete: exp::IncompletePolicy::Skip,
+    };
+    exp::analyse(&mut Exp, &config).await.unwrap();
+    exp::analyse(&mut Exp, &config).await.unwrap();
+    assert!(results_dir
+        .join(exp::ANALYSIS_DIR)
+        .join(exp::SUMMARY_FILE)
+        .exists());
+}
+
+#[tokio::test]
+async fn incremental_analysis_caches_configurations() {
+    let results_dir = results("exp-analyse-incremental-test").await;
+    let config = exp::AnalyseConfig {
+        results_dir: results_dir.clone(),
+        output_dir: None,
+        incremental: true,
+        filter: None,
+        incomplete: exp::IncompletePolicy::Skip,
+    };
+    let summary = exp::analyse(&mut Exp, &config).await.unwrap();
+    assert_eq!(summary["total"], 3);
+    assert!(results_dir
+        .join(exp::ANALYSIS_DIR)
+        .join(exp::CACHE_FILE)
+        .exists());
+
+    // unmodified configurations are read from the cache
+    let config_file = results_dir.join("a").join("configuration.json");
+    let modified = config_file.metadata().unwrap().modified().unwrap();
+    write(&config_file, r#"{"n":10}"#).unwrap();
+    File::options()
+        .write(true)
+        .open(&config_file)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let summary = exp::analyse(&mut Exp, &config).await.unwrap();
+    assert_eq!(summary["total"], 3);
+
+    // and modified ones are read again
+    File::options()
+        .write(true)
+        .open(&config_file)
+        .unwrap()
+        .set_modified(modified + Duration::from_secs(1))
+        .unwrap();
+    let summary = exp::analyse(&mut Exp, &config).await.unwrap();
+    assert_eq!(summary["total"], 12);
+}
+
+#[tokio::test]
+async fn analyse_filtered_configurations() {
+    let results_dir = results("exp-analyse-filter-test").await;
+    let config = exp::AnalyseConfig {
+        results_dir: results_dir.clone(),
+        output_dir: None,
+        incremental: false,
+        filter: Some(Box::new(|c| c["n"] == 2)),
+        incomplete: exp::IncompletePolicy::Skip,
+    };
+    let summary = exp::analyse(&mut Exp, &config).await.unwrap();
+    assert_eq!(summary["total"], 2);
+    let inputs: AnalysisInputs = serde_json::from_reader(
+        File::open(results_dir.join(exp::ANALYSIS_DIR).join(exp::INPUTS_FILE)).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(inputs.results, vec!["b"]);
+}
+
+#[tokio::test]
+async fn analyse_with_incomplete_runs() {
+    let results_dir = results("exp-analyse-incomplete-test").await;
+    create_dir_all(results_dir.join("c.failed")).unwrap();
+    write(
+        results_dir.join("c.failed").join("configuration.json"),
+        r#"{"n":4}"#,
+    )
+    .unwrap();
+    // interrupted before writing its configuration
+    create_dir_all(results_dir.join("d.running")).unwrap();
+
+    let config = |incomplete| exp::AnalyseConfig {
+        results_dir: results_dir.clone(),
+        output_dir: None,
+        incremental: false,
+        filter: None,
+        incomplete,
+    };
+    let summary = exp::analyse(&mut Exp, &config(IncompletePolicy::Skip))
+        .await
+        .unwrap();
+    assert_eq!(summary["total"], 3);
+
+    let summary = exp::analyse(&mut Exp, &config(IncompletePolicy::Include))
+        .await
+        .unwrap();
+    assert_eq!(summary["total"], 7);
+    assert_eq!(
+        ConfigurationState::of(&results_dir.join("c.failed")),
+        Some(ConfigurationState::Failed)
+    );
+
+    match exp::analyse(&mut Exp, &config(IncompletePolicy::Fail)).await {
+        Err(AnalyseError::Incomplete(names)) => assert_eq!(names, ["c.failed", "d.running"]),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
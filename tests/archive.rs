@@ -0,0 +1,43 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all, write},
+    path::Path,
+};
+
+fn write_config(results_dir: &Path, hash: &str, config: &str) {
+    let dir = results_dir.join(hash);
+    create_dir_all(dir.join("metrics")).unwrap();
+    write(dir.join("configuration.json"), config).unwrap();
+    write(dir.join("metrics").join("stats.csv"), "a,b\n1,2\n").unwrap();
+}
+
+#[test]
+fn export_import_merges_results() {
+    let dir = std::env::temp_dir().join("exp-archive-test");
+    let _ = remove_dir_all(&dir);
+    let source = dir.join("source");
+    let dest = dir.join("dest");
+    create_dir_all(&source).unwrap();
+    write(source.join("environment.json"), "{}").unwrap();
+    write_config(&source, "a", r#"{"n":1}"#);
+    write_config(&source, "b", r#"{"n":2}"#);
+    write_config(&source, "c", r#"{"n":3}"#);
+    write_config(&source, "skip", r#"{"n":4}"#);
+    create_dir_all(source.join("d.running")).unwrap();
+
+    let archive = dir.join("results.tar.zst");
+    let mut exported = exp::archive::export(&source, &archive, |config| config["n"] != 4).unwrap();
+    exported.exported.sort();
+    assert_eq!(exported.exported, vec!["a", "b", "c"]);
+
+    write_config(&dest, "a", r#"{"n":1}"#);
+    write_config(&dest, "b", r#"{"n":5}"#);
+    let mut imported = exp::archive::import(&archive, &dest).unwrap();
+    imported.imported.sort();
+    assert_eq!(imported.imported, vec!["c"]);
+    assert_eq!(imported.duplicates, vec!["a"]);
+    assert_eq!(imported.collisions, vec!["b"]);
+    assert!(dest.join("environment.json").exists());
+    assert!(dest.join("c").join("metrics").join("stats.csv").exists());
+    assert!(!dest.join("skip").exists());
+    assert!(!dest.join("d.running").exists());
+}
@@ -0,0 +1,168 @@
+use std::{
+    fs::{remove_dir_all, write, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use exp::{
+    disk_usage, results::list_configurations, AnalysisDirs, ArtifactQuota, DirSize, Environment,
+    ExpResult, Experiment, ExperimentConfiguration, Measurements, QuotaAction, RunConfig,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    bytes: usize,
+    /// Keep writing, like a runaway log.
+    runaway: bool,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![
+            Config {
+                bytes: 100,
+                runaway: false,
+            },
+            Config {
+                bytes: 5000,
+                runaway: false,
+            },
+            Config {
+                bytes: 1000,
+                runaway: true,
+            },
+        ]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        let data = vec![b'x'; configuration.bytes];
+        if !configuration.runaway {
+            write(configuration_dir.join("output"), data)?;
+            return Ok(());
+        }
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(configuration_dir.join("runaway.log"))?;
+        for _ in 0..100 {
+            log.write_all(&data)?;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+fn states(results_dir: &Path) -> Vec<(String, String)> {
+    let mut states = list_configurations(results_dir)
+        .unwrap()
+        .into_iter()
+        .map(|entry| (entry.hash, entry.state.to_string()))
+        .collect::<Vec<_>>();
+    states.sort();
+    states
+}
+
+#[tokio::test]
+async fn quota_fails_runs() {
+    let results_dir = PathBuf::from("results/artifacts");
+    let _ = remove_dir_all(&results_dir);
+    let mut quota = ArtifactQuota::new(2000, QuotaAction::Fail);
+    quota.interval = Some(Duration::from_millis(20));
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .artifact_quota(quota)
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+    // the runaway run is stopped rather than left to write for 5 seconds
+    assert!(start.elapsed() < Duration::from_secs(3));
+
+    let small = Config {
+        bytes: 100,
+        runaway: false,
+    }
+    .hash_serialized()
+    .unwrap();
+    let large = Config {
+        bytes: 5000,
+        runaway: false,
+    }
+    .hash_serialized()
+    .unwrap();
+    let runaway = Config {
+        bytes: 1000,
+        runaway: true,
+    }
+    .hash_serialized()
+    .unwrap();
+    let mut expected = vec![
+        (small.clone(), "completed".to_owned()),
+        (large.clone(), "failed".to_owned()),
+        (runaway.clone(), "failed".to_owned()),
+    ];
+    expected.sort();
+    assert_eq!(states(&results_dir), expected);
+
+    let size = DirSize::from_configuration(&results_dir.join(&small)).unwrap();
+    assert!(size.bytes >= 100 && size.bytes < 2000);
+    let size = DirSize::from_configuration(&results_dir.join(format!("{}.failed", large))).unwrap();
+    assert!(size.bytes >= 5000);
+
+    let usage = disk_usage(&results_dir).unwrap();
+    let large_usage = usage
+        .iter()
+        .find(|entry| entry.path == results_dir.join(format!("{}.failed", large)))
+        .unwrap();
+    assert!(large_usage.size.bytes >= 5000);
+    assert!(large_usage.size.files >= 2);
+    assert!(usage.windows(2).all(|w| w[0].size.bytes >= w[1].size.bytes));
+}
+
+#[tokio::test]
+async fn quota_warns() {
+    let results_dir = PathBuf::from("results/artifacts-warn");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .artifact_quota(ArtifactQuota::new(2000, QuotaAction::Warn))
+        .build()
+        .unwrap();
+    let mut experiment = Exp;
+    exp::run(&mut experiment, &run_config).await.unwrap();
+    assert!(states(&results_dir)
+        .iter()
+        .all(|(_, state)| state == "completed"));
+}
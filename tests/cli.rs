@@ -0,0 +1,175 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use exp::{
+    cli::{ExpArgs, Mode},
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, Measurements,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    nodes: u32,
+    name: String,
+}
+
+impl ExperimentConfiguration for Config {}
+
+#[derive(Default)]
+struct Exp {
+    analysed: Vec<u32>,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        (1..=3)
+            .map(|nodes| Config {
+                nodes,
+                name: format!("n{}", nodes),
+            })
+            .collect()
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(&mut self, _: &Self::Configuration, _: &Path, _: &Measurements) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        configurations: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        self.analysed = configurations.iter().map(|(c, _)| c.nodes).collect();
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[test]
+fn parse_args() {
+    let args = ExpArgs::try_parse_from([
+        "experiment",
+        "run",
+        "--results-dir",
+        "results/cli",
+        "--repeats",
+        "2",
+        "--timeout",
+        "1.5",
+        "--filter",
+        "nodes=2",
+        "--filter",
+        "name=n2",
+    ])
+    .unwrap();
+    assert_eq!(args.mode, Mode::Run);
+    assert_eq!(args.filter.len(), 2);
+    assert_eq!(args.filter[0], ("nodes".to_owned(), serde_json::json!(2)));
+    assert_eq!(args.filter[1], ("name".to_owned(), serde_json::json!("n2")));
+    let config = args.run_config().unwrap();
+    assert_eq!(config.results_dir, PathBuf::from("results/cli"));
+    assert_eq!(config.repeats, 2);
+    assert_eq!(config.timeout, Some(Duration::from_millis(1500)));
+
+    let args = ExpArgs::try_parse_from(["experiment"]).unwrap();
+    assert_eq!(args.mode, Mode::All);
+    assert_eq!(
+        args.run_config().unwrap().results_dir,
+        PathBuf::from("results")
+    );
+
+    assert!(ExpArgs::try_parse_from(["experiment", "--filter", "nodes"]).is_err());
+    assert!(ExpArgs::try_parse_from(["experiment", "--repeats", "0"])
+        .unwrap()
+        .run_config()
+        .is_err());
+}
+
+#[tokio::test]
+async fn execute_filtered() {
+    let results_dir = PathBuf::from("results/cli");
+    let _ = remove_dir_all(&results_dir);
+    let args = ExpArgs::try_parse_from([
+        "experiment",
+        "--results-dir",
+        "results/cli",
+        "--filter",
+        "nodes=2",
+    ])
+    .unwrap();
+    let mut experiment = Exp::default();
+    args.execute(&mut experiment).await.unwrap();
+    assert_eq!(experiment.analysed, vec![2]);
+
+    // analysing all that was run
+    let args =
+        ExpArgs::try_parse_from(["experiment", "analyse", "--results-dir", "results/cli"]).unwrap();
+    args.execute(&mut experiment).await.unwrap();
+    assert_eq!(experiment.analysed, vec![2]);
+}
+
+#[tokio::test]
+async fn execute_overrides() {
+    let results_dir = PathBuf::from("results/cli-overrides");
+    let _ = remove_dir_all(&results_dir);
+    let args = ExpArgs::try_parse_from([
+        "experiment",
+        "--results-dir",
+        "results/cli-overrides",
+        "--set",
+        "name=custom",
+        "--filter",
+        "nodes=3",
+    ])
+    .unwrap();
+    let mut experiment = Exp::default();
+    args.execute(&mut experiment).await.unwrap();
+    assert_eq!(experiment.analysed, vec![3]);
+    let hash = Config {
+        nodes: 3,
+        name: "custom".to_owned(),
+    }
+    .hash_serialized()
+    .unwrap();
+    assert!(results_dir.join(hash).is_dir());
+
+    // fields that don't deserialize fail the run
+    let args = ExpArgs::try_parse_from([
+        "experiment",
+        "run",
+        "--results-dir",
+        "results/cli-overrides",
+        "--set",
+        "nodes=many",
+    ])
+    .unwrap();
+    assert!(args.execute(&mut experiment).await.is_err());
+    let args = ExpArgs::try_parse_from([
+        "experiment",
+        "run",
+        "--results-dir",
+        "results/cli-overrides",
+        "--set",
+        "missing.field=1",
+    ])
+    .unwrap();
+    assert!(args.execute(&mut experiment).await.is_err());
+}
@@ -0,0 +1,102 @@
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, write},
+    path::Path,
+};
+
+use exp::{compare_with, CompareConfig, MatchBy, Metrics};
+
+/// Write the runs of a configuration, as `<hash>`, `<hash>-1`, ... with the latencies of each
+/// run.
+fn write_runs(dir: &Path, hash: &str, configuration: &str, runs: &[&[f64]]) {
+    for (repeat, latencies) in runs.iter().enumerate() {
+        let config_dir = match repeat {
+            0 => dir.join(hash),
+            repeat => dir.join(format!("{}-{}", hash, repeat)),
+        };
+        create_dir_all(&config_dir).unwrap();
+        write(config_dir.join("configuration.json"), configuration).unwrap();
+        let mut metrics = Metrics::new();
+        metrics.insert("latency".to_owned(), latencies.to_vec());
+        metrics.insert(
+            "throughput".to_owned(),
+            vec![[100.0, 101.0, 99.0][repeat % 3]; 2],
+        );
+        write(
+            config_dir.join("metrics.json"),
+            serde_json::to_string(&metrics).unwrap(),
+        )
+        .unwrap();
+    }
+}
+
+fn load(dir: &Path) -> exp::ExpResult<Metrics> {
+    Ok(serde_json::from_str(&read_to_string(
+        dir.join("metrics.json"),
+    )?)?)
+}
+
+#[test]
+fn compare_finds_regressions() {
+    let dir = std::env::temp_dir().join("exp-compare-test");
+    let _ = remove_dir_all(&dir);
+    let (a, b) = (dir.join("a"), dir.join("b"));
+    write_runs(
+        &a,
+        "a1",
+        r#"{"nodes":1,"image":"v1"}"#,
+        &[&[10.0, 11.0], &[10.5, 9.5], &[10.2, 10.0]],
+    );
+    write_runs(
+        &a,
+        "a3",
+        r#"{"nodes":3,"image":"v1"}"#,
+        &[&[20.0, 21.0], &[19.5, 19.5], &[20.5, 19.5]],
+    );
+    write_runs(&a, "a5", r#"{"nodes":5,"image":"v1"}"#, &[&[30.0]]);
+    write_runs(
+        &b,
+        "b1",
+        r#"{"nodes":1,"image":"v2"}"#,
+        &[&[10.1, 10.9], &[10.4, 9.6], &[10.0, 10.3]],
+    );
+    write_runs(
+        &b,
+        "b3",
+        r#"{"nodes":3,"image":"v2"}"#,
+        &[&[30.0, 31.0], &[29.5, 29.5], &[30.5, 29.5]],
+    );
+
+    // the hashes differ so nothing matches
+    let comparison = compare_with(&a, &b, &CompareConfig::default(), load).unwrap();
+    assert!(comparison.deltas.is_empty());
+    assert_eq!(comparison.only_a, ["a1", "a3", "a5"]);
+    assert_eq!(comparison.only_b, ["b1", "b3"]);
+
+    let config = CompareConfig {
+        match_by: MatchBy::Fields(vec!["image".to_owned()]),
+        higher_is_better: vec!["throughput".to_owned()],
+        ..CompareConfig::default()
+    };
+    let comparison = compare_with(&a, &b, &config, load).unwrap();
+    assert_eq!(comparison.deltas.len(), 4);
+    assert_eq!(comparison.only_a, ["a5"]);
+    assert!(comparison.only_b.is_empty());
+    let regressions = comparison.regressions().collect::<Vec<_>>();
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].hash_a, "a3");
+    assert_eq!(regressions[0].hash_b, "b3");
+    assert_eq!(regressions[0].metric, "latency");
+    assert!((regressions[0].change - 0.5).abs() < 1e-9);
+    // one value per run rather than per sample
+    assert_eq!(regressions[0].a.count, 3);
+    assert!(regressions[0].p_value.unwrap() < 0.001);
+
+    let unchanged = comparison
+        .deltas
+        .iter()
+        .find(|delta| delta.hash_a == "a1" && delta.metric == "latency")
+        .unwrap();
+    assert!(unchanged.p_value.unwrap() > 0.5);
+
+    remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,142 @@
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+use exp::{docker_runner::HostPort, ComposeError, Topology};
+
+#[test]
+fn from_compose() {
+    let dir = std::env::temp_dir().join("exp-compose-test").join("shop");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let path = dir.join("docker-compose.yml");
+    write(
+        &path,
+        r#"version: "3.8"
+services:
+  db:
+    image: postgres:15-alpine
+    environment:
+      POSTGRES_PASSWORD: secret
+      POSTGRES_PORT: 5432
+    volumes:
+      - data:/var/lib/postgresql/data
+      - ./init.sql:/docker-entrypoint-initdb.d/init.sql:ro
+    healthcheck:
+      test: ["CMD", "pg_isready"]
+  web:
+    image: localhost:5000/shop/web
+    container_name: shop-web
+    command: serve --port 8080
+    environment:
+      - DATABASE_URL=postgres://db/shop
+    ports:
+      - "8080:8080"
+      - 9090
+      - "127.0.0.1:9091:9091/tcp"
+    networks:
+      - front
+    depends_on:
+      - db
+volumes:
+  data: {}
+networks:
+  front: {}
+"#,
+    )
+    .unwrap();
+
+    let topology = Topology::from_compose(&path).unwrap();
+    assert_eq!(topology.containers.len(), 2);
+
+    let db = &topology.containers[0];
+    assert_eq!(db.name, "db");
+    assert_eq!(
+        (db.image_name.as_str(), db.image_tag.as_str()),
+        ("postgres", "15-alpine")
+    );
+    assert_eq!(db.network.as_deref(), Some("shop_default"));
+    assert_eq!(
+        db.env,
+        Some(vec![
+            "POSTGRES_PASSWORD=secret".to_owned(),
+            "POSTGRES_PORT=5432".to_owned()
+        ])
+    );
+    assert_eq!(
+        db.named_volumes,
+        vec![("data".to_owned(), "/var/lib/postgresql/data".to_owned())]
+    );
+    let init = dir.canonicalize().unwrap().join("init.sql");
+    assert!(db.volumes.is_empty());
+    assert_eq!(
+        db.read_only_volumes,
+        vec![(
+            init.to_string_lossy().into_owned(),
+            "/docker-entrypoint-initdb.d/init.sql".to_owned()
+        )]
+    );
+    assert!(db.ports.is_none());
+
+    let web = &topology.containers[1];
+    assert_eq!(web.name, "shop-web");
+    assert_eq!(
+        (web.image_name.as_str(), web.image_tag.as_str()),
+        ("localhost:5000/shop/web", "latest")
+    );
+    assert_eq!(web.network.as_deref(), Some("front"));
+    assert_eq!(
+        web.command,
+        Some(vec![
+            "serve".to_owned(),
+            "--port".to_owned(),
+            "8080".to_owned()
+        ])
+    );
+    assert_eq!(
+        web.ports,
+        Some(vec![
+            (HostPort::Fixed("8080".to_owned()), "8080".to_owned()),
+            (HostPort::Auto, "9090".to_owned()),
+            (HostPort::Fixed("9091".to_owned()), "9091".to_owned()),
+        ])
+    );
+
+    write(
+        &path,
+        "services:\n  dns:\n    image: coredns/coredns\n    ports:\n      - \"53:53/udp\"\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        Topology::from_compose(&path),
+        Err(ComposeError::InvalidPort { .. })
+    ));
+
+    write(
+        &path,
+        "services:\n  web:\n    image: nginx\n    ports:\n      - \"8000-8010:8000-8010\"\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        Topology::from_compose(&path),
+        Err(ComposeError::InvalidPort { .. })
+    ));
+
+    write(
+        &path,
+        "services:\n  db:\n    image: postgres\n    volumes:\n      - data:/var/lib/postgresql/data:ro\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        Topology::from_compose(&path),
+        Err(ComposeError::InvalidVolume { .. })
+    ));
+
+    write(
+        &path,
+        "services:\n  web:\n    image: nginx\n    command: sh -c 'nginx -g \"daemon off;\"'\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        Topology::from_compose(&path),
+        Err(ComposeError::QuotedCommand(_))
+    ));
+}
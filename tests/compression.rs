@@ -0,0 +1,35 @@
+use std::{fs::File, io::Write};
+
+use exp::{compression::compress_dir, docker_runner::Logs, CompressionConfig};
+
+#[test]
+fn logs_from_compressed_file() {
+    let dir = std::env::temp_dir().join("exp-compression-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let logs_dir = dir.join("logs");
+    std::fs::create_dir_all(&logs_dir).unwrap();
+    let path = logs_dir.join("docker-exp-test.log");
+    let mut file = File::create(&path).unwrap();
+    writeln!(file, "2022-06-01T12:00:00.000000000Z started").unwrap();
+    writeln!(file, "2022-06-01T12:00:01.500000000Z stopped").unwrap();
+    drop(file);
+    let small = dir.join("small.log");
+    File::create(&small).unwrap();
+
+    let config = CompressionConfig {
+        threshold: 1,
+        ..Default::default()
+    };
+    compress_dir(&dir, &config).unwrap();
+    assert!(!path.exists());
+    assert!(logs_dir.join("docker-exp-test.log.zst").exists());
+    assert!(small.exists());
+
+    let logs = Logs::from_file(&path).unwrap();
+    assert_eq!(logs.container_name, "exp-test");
+    assert_eq!(logs.lines.len(), 2);
+    assert_eq!(logs.lines[1].1, "stopped");
+
+    let logs = Logs::from_file(&logs_dir.join("docker-exp-test.log.zst")).unwrap();
+    assert_eq!(logs.container_name, "exp-test");
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::{create_dir_all, write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use exp::{AnalyseConfig, ConfigFileError, IncompletePolicy, RunConfig};
+
+fn config_dir() -> PathBuf {
+    let dir = PathBuf::from("results/config_file");
+    create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn run_config_from_toml() {
+    let path = config_dir().join("run.toml");
+    std::env::set_var("EXP_CONFIG_FILE_RESULTS", "results/from-env");
+    write(
+        &path,
+        r#"
+results_dir = "${EXP_CONFIG_FILE_RESULTS}/run"
+store_dir = "${EXP_CONFIG_FILE_UNSET:-results/store}"
+repeats = 3
+timeout = 2.5
+provenance_repos = ["cost$$"]
+"#,
+    )
+    .unwrap();
+    let config = RunConfig::from_file(&path).unwrap();
+    assert_eq!(config.results_dir, PathBuf::from("results/from-env/run"));
+    assert_eq!(config.store_dir, Some(PathBuf::from("results/store")));
+    assert_eq!(config.repeats, 3);
+    assert_eq!(config.timeout, Some(Duration::from_millis(2500)));
+    assert_eq!(config.provenance_repos, vec![PathBuf::from("cost$")]);
+}
+
+#[test]
+fn run_config_from_yaml() {
+    let path = config_dir().join("run.yaml");
+    write(
+        &path,
+        "results_dir: results/yaml\nforce_rerun: true\nstore_dir: results/store\n",
+    )
+    .unwrap();
+    let config = RunConfig::from_file(&path).unwrap();
+    assert_eq!(config.results_dir, PathBuf::from("results/yaml"));
+    assert!(config.force_rerun);
+    assert_eq!(config.repeats, 1);
+
+    // settings are still validated
+    write(&path, "results_dir: results/yaml\nforce_rerun: true\n").unwrap();
+    assert!(matches!(
+        RunConfig::from_file(&path),
+        Err(ConfigFileError::Invalid(_))
+    ));
+}
+
+#[test]
+fn config_file_errors() {
+    let dir = config_dir();
+    let path = dir.join("missing-var.toml");
+    write(&path, "results_dir = \"${EXP_CONFIG_FILE_UNSET}\"\n").unwrap();
+    assert!(matches!(
+        RunConfig::from_file(&path),
+        Err(ConfigFileError::MissingVariable(name)) if name == "EXP_CONFIG_FILE_UNSET"
+    ));
+
+    let path = dir.join("run.json");
+    write(&path, "{}").unwrap();
+    assert!(matches!(
+        RunConfig::from_file(&path),
+        Err(ConfigFileError::UnknownFormat(_))
+    ));
+
+    let path = dir.join("typo.toml");
+    write(&path, "results_dir = \"results\"\nrepeat = 2\n").unwrap();
+    assert!(matches!(
+        RunConfig::from_file(&path),
+        Err(ConfigFileError::Toml(_))
+    ));
+}
+
+#[test]
+fn analyse_config_from_file() {
+    let path = config_dir().join("analyse.yml");
+    write(
+        &path,
+        "results_dir: results/analyse\nincremental: true\nincomplete: include\n",
+    )
+    .unwrap();
+    let config = AnalyseConfig::from_file(&path).unwrap();
+    assert_eq!(config.results_dir, PathBuf::from("results/analyse"));
+    assert_eq!(config.output_dir, None);
+    assert!(config.incremental);
+    assert!(config.filter.is_none());
+    assert_eq!(config.incomplete, IncompletePolicy::Include);
+}
@@ -0,0 +1,148 @@
+#![cfg(feature = "polars")]
+
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+use exp::data::{
+    convert_to_parquet, export_parquet, load_container_stats, load_container_top,
+    load_process_monitor,
+};
+use polars::prelude::*;
+
+#[test]
+fn load_stats_with_types() {
+    let dir = std::env::temp_dir().join("exp-data-test");
+    let _ = remove_dir_all(&dir);
+    let metrics_dir = dir.join("metrics");
+    create_dir_all(metrics_dir.join("load")).unwrap();
+    let header = "read,preread,num_procs,networks_name,memory_stats_usage\n";
+    write(
+        metrics_dir.join("docker-a-stat.csv"),
+        format!(
+            "{}2023-06-01T10:00:00Z,2023-06-01T09:59:59Z,0,eth0,1024\n",
+            header
+        ),
+    )
+    .unwrap();
+    write(
+        metrics_dir.join("load").join("docker-a-stat.csv"),
+        format!(
+            "{}2023-06-01T10:00:01Z,2023-06-01T10:00:00Z,0,,\n2023-06-01T10:00:02Z,2023-06-01T10:00:01Z,0,eth0,2048\n",
+            header
+        ),
+    )
+    .unwrap();
+    write(
+        metrics_dir.join("process-b.csv"),
+        "time,pid,parent,cpu_usage_percentage,memory_usage_bytes,virtual_memory_usage_bytes,disk_bytes_written,disk_bytes_read,name\n2023-06-01T10:00:00Z,10,1,12.5,100,200,0,0,b\n",
+    )
+    .unwrap();
+
+    let stats = load_container_stats(&dir).unwrap();
+    assert_eq!(stats.height(), 3);
+    assert_eq!(
+        stats.column("read").unwrap().dtype(),
+        &DataType::Datetime(TimeUnit::Milliseconds, Some("UTC".to_owned()))
+    );
+    assert_eq!(
+        stats.column("memory_stats_usage").unwrap().dtype(),
+        &DataType::UInt64
+    );
+    assert_eq!(stats.column("memory_stats_usage").unwrap().null_count(), 1);
+    assert_eq!(
+        stats.column("container").unwrap().utf8().unwrap().get(0),
+        Some("a")
+    );
+    assert_eq!(stats.column("phase").unwrap().null_count(), 1);
+
+    let processes = load_process_monitor(&metrics_dir.join("process-b.csv")).unwrap();
+    assert_eq!(processes.height(), 1);
+    assert_eq!(
+        processes.column("cpu_usage_percentage").unwrap().dtype(),
+        &DataType::Float32
+    );
+    assert_eq!(processes.column("pid").unwrap().dtype(), &DataType::UInt32);
+}
+
+#[test]
+fn export_metrics_to_parquet() {
+    let dir = std::env::temp_dir().join("exp-parquet-test");
+    let _ = remove_dir_all(&dir);
+    let metrics_dir = dir.join("results").join("abc").join("metrics");
+    create_dir_all(&metrics_dir).unwrap();
+    write(
+        dir.join("results").join("abc").join("configuration.json"),
+        "{}",
+    )
+    .unwrap();
+    write(
+        metrics_dir.join("docker-a-top.csv"),
+        "USER,PID,%CPU,%MEM,VSZ,RSS,TTY,STAT,START,TIME,COMMAND,timestamp_nanos\nroot,1,0.5,0.1,1000,200,?,Ss,10:00,0:00,sleep 10,1685613600000000000\n",
+    )
+    .unwrap();
+
+    let top = load_container_top(&dir.join("results").join("abc")).unwrap();
+    assert_eq!(top.column("%CPU").unwrap().dtype(), &DataType::Float32);
+    assert_eq!(
+        top.column("timestamp_nanos").unwrap().dtype(),
+        &DataType::Datetime(TimeUnit::Nanoseconds, Some("UTC".to_owned()))
+    );
+
+    let written = export_parquet(&dir.join("results"), &dir.join("parquet")).unwrap();
+    assert_eq!(
+        written,
+        vec![dir
+            .join("parquet")
+            .join("container_top")
+            .join("abc")
+            .join("data.parquet")]
+    );
+    assert!(written[0].exists());
+}
+
+#[test]
+fn convert_metrics_to_parquet() {
+    let dir = std::env::temp_dir().join("exp-convert-parquet-test");
+    let _ = remove_dir_all(&dir);
+    let metrics_dir = dir.join("metrics");
+    create_dir_all(metrics_dir.join("load")).unwrap();
+    write(
+        metrics_dir.join("load").join("docker-a-stat.csv"),
+        "read,preread,num_procs,networks_name,memory_stats_usage\n2023-06-01T10:00:01Z,2023-06-01T10:00:00Z,0,,\n2023-06-01T10:00:02Z,2023-06-01T10:00:01Z,0,eth0,2048\n",
+    )
+    .unwrap();
+    write(
+        metrics_dir.join("docker-a-network.csv"),
+        "read,interface,rx_bytes,rx_bytes_per_second\n2023-06-01T10:00:01Z,eth0,100,\n",
+    )
+    .unwrap();
+    write(metrics_dir.join("phases.csv"), "name,start\n").unwrap();
+
+    let mut written = convert_to_parquet(&dir).unwrap();
+    written.sort();
+    assert_eq!(
+        written,
+        vec![
+            metrics_dir.join("docker-a-network.parquet"),
+            metrics_dir.join("load").join("docker-a-stat.parquet"),
+        ]
+    );
+    assert!(!metrics_dir.join("docker-a-network.csv").exists());
+    assert!(!metrics_dir.join("load").join("docker-a-stat.csv").exists());
+    assert!(metrics_dir.join("phases.csv").exists());
+
+    let stats = load_container_stats(&dir).unwrap();
+    assert_eq!(stats.height(), 2);
+    assert_eq!(
+        stats.column("read").unwrap().dtype(),
+        &DataType::Datetime(TimeUnit::Milliseconds, Some("UTC".to_owned()))
+    );
+    assert_eq!(stats.column("memory_stats_usage").unwrap().null_count(), 1);
+    assert_eq!(
+        stats.column("container").unwrap().utf8().unwrap().get(0),
+        Some("a")
+    );
+    assert_eq!(
+        stats.column("phase").unwrap().utf8().unwrap().get(0),
+        Some("load")
+    );
+}
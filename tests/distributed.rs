@@ -0,0 +1,232 @@
+use std::{
+    fs::remove_dir_all,
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use exp::{
+    run_coordinator, run_worker, AnalysisDirs, Environment, ExpResult, Experiment,
+    ExperimentConfiguration, Measurements, RunConfig, RunHooks,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    name: String,
+    fail: bool,
+    after: Option<String>,
+}
+
+impl ExperimentConfiguration for Config {
+    fn dependencies(&self) -> Vec<String> {
+        self.after
+            .iter()
+            .map(|name| config(name, true, None).hash_serialized().unwrap())
+            .collect()
+    }
+}
+
+fn config(name: &str, fail: bool, after: Option<&str>) -> Config {
+    Config {
+        name: name.to_owned(),
+        fail,
+        after: after.map(str::to_owned),
+    }
+}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![
+            config("base", true, None),
+            config("dependent", false, Some("base")),
+            config("other", false, None),
+        ]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        if configuration.fail {
+            Err("failed".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+fn run_config(results_dir: &Path) -> RunConfig {
+    RunConfig::builder()
+        .results_dir(results_dir.to_owned())
+        .build()
+        .unwrap()
+}
+
+fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn connect(addr: SocketAddr) -> TcpStream {
+    loop {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    }
+}
+
+/// Coordinate a run with a worker, after the given worker has finished.
+async fn coordinate(
+    name: &str,
+    addr: SocketAddr,
+    worker: impl std::future::Future<Output = ()>,
+) -> PathBuf {
+    let results_dir = PathBuf::from("results/distributed").join(name);
+    let coordinator_config = run_config(&results_dir.join("coordinator"));
+    let worker_dir = results_dir.join("worker");
+    let _ = remove_dir_all(&worker_dir);
+    let worker_config = run_config(&worker_dir);
+    let (mut coordinator, mut local_worker) = (Exp, Exp);
+    let (coordinated, ()) = tokio::join!(
+        run_coordinator(&mut coordinator, &coordinator_config, addr),
+        async {
+            worker.await;
+            connect(addr).await;
+            run_worker(&mut local_worker, &worker_config, addr)
+                .await
+                .unwrap();
+        }
+    );
+    coordinated.unwrap();
+    results_dir.join("coordinator")
+}
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl RunHooks for Recorder {
+    async fn before_all(&self, _: &Path) -> ExpResult<()> {
+        self.0.lock().unwrap().push("before_all".to_owned());
+        Ok(())
+    }
+
+    async fn before_config(&self, hash: &str, _: &Path) -> ExpResult<()> {
+        self.0.lock().unwrap().push(hash.to_owned());
+        Ok(())
+    }
+
+    async fn after_all(&self, _: &Path) -> ExpResult<()> {
+        self.0.lock().unwrap().push("after_all".to_owned());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn dependents_of_failed_configurations_are_skipped() {
+    let _ = remove_dir_all("results/distributed/dependencies");
+    // the second time the failure is replaced
+    for _ in 0..2 {
+        let dir = coordinate("dependencies", free_addr(), async {}).await;
+        let base = config("base", true, None).hash_serialized().unwrap();
+        let dependent = config("dependent", false, Some("base"))
+            .hash_serialized()
+            .unwrap();
+        let other = config("other", false, None).hash_serialized().unwrap();
+        assert!(dir.join(format!("{}.failed", base)).is_dir());
+        assert!(!dir.join(&dependent).exists());
+        assert!(!dir.join(format!("{}.failed", dependent)).exists());
+        assert!(dir.join(other).is_dir());
+    }
+}
+
+#[tokio::test]
+async fn results_for_other_runs_are_rejected() {
+    let _ = remove_dir_all("results/distributed/mismatch");
+    let addr = free_addr();
+    let dir = coordinate("mismatch", addr, async move {
+        let stream = connect(addr).await;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer.write_all(b"\"Next\"\n").await.unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert!(line.contains("Run"));
+        writer
+            .write_all(b"{\"Result\":{\"hash\":\"bogus\",\"success\":true,\"files\":0}}\n")
+            .await
+            .unwrap();
+        // the coordinator hangs up rather than taking the result
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).await.unwrap(), 0);
+    })
+    .await;
+    assert!(!dir.join("bogus").exists());
+    let other = config("other", false, None).hash_serialized().unwrap();
+    // the run handed to the bad worker is run by the real one instead
+    assert!(dir.join(other).is_dir());
+}
+
+#[tokio::test]
+async fn workers_call_their_hooks() {
+    let results_dir = PathBuf::from("results/distributed/hooks");
+    let _ = remove_dir_all(&results_dir);
+    let addr = free_addr();
+    let coordinator_config = run_config(&results_dir.join("coordinator"));
+    let recorder = Recorder::default();
+    let worker_config = RunConfig::builder()
+        .results_dir(results_dir.join("worker"))
+        .hook(recorder.clone())
+        .build()
+        .unwrap();
+    let (mut coordinator, mut worker) = (Exp, Exp);
+    let (coordinated, worked) = tokio::join!(
+        run_coordinator(&mut coordinator, &coordinator_config, addr),
+        async {
+            connect(addr).await;
+            run_worker(&mut worker, &worker_config, addr).await
+        }
+    );
+    coordinated.unwrap();
+    worked.unwrap();
+
+    let calls = recorder.0.lock().unwrap().clone();
+    // the failed base configuration and the other one, the dependent is skipped
+    assert_eq!(calls.len(), 4, "{:?}", calls);
+    assert_eq!(calls.first().map(String::as_str), Some("before_all"));
+    assert_eq!(calls.last().map(String::as_str), Some("after_all"));
+}
@@ -0,0 +1,267 @@
+use std::fs::{create_dir_all, write};
+
+use chrono::{TimeZone, Utc};
+use exp::docker_runner::{BlkioStats, ContainerStats, NetStats, Stats, StatsRecord, TopRecord};
+
+#[test]
+fn derive_stats_of_old_files() {
+    let dir = std::env::temp_dir().join("exp-docker-stats-test");
+    create_dir_all(&dir).unwrap();
+    let path = dir.join("docker-a-stat.csv");
+    let usage = "cpu_stats_cpu_usage_usage_in_usermode,cpu_stats_cpu_usage_usage_in_kernelmode,cpu_stats_throttling_data_periods,cpu_stats_throttling_data_throttled_periods,cpu_stats_throttling_data_throttled_time,precpu_stats_cpu_usage_usage_in_usermode,precpu_stats_cpu_usage_usage_in_kernelmode,precpu_stats_throttling_data_periods,precpu_stats_throttling_data_throttled_periods,precpu_stats_throttling_data_throttled_time";
+    write(
+        &path,
+        format!(
+            "read,preread,num_procs,name,id,{},cpu_stats_cpu_usage_total_usage,precpu_stats_cpu_usage_total_usage,cpu_stats_system_cpu_usage,precpu_stats_system_cpu_usage,cpu_stats_online_cpus,memory_stats_usage,memory_stats_stats_v2_inactive_file,memory_stats_limit,networks_rx_bytes,networks_tx_bytes\n\
+             2023-06-01T10:00:00Z,2023-06-01T09:59:59Z,0,a,1,0,0,0,0,0,0,0,0,0,0,100,0,4000,0,2,300,100,800,1000,500\n\
+             2023-06-01T10:00:02Z,2023-06-01T10:00:00Z,0,a,1,0,0,0,0,0,0,0,0,0,0,300,100,2000,1000,2,500,100,800,3000,1500\n",
+            usage
+        ),
+    )
+    .unwrap();
+
+    let stats = Stats::from_file(&path).unwrap();
+    assert_eq!(stats[0].cpu_percent, Some(5.0));
+    assert_eq!(stats[1].cpu_percent, Some(40.0));
+    // inactive page cache isn't counted as used
+    assert_eq!(stats[0].memory_used(), Some(200));
+    assert_eq!(stats[0].memory_percent, Some(25.0));
+    assert_eq!(stats[1].memory_percent, Some(50.0));
+    // rates need a previous sample
+    assert_eq!(stats[0].network_rx_bytes_per_second, None);
+    assert_eq!(stats[1].network_rx_bytes_per_second, Some(1000.0));
+    assert_eq!(stats[1].network_tx_bytes_per_second, Some(500.0));
+}
+
+#[test]
+fn derive_cpu_of_one_shot_stats() {
+    let dir = std::env::temp_dir().join("exp-docker-one-shot-stats-test");
+    create_dir_all(&dir).unwrap();
+    let path = dir.join("docker-a-stat.csv");
+    let usage = "cpu_stats_cpu_usage_usage_in_usermode,cpu_stats_cpu_usage_usage_in_kernelmode,cpu_stats_throttling_data_periods,cpu_stats_throttling_data_throttled_periods,cpu_stats_throttling_data_throttled_time,precpu_stats_cpu_usage_usage_in_usermode,precpu_stats_cpu_usage_usage_in_kernelmode,precpu_stats_throttling_data_periods,precpu_stats_throttling_data_throttled_periods,precpu_stats_throttling_data_throttled_time";
+    // one-shot samples have empty `precpu_stats`
+    write(
+        &path,
+        format!(
+            "read,preread,num_procs,name,id,{},cpu_stats_cpu_usage_total_usage,precpu_stats_cpu_usage_total_usage,cpu_stats_system_cpu_usage,precpu_stats_system_cpu_usage,cpu_stats_online_cpus\n\
+             2023-06-01T10:00:00Z,0001-01-01T00:00:00Z,0,a,1,0,0,0,0,0,0,0,0,0,0,1000,0,10000,,2\n\
+             2023-06-01T10:00:05Z,0001-01-01T00:00:00Z,0,a,1,0,0,0,0,0,0,0,0,0,0,1300,0,11000,,2\n",
+            usage
+        ),
+    )
+    .unwrap();
+
+    let stats = Stats::from_file(&path).unwrap();
+    // rather than the mean since the container started
+    assert_eq!(stats[0].cpu_percent, None);
+    assert_eq!(stats[1].cpu_percent, Some(60.0));
+    assert_eq!(stats[1].precpu_stats_cpu_usage_total_usage, 1000);
+    assert_eq!(stats[1].cpu_percentage(), Some(60.0));
+}
+
+#[test]
+fn converted_stats_arent_skipped() {
+    let dir = std::env::temp_dir().join("exp-docker-parquet-stats-test");
+    let metrics_dir = dir.join("metrics");
+    create_dir_all(metrics_dir.join("load")).unwrap();
+    write(metrics_dir.join("load").join("docker-a-stat.parquet"), "").unwrap();
+    write(metrics_dir.join("docker-a-network.parquet"), "").unwrap();
+
+    assert!(Stats::from_configuration(&dir).is_err());
+    assert!(NetStats::from_configuration(&dir).is_err());
+    assert!(BlkioStats::from_configuration(&dir).unwrap().is_empty());
+}
+
+#[test]
+fn parse_top_of_ps_formats() {
+    let time = Utc.timestamp_nanos(1_000);
+    let procps = [
+        "USER", "PID", "%CPU", "%MEM", "VSZ", "RSS", "TTY", "STAT", "START", "TIME", "COMMAND",
+    ];
+    let row = [
+        "root",
+        "12",
+        "1.5",
+        "0.3",
+        "1000",
+        "2048",
+        "?",
+        "Ss",
+        "10:00",
+        "0:00",
+        "nginx -g daemon off;",
+    ];
+    let record = TopRecord::parse(&procps, &row, time).unwrap();
+    assert_eq!(record.pid, 12);
+    assert_eq!(record.user.as_deref(), Some("root"));
+    assert_eq!(record.cpu_percent, Some(1.5));
+    assert_eq!(record.memory_percent, Some(0.3));
+    assert_eq!(record.rss_bytes, Some(2048 * 1024));
+    assert_eq!(record.command, "nginx -g daemon off;");
+    assert_eq!(record.timestamp, time);
+
+    // busybox ps in alpine images ignores `aux`
+    let busybox = ["PID", "USER", "TIME", "COMMAND"];
+    let record = TopRecord::parse(&busybox, &["7", "1000", "0:01", "sleep 10"], time).unwrap();
+    assert_eq!(record.pid, 7);
+    assert_eq!(record.cpu_percent, None);
+    assert_eq!(record.rss_bytes, None);
+    assert_eq!(record.command, "sleep 10");
+
+    let record = TopRecord::parse(&["PID", "RSZ"], &["7", "1.5m"], time).unwrap();
+    assert_eq!(record.rss_bytes, Some(3 << 19));
+    assert!(TopRecord::parse(&["PID", "COMMAND"], &["", "zombie"], time).is_none());
+}
+
+#[test]
+fn load_top_of_configuration() {
+    let dir = std::env::temp_dir().join("exp-docker-top-test");
+    let metrics_dir = dir.join("metrics");
+    create_dir_all(metrics_dir.join("load")).unwrap();
+    write(
+        metrics_dir.join("docker-a-top.csv"),
+        "USER,PID,%CPU,%MEM,VSZ,RSS,TTY,STAT,START,TIME,COMMAND,timestamp_nanos\n\
+         root,1,0.0,0.1,100,10,?,Ss,10:00,0:00,sh,1654077600000000000\n",
+    )
+    .unwrap();
+    write(
+        metrics_dir.join("load").join("docker-b-top.csv"),
+        "PID,USER,TIME,COMMAND,timestamp_nanos\n1,root,0:00,sleep 10,1654077601000000000\n",
+    )
+    .unwrap();
+
+    let records = TopRecord::from_configuration(&dir).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].0, "a");
+    assert_eq!(records[0].1.rss_bytes, Some(10 * 1024));
+    assert_eq!(
+        records[0].1.timestamp,
+        Utc.timestamp_nanos(1654077600000000000)
+    );
+    assert_eq!(records[1].0, "b");
+    assert_eq!(records[1].1.command, "sleep 10");
+}
+
+fn sample(read: &str, rx_bytes: u64, read_bytes: u64) -> bollard::container::Stats {
+    serde_json::from_value(serde_json::json!({
+        "read": read,
+        "preread": "2023-06-01T09:59:59Z",
+        "num_procs": 0,
+        "pids_stats": {"current": 3},
+        "networks": {
+            "eth1": {"rx_bytes": 10, "rx_packets": 1, "rx_errors": 0, "rx_dropped": 0, "tx_bytes": 10, "tx_packets": 1, "tx_errors": 0, "tx_dropped": 0},
+            "eth0": {"rx_bytes": rx_bytes, "rx_packets": 1, "rx_errors": 0, "rx_dropped": 0, "tx_bytes": 0, "tx_packets": 0, "tx_errors": 0, "tx_dropped": 0}
+        },
+        "memory_stats": {
+            "usage": 300,
+            "limit": 800,
+            "stats": {"anon": 150, "file": 150, "kernel_stack": 0, "slab": 0, "sock": 0, "shmem": 0, "file_mapped": 0, "file_dirty": 0, "file_writeback": 0, "anon_thp": 0, "inactive_anon": 0, "active_anon": 0, "inactive_file": 100, "active_file": 50, "unevictable": 0, "slab_reclaimable": 0, "slab_unreclaimable": 0, "pgfault": 7, "pgmajfault": 1, "workingset_refault": 0, "workingset_activate": 0, "workingset_nodereclaim": 0, "pgrefill": 0, "pgscan": 0, "pgsteal": 0, "pgactivate": 0, "pgdeactivate": 0, "pglazyfree": 0, "pglazyfreed": 0, "thp_fault_alloc": 0, "thp_collapse_alloc": 0}
+        },
+        "blkio_stats": {
+            "io_service_bytes_recursive": [
+                {"major": 8, "minor": 0, "op": "read", "value": read_bytes},
+                {"major": 8, "minor": 0, "op": "write", "value": 20},
+                {"major": 8, "minor": 16, "op": "Read", "value": 5},
+                {"major": 8, "minor": 16, "op": "Total", "value": 5}
+            ],
+            "io_serviced_recursive": [{"major": 8, "minor": 0, "op": "read", "value": 2}]
+        },
+        "cpu_stats": {
+            "cpu_usage": {"total_usage": 300, "usage_in_usermode": 200, "usage_in_kernelmode": 100},
+            "system_cpu_usage": 2000,
+            "online_cpus": 2,
+            "throttling_data": {"periods": 0, "throttled_periods": 0, "throttled_time": 0}
+        },
+        "precpu_stats": {
+            "cpu_usage": {"total_usage": 100, "usage_in_usermode": 0, "usage_in_kernelmode": 0},
+            "system_cpu_usage": 1000,
+            "throttling_data": {"periods": 0, "throttled_periods": 0, "throttled_time": 0}
+        },
+        "storage_stats": {},
+        "name": "/a",
+        "id": "1"
+    }))
+    .unwrap()
+}
+
+#[test]
+fn split_stats_into_records() {
+    let first = ContainerStats::from_bollard(&sample("2023-06-01T10:00:00Z", 1000, 10), None);
+    assert_eq!(first.cpu.percent, Some(40.0));
+    assert_eq!(first.cpu.pids, Some(3));
+    assert_eq!(first.memory.used, Some(200));
+    assert_eq!(first.memory.percent, Some(25.0));
+    assert_eq!(first.memory.anon, Some(150));
+    assert_eq!(first.memory.pgmajfault, Some(1));
+    let interfaces = first
+        .networks
+        .iter()
+        .map(|network| network.interface.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(interfaces, ["eth0", "eth1"]);
+    assert_eq!(first.networks[0].rx_bytes_per_second, None);
+    assert_eq!(first.blkio.len(), 2);
+    assert_eq!((first.blkio[0].major, first.blkio[0].minor), (8, 0));
+    assert_eq!(first.blkio[0].read_bytes, Some(10));
+    assert_eq!(first.blkio[0].write_bytes, Some(20));
+    assert_eq!(first.blkio[0].read_ops, Some(2));
+    assert_eq!(first.blkio[0].write_ops, None);
+    // totals aren't counted again
+    assert_eq!(first.blkio[1].read_bytes, Some(5));
+
+    let second =
+        ContainerStats::from_bollard(&sample("2023-06-01T10:00:02Z", 3000, 30), Some(&first));
+    assert_eq!(second.networks[0].rx_bytes_per_second, Some(1000.0));
+    assert_eq!(second.networks[1].rx_bytes_per_second, Some(0.0));
+
+    // the flattened stats are made from the split records
+    let flat = Stats::flatten(&first, None, "/a", "1");
+    assert_eq!(flat.name, "/a");
+    assert_eq!(flat.cpu_percent, Some(40.0));
+    assert_eq!(flat.memory_stats_stats_v2_inactive_file, Some(100));
+    assert_eq!(flat.memory_stats_stats_v1_total_inactive_file, None);
+    assert_eq!(flat.memory_used(), Some(200));
+    assert_eq!(flat.networks_names.as_deref(), Some("eth0,eth1"));
+    assert_eq!(flat.networks_total_rx_bytes, Some(1010));
+    assert_eq!(flat.network_rx_bytes_per_second, None);
+    let flat = Stats::flatten(&second, Some(&first), "/a", "1");
+    assert_eq!(flat.precpu_stats_cpu_usage_total_usage, 300);
+    assert_eq!(flat.networks_total_rx_bytes, Some(3010));
+    assert_eq!(flat.network_rx_bytes_per_second, Some(1000.0));
+
+    // one-shot samples don't have the previous usage
+    let mut one_shot = sample("2023-06-01T10:00:04Z", 3000, 30);
+    one_shot.precpu_stats.cpu_usage.total_usage = 0;
+    one_shot.precpu_stats.system_cpu_usage = None;
+    one_shot.cpu_stats.cpu_usage.total_usage = 500;
+    one_shot.cpu_stats.system_cpu_usage = Some(3000);
+    let third = ContainerStats::from_bollard(&one_shot, Some(&second));
+    assert_eq!(third.cpu.percent, Some(40.0));
+    assert_eq!(
+        ContainerStats::from_bollard(&one_shot, None).cpu.percent,
+        None
+    );
+
+    let dir = std::env::temp_dir().join("exp-docker-split-stats-test");
+    let metrics_dir = dir.join("metrics");
+    create_dir_all(metrics_dir.join("load")).unwrap();
+    let mut writer = csv::Writer::from_path(metrics_dir.join("docker-a-network.csv")).unwrap();
+    for network in &first.networks {
+        writer.serialize(network).unwrap();
+    }
+    writer.flush().unwrap();
+    let mut writer =
+        csv::Writer::from_path(metrics_dir.join("load").join("docker-a-blkio.csv")).unwrap();
+    for device in &second.blkio {
+        writer.serialize(device).unwrap();
+    }
+    writer.flush().unwrap();
+
+    let networks = NetStats::from_configuration(&dir).unwrap();
+    assert_eq!(networks.len(), 2);
+    assert_eq!(networks[0].0, "a");
+    assert_eq!(networks[0].1, first.networks[0]);
+    let devices = BlkioStats::from_configuration(&dir).unwrap();
+    assert_eq!(devices.len(), 2);
+    assert_eq!(devices[0].1.read_bytes, Some(30));
+}
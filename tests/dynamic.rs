@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, DynExperiment, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    Measurements,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+    label: String,
+}
+
+impl ExperimentConfiguration for Config {
+    const SCHEMA_VERSION: u32 = 2;
+    const SKIP_HASH_FIELDS: &'static [&'static str] = &["label"];
+}
+
+fn config(n: u32) -> Config {
+    Config {
+        n,
+        label: format!("n={}", n),
+    }
+}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![config(1), config(2)]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()> {
+        measurements.record_scalar("n", configuration.n as f64);
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        configurations: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        let total: u32 = configurations.iter().map(|(c, _)| c.n).sum();
+        Ok(serde_json::json!({ "total": total }))
+    }
+}
+
+#[tokio::test]
+async fn run_type_erased_experiments() {
+    let results_dir = std::env::temp_dir().join("exp-dynamic-test");
+    let _ = std::fs::remove_dir_all(&results_dir);
+    let mut experiment: Box<dyn DynExperiment> = Box::new(Exp);
+    let run_config = exp::RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    exp::run(&mut experiment, &run_config).await.unwrap();
+    // results are in the same directories as when running the typed experiment
+    for n in [1, 2] {
+        let hash = config(n).hash_serialized().unwrap();
+        let schema = std::fs::read_to_string(results_dir.join(hash).join("schema.json")).unwrap();
+        let schema: serde_json::Value = serde_json::from_str(&schema).unwrap();
+        assert_eq!(schema["version"], 2);
+    }
+    // the label isn't hashed
+    assert_eq!(
+        config(1).hash_serialized().unwrap(),
+        Config {
+            n: 1,
+            label: "other".to_owned()
+        }
+        .hash_serialized()
+        .unwrap()
+    );
+
+    let config = exp::AnalyseConfig {
+        results_dir,
+        output_dir: None,
+        incremental: false,
+        filter: None,
+        incomplete: exp::IncompletePolicy::Skip,
+    };
+    let summary = exp::analyse(&mut experiment, &config).await.unwrap();
+    assert_eq!(summary["total"], 3);
+}
@@ -0,0 +1,108 @@
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    read_events, AnalysisDirs, EarlyStopping, Environment, ExpResult, Experiment,
+    ExperimentConfiguration, Measurements, RunConfig, RunConfigError, RunEvent, Seed,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    noisy: bool,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { noisy: false }, Config { noisy: true }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()> {
+        let latency = if configuration.noisy {
+            let seed = Seed::from_configuration(configuration_dir)?;
+            (seed.seed % 1000) as f64 + 1.0
+        } else {
+            100.0
+        };
+        measurements.record_scalar("latency", latency);
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn stops_once_within_tolerance() {
+    let results_dir = PathBuf::from("results/early_stopping");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(5)
+        .early_stopping(EarlyStopping::new("latency", 0.01))
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    let steady = Config { noisy: false }.hash_serialized().unwrap();
+    let noisy = Config { noisy: true }.hash_serialized().unwrap();
+    // a constant metric stops after the fewest repeats
+    assert!(results_dir.join(&steady).is_dir());
+    assert!(results_dir.join(format!("{}-1", steady)).is_dir());
+    assert!(!results_dir.join(format!("{}-2", steady)).exists());
+    // a noisy one runs them all
+    assert!(results_dir.join(format!("{}-4", noisy)).is_dir());
+
+    let skipped = read_events(&results_dir)
+        .unwrap()
+        .into_iter()
+        .filter(|record| {
+            matches!(
+                &record.event,
+                RunEvent::ConfigurationSkipped { reason, .. }
+                    if reason == "confidence interval within tolerance"
+            )
+        })
+        .count();
+    assert_eq!(skipped, 3);
+}
+
+#[test]
+fn tolerance_must_be_positive() {
+    assert!(matches!(
+        RunConfig::builder()
+            .results_dir("results")
+            .early_stopping(EarlyStopping::new("latency", 0.0))
+            .build(),
+        Err(RunConfigError::NonPositiveTolerance)
+    ));
+}
@@ -0,0 +1,24 @@
+use serde_json::json;
+
+#[test]
+fn expand_meta_configuration() {
+    let configurations = exp::expand(&json!({
+        "nodes": [1, 3],
+        "labels": [["a", "b"]],
+        "workload": [{"kind": "read", "clients": [1, 10]}, {"kind": "write"}],
+        "seed": 42,
+    }));
+    assert_eq!(
+        configurations,
+        vec![
+            json!({"labels": ["a", "b"], "nodes": 1, "seed": 42, "workload": {"kind": "read", "clients": 1}}),
+            json!({"labels": ["a", "b"], "nodes": 1, "seed": 42, "workload": {"kind": "read", "clients": 10}}),
+            json!({"labels": ["a", "b"], "nodes": 1, "seed": 42, "workload": {"kind": "write"}}),
+            json!({"labels": ["a", "b"], "nodes": 3, "seed": 42, "workload": {"kind": "read", "clients": 1}}),
+            json!({"labels": ["a", "b"], "nodes": 3, "seed": 42, "workload": {"kind": "read", "clients": 10}}),
+            json!({"labels": ["a", "b"], "nodes": 3, "seed": 42, "workload": {"kind": "write"}}),
+        ]
+    );
+    // an empty list of options gives no configurations
+    assert!(exp::expand(&json!({"nodes": []})).is_empty());
+}
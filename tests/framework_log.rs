@@ -0,0 +1,80 @@
+use std::{
+    fs::{read_to_string, remove_dir_all},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, FrameworkLog,
+    Measurements, RunConfig, FRAMEWORK_LOG_FILE,
+};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { n: 1 }, Config { n: 2 }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        tracing::info!(n = configuration.n, "Running in the experiment");
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn framework_log_per_configuration() {
+    let _subscriber =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(FrameworkLog));
+    let results_dir = PathBuf::from("results/framework_log");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .framework_log(true)
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    for n in [1, 2] {
+        let hash = Config { n }.hash_serialized().unwrap();
+        let log = read_to_string(results_dir.join(hash).join(FRAMEWORK_LOG_FILE)).unwrap();
+        assert!(log.contains("Running in the experiment"), "{}", log);
+        assert!(log.contains(&format!("n={}", n)), "{}", log);
+        // only the logs of this configuration
+        assert!(!log.contains(&format!("n={}", 3 - n)), "{}", log);
+    }
+}
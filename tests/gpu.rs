@@ -0,0 +1,24 @@
+#![cfg(feature = "nvml")]
+
+use std::collections::HashMap;
+
+use exp::gpu::cgroup_container;
+
+#[test]
+fn container_of_cgroup() {
+    let id = "4f2a9c0d1e7b".repeat(5) + "abcd";
+    let containers = HashMap::from([
+        (id.clone(), "trainer".to_owned()),
+        ("9".repeat(64), "other".to_owned()),
+    ]);
+    let v2 = format!("0::/system.slice/docker-{}.scope\n", id);
+    assert_eq!(cgroup_container(&v2, &containers), Some("trainer"));
+    let v1 = format!(
+        "12:devices:/docker/{}\n11:memory:/docker/{}\n1:name=systemd:/docker/{}\n",
+        id, id, id
+    );
+    assert_eq!(cgroup_container(&v1, &containers), Some("trainer"));
+    // processes of the host
+    let host = "0::/user.slice/user-1000.slice/session-2.scope\n";
+    assert_eq!(cgroup_container(host, &containers), None);
+}
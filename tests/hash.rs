@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use exp::ExperimentConfiguration;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct A {
+    nodes: u32,
+    rate: f64,
+    labels: HashMap<String, String>,
+}
+
+impl ExperimentConfiguration for A {}
+
+#[derive(Serialize, Deserialize)]
+struct B {
+    labels: HashMap<String, String>,
+    rate: u32,
+    nodes: u32,
+}
+
+impl ExperimentConfiguration for B {}
+
+#[test]
+fn hash_is_canonical() {
+    let labels = (0..10)
+        .map(|i| (i.to_string(), i.to_string()))
+        .collect::<HashMap<_, _>>();
+    let a = A {
+        nodes: 3,
+        rate: 2.0,
+        labels,
+    };
+    let b = B {
+        labels: (0..10)
+            .rev()
+            .map(|i| (i.to_string(), i.to_string()))
+            .collect(),
+        rate: 2,
+        nodes: 3,
+    };
+    assert_eq!(a.hash_serialized().unwrap(), b.hash_serialized().unwrap());
+
+    let c = A {
+        nodes: 3,
+        rate: 2.5,
+        labels: HashMap::new(),
+    };
+    assert_ne!(a.hash_serialized().unwrap(), c.hash_serialized().unwrap());
+}
+
+#[derive(Serialize, Deserialize)]
+struct Labelled {
+    nodes: u32,
+    label: String,
+}
+
+impl ExperimentConfiguration for Labelled {
+    const SKIP_HASH_FIELDS: &'static [&'static str] = &["label"];
+}
+
+#[test]
+fn hash_skips_fields() {
+    let a = Labelled {
+        nodes: 3,
+        label: "first".to_owned(),
+    };
+    let b = Labelled {
+        nodes: 3,
+        label: "second".to_owned(),
+    };
+    assert_eq!(a.hash_serialized().unwrap(), b.hash_serialized().unwrap());
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "histogram")]
+
+use std::{
+    fs::{create_dir_all, remove_dir_all, write},
+    time::Duration,
+};
+
+use base64::Engine;
+use exp::histogram::{
+    interval_percentiles, load_histogram, load_interval_log, merge, merge_intervals,
+    percentiles_of, Histogram,
+};
+use hdrhistogram::serialization::interval_log::{IntervalLogWriterBuilder, Tag};
+use hdrhistogram::serialization::{Serializer, V2Serializer};
+
+fn histogram(values: &[u64]) -> Histogram<u64> {
+    let mut histogram = Histogram::new(3).unwrap();
+    for value in values {
+        histogram.record(*value).unwrap();
+    }
+    histogram
+}
+
+fn interval_log(intervals: &[(&[u64], Option<&str>)]) -> Vec<u8> {
+    let mut log = Vec::new();
+    let mut serializer = V2Serializer::new();
+    let mut writer = IntervalLogWriterBuilder::new()
+        .begin_log_with(&mut log, &mut serializer)
+        .unwrap();
+    for (i, (values, tag)) in intervals.iter().enumerate() {
+        writer
+            .write_histogram(
+                &histogram(values),
+                Duration::from_secs(i as u64),
+                Duration::from_secs(1),
+                tag.and_then(Tag::new),
+            )
+            .unwrap();
+    }
+    log
+}
+
+#[test]
+fn load_and_merge_interval_logs() {
+    let dir = std::env::temp_dir().join("exp-histogram-test");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let a = dir.join("a.hlog");
+    let b = dir.join("b.hlog");
+    write(
+        &a,
+        interval_log(&[(&[1, 2, 3], None), (&[10, 20], None), (&[5], Some("write"))]),
+    )
+    .unwrap();
+    write(&b, interval_log(&[(&[4], None), (&[30], None)])).unwrap();
+
+    let a = load_interval_log(&a).unwrap();
+    assert_eq!(a.len(), 3);
+    assert_eq!(a[1].start, Duration::from_secs(1));
+    assert_eq!(a[2].tag.as_deref(), Some("write"));
+    let percentiles = interval_percentiles(&a, &[50.0, 100.0]);
+    assert_eq!(percentiles[0].count, 3);
+    assert_eq!(percentiles[0].values, [(50.0, 2), (100.0, 3)]);
+
+    let merged = merge_intervals(&[a.clone(), load_interval_log(&b).unwrap()]).unwrap();
+    assert_eq!(merged.len(), 3);
+    assert_eq!(merged[0].histogram.len(), 4);
+    assert_eq!(merged[1].histogram.max(), 30);
+    assert_eq!(merged[2].histogram.len(), 1);
+
+    let whole = merge(a.iter().map(|i| &i.histogram)).unwrap().unwrap();
+    assert_eq!(percentiles_of(&whole, &[100.0]).values, [(100.0, 20)]);
+    assert!(merge(Vec::new()).unwrap().is_none());
+
+    remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn load_encoded_histograms() {
+    let dir = std::env::temp_dir().join("exp-histogram-encoded-test");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let mut bytes = Vec::new();
+    V2Serializer::new()
+        .serialize(&histogram(&[7, 8, 9]), &mut bytes)
+        .unwrap();
+    write(dir.join("binary"), &bytes).unwrap();
+    write(
+        dir.join("text"),
+        format!(
+            "{}\n",
+            base64::engine::general_purpose::STANDARD.encode(&bytes)
+        ),
+    )
+    .unwrap();
+
+    for name in ["binary", "text"] {
+        let histogram = load_histogram(&dir.join(name)).unwrap();
+        assert_eq!(histogram.len(), 3);
+        assert_eq!(histogram.max(), 9);
+    }
+
+    remove_dir_all(&dir).unwrap();
+}
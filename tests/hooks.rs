@@ -0,0 +1,165 @@
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, Measurements,
+    RunConfig, RunHooks,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    fail: bool,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { fail: false }, Config { fail: true }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        if configuration.fail {
+            Err("failed".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl RunHooks for Recorder {
+    async fn before_all(&self, _: &Path) -> ExpResult<()> {
+        self.0.lock().unwrap().push("before_all".to_owned());
+        Ok(())
+    }
+
+    async fn before_config(&self, hash: &str, dir: &Path) -> ExpResult<()> {
+        assert!(dir.is_dir());
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("before_config {}", hash));
+        Ok(())
+    }
+
+    async fn after_config(&self, hash: &str, _: &Path, success: bool) -> ExpResult<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("after_config {} {}", hash, success));
+        Ok(())
+    }
+
+    async fn on_failure(
+        &self,
+        hash: &str,
+        _: &Path,
+        error: &(dyn std::error::Error + Send + Sync),
+    ) -> ExpResult<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("on_failure {} {}", hash, error));
+        Ok(())
+    }
+
+    async fn after_all(&self, _: &Path) -> ExpResult<()> {
+        self.0.lock().unwrap().push("after_all".to_owned());
+        Ok(())
+    }
+}
+
+struct FailBefore;
+
+#[async_trait]
+impl RunHooks for FailBefore {
+    async fn before_config(&self, _: &str, _: &Path) -> ExpResult<()> {
+        Err("not ready".into())
+    }
+}
+
+#[tokio::test]
+async fn hooks_called_around_phases() {
+    let results_dir = PathBuf::from("results/hooks");
+    let _ = remove_dir_all(&results_dir);
+    let recorder = Recorder::default();
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .hook(recorder.clone())
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    let ok = Config { fail: false }.hash_serialized().unwrap();
+    let failed = Config { fail: true }.hash_serialized().unwrap();
+    let calls = recorder.0.lock().unwrap().clone();
+    assert_eq!(
+        calls,
+        vec![
+            "before_all".to_owned(),
+            format!("before_config {}", ok),
+            format!("after_config {} true", ok),
+            format!("before_config {}", failed),
+            format!("after_config {} false", failed),
+            format!("on_failure {} failed", failed),
+            "after_all".to_owned(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn failing_hook_fails_configuration() {
+    let results_dir = PathBuf::from("results/hooks-fail");
+    let _ = remove_dir_all(&results_dir);
+    let recorder = Recorder::default();
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .hook(FailBefore)
+        .hook(recorder.clone())
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    let ok = Config { fail: false }.hash_serialized().unwrap();
+    assert!(results_dir.join(format!("{}.failed", ok)).is_dir());
+    let calls = recorder.0.lock().unwrap().clone();
+    assert!(calls.contains(&format!("on_failure {} not ready", ok)));
+    // later before_config hooks aren't called once one fails
+    assert!(!calls.contains(&format!("before_config {}", ok)));
+}
@@ -0,0 +1,90 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all, write},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, CacheHygiene, DiskSpace, Environment, ExpResult, Experiment,
+    ExperimentConfiguration, Measurements, RunConfig,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp {
+    scratch_dir: PathBuf,
+    leftovers: Vec<usize>,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config {}]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(&mut self, _: &Self::Configuration, _: &Path, _: &Measurements) -> ExpResult<()> {
+        self.leftovers
+            .push(std::fs::read_dir(&self.scratch_dir)?.count());
+        write(self.scratch_dir.join("data"), "from the last repeat")?;
+        create_dir_all(self.scratch_dir.join("nested/dir"))?;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn scratch_emptied_between_repeats() {
+    let scratch_dir = PathBuf::from("results/hygiene-scratch");
+    let _ = remove_dir_all(&scratch_dir);
+    create_dir_all(&scratch_dir).unwrap();
+    write(scratch_dir.join("stale"), "before the run").unwrap();
+    let results_dir = PathBuf::from("results/hygiene");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(3)
+        .hook(CacheHygiene {
+            scratch_dir: Some(scratch_dir.clone()),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let mut experiment = Exp {
+        scratch_dir: scratch_dir.clone(),
+        leftovers: Vec::new(),
+    };
+    exp::run(&mut experiment, &run_config).await.unwrap();
+    assert_eq!(experiment.leftovers, vec![0, 0, 0]);
+    assert!(scratch_dir.is_dir());
+
+    let hash = Config {}.hash_serialized().unwrap();
+    for dir in [hash.clone(), format!("{}-2", hash)] {
+        let disk_space = DiskSpace::from_configuration(&results_dir.join(dir)).unwrap();
+        assert_eq!(disk_space.len(), 1);
+        assert_eq!(disk_space[0].path, scratch_dir);
+        assert!(disk_space[0].total_bytes >= disk_space[0].available_bytes);
+        assert!(disk_space[0].total_bytes > 0);
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::BTreeMap,
+    fs::{remove_dir_all, write},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    read_live_results, AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    Measurements, RunConfig,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    clients: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![
+            Config { clients: 1 },
+            Config { clients: 0 },
+            Config { clients: 4 },
+        ]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        if configuration.clients == 0 {
+            return Err("no clients".into());
+        }
+        write(
+            configuration_dir.join("throughput"),
+            (configuration.clients * 100).to_string(),
+        )?;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn quick_look(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+    ) -> ExpResult<BTreeMap<String, f64>> {
+        let throughput = std::fs::read_to_string(configuration_dir.join("throughput"))?;
+        let mut scalars = BTreeMap::new();
+        scalars.insert("throughput".to_owned(), throughput.parse()?);
+        scalars.insert("clients".to_owned(), configuration.clients.into());
+        Ok(scalars)
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn live_results() {
+    let results_dir = PathBuf::from("results/live_results");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(2)
+        .live_results(true)
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    // failed runs are left out
+    let results = read_live_results(&results_dir).unwrap();
+    let rows = results
+        .iter()
+        .map(|r| (r.hash.clone(), r.repeat, r.name.as_str(), r.value))
+        .collect::<Vec<_>>();
+    let one = Config { clients: 1 }.hash_serialized().unwrap();
+    let four = Config { clients: 4 }.hash_serialized().unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            (one.clone(), 0, "clients", 1.0),
+            (one.clone(), 0, "throughput", 100.0),
+            (format!("{}-1", one), 1, "clients", 1.0),
+            (format!("{}-1", one), 1, "throughput", 100.0),
+            (four.clone(), 0, "clients", 4.0),
+            (four.clone(), 0, "throughput", 400.0),
+            (format!("{}-1", four), 1, "clients", 4.0),
+            (format!("{}-1", four), 1, "throughput", 400.0),
+        ]
+    );
+
+    // a later run appends
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(3)
+        .live_results(true)
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+    assert_eq!(read_live_results(&results_dir).unwrap().len(), 12);
+}
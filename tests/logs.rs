@@ -0,0 +1,159 @@
+use std::{fs::File, io::Write};
+
+use exp::docker_runner::Logs;
+
+#[test]
+fn logs_from_jsonl() {
+    let dir = std::env::temp_dir().join("exp-logs-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("docker-exp-test-json.jsonl");
+    let mut file = File::create(&path).unwrap();
+    writeln!(
+        file,
+        r#"{{"level":"info","msg":"started","docker_timestamp":"2022-06-01T12:00:00.000000000Z"}}"#
+    )
+    .unwrap();
+    writeln!(
+        file,
+        r#"{{"message":"plain line","docker_timestamp":"2022-06-01T12:00:01.500000000Z"}}"#
+    )
+    .unwrap();
+    drop(file);
+
+    let logs = Logs::from_jsonl(&path).unwrap();
+    assert_eq!(logs.container_name, "exp-test-json");
+    assert_eq!(logs.lines.len(), 2);
+    assert_eq!(logs.lines[0].1["msg"], "started");
+    assert!(logs.lines[0].1.get("docker_timestamp").is_none());
+    assert_eq!(logs.lines[1].1["message"], "plain line");
+    assert!(logs.lines[0].0 < logs.lines[1].0);
+}
+
+#[test]
+fn extract_metrics_from_logs() {
+    use exp::{LogExtractor, LogMetric, LogSample, LogSelector};
+
+    let dir = std::env::temp_dir().join("exp-log-metrics-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let logs_dir = dir.join("logs");
+    std::fs::create_dir_all(logs_dir.join("load")).unwrap();
+    std::fs::write(
+        logs_dir.join("docker-db.log"),
+        "2022-06-01T12:00:00.100000000Z starting compaction\n\
+         2022-06-01T12:00:00.500000000Z starting compaction\n\
+         2022-06-01T12:00:01.200000000Z pending=12\n\
+         2022-06-01T12:00:02.900000000Z starting compaction\n\
+         2022-06-01T12:00:03.000000000Z {\"stats\":{\"pending\":7}}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        logs_dir.join("load").join("docker-db.jsonl"),
+        "{\"stats\":{\"pending\":3},\"docker_timestamp\":\"2022-06-01T12:00:05Z\"}\n\
+         {\"message\":\"pending=4\",\"docker_timestamp\":\"2022-06-01T12:00:06Z\"}\n",
+    )
+    .unwrap();
+
+    let extractor = LogExtractor {
+        metrics: vec![
+            LogMetric {
+                name: "compactions".to_owned(),
+                selector: LogSelector::Regex("compaction".to_owned()),
+                per_second: true,
+            },
+            LogMetric {
+                name: "pending".to_owned(),
+                selector: LogSelector::Regex(r"pending=(\d+)".to_owned()),
+                per_second: false,
+            },
+            LogMetric {
+                name: "stats-pending".to_owned(),
+                selector: LogSelector::JsonField("stats.pending".to_owned()),
+                per_second: false,
+            },
+        ],
+    };
+    let written = extractor.extract(&dir).unwrap();
+    assert_eq!(written.len(), 6);
+
+    let metrics_dir = dir.join("metrics");
+    let compactions = LogSample::from_file(&metrics_dir.join("log-db-compactions.csv")).unwrap();
+    let values = compactions.iter().map(|s| s.value).collect::<Vec<_>>();
+    assert_eq!(values, [2.0, 0.0, 1.0, 0.0]);
+    let pending = LogSample::from_file(&metrics_dir.join("log-db-pending.csv")).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].value, 12.0);
+    let stats = LogSample::from_file(&metrics_dir.join("log-db-stats-pending.csv")).unwrap();
+    assert_eq!(stats[0].value, 7.0);
+
+    let load_dir = metrics_dir.join("load");
+    let pending = LogSample::from_file(&load_dir.join("log-db-pending.csv")).unwrap();
+    assert_eq!(pending.iter().map(|s| s.value).collect::<Vec<_>>(), [4.0]);
+    let stats = LogSample::from_file(&load_dir.join("log-db-stats-pending.csv")).unwrap();
+    assert_eq!(stats.iter().map(|s| s.value).collect::<Vec<_>>(), [3.0]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn merge_and_grep_logs() {
+    use chrono::{DateTime, Utc};
+    use regex::Regex;
+
+    let dir = std::env::temp_dir().join("exp-logs-merge-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let logs_dir = dir.join("logs");
+    std::fs::create_dir_all(logs_dir.join("load")).unwrap();
+    std::fs::write(
+        logs_dir.join("docker-server.log"),
+        "2022-06-01T12:00:00.000000000Z listening\n\
+         2022-06-01T12:00:02.000000000Z error: connection reset\n",
+    )
+    .unwrap();
+    std::fs::write(
+        logs_dir.join("load").join("docker-server.log"),
+        "2022-06-01T12:00:04.000000000Z error: timeout\n",
+    )
+    .unwrap();
+    std::fs::write(
+        logs_dir.join("docker-client.jsonl"),
+        "{\"message\":\"connecting\",\"docker_timestamp\":\"2022-06-01T12:00:01Z\"}\n\
+         {\"level\":\"error\",\"docker_timestamp\":\"2022-06-01T12:00:03Z\"}\n",
+    )
+    .unwrap();
+
+    let logs = Logs::load_all(&dir).unwrap();
+    let names = logs
+        .iter()
+        .map(|logs| logs.container_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["client", "server"]);
+    // phases are combined
+    assert_eq!(logs[1].lines.len(), 3);
+
+    let merged = Logs::merge(&logs)
+        .map(|line| (line.container_name, line.line.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        merged,
+        vec![
+            ("server", "listening"),
+            ("client", "connecting"),
+            ("server", "error: connection reset"),
+            ("client", r#"{"level":"error"}"#),
+            ("server", "error: timeout"),
+        ]
+    );
+
+    let time = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+    let errors = Regex::new("^error").unwrap();
+    let matched = logs[1].grep(&errors).collect::<Vec<_>>();
+    assert_eq!(matched.len(), 2);
+    assert_eq!(matched[0].time, time("2022-06-01T12:00:02Z"));
+    assert_eq!(matched[1].line, "error: timeout");
+
+    let window = logs[1]
+        .between(time("2022-06-01T12:00:01Z")..time("2022-06-01T12:00:04Z"))
+        .map(|line| line.line.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(window, vec!["error: connection reset"]);
+}
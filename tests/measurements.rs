@@ -0,0 +1,92 @@
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use exp::{
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, Measurements,
+    Recorded, Sample,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    rate: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { rate: 10 }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()> {
+        measurements.record_scalar("throughput", configuration.rate as f64 * 2.0);
+        let handle = measurements.clone();
+        tokio::spawn(async move {
+            for second in 0..3 {
+                handle.record_series(
+                    "latency",
+                    Utc.timestamp_opt(second, 0).unwrap(),
+                    second as f64,
+                );
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn record_and_load_measurements() {
+    let results_dir = std::env::temp_dir().join("exp-measurements-test");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = exp::RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    let hash = Config { rate: 10 }.hash_serialized().unwrap();
+    let recorded = Recorded::from_configuration(&results_dir.join(hash)).unwrap();
+    assert_eq!(recorded.scalars["throughput"], 20.0);
+    assert_eq!(
+        recorded.series["latency"],
+        (0..3)
+            .map(|second| Sample {
+                time: Utc.timestamp_opt(second, 0).unwrap(),
+                value: second as f64,
+            })
+            .collect::<Vec<_>>()
+    );
+}
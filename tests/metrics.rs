@@ -0,0 +1,86 @@
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, Measurements,
+    RunConfig,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp(u32);
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        (0..self.0).map(|n| Config { n }).collect()
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        if configuration.n == 0 {
+            Err("failed".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+fn gauge(metrics: &str, name: &str) -> String {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("{} ", name)))
+        .unwrap()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn metrics_are_of_the_last_run() {
+    for (name, configurations) in [("first", 3), ("second", 2)] {
+        let results_dir = PathBuf::from("results/metrics").join(name);
+        let _ = remove_dir_all(&results_dir);
+        let run_config = RunConfig::builder()
+            .results_dir(results_dir)
+            .build()
+            .unwrap();
+        exp::run(&mut Exp(configurations), &run_config)
+            .await
+            .unwrap();
+    }
+    let metrics = exp::metrics::render();
+    assert_eq!(gauge(&metrics, "exp_configurations_total"), "2");
+    assert_eq!(gauge(&metrics, "exp_configurations_completed"), "2");
+    assert_eq!(gauge(&metrics, "exp_configurations_failed"), "1");
+}
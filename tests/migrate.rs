@@ -0,0 +1,62 @@
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+use exp::{ExpResult, ExperimentConfiguration};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Config {
+    nodes: u32,
+    replicas: u32,
+}
+
+impl ExperimentConfiguration for Config {
+    const SCHEMA_VERSION: u32 = 1;
+
+    fn migrate(mut old: serde_json::Value, version: u32) -> ExpResult<Self> {
+        if version == 0 {
+            old["replicas"] = 1.into();
+        }
+        Ok(serde_json::from_value(old)?)
+    }
+}
+
+#[test]
+fn migrate_moves_results_to_new_hash() {
+    let dir = std::env::temp_dir().join("exp-migrate-test");
+    let _ = remove_dir_all(&dir);
+    let old_dir = dir.join("oldhash");
+    create_dir_all(&old_dir).unwrap();
+    write(old_dir.join("configuration.json"), r#"{"nodes":3}"#).unwrap();
+    write(old_dir.join("data.csv"), "a\n1\n").unwrap();
+    create_dir_all(dir.join("analysis")).unwrap();
+    let old_repeat_dir = dir.join("oldhash-1");
+    create_dir_all(&old_repeat_dir).unwrap();
+    write(old_repeat_dir.join("configuration.json"), r#"{"nodes":3}"#).unwrap();
+
+    let summary = exp::migrate::<Config>(&dir).unwrap();
+    let new_hash = Config {
+        nodes: 3,
+        replicas: 1,
+    }
+    .hash_serialized()
+    .unwrap();
+    let mut migrated = summary.migrated;
+    migrated.sort();
+    assert_eq!(
+        migrated,
+        vec![
+            ("oldhash".to_owned(), new_hash.clone()),
+            ("oldhash-1".to_owned(), format!("{}-1", new_hash)),
+        ]
+    );
+    assert!(summary.conflicts.is_empty());
+    assert!(!old_dir.exists());
+    assert!(dir.join(&new_hash).join("data.csv").exists());
+    assert!(dir.join(format!("{}-1", new_hash)).exists());
+
+    let summary = exp::migrate::<Config>(&dir).unwrap();
+    assert!(summary.migrated.is_empty());
+    let mut current = summary.current;
+    current.sort();
+    assert_eq!(current, vec![new_hash.clone(), format!("{}-1", new_hash)]);
+}
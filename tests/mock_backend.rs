@@ -0,0 +1,258 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use exp::{
+    docker_runner::{
+        read_container_exits, read_image_pulls, ContainerConfig, ImagePull, Runner, VolumeConfig,
+    },
+    AnalysisDirs, ArtifactQuota, BackendCall, Environment, ExpResult, Experiment,
+    ExperimentConfiguration, Measurements, MockBackend, QuotaAction, RunConfig,
+};
+use serde::{Deserialize, Serialize};
+
+fn container(name: &str) -> ContainerConfig {
+    ContainerConfig {
+        name: name.to_owned(),
+        image_name: "nginx".to_owned(),
+        image_tag: "alpine".to_owned(),
+        network: Some("exp-mock-net".to_owned()),
+        pull: true,
+        named_volumes: vec![("exp-mock-data".to_owned(), "/data".to_owned())],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn run_containers_without_docker() {
+    let config_dir = std::env::temp_dir()
+        .join("exp-mock-backend-test")
+        .join("abc-1");
+    let _ = remove_dir_all(&config_dir);
+    create_dir_all(&config_dir).unwrap();
+
+    let backend = MockBackend::new();
+    backend.exec_output("db", vec!["ready\n".to_owned()], Vec::new());
+    // failing teardown shouldn't stop the rest of it
+    backend.fail(BackendCall::RemoveNetwork("exp-mock-net".to_owned()), 1);
+    let mut runner = Runner::with_backend(config_dir.clone(), backend.clone());
+    runner
+        .add_volume(&VolumeConfig {
+            name: "exp-mock-data".to_owned(),
+            driver: None,
+            preserve: true,
+        })
+        .await;
+    runner.add_container(&container("db")).await;
+    runner.add_container(&container("client")).await;
+    let (out, err) = runner.execute_command("db", vec!["pg_isready"]).await;
+    assert_eq!(out, ["ready\n"]);
+    assert!(err.is_empty());
+    // checkpoints go through the docker CLI
+    assert!(runner.checkpoint("db").await.is_err());
+    assert!(runner.restore("db", "db-1").await.is_err());
+    backend.exit_container("db", 137, true);
+    runner.finish().await;
+
+    let calls = backend.calls();
+    let position = |call: BackendCall| {
+        calls
+            .iter()
+            .position(|c| *c == call)
+            .unwrap_or_else(|| panic!("missing call {:?}", call))
+    };
+    // the network and volume are only created once
+    assert_eq!(
+        calls
+            .iter()
+            .filter(|c| matches!(
+                c,
+                BackendCall::CreateNetwork(_) | BackendCall::CreateVolume(_)
+            ))
+            .count(),
+        2
+    );
+    assert!(
+        position(BackendCall::CreateNetwork("exp-mock-net".to_owned()))
+            < position(BackendCall::CreateContainer("db".to_owned()))
+    );
+    assert!(
+        position(BackendCall::PullImage("nginx:alpine".to_owned()))
+            < position(BackendCall::CreateContainer("db".to_owned()))
+    );
+    // the image is only pulled once for the run
+    assert_eq!(
+        calls
+            .iter()
+            .filter(|c| matches!(c, BackendCall::PullImage(_)))
+            .count(),
+        1
+    );
+    assert_eq!(
+        calls[calls.len() - 6..],
+        [
+            BackendCall::StopContainer("db".to_owned()),
+            BackendCall::RemoveContainer("db".to_owned()),
+            BackendCall::StopContainer("client".to_owned()),
+            BackendCall::RemoveContainer("client".to_owned()),
+            BackendCall::RemoveVolume("exp-mock-data".to_owned()),
+            BackendCall::RemoveNetwork("exp-mock-net".to_owned()),
+        ]
+    );
+    let (containers, volumes, networks) = backend.resources();
+    assert!(containers.is_empty());
+    assert!(volumes.is_empty());
+    assert_eq!(networks, ["exp-mock-net"]);
+
+    let exits = read_container_exits(&config_dir).unwrap();
+    assert_eq!(exits.len(), 2);
+    let db = exits.iter().find(|exit| exit.name == "db").unwrap();
+    assert!(!db.running);
+    assert!(db.oom_killed);
+    assert_eq!(db.exit_code, Some(137));
+    let client = exits.iter().find(|exit| exit.name == "client").unwrap();
+    assert!(client.running);
+    assert!(!client.oom_killed);
+}
+
+#[tokio::test]
+async fn prepulled_images_arent_pulled_again() {
+    let config_dir = std::env::temp_dir()
+        .join("exp-mock-backend-prepull-test")
+        .join("abc-1");
+    let _ = remove_dir_all(&config_dir);
+    create_dir_all(config_dir.join("config")).unwrap();
+    let pull = ImagePull {
+        image: "nginx:alpine".to_owned(),
+        start: chrono::Utc::now(),
+        duration_secs: 1.5,
+        repo_digests: Vec::new(),
+        layers: Vec::new(),
+        log: Vec::new(),
+    };
+    std::fs::write(
+        config_dir.join("config/docker-pull-nginx_alpine.json"),
+        serde_json::to_string(&pull).unwrap(),
+    )
+    .unwrap();
+
+    let backend = MockBackend::new();
+    let mut runner = Runner::with_backend(config_dir.clone(), backend.clone());
+    runner.add_container(&container("db")).await;
+    runner.finish().await;
+    assert!(!backend
+        .calls()
+        .iter()
+        .any(|call| matches!(call, BackendCall::PullImage(_))));
+    assert_eq!(read_image_pulls(&config_dir).unwrap(), [pull]);
+}
+
+#[tokio::test]
+#[should_panic(expected = "Failed to start container")]
+async fn failed_calls_fail_the_run() {
+    let config_dir = std::env::temp_dir()
+        .join("exp-mock-backend-fail-test")
+        .join("abc-1");
+    create_dir_all(&config_dir).unwrap();
+    let backend = MockBackend::new();
+    backend.fail(BackendCall::StartContainer("db".to_owned()), 1);
+    let mut runner = Runner::with_backend(config_dir, backend);
+    runner.add_container(&container("db")).await;
+}
+
+#[derive(Serialize, Deserialize)]
+struct Config {}
+
+impl ExperimentConfiguration for Config {}
+
+struct Hangs(MockBackend);
+
+#[async_trait]
+impl Experiment for Hangs {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config {}]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        _: &Self::Configuration,
+        configuration_dir: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        let mut runner = Runner::with_backend(configuration_dir.to_owned(), self.0.clone());
+        runner.add_container(&container("db")).await;
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+        runner.finish().await;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn timed_out_runs_are_torn_down() {
+    let results_dir = PathBuf::from("results/mock-backend-timeout");
+    let _ = remove_dir_all(&results_dir);
+    let backend = MockBackend::new();
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    exp::run(&mut Hangs(backend.clone()), &run_config)
+        .await
+        .unwrap();
+
+    let hash = Config {}.hash_serialized().unwrap();
+    assert!(results_dir.join(format!("{}.failed", hash)).is_dir());
+    assert!(backend
+        .calls()
+        .contains(&BackendCall::RemoveContainer("db".to_owned())));
+    let (containers, volumes, networks) = backend.resources();
+    assert!(containers.is_empty());
+    assert!(volumes.is_empty());
+    assert!(networks.is_empty());
+}
+
+#[tokio::test]
+async fn runs_over_quota_are_torn_down() {
+    let results_dir = PathBuf::from("results/mock-backend-quota");
+    let _ = remove_dir_all(&results_dir);
+    let backend = MockBackend::new();
+    // the configuration file alone is over the quota
+    let mut quota = ArtifactQuota::new(0, QuotaAction::Fail);
+    quota.interval = Some(Duration::from_millis(20));
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .artifact_quota(quota)
+        .build()
+        .unwrap();
+    exp::run(&mut Hangs(backend.clone()), &run_config)
+        .await
+        .unwrap();
+
+    let hash = Config {}.hash_serialized().unwrap();
+    assert!(results_dir.join(format!("{}.failed", hash)).is_dir());
+    let (containers, _, _) = backend.resources();
+    assert!(containers.is_empty());
+}
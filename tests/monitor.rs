@@ -0,0 +1,186 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use exp::monitor::{MonitorError, ProcessMetric, ProcessMonitor, SystemMonitor};
+use regex::Regex;
+use tokio::process::Command;
+
+#[tokio::test]
+async fn spawn_command_monitors_descendants() {
+    let dir = PathBuf::from("results/monitor");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+
+    let mut command = Command::new("sh");
+    command.args(["-c", "sleep 1 & wait"]);
+    let (status, path) = ProcessMonitor::spawn_command(command, &dir, Duration::from_millis(250))
+        .await
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(path, dir.join("process-sh.csv"));
+
+    let mut reader = csv::Reader::from_path(&path).unwrap();
+    let name = reader
+        .headers()
+        .unwrap()
+        .iter()
+        .position(|header| header == "name")
+        .unwrap();
+    let names = reader
+        .records()
+        .map(|record| record.unwrap()[name].to_owned())
+        .collect::<Vec<_>>();
+    assert!(names.iter().any(|name| name == "sh"));
+    assert!(names.iter().any(|name| name == "sleep"));
+}
+
+#[tokio::test]
+async fn spawn_command_kills_unmonitored_child() {
+    let mut command = Command::new("sleep");
+    command.arg("31.25");
+    let result = ProcessMonitor::spawn_command(
+        command,
+        &PathBuf::from("results/monitor-missing/metrics"),
+        Duration::from_millis(250),
+    )
+    .await;
+    assert!(matches!(result, Err(MonitorError::Csv(_))));
+    let running = std::fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|entry| std::fs::read(entry.unwrap().path().join("cmdline")).ok())
+        .any(|cmdline| cmdline == b"sleep\x0031.25\x00");
+    assert!(!running);
+}
+
+#[tokio::test]
+async fn find_by_name() {
+    let mut child = Command::new("sleep").arg("2").spawn().unwrap();
+    let path = std::env::temp_dir().join("exp-monitor-find.csv");
+    let monitor = ProcessMonitor::find_by_name(
+        &Regex::new("^sleep$").unwrap(),
+        &path,
+        Duration::from_secs(1),
+    );
+    assert!(monitor.is_ok());
+    let monitor = ProcessMonitor::find_by_name(
+        &Regex::new("^no-such-process-exists$").unwrap(),
+        &path,
+        Duration::from_secs(1),
+    );
+    assert!(matches!(monitor, Err(MonitorError::NotFound(_))));
+    child.kill().await.unwrap();
+}
+
+#[tokio::test]
+async fn selected_metrics() {
+    let dir = PathBuf::from("results/monitor-metrics");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let path = dir.join("process-sh.csv");
+
+    let mut child = Command::new("sh").args(["-c", "sleep 1"]).spawn().unwrap();
+    let (end_tx, end_rx) = tokio::sync::watch::channel(());
+    let monitor = ProcessMonitor::new(child.id().unwrap(), &path, Duration::from_millis(250))
+        .unwrap()
+        .metrics(&[
+            ProcessMetric::CpuPerCore,
+            ProcessMetric::DiskRates,
+            ProcessMetric::OpenFiles,
+            ProcessMetric::Threads,
+        ])
+        .spawn(end_rx);
+    assert!(child.wait().await.unwrap().success());
+    end_tx.send(()).unwrap();
+    monitor.await.unwrap().unwrap();
+
+    let mut reader = csv::Reader::from_path(&path).unwrap();
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec![
+            "time",
+            "pid",
+            "parent",
+            "cpu_usage_per_core_percentage",
+            "disk_write_bytes_per_second",
+            "disk_read_bytes_per_second",
+            "open_files",
+            "threads",
+            "name",
+        ]
+    );
+    let records = reader.records().collect::<Result<Vec<_>, _>>().unwrap();
+    assert!(records.len() > 1);
+    // rates need a previous sample
+    assert_eq!(&records[0][4], "");
+    assert!(records[1][4].parse::<f64>().is_ok());
+    assert!(records[0][6].parse::<u64>().unwrap() >= 3);
+    assert_eq!(&records[0][7], "1");
+}
+
+#[tokio::test]
+async fn system_monitor() {
+    let dir = PathBuf::from("results/monitor-system");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let path = dir.join("system.csv");
+
+    let (end_tx, end_rx) = tokio::sync::watch::channel(());
+    let monitor = SystemMonitor::new(&path, Duration::from_millis(250))
+        .unwrap()
+        .spawn(end_rx);
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    end_tx.send(()).unwrap();
+    monitor.await.unwrap().unwrap();
+
+    let mut reader = csv::Reader::from_path(&path).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    assert_eq!(&headers[0], "time");
+    assert_eq!(&headers[1], "cpu_usage_percentage");
+    assert_eq!(&headers[2], "cpu0_usage_percentage");
+    assert!(headers.iter().any(|header| header == "memory_used_bytes"));
+    assert!(headers.iter().any(|header| header == "swap_total_bytes"));
+    let records = reader.records().collect::<Result<Vec<_>, _>>().unwrap();
+    assert!(records.len() >= 2);
+    assert!(records.iter().all(|record| record.len() == headers.len()));
+    let memory = headers
+        .iter()
+        .position(|header| header == "memory_total_bytes")
+        .unwrap();
+    assert!(records[0][memory].parse::<u64>().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn stop_before_process_exits() {
+    let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+    let path = std::env::temp_dir().join("exp-monitor-stop.csv");
+    let (_end_tx, end_rx) = tokio::sync::watch::channel(());
+
+    let start = Instant::now();
+    let monitor = ProcessMonitor::new(child.id().unwrap(), &path, Duration::from_millis(250))
+        .unwrap()
+        .max_duration(Duration::from_millis(500));
+    monitor.spawn(end_rx.clone()).await.unwrap().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    let mut monitor =
+        ProcessMonitor::new(child.id().unwrap(), &path, Duration::from_millis(250)).unwrap();
+    let stop = monitor.stop_handle();
+    let monitor = std::thread::spawn(move || monitor.run());
+    std::thread::sleep(Duration::from_millis(500));
+    stop.stop();
+    monitor.join().unwrap().unwrap();
+    assert!(csv::Reader::from_path(&path).unwrap().records().count() > 0);
+    child.kill().await.unwrap();
+}
+
+#[test]
+fn interval_too_short() {
+    let path = std::env::temp_dir().join("exp-monitor-short.csv");
+    assert!(matches!(
+        ProcessMonitor::new(std::process::id(), &path, Duration::from_millis(1)),
+        Err(MonitorError::IntervalTooShort { .. })
+    ));
+}
@@ -2,7 +2,8 @@ use std::{path::Path, path::PathBuf, time::Duration};
 
 use async_trait::async_trait;
 use exp::{
-    docker_runner::ContainerConfig, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    docker_runner::ContainerConfig, AnalysisDirs, Environment, ExpResult, Experiment,
+    ExperimentConfiguration, Measurements,
 };
 use serde::{Deserialize, Serialize};
 
@@ -26,7 +27,12 @@ impl Experiment for ExpA {
         println!("prerun a");
         Ok(())
     }
-    async fn run(&mut self, _: &Self::Configuration, conf_dir: &Path) -> ExpResult<()> {
+    async fn run(
+        &mut self,
+        _: &Self::Configuration,
+        conf_dir: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
         println!("run a {:?}", conf_dir);
 
         let mut runner = exp::docker_runner::Runner::new(conf_dir.to_path_buf()).await;
@@ -37,16 +43,9 @@ impl Experiment for ExpA {
                 image_name: "nginx".to_owned(),
                 image_tag: "alpine".to_owned(),
                 network: Some("exp-test-net".to_owned()),
-                network_subnet: None,
-                command: None,
-                env: None,
-                ports: Some(vec![("90".to_owned(), "80".to_owned())]),
-                capabilities: None,
-                cpus: None,
-                memory: None,
+                ports: Some(vec![("90".into(), "80".to_owned())]),
                 pull: true,
-                tmpfs: Vec::new(),
-                volumes: Vec::new(),
+                ..Default::default()
             })
             .await;
         tokio::time::sleep(Duration::from_secs(5)).await;
@@ -60,10 +59,11 @@ impl Experiment for ExpA {
 
     fn analyse(
         &mut self,
-        _exp_dir: &Path,
+        _dirs: &AnalysisDirs,
         _environment: Environment,
-        _configurations: Vec<(Self::Configuration, PathBuf)>,
-    ) {
+        configurations: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::json!({ "configurations": configurations.len() }))
     }
 }
 
@@ -73,10 +73,23 @@ async fn multiple() {
         configurations: vec![ExpAConfig {}],
     };
     let results_dir = PathBuf::from("results/multiple");
-    let run_config = exp::RunConfig {
+    let run_config = exp::RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build_metadata(exp::build_metadata!())
+        .build()
+        .unwrap();
+    exp::run(&mut exp, &run_config).await.unwrap();
+    let analyse_config = exp::AnalyseConfig {
         results_dir: results_dir.clone(),
+        output_dir: None,
+        incremental: false,
+        filter: None,
+        incomplete: exp::IncompletePolicy::Skip,
     };
-    exp::run(&mut exp, &run_config).await.unwrap();
-    let analyse_config = exp::AnalyseConfig { results_dir };
-    exp::analyse(&mut exp, &analyse_config).await.unwrap();
+    let summary = exp::analyse(&mut exp, &analyse_config).await.unwrap();
+    assert_eq!(summary["configurations"], 1);
+    assert!(results_dir
+        .join(exp::ANALYSIS_DIR)
+        .join(exp::SUMMARY_FILE)
+        .exists());
 }
@@ -0,0 +1,41 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    net::TcpListener,
+    time::{Duration, Instant},
+};
+
+use exp::notify::{Notification, Notifier, Webhook};
+
+#[tokio::test]
+async fn webhook_doesnt_block_the_run() {
+    // accepts the request but never responds
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let webhook = Webhook {
+        url: format!("http://{}/hook", listener.local_addr().unwrap()),
+    };
+    let start = Instant::now();
+    webhook.notify(&Notification::FirstFailure {
+        experiment: "exp".to_owned(),
+        hash: "abc-1".to_owned(),
+    });
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap();
+        }
+        if line == "\r\n" {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["event"], "first_failure");
+    assert_eq!(body["text"], "exp: configuration abc-1 failed");
+}
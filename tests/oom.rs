@@ -0,0 +1,93 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all, File},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    docker_runner::{read_container_exits, ContainerExit},
+    results::list_configurations,
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, Measurements,
+    RunConfig,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    oom_killed: bool,
+}
+
+impl ExperimentConfiguration for Config {}
+
+/// Writes the exit of a container as a `Runner` does when it finishes.
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { oom_killed: false }, Config { oom_killed: true }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        configuration_dir: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        let config_dir = configuration_dir.join("config");
+        create_dir_all(&config_dir)?;
+        let exit = ContainerExit {
+            name: "db".to_owned(),
+            running: false,
+            exit_code: Some(137),
+            oom_killed: configuration.oom_killed,
+            error: None,
+        };
+        serde_json::to_writer(File::create(config_dir.join("docker-db-exit.json"))?, &exit)?;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn oom_killed_runs_fail() {
+    let results_dir = PathBuf::from("results/oom");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .fail_on_oom(true)
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    let killed = Config { oom_killed: true }.hash_serialized().unwrap();
+    for entry in list_configurations(&results_dir).unwrap() {
+        let expected = if entry.hash == killed {
+            "failed"
+        } else {
+            "completed"
+        };
+        assert_eq!(entry.state.to_string(), expected);
+        let exits = read_container_exits(&entry.path).unwrap();
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0].name, "db");
+    }
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "otel")]
+
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use exp::{
+    otel::OtelExporter, AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    Measurements, RunConfig,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::Context, layer::SubscriberExt, registry::LookupSpan, Layer};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { n: 1 }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(&mut self, _: &Self::Configuration, _: &Path, _: &Measurements) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+/// Name of a span and of its parent.
+type SpanName = (&'static str, Option<&'static str>);
+
+/// Records the name of each span with the name of its parent.
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<Vec<SpanName>>>);
+
+impl<S> Layer<S> for Spans
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let parent = span.parent().map(|parent| parent.name());
+        self.0.lock().unwrap().push((span.name(), parent));
+    }
+}
+
+#[tokio::test]
+async fn orchestration_spans() {
+    // nothing is listening, so the spans are dropped when exported
+    let exporter = OtelExporter::new("exp-test", Some("http://127.0.0.1:1")).unwrap();
+    let spans = Spans::default();
+    let _subscriber = tracing::subscriber::set_default(
+        tracing_subscriber::registry()
+            .with(exporter.layer())
+            .with(spans.clone()),
+    );
+    let results_dir = PathBuf::from("results/otel");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    let spans = spans.0.lock().unwrap().clone();
+    let parent_of = |name: &str| {
+        spans
+            .iter()
+            .find(|(span, _)| *span == name)
+            .map(|(_, parent)| *parent)
+    };
+    assert_eq!(parent_of("experiment"), Some(None));
+    assert_eq!(parent_of("configuration"), Some(Some("experiment")));
+    for phase in ["pre_run", "run", "post_run"] {
+        assert_eq!(parent_of(phase), Some(Some("configuration")));
+    }
+}
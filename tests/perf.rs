@@ -0,0 +1,47 @@
+#![cfg(all(target_os = "linux", feature = "perf"))]
+
+use std::{
+    fs::{create_dir_all, remove_dir_all},
+    path::PathBuf,
+    time::Duration,
+};
+
+use exp::monitor::ProcessMonitor;
+use tokio::process::Command;
+
+#[tokio::test]
+async fn perf_counters_are_recorded() {
+    let dir = PathBuf::from("results/perf");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let path = dir.join("process-sh.csv");
+
+    let mut child = Command::new("sh")
+        .args(["-c", "i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done"])
+        .spawn()
+        .unwrap();
+    let (end_tx, end_rx) = tokio::sync::watch::channel(());
+    let monitor = ProcessMonitor::new(child.id().unwrap(), &path, Duration::from_millis(250))
+        .unwrap()
+        .perf_counters()
+        .spawn(end_rx);
+    assert!(child.wait().await.unwrap().success());
+    end_tx.send(()).unwrap();
+    monitor.await.unwrap().unwrap();
+
+    let mut reader = csv::Reader::from_path(&path).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    for column in ["instructions", "cycles", "cache_misses", "context_switches"] {
+        assert!(headers.iter().any(|header| header == column), "{}", column);
+    }
+    // counters may not be permitted here, so only check they are parsed
+    let records = reader.records().collect::<Result<Vec<_>, _>>().unwrap();
+    assert!(!records.is_empty());
+    let switches = headers
+        .iter()
+        .position(|header| header == "context_switches")
+        .unwrap();
+    assert!(records
+        .iter()
+        .all(|record| record[switches].is_empty() || record[switches].parse::<u64>().is_ok()));
+}
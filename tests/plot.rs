@@ -0,0 +1,34 @@
+#![cfg(feature = "plotters")]
+
+use std::fs::{create_dir_all, read_to_string, remove_dir_all};
+
+use exp::plot::{latency_cdf, parameter_chart};
+use exp::stats::Summary;
+
+#[test]
+fn write_svg_charts() {
+    let dir = std::env::temp_dir().join("exp-plot-test");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+
+    let series = vec![(
+        "etcd".to_owned(),
+        vec![
+            (1.0, Summary::of(&[10.0, 12.0]).unwrap()),
+            (2.0, Summary::of(&[20.0, 22.0, 21.0]).unwrap()),
+        ],
+    )];
+    let path = dir.join("throughput.svg");
+    parameter_chart(&path, "throughput", "clients", "requests/s", &series).unwrap();
+    assert!(read_to_string(&path).unwrap().contains("<svg"));
+
+    let path = dir.join("latency.svg");
+    latency_cdf(
+        &path,
+        "latency",
+        "latency (ms)",
+        &[("etcd".to_owned(), vec![3.0, 1.0, 2.0])],
+    )
+    .unwrap();
+    assert!(read_to_string(&path).unwrap().contains("latency"));
+}
@@ -0,0 +1,19 @@
+use exp::docker_runner::HostPort;
+
+#[test]
+fn host_ports_are_written_as_strings() {
+    let ports: Vec<(HostPort, String)> =
+        serde_json::from_str(r#"[["8080", "80"], ["auto", "9090"]]"#).unwrap();
+    assert_eq!(
+        ports,
+        vec![
+            (HostPort::Fixed("8080".to_owned()), "80".to_owned()),
+            (HostPort::Auto, "9090".to_owned()),
+        ]
+    );
+    assert_eq!(
+        serde_json::to_string(&ports).unwrap(),
+        r#"[["8080","80"],["auto","9090"]]"#
+    );
+    assert_eq!(HostPort::from("auto"), HostPort::Auto);
+}
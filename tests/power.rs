@@ -0,0 +1,118 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all, write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use exp::{
+    read_energy, AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    Measurements, PowerMeter, PowerMonitor, PowerSample, RunConfig, POWER_FILE,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {}
+
+impl ExperimentConfiguration for Config {}
+
+/// Uses energy by bumping the counters of a fake powercap directory.
+struct Exp {
+    rapl_dir: PathBuf,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config {}]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(&mut self, _: &Self::Configuration, _: &Path, _: &Measurements) -> ExpResult<()> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // the package counter wraps
+        write(self.rapl_dir.join("intel-rapl:0/energy_uj"), "1000000")?;
+        write(self.rapl_dir.join("intel-rapl:0:0/energy_uj"), "500000")?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+fn zone(rapl_dir: &Path, id: &str, name: &str, energy: u64) {
+    let dir = rapl_dir.join(id);
+    create_dir_all(&dir).unwrap();
+    write(dir.join("name"), format!("{}\n", name)).unwrap();
+    write(dir.join("energy_uj"), format!("{}\n", energy)).unwrap();
+    write(dir.join("max_energy_range_uj"), "4000000\n").unwrap();
+}
+
+#[tokio::test]
+async fn power_is_measured() {
+    let dir = PathBuf::from("results/power");
+    let _ = remove_dir_all(&dir);
+    let rapl_dir = dir.join("powercap");
+    zone(&rapl_dir, "intel-rapl:0", "package-0", 3000000);
+    zone(&rapl_dir, "intel-rapl:0:0", "dram", 0);
+    let meter = dir.join("meter");
+    write(&meter, "meter starting\n50.0 W\n").unwrap();
+
+    let results_dir = dir.join("results");
+    let power = PowerMonitor {
+        rapl_dir: Some(rapl_dir.clone()),
+        meter: Some(PowerMeter::Serial { path: meter }),
+        interval: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .power(power)
+        .build()
+        .unwrap();
+    exp::run(&mut Exp { rapl_dir }, &run_config).await.unwrap();
+
+    let configuration_dir = results_dir.join(Config {}.hash_serialized().unwrap());
+    let energy = read_energy(&configuration_dir).unwrap();
+    let sources = energy
+        .iter()
+        .map(|energy| energy.source.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(sources, vec!["meter", "package-0", "package-0/dram"]);
+    assert!((energy[1].joules - 2.0).abs() < 1e-9);
+    assert!((energy[2].joules - 0.5).abs() < 1e-9);
+    assert!(energy[0].joules > 0.0);
+    assert!(energy[0].mean_watts <= 50.0);
+
+    let samples = csv::Reader::from_path(configuration_dir.join("metrics").join(POWER_FILE))
+        .unwrap()
+        .deserialize()
+        .collect::<Result<Vec<PowerSample>, _>>()
+        .unwrap();
+    assert!(samples.len() > 3);
+    assert!(samples
+        .iter()
+        .filter(|sample| sample.source == "meter")
+        .all(|sample| sample.watts == 50.0));
+    let last_package = samples
+        .iter()
+        .rfind(|sample| sample.source == "package-0")
+        .unwrap();
+    assert!((last_package.joules - 2.0).abs() < 1e-9);
+}
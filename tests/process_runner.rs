@@ -0,0 +1,44 @@
+use std::path::{Path, PathBuf};
+
+use exp::process_runner::{cgroup_limits, cgroup_path, ProcessConfig};
+
+fn config(cpus: Option<f64>, memory: Option<i64>) -> ProcessConfig {
+    ProcessConfig {
+        name: "server".to_owned(),
+        binary: PathBuf::from("/bin/true"),
+        args: Vec::new(),
+        env: Vec::new(),
+        working_dir: None,
+        cpus,
+        memory,
+        logs: Default::default(),
+    }
+}
+
+#[test]
+fn cgroups_are_per_run() {
+    assert_eq!(
+        cgroup_path(Path::new("results/exp/abc-1.running"), "server"),
+        Path::new("/sys/fs/cgroup/exp-abc-1-server")
+    );
+    assert_ne!(
+        cgroup_path(Path::new("results/exp/abc.running"), "server"),
+        cgroup_path(Path::new("results/exp/def.running"), "server")
+    );
+}
+
+#[test]
+fn cgroup_limit_formats() {
+    assert!(cgroup_limits(&config(None, None)).is_empty());
+    assert_eq!(
+        cgroup_limits(&config(Some(1.5), Some(1 << 30))),
+        [
+            ("cpu.max", "150000 100000".to_owned()),
+            ("memory.max", "1073741824".to_owned()),
+        ]
+    );
+    assert_eq!(
+        cgroup_limits(&config(Some(0.25), None)),
+        [("cpu.max", "25000 100000".to_owned())]
+    );
+}
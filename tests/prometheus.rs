@@ -0,0 +1,40 @@
+use chrono::Utc;
+use exp::{parse_prometheus, PrometheusRecord};
+
+#[test]
+fn parse_exposition_format() {
+    let text = r#"# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{method="post",code="400"}    3 1395066363000
+
+msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\""} 1.458255915e9
+go_goroutines 42
+http_request_duration_seconds_bucket{le="+Inf",} 144320
+not a sample
+"#;
+    let samples = parse_prometheus(text);
+    assert_eq!(samples.len(), 5);
+    assert_eq!(samples[0].name, "http_requests_total");
+    assert_eq!(samples[0].labels["code"], "200");
+    assert_eq!(samples[0].labels["method"], "post");
+    assert_eq!(samples[0].value, 1027.0);
+    assert_eq!(samples[1].value, 3.0);
+    assert_eq!(samples[2].labels["path"], r"C:\DIR\FILE.TXT");
+    assert_eq!(
+        samples[2].labels["error"],
+        "Cannot find file:\n\"FILE.TXT\""
+    );
+    assert_eq!(samples[2].value, 1.458255915e9);
+    assert!(samples[3].labels.is_empty());
+    assert_eq!(samples[3].value, 42.0);
+    assert_eq!(samples[4].labels["le"], "+Inf");
+
+    let record = PrometheusRecord::new(Utc::now(), samples[0].clone());
+    assert_eq!(record.labels, r#"code="200",method="post""#);
+    let record = PrometheusRecord::new(Utc::now(), samples[2].clone());
+    assert_eq!(
+        record.labels,
+        r#"error="Cannot find file:\n\"FILE.TXT\"",path="C:\\DIR\\FILE.TXT""#
+    );
+}
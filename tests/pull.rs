@@ -1,6 +1,12 @@
 #[tokio::test]
 async fn pull() {
-    exp::docker_runner::pull_image("busybox", "latest")
+    let pull = exp::docker_runner::pull_image("busybox", "latest")
         .await
         .unwrap();
+    assert_eq!(pull.image, "busybox:latest");
+    assert!(!pull.layers.is_empty());
+    assert!(pull
+        .repo_digests
+        .iter()
+        .any(|digest| digest.starts_with("busybox@sha256:")));
 }
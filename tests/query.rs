@@ -0,0 +1,58 @@
+#![cfg(feature = "sql")]
+
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+use polars::prelude::*;
+
+#[test]
+fn query_configurations_and_metrics() {
+    let dir = std::env::temp_dir().join("exp-query-test");
+    let _ = remove_dir_all(&dir);
+    let configurations = [
+        ("aaaa", "", r#"{"nodes":1,"workload":{"name":"read"}}"#),
+        ("bbbb", "", r#"{"nodes":3,"workload":{"name":"write"}}"#),
+        (
+            "cccc",
+            ".failed",
+            r#"{"nodes":3,"workload":{"name":"read"}}"#,
+        ),
+    ];
+    for (hash, extension, configuration) in configurations {
+        let config_dir = dir.join(format!("{}{}", hash, extension));
+        create_dir_all(config_dir.join("metrics")).unwrap();
+        write(config_dir.join("configuration.json"), configuration).unwrap();
+        write(
+            config_dir.join("metrics").join("docker-node-stat.csv"),
+            "read,preread,memory_stats_usage\n2023-06-01T10:00:00Z,2023-06-01T09:59:59Z,100\n2023-06-01T10:00:01Z,2023-06-01T10:00:00Z,300\n",
+        )
+        .unwrap();
+    }
+
+    let df = exp::query::sql(
+        &dir,
+        r#"SELECT hash, nodes, "workload.name" FROM configurations WHERE state = 'completed' ORDER BY hash"#,
+    )
+    .unwrap();
+    assert_eq!(df.height(), 2);
+    assert_eq!(df.column("nodes").unwrap().dtype(), &DataType::Int64);
+    assert_eq!(
+        df.column("workload.name").unwrap().utf8().unwrap().get(1),
+        Some("write")
+    );
+
+    // failed runs have no metrics
+    let df = exp::query::sql(
+        &dir,
+        "SELECT nodes, SUM(memory_stats_usage) AS memory FROM container_stats \
+         JOIN configurations ON container_stats.configuration = configurations.hash \
+         GROUP BY nodes ORDER BY nodes",
+    )
+    .unwrap();
+    assert_eq!(df.height(), 2);
+    assert_eq!(
+        df.column("memory").unwrap().u64().unwrap().get(0),
+        Some(400)
+    );
+
+    remove_dir_all(&dir).unwrap();
+}
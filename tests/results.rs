@@ -0,0 +1,75 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all, write, File},
+    os::unix::io::AsRawFd,
+};
+
+use exp::results::{clean, diff_configurations, gc, list_configurations, ConfigurationState};
+use serde_json::json;
+
+#[test]
+fn clean_and_gc_results() {
+    let dir = std::env::temp_dir().join("exp-results-test");
+    let _ = remove_dir_all(&dir);
+    for name in ["a", "b.failed", "c.running"] {
+        create_dir_all(dir.join(name)).unwrap();
+        write(dir.join(name).join("configuration.json"), r#"{"n":1}"#).unwrap();
+    }
+    create_dir_all(dir.join("analysis")).unwrap();
+    create_dir_all(dir.join(".import-1")).unwrap();
+    // held by a process that can't exist on this host
+    let hostname = nix::sys::utsname::uname().unwrap();
+    let stale = json!({
+        "hostname": hostname.nodename().to_string_lossy(),
+        "pid": i32::MAX,
+        "acquired": "2023-01-01T00:00:00Z",
+    });
+    write(dir.join("c.lock"), stale.to_string()).unwrap();
+    // held by this process, whatever the owner it names
+    write(dir.join("d.lock"), stale.to_string()).unwrap();
+    let held = File::open(dir.join("d.lock")).unwrap();
+    nix::fcntl::flock(
+        held.as_raw_fd(),
+        nix::fcntl::FlockArg::LockExclusiveNonblock,
+    )
+    .unwrap();
+
+    let entries = list_configurations(&dir).unwrap();
+    let states = entries
+        .iter()
+        .map(|entry| (entry.hash.as_str(), entry.state))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        vec![
+            ("a", ConfigurationState::Completed),
+            ("b", ConfigurationState::Failed),
+            ("c", ConfigurationState::Running),
+        ]
+    );
+
+    let removed = clean(&dir, true).unwrap();
+    assert_eq!(removed, vec![dir.join("b.failed"), dir.join("c.running")]);
+    assert!(dir.join("b.failed").exists());
+    clean(&dir, false).unwrap();
+    assert!(!dir.join("b.failed").exists());
+    assert!(!dir.join("c.running").exists());
+    assert!(dir.join("a").exists());
+
+    let removed = gc(&dir, false).unwrap();
+    assert_eq!(removed, vec![dir.join(".import-1"), dir.join("c.lock")]);
+    assert!(dir.join("d.lock").exists());
+    drop(held);
+    assert_eq!(gc(&dir, false).unwrap(), vec![dir.join("d.lock")]);
+    assert!(dir.join("analysis").exists());
+}
+
+#[test]
+fn diff_nested_configurations() {
+    let a = json!({"nodes": 3, "image": {"name": "etcd", "tag": "v3"}, "extra": [1]});
+    let b = json!({"nodes": 5, "image": {"name": "etcd", "tag": "v3"}, "other": true});
+    let diff = diff_configurations(&a, &b);
+    assert_eq!(diff.len(), 3);
+    assert_eq!(diff["nodes"], (Some(json!(3)), Some(json!(5))));
+    assert_eq!(diff["extra.0"], (Some(json!(1)), None));
+    assert_eq!(diff["other"], (None, Some(json!(true))));
+}
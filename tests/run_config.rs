@@ -0,0 +1,290 @@
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use exp::{
+    read_events, results::list_configurations, AnalysisDirs, Environment, ExpResult, Experiment,
+    ExperimentConfiguration, IdleWait, Measurements, RunConfig, RunConfigBuilder, RunConfigError,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    sleep_ms: u64,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp;
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { sleep_ms: 0 }, Config { sleep_ms: 5000 }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        tokio::time::sleep(Duration::from_millis(configuration.sleep_ms)).await;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+/// Sleeps for each configuration, with replicas to run them side by side.
+struct Parallel;
+
+#[async_trait]
+impl Experiment for Parallel {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { sleep_ms: 300 }, Config { sleep_ms: 301 }]
+    }
+
+    fn replica(&self) -> Option<Self> {
+        Some(Parallel)
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        tokio::time::sleep(Duration::from_millis(configuration.sleep_ms)).await;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[test]
+fn builder_validation() {
+    assert!(matches!(
+        RunConfig::builder().build(),
+        Err(RunConfigError::MissingResultsDir)
+    ));
+    assert!(matches!(
+        RunConfig::builder()
+            .results_dir("results")
+            .repeats(0)
+            .build(),
+        Err(RunConfigError::NoRepeats)
+    ));
+    assert!(matches!(
+        RunConfig::builder()
+            .results_dir("results")
+            .timeout(Duration::ZERO)
+            .build(),
+        Err(RunConfigError::ZeroTimeout)
+    ));
+    assert!(matches!(
+        RunConfig::builder()
+            .results_dir("results")
+            .force_rerun(true)
+            .build(),
+        Err(RunConfigError::ForceRerunWithoutStore)
+    ));
+    assert!(matches!(
+        RunConfig::builder()
+            .results_dir("results")
+            .max_duration(Duration::ZERO)
+            .build(),
+        Err(RunConfigError::ZeroMaxDuration)
+    ));
+    assert!(matches!(
+        RunConfig::builder()
+            .results_dir("results")
+            .max_parallel(0)
+            .build(),
+        Err(RunConfigError::NoParallelism)
+    ));
+
+    let config = RunConfig::builder().results_dir("results").build().unwrap();
+    assert_eq!(config.repeats, 1);
+    assert_eq!(config.timeout, None);
+    assert_eq!(config.cooldown, Duration::ZERO);
+    assert!(config.idle_wait.is_none());
+    assert_eq!(config.max_parallel, 1);
+}
+
+#[test]
+fn builder_from_settings() {
+    let builder: RunConfigBuilder = serde_json::from_value(serde_json::json!({
+        "results_dir": "results/settings",
+        "repeats": 3,
+        "timeout": 1.5,
+        "cooldown": 2,
+        "idle_wait": { "max_load": 0.5, "interval": 0.5 },
+    }))
+    .unwrap();
+    let config = builder.build().unwrap();
+    assert_eq!(config.results_dir, PathBuf::from("results/settings"));
+    assert_eq!(config.repeats, 3);
+    assert_eq!(config.timeout, Some(Duration::from_millis(1500)));
+    assert_eq!(config.cooldown, Duration::from_secs(2));
+    let idle_wait = config.idle_wait.unwrap();
+    assert_eq!(idle_wait.max_load, Some(0.5));
+    assert_eq!(idle_wait.interval, Some(Duration::from_millis(500)));
+
+    assert!(
+        serde_json::from_value::<RunConfigBuilder>(serde_json::json!({
+            "results_dir": "results/settings",
+            "repeat": 3,
+        }))
+        .is_err()
+    );
+}
+
+#[tokio::test]
+async fn repeats_and_timeout() {
+    let results_dir = PathBuf::from("results/run_config");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(2)
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    let quick = Config { sleep_ms: 0 }.hash_serialized().unwrap();
+    let slow = Config { sleep_ms: 5000 }.hash_serialized().unwrap();
+    let entries = list_configurations(&results_dir).unwrap();
+    let states = entries
+        .iter()
+        .map(|entry| (entry.hash.clone(), entry.repeat, entry.state.to_string()))
+        .collect::<Vec<_>>();
+    let mut expected = vec![
+        (quick.clone(), 0, "completed".to_owned()),
+        (quick.clone(), 1, "completed".to_owned()),
+        (slow.clone(), 0, "failed".to_owned()),
+        (slow.clone(), 1, "failed".to_owned()),
+    ];
+    expected.sort();
+    assert_eq!(states, expected);
+    assert!(results_dir.join(&quick).is_dir());
+    assert!(results_dir.join(format!("{}-1", quick)).is_dir());
+    assert!(results_dir.join(format!("{}-1.failed", slow)).is_dir());
+
+    // completed repeats are kept, only new ones are run
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(3)
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+    assert!(results_dir.join(format!("{}-2", quick)).is_dir());
+}
+
+#[tokio::test]
+async fn cooldown_and_idle_wait() {
+    let results_dir = PathBuf::from("results/run_config-cooldown");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .timeout(Duration::from_millis(100))
+        .cooldown(Duration::from_millis(300))
+        .idle_wait(IdleWait {
+            // never idle, so waits until the timeout each time
+            max_load: Some(-1.0),
+            interval: Some(Duration::from_millis(10)),
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+    // two idle waits, one cooldown between the configurations and the timed out run
+    assert!(start.elapsed() >= Duration::from_millis(2 * 50 + 300 + 100));
+}
+
+#[tokio::test]
+async fn max_duration() {
+    let results_dir = PathBuf::from("results/run_config-max_duration");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(2)
+        .timeout(Duration::from_millis(100))
+        .cooldown(Duration::from_millis(300))
+        .max_duration(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    exp::run(&mut Exp, &run_config).await.unwrap();
+
+    // only the first run started, the cooldown after it used up the budget
+    assert_eq!(list_configurations(&results_dir).unwrap().len(), 1);
+    let skipped = read_events(&results_dir)
+        .unwrap()
+        .into_iter()
+        .filter(|record| {
+            matches!(
+                &record.event,
+                exp::RunEvent::ConfigurationSkipped { reason, .. }
+                    if reason == "time budget exhausted"
+            )
+        })
+        .count();
+    assert_eq!(skipped, 3);
+}
+
+#[tokio::test]
+async fn max_parallel() {
+    let results_dir = PathBuf::from("results/run_config-max_parallel");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .max_parallel(2)
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    exp::run(&mut Parallel, &run_config).await.unwrap();
+
+    // both configurations ran, alongside each other
+    assert_eq!(list_configurations(&results_dir).unwrap().len(), 2);
+    assert!(start.elapsed() < Duration::from_millis(600));
+}
@@ -0,0 +1,153 @@
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    read_events, AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    Measurements, RunConfig, RunError, RunEvent,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    name: String,
+    #[serde(default)]
+    priority: i64,
+    #[serde(default)]
+    depends_on: Vec<String>,
+}
+
+impl ExperimentConfiguration for Config {
+    const SKIP_HASH_FIELDS: &'static [&'static str] = &["priority", "depends_on"];
+
+    fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.depends_on.iter().map(|name| hash(name)).collect()
+    }
+}
+
+fn config(name: &str, priority: i64, depends_on: &[&str]) -> Config {
+    Config {
+        name: name.to_owned(),
+        priority,
+        depends_on: depends_on.iter().map(|d| (*d).to_owned()).collect(),
+    }
+}
+
+fn hash(name: &str) -> String {
+    config(name, 0, &[]).hash_serialized().unwrap()
+}
+
+struct Exp {
+    configurations: Vec<(&'static str, i64, Vec<&'static str>)>,
+    ran: Vec<String>,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        self.configurations
+            .iter()
+            .map(|(name, priority, depends_on)| config(name, *priority, depends_on))
+            .collect()
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        self.ran.push(configuration.name.clone());
+        if configuration.name.starts_with("broken") {
+            return Err("broken".into());
+        }
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[tokio::test]
+async fn dependencies_then_priority() {
+    let results_dir = PathBuf::from("results/schedule");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    let mut experiment = Exp {
+        configurations: vec![
+            ("low", -1, vec![]),
+            ("tuned", 10, vec!["baseline"]),
+            ("baseline", 0, vec![]),
+            ("urgent", 5, vec![]),
+            ("after-broken", 20, vec!["broken"]),
+            ("broken", 0, vec![]),
+        ],
+        ran: Vec::new(),
+    };
+    exp::run(&mut experiment, &run_config).await.unwrap();
+    assert_eq!(
+        experiment.ran,
+        vec!["urgent", "baseline", "tuned", "broken", "low"]
+    );
+    let skipped = read_events(&results_dir)
+        .unwrap()
+        .into_iter()
+        .filter_map(|record| match record.event {
+            RunEvent::ConfigurationSkipped { hash, reason } => Some((hash, reason)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        skipped,
+        vec![(
+            hash("after-broken"),
+            format!("dependency {} failed", hash("broken"))
+        )]
+    );
+}
+
+#[tokio::test]
+async fn dependency_cycle() {
+    let results_dir = PathBuf::from("results/schedule-cycle");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    let mut experiment = Exp {
+        configurations: vec![("a", 0, vec!["b"]), ("b", 0, vec!["a"]), ("c", 0, vec![])],
+        ran: Vec::new(),
+    };
+    match exp::run(&mut experiment, &run_config).await {
+        Err(RunError::DependencyCycle(hashes)) => {
+            assert_eq!(hashes, vec![hash("a"), hash("b")]);
+        }
+        other => panic!("expected a dependency cycle, got {:?}", other.err()),
+    }
+    assert!(experiment.ran.is_empty());
+}
@@ -0,0 +1,231 @@
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use exp::{
+    search::{
+        search, Goal, Parameter, SearchConfig, SearchError, SearchExperiment, SearchResult,
+        SearchSpace, Strategy,
+    },
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, Measurements,
+    Recorded, RunConfig, RunHooks,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    x: i64,
+    y: f64,
+    name: String,
+}
+
+impl ExperimentConfiguration for Config {}
+
+#[derive(Default)]
+struct Exp {
+    runs: usize,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        Vec::new()
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        configuration: &Self::Configuration,
+        _: &Path,
+        measurements: &Measurements,
+    ) -> ExpResult<()> {
+        self.runs += 1;
+        let loss = (configuration.x - 3).pow(2) as f64 + (configuration.y - 0.5).powi(2);
+        measurements.record_scalar("loss", loss);
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+impl SearchExperiment for Exp {
+    fn objective(&mut self, _: &Self::Configuration, configuration_dir: &Path) -> ExpResult<f64> {
+        let recorded = Recorded::from_configuration(configuration_dir)?;
+        Ok(recorded.scalars["loss"])
+    }
+}
+
+fn space() -> SearchSpace {
+    SearchSpace::new(json!({"x": 0, "y": 0.0, "name": "search"}))
+        .parameter("x", Parameter::Int { low: 0, high: 5 })
+}
+
+#[tokio::test]
+async fn grid_search() {
+    let results_dir = PathBuf::from("results/search-grid");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    let config = SearchConfig::new(
+        space().parameter("y", Parameter::Choice(vec![json!(0.0), json!(0.5)])),
+        Strategy::Grid,
+        100,
+    );
+    let mut experiment = Exp::default();
+    let result = search(&mut experiment, &run_config, &config).await.unwrap();
+    assert_eq!(result.trials.len(), 12);
+    assert_eq!(experiment.runs, 12);
+    let best = result.best().unwrap();
+    assert_eq!(
+        best.configuration,
+        json!({"x": 3, "y": 0.5, "name": "search"})
+    );
+    assert_eq!(best.objective, Some(0.0));
+    assert_eq!(SearchResult::from_dir(&results_dir).unwrap(), result);
+
+    // searching again reuses the completed runs
+    search(&mut experiment, &run_config, &config).await.unwrap();
+    assert_eq!(experiment.runs, 12);
+
+    let config = SearchConfig::new(
+        space().parameter(
+            "y",
+            Parameter::Float {
+                low: 0.0,
+                high: 1.0,
+                log: false,
+            },
+        ),
+        Strategy::Grid,
+        100,
+    );
+    assert!(matches!(
+        search(&mut experiment, &run_config, &config).await,
+        Err(SearchError::NotDiscrete(field)) if field == "y"
+    ));
+}
+
+#[tokio::test]
+async fn tpe_search() {
+    let results_dir = PathBuf::from("results/search-tpe");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .build()
+        .unwrap();
+    let space = space().parameter(
+        "y",
+        Parameter::Float {
+            low: 0.01,
+            high: 10.0,
+            log: true,
+        },
+    );
+    let config = SearchConfig::new(space, Strategy::Tpe { startup_trials: 5 }, 30);
+    let result = search(&mut Exp::default(), &run_config, &config)
+        .await
+        .unwrap();
+    assert_eq!(result.trials.len(), 30);
+    for trial in &result.trials {
+        let y = trial.configuration["y"].as_f64().unwrap();
+        assert!((0.01..=10.0).contains(&y));
+    }
+    let best = result.best().unwrap();
+    assert_eq!(best.configuration["x"], json!(3));
+    assert!(best.objective.unwrap() < 0.5);
+
+    // maximising finds the other end
+    let config = SearchConfig::new(
+        SearchSpace::new(json!({"x": 0, "y": 0.0, "name": "max"}))
+            .parameter("x", Parameter::Int { low: 0, high: 5 }),
+        Strategy::Random,
+        20,
+    )
+    .goal(Goal::Maximise);
+    let result = search(&mut Exp::default(), &run_config, &config)
+        .await
+        .unwrap();
+    assert_eq!(result.best().unwrap().configuration["x"], json!(0));
+}
+
+#[derive(Clone, Default)]
+struct Sessions(Arc<AtomicUsize>);
+
+#[async_trait]
+impl RunHooks for Sessions {
+    async fn before_all(&self, _: &Path) -> ExpResult<()> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn random_search_tries_configurations_once() {
+    let results_dir = PathBuf::from("results/search-random");
+    let _ = remove_dir_all(&results_dir);
+    let sessions = Sessions::default();
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .hook(sessions.clone())
+        .build()
+        .unwrap();
+    // more trials than there are configurations
+    let config = SearchConfig::new(space(), Strategy::Random, 10);
+    let mut experiment = Exp::default();
+    let result = search(&mut experiment, &run_config, &config).await.unwrap();
+    assert_eq!(result.trials.len(), 6);
+    assert_eq!(experiment.runs, 6);
+    assert_eq!(sessions.0.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn invalid_parameters() {
+    let run_config = RunConfig::builder()
+        .results_dir(PathBuf::from("results/search-invalid"))
+        .build()
+        .unwrap();
+    let config = SearchConfig::new(
+        space().parameter("y", Parameter::Choice(Vec::new())),
+        Strategy::Random,
+        10,
+    );
+    assert!(matches!(
+        search(&mut Exp::default(), &run_config, &config).await,
+        Err(SearchError::NoChoices(field)) if field == "y"
+    ));
+    let config = SearchConfig::new(
+        SearchSpace::new(json!({"x": 0, "y": 0.0, "name": "search"}))
+            .parameter("x", Parameter::Int { low: 5, high: 0 }),
+        Strategy::Grid,
+        10,
+    );
+    assert!(matches!(
+        search(&mut Exp::default(), &run_config, &config).await,
+        Err(SearchError::InvalidRange(field)) if field == "x"
+    ));
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashSet,
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, Measurements,
+    RunConfig, Seed,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+#[derive(Default)]
+struct Exp {
+    seeds: Vec<Seed>,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { n: 1 }, Config { n: 2 }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        _: &Self::Configuration,
+        configuration_dir: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        self.seeds
+            .push(Seed::from_configuration(configuration_dir)?);
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[test]
+fn derived_seeds() {
+    let seed = Seed::derive(7, "abc", 1);
+    assert_eq!(seed, Seed::derive(7, "abc", 1));
+    assert_eq!(seed.base_seed, 7);
+    assert_eq!(seed.repeat, 1);
+    assert_ne!(seed.seed, Seed::derive(8, "abc", 1).seed);
+    assert_ne!(seed.seed, Seed::derive(7, "abd", 1).seed);
+    assert_ne!(seed.seed, Seed::derive(7, "abc", 2).seed);
+}
+
+#[tokio::test]
+async fn seeds_per_repeat() {
+    let results_dir = PathBuf::from("results/seed");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .repeats(2)
+        .base_seed(42)
+        .build()
+        .unwrap();
+    let mut experiment = Exp::default();
+    exp::run(&mut experiment, &run_config).await.unwrap();
+
+    assert_eq!(experiment.seeds.len(), 4);
+    let unique = experiment
+        .seeds
+        .iter()
+        .map(|seed| seed.seed)
+        .collect::<HashSet<_>>();
+    assert_eq!(unique.len(), 4);
+
+    // recorded in each repeat's directory
+    let hash = Config { n: 2 }.hash_serialized().unwrap();
+    let recorded = Seed::from_configuration(&results_dir.join(format!("{}-1", hash))).unwrap();
+    assert_eq!(recorded, Seed::derive(42, &hash, 1));
+}
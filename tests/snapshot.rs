@@ -0,0 +1,45 @@
+use std::{
+    fs::{create_dir_all, remove_dir_all, write},
+    path::Path,
+};
+
+use exp::docker_runner::{export_archive_path, read_file_changes, FileChange, FileChangeKind};
+
+#[test]
+fn read_diff() {
+    let dir = std::env::temp_dir().join("exp-snapshot-test");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(dir.join("config")).unwrap();
+    write(
+        dir.join("config").join("docker-db-diff.json"),
+        r#"[{"path": "/var/lib/db", "kind": "modified"}, {"path": "/var/lib/db/wal", "kind": "added"}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        read_file_changes(&dir, "db").unwrap(),
+        vec![
+            FileChange {
+                path: "/var/lib/db".to_owned(),
+                kind: FileChangeKind::Modified,
+            },
+            FileChange {
+                path: "/var/lib/db/wal".to_owned(),
+                kind: FileChangeKind::Added,
+            },
+        ]
+    );
+    assert!(read_file_changes(&dir, "missing").is_err());
+}
+
+#[test]
+fn export_paths() {
+    let dir = Path::new("results/exp/abc");
+    assert_eq!(
+        export_archive_path(dir, "db", "/var/lib/db/"),
+        dir.join("exports").join("db").join("var_lib_db.tar")
+    );
+    assert_eq!(
+        export_archive_path(dir, "db", "/"),
+        dir.join("exports").join("db").join("root.tar")
+    );
+}
@@ -0,0 +1,63 @@
+use chrono::{TimeZone, Utc};
+use exp::ssh_runner::{clock_offset, ClockOffset, RemoteProcessConfig, SshHost};
+
+fn config(env: Vec<(&str, &str)>, working_dir: Option<&str>) -> RemoteProcessConfig {
+    RemoteProcessConfig {
+        name: "server".to_owned(),
+        host: SshHost {
+            name: "node1".to_owned(),
+            address: "user@node1".to_owned(),
+            port: None,
+            identity_file: None,
+        },
+        command: "./server --port 8080".to_owned(),
+        env: env
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect(),
+        working_dir: working_dir.map(str::to_owned),
+        monitor: false,
+        logs: Default::default(),
+    }
+}
+
+#[test]
+fn remote_script_quotes_env() {
+    assert_eq!(
+        config(Vec::new(), None).script(),
+        "echo $$; exec env sh -c './server --port 8080'"
+    );
+    assert_eq!(
+        config(
+            vec![("GREETING", "it's $HOME"), ("A B", "1; rm -rf /")],
+            Some("/srv/my app"),
+        )
+        .script(),
+        "cd '/srv/my app' || exit 1; echo $$; exec env 'GREETING=it'\\''s $HOME' 'A B=1; rm -rf /' \
+         sh -c './server --port 8080'"
+    );
+}
+
+#[test]
+fn clock_offset_of_shortest_round_trip() {
+    assert_eq!(clock_offset(&[]), None);
+    // the remote clock is 500ns ahead, the second exchange was delayed on the way back
+    let exchanges = [
+        (1_000, 1_550, 1_100),
+        (2_000, 2_550, 2_900),
+        (3_000, 3_530, 3_060),
+    ];
+    assert_eq!(clock_offset(&exchanges), Some((60, 500)));
+
+    let offset = ClockOffset {
+        host: "node1".to_owned(),
+        offset_nanos: 500,
+        round_trip_nanos: 60,
+        measured_at: Utc.timestamp_nanos(0),
+        chrony_offset_seconds: None,
+    };
+    assert_eq!(
+        offset.to_local(Utc.timestamp_nanos(3_530)),
+        Utc.timestamp_nanos(3_030)
+    );
+}
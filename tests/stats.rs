@@ -0,0 +1,95 @@
+use exp::stats::{
+    bootstrap_ci, bootstrap_difference_ci, mann_whitney, mean, summarise, summarise_series, t_test,
+    Summary,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Config {
+    nodes: u32,
+    image: Image,
+}
+
+#[derive(Serialize)]
+struct Image {
+    tag: &'static str,
+}
+
+fn config(nodes: u32) -> Config {
+    Config {
+        nodes,
+        image: Image { tag: "v1" },
+    }
+}
+
+#[test]
+fn summary_statistics() {
+    let summary = Summary::of(&[4.0, 2.0, 6.0, f64::NAN]).unwrap();
+    assert_eq!(summary.count, 3);
+    assert_eq!(summary.mean, 4.0);
+    assert_eq!(summary.median, 4.0);
+    assert_eq!(summary.stddev, 2.0);
+    assert_eq!((summary.min, summary.max), (2.0, 6.0));
+    // t = 4.303 for 2 degrees of freedom
+    let margin = 4.303 * 2.0 / 3f64.sqrt();
+    assert!((summary.ci_high - (4.0 + margin)).abs() < 1e-9);
+    assert!((summary.ci_low - (4.0 - margin)).abs() < 1e-9);
+
+    assert_eq!(Summary::of(&[1.0, 3.0]).unwrap().median, 2.0);
+    assert!(Summary::of(&[]).is_none());
+}
+
+#[test]
+fn summarise_by_configuration() {
+    let rows = summarise(vec![(config(3), 10.0), (config(5), 1.0), (config(3), 20.0)]).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].configuration["nodes"], 3);
+    assert_eq!(rows[0].configuration["image.tag"], "v1");
+    assert_eq!(rows[0].summary.mean, 15.0);
+    assert_eq!(rows[1].summary.count, 1);
+
+    let rows = summarise_series(vec![
+        (config(3), vec![1.0, 2.0, 3.0]),
+        (config(3), vec![3.0, 4.0]),
+    ])
+    .unwrap();
+    let means = rows
+        .iter()
+        .map(|row| (row.index, row.summary.mean))
+        .collect::<Vec<_>>();
+    assert_eq!(means, vec![(Some(0), 2.0), (Some(1), 3.0), (Some(2), 3.0)]);
+}
+
+#[test]
+fn significance_tests() {
+    let a = [1.0, 2.0, 3.0, 4.0, 5.0];
+    let b = [2.0, 4.0, 6.0, 8.0, 10.0];
+    let t = t_test(&a, &b).unwrap();
+    assert!((t.statistic - -1.8974).abs() < 1e-4);
+    assert!((t.p_value - 0.1075).abs() < 1e-4);
+    assert!(!t.is_significant(0.05));
+    assert!(t_test(&a, &[1.0]).is_none());
+
+    let u = mann_whitney(&a, &[6.0, 7.0, 8.0, 9.0, 10.0]).unwrap();
+    assert_eq!(u.statistic, 0.0);
+    assert!((u.p_value - 0.0122).abs() < 1e-4);
+    assert!(u.is_significant(0.05));
+    // all tied
+    assert_eq!(mann_whitney(&[1.0, 1.0], &[1.0]).unwrap().p_value, 1.0);
+}
+
+#[test]
+fn bootstrap_intervals() {
+    let a = (0..50).map(|i| 10.0 + (i % 5) as f64).collect::<Vec<_>>();
+    let b = a.iter().map(|m| m + 3.0).collect::<Vec<_>>();
+    let interval = bootstrap_ci(&a, mean, 0.95, 1000).unwrap();
+    assert!(interval.contains(mean(&a)));
+    assert!(interval.low > 11.0 && interval.high < 13.0);
+    // seeded so repeatable
+    assert_eq!(bootstrap_ci(&a, mean, 0.95, 1000), Some(interval));
+
+    let difference = bootstrap_difference_ci(&a, &b, mean, 0.95, 1000).unwrap();
+    assert!(difference.contains(3.0));
+    assert!(!difference.contains(0.0));
+    assert!(bootstrap_ci(&[], mean, 0.95, 1000).is_none());
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs::{read_dir, remove_dir_all, symlink_metadata},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    read_events, AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    Measurements, RunConfig, RunEvent,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+#[derive(Default)]
+struct Exp {
+    configurations: Vec<u32>,
+    runs: usize,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        self.configurations.iter().map(|&n| Config { n }).collect()
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(
+        &mut self,
+        _: &Self::Configuration,
+        configuration_dir: &Path,
+        _: &Measurements,
+    ) -> ExpResult<()> {
+        self.runs += 1;
+        std::fs::write(configuration_dir.join("output"), "done")?;
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+fn run_config(results_dir: &str, force_rerun: bool) -> RunConfig {
+    RunConfig::builder()
+        .results_dir(PathBuf::from("results/store-test").join(results_dir))
+        .store_dir("results/store-test/store")
+        .force_rerun(force_rerun)
+        .build()
+        .unwrap()
+}
+
+fn is_link(path: &Path) -> bool {
+    symlink_metadata(path).unwrap().file_type().is_symlink()
+}
+
+#[tokio::test]
+async fn runs_are_linked_from_the_store() {
+    let _ = remove_dir_all("results/store-test");
+    let hash = |n| Config { n }.hash_serialized().unwrap();
+
+    let mut experiment = Exp {
+        configurations: vec![1],
+        runs: 0,
+    };
+    exp::run(&mut experiment, &run_config("a", false))
+        .await
+        .unwrap();
+    assert_eq!(experiment.runs, 1);
+    let run = Path::new("results/store-test/a").join(hash(1));
+    assert!(is_link(&run));
+    assert!(run.join("output").is_file());
+
+    // a hit for the stored configuration, a miss for the new one
+    let mut experiment = Exp {
+        configurations: vec![1, 2],
+        runs: 0,
+    };
+    exp::run(&mut experiment, &run_config("b", false))
+        .await
+        .unwrap();
+    assert_eq!(experiment.runs, 1);
+    let linked = Path::new("results/store-test/b").join(hash(1));
+    assert!(is_link(&linked));
+    assert!(linked.join("output").is_file());
+    assert!(Path::new("results/store-test/store").join(hash(2)).is_dir());
+    let events = read_events(Path::new("results/store-test/b")).unwrap();
+    assert!(events
+        .iter()
+        .any(|record| record.event == RunEvent::ConfigurationLinked { hash: hash(1) }));
+    assert!(!events
+        .iter()
+        .any(|record| matches!(record.event, RunEvent::ConfigurationSkipped { .. })));
+
+    // forced reruns replace what is stored
+    let mut experiment = Exp {
+        configurations: vec![1],
+        runs: 0,
+    };
+    exp::run(&mut experiment, &run_config("c", true))
+        .await
+        .unwrap();
+    assert_eq!(experiment.runs, 1);
+
+    // no locks are left behind
+    let locks = read_dir("results/store-test/store")
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "lock")
+        })
+        .count();
+    assert_eq!(locks, 0);
+}
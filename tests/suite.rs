@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, DynExperiment, Environment, ExpResult, Experiment, ExperimentConfiguration,
+    ExperimentMetadata, Measurements, SuiteError, SuiteManifest,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    n: u32,
+}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp {
+    name: &'static str,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn metadata(&self) -> ExperimentMetadata {
+        ExperimentMetadata {
+            name: self.name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config { n: 1 }]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(&mut self, _: &Self::Configuration, _: &Path, _: &Measurements) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+fn suite_config(results_dir: PathBuf) -> exp::SuiteConfig {
+    exp::SuiteConfig {
+        run: exp::RunConfig::builder()
+            .results_dir(results_dir)
+            .build()
+            .unwrap(),
+        keep_going: false,
+    }
+}
+
+#[tokio::test]
+async fn run_experiments_as_suite() {
+    let results_dir = std::env::temp_dir().join("exp-suite-test");
+    let _ = std::fs::remove_dir_all(&results_dir);
+    let config = suite_config(results_dir.clone());
+    let experiments: Vec<Box<dyn DynExperiment>> =
+        vec![Box::new(Exp { name: "a" }), Box::new(Exp { name: "b" })];
+    exp::run_suite(experiments, &config).await.unwrap();
+
+    let hash = Config { n: 1 }.hash_serialized().unwrap();
+    let manifest = SuiteManifest::from_dir(&results_dir).unwrap();
+    let names = manifest
+        .experiments
+        .iter()
+        .map(|e| e.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b"]);
+    for entry in &manifest.experiments {
+        assert!(entry.finished.is_some());
+        assert!(entry.error.is_none());
+        assert!(results_dir.join(&entry.dir).join(&hash).exists());
+    }
+
+    let experiments: Vec<Box<dyn DynExperiment>> =
+        vec![Box::new(Exp { name: "a" }), Box::new(Exp { name: "a" })];
+    match exp::run_suite(experiments, &config).await {
+        Err(SuiteError::DuplicateName(name)) => assert_eq!(name, "a"),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, write},
+    path::PathBuf,
+};
+
+use exp::sync::{pull, push, Manifest, Remote, SyncError, MANIFEST_FILE};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join("exp-sync-test").join(name);
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn push_and_pull_between_machines() {
+    let remote_dir = dir("remote");
+    let remote = Remote::Local {
+        path: remote_dir.clone(),
+    };
+
+    let a = dir("a");
+    create_dir_all(a.join("exp").join("abc-1")).unwrap();
+    write(a.join("exp").join("abc-1").join("out.csv"), "a,b\n1,2\n").unwrap();
+    create_dir_all(a.join("exp").join("def-1.running")).unwrap();
+    write(a.join("exp").join("def-1.running").join("out.csv"), "").unwrap();
+    let summary = push(&a, &remote).await.unwrap();
+    assert_eq!(summary.transferred, 1);
+    assert!(!remote_dir.join("exp").join("def-1.running").exists());
+
+    let summary = push(&a, &remote).await.unwrap();
+    assert_eq!(summary.transferred, 0);
+    assert_eq!(summary.unchanged, 1);
+
+    // another machine pushes other results, keeping those of the first
+    let b = dir("b");
+    create_dir_all(b.join("exp").join("ghi-1")).unwrap();
+    write(b.join("exp").join("ghi-1").join("out.csv"), "a,b\n3,4\n").unwrap();
+    push(&b, &remote).await.unwrap();
+    let manifest: Manifest =
+        serde_json::from_str(&read_to_string(remote_dir.join(MANIFEST_FILE)).unwrap()).unwrap();
+    assert_eq!(
+        manifest.keys().collect::<Vec<_>>(),
+        ["exp/abc-1/out.csv", "exp/ghi-1/out.csv"]
+    );
+
+    let summary = pull(&a, &remote).await.unwrap();
+    assert_eq!(summary.transferred, 1);
+    assert_eq!(summary.unchanged, 1);
+    assert_eq!(
+        read_to_string(a.join("exp").join("ghi-1").join("out.csv")).unwrap(),
+        "a,b\n3,4\n"
+    );
+
+    // a pull into an empty directory fetches everything
+    let c = dir("c");
+    let summary = pull(&c, &remote).await.unwrap();
+    assert_eq!(summary.transferred, 2);
+
+    // changed remote files fail the integrity check
+    write(remote_dir.join("exp").join("abc-1").join("out.csv"), "tampered").unwrap();
+    let d = dir("d");
+    assert!(matches!(
+        pull(&d, &remote).await,
+        Err(SyncError::IntegrityError { .. })
+    ));
+}
+
+#[tokio::test]
+async fn unreadable_remote_manifest_is_an_error() {
+    let remote_dir = dir("broken-remote");
+    write(remote_dir.join(MANIFEST_FILE), "not json").unwrap();
+    let remote = Remote::Local { path: remote_dir };
+    let local = dir("broken-local");
+    write(local.join("out.csv"), "a\n").unwrap();
+    assert!(push(&local, &remote).await.is_err());
+
+    // an empty remote is fine
+    let remote = Remote::Local {
+        path: dir("empty-remote"),
+    };
+    let summary = pull(&dir("empty-local"), &remote).await.unwrap();
+    assert_eq!(summary.transferred, 0);
+}
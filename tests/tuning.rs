@@ -0,0 +1,139 @@
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, write, File},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use exp::{
+    AnalysisDirs, Environment, ExpResult, Experiment, ExperimentConfiguration, HostTuning,
+    Measurements, RunConfig, RunError, TuningSetting,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {}
+
+impl ExperimentConfiguration for Config {}
+
+struct Exp {
+    sysfs: PathBuf,
+    seen: Vec<String>,
+}
+
+#[async_trait]
+impl Experiment for Exp {
+    type Configuration = Config;
+
+    fn configurations(&mut self) -> Vec<Self::Configuration> {
+        vec![Config {}]
+    }
+
+    async fn pre_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    async fn run(&mut self, _: &Self::Configuration, _: &Path, _: &Measurements) -> ExpResult<()> {
+        self.seen = read_settings(&self.sysfs);
+        Ok(())
+    }
+
+    async fn post_run(&mut self, _: &Self::Configuration) -> ExpResult<()> {
+        Ok(())
+    }
+
+    fn analyse(
+        &mut self,
+        _: &AnalysisDirs,
+        _: Environment,
+        _: Vec<(Self::Configuration, PathBuf)>,
+    ) -> ExpResult<serde_json::Value> {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+const FILES: [&str; 4] = [
+    "devices/system/cpu/cpu0/cpufreq/scaling_governor",
+    "devices/system/cpu/cpu1/cpufreq/scaling_governor",
+    "devices/system/cpu/intel_pstate/no_turbo",
+    "devices/system/cpu/smt/control",
+];
+
+fn read_settings(sysfs: &Path) -> Vec<String> {
+    FILES
+        .iter()
+        .map(|file| read_to_string(sysfs.join(file)).unwrap().trim().to_owned())
+        .collect()
+}
+
+fn fake_sysfs(sysfs: &Path) {
+    let _ = remove_dir_all(sysfs);
+    for (file, value) in FILES.iter().zip(["powersave", "powersave", "0", "on"]) {
+        let path = sysfs.join(file);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(path, format!("{}\n", value)).unwrap();
+    }
+    // not a cpu
+    create_dir_all(sysfs.join("devices/system/cpu/cpufreq")).unwrap();
+}
+
+#[tokio::test]
+async fn tuning_applied_and_restored() {
+    let sysfs = PathBuf::from("results/tuning-sysfs");
+    fake_sysfs(&sysfs);
+    let results_dir = PathBuf::from("results/tuning");
+    let _ = remove_dir_all(&results_dir);
+    let run_config = RunConfig::builder()
+        .results_dir(results_dir.clone())
+        .tuning(HostTuning {
+            governor: Some("performance".to_owned()),
+            disable_turbo: true,
+            disable_smt: true,
+            sysfs: Some(sysfs.clone()),
+        })
+        .build()
+        .unwrap();
+    let mut experiment = Exp {
+        sysfs: sysfs.clone(),
+        seen: Vec::new(),
+    };
+    exp::run(&mut experiment, &run_config).await.unwrap();
+
+    assert_eq!(experiment.seen, ["performance", "performance", "1", "off"]);
+    assert_eq!(read_settings(&sysfs), ["powersave", "powersave", "0", "on"]);
+
+    let environment: serde_json::Value =
+        serde_json::from_reader(File::open(results_dir.join("environment.json")).unwrap()).unwrap();
+    let tuning: Vec<TuningSetting> = serde_json::from_value(environment["tuning"].clone()).unwrap();
+    assert_eq!(tuning.len(), 4);
+    assert_eq!(tuning[3].path, sysfs.join(FILES[3]));
+    assert_eq!(tuning[3].previous, "on");
+    assert_eq!(tuning[3].applied, "off");
+}
+
+#[tokio::test]
+async fn tuning_failure_restores() {
+    let sysfs = PathBuf::from("results/tuning-sysfs-partial");
+    fake_sysfs(&sysfs);
+    // no way to turn off smt
+    remove_dir_all(sysfs.join("devices/system/cpu/smt")).unwrap();
+    let run_config = RunConfig::builder()
+        .results_dir("results/tuning-partial")
+        .tuning(HostTuning {
+            governor: Some("performance".to_owned()),
+            disable_smt: true,
+            sysfs: Some(sysfs.clone()),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let mut experiment = Exp {
+        sysfs: sysfs.clone(),
+        seen: Vec::new(),
+    };
+    let result = exp::run(&mut experiment, &run_config).await;
+    assert!(matches!(result, Err(RunError::Tuning(..))), "{:?}", result);
+    assert_eq!(
+        read_to_string(sysfs.join(FILES[0])).unwrap().trim(),
+        "powersave"
+    );
+}
@@ -0,0 +1,153 @@
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+use exp::{
+    workload_metrics, WorkloadExtractor, WorkloadFormat, WorkloadMetric, WorkloadOutput,
+    WorkloadValue,
+};
+
+fn get(values: &[WorkloadValue], operation: Option<&str>, metric: &str) -> f64 {
+    values
+        .iter()
+        .find(|value| value.operation.as_deref() == operation && value.metric == metric)
+        .unwrap_or_else(|| panic!("missing {:?} {}", operation, metric))
+        .value
+}
+
+#[test]
+fn parse_ycsb() {
+    let output = "Loading workload...\n\
+                  2022-06-01 12:00:10:000 10 sec: 1000 operations; 100 current ops/sec;\n\
+                  [OVERALL], RunTime(ms), 10000\n\
+                  [OVERALL], Throughput(ops/sec), 1234.5\n\
+                  [READ], Operations, 500\n\
+                  [READ], AverageLatency(us), 250.0\n\
+                  [READ], MinLatency(us), 100\n\
+                  [READ], MaxLatency(us), 9000\n\
+                  [READ], 95thPercentileLatency(us), 400\n\
+                  [READ], 99thPercentileLatency(us), 800\n\
+                  [READ], Return=OK, 500\n";
+    let values = WorkloadFormat::Ycsb.parse(output).unwrap();
+    assert_eq!(values.len(), 8);
+    assert_eq!(get(&values, None, "throughput_ops_per_second"), 1234.5);
+    assert_eq!(get(&values, None, "runtime_seconds"), 10.0);
+    assert_eq!(get(&values, Some("READ"), "operations"), 500.0);
+    assert_eq!(get(&values, Some("READ"), "latency_mean_ms"), 0.25);
+    assert_eq!(get(&values, Some("READ"), "latency_max_ms"), 9.0);
+    assert_eq!(get(&values, Some("READ"), "latency_p99_ms"), 0.8);
+}
+
+#[test]
+fn parse_sysbench() {
+    let output = "SQL statistics:\n\
+                  \x20   queries performed:\n\
+                  \x20       read:                            140000\n\
+                  \x20   transactions:                        10000  (333.28 per sec.)\n\
+                  \x20   queries:                             200000 (6665.58 per sec.)\n\
+                  \x20   ignored errors:                      2      (0.07 per sec.)\n\
+                  \n\
+                  General statistics:\n\
+                  \x20   total time:                          30.0025s\n\
+                  \x20   total number of events:              10000\n\
+                  \n\
+                  Latency (ms):\n\
+                  \x20        min:                                    2.13\n\
+                  \x20        avg:                                   29.99\n\
+                  \x20        max:                                  102.11\n\
+                  \x20        95th percentile:                       44.17\n\
+                  \x20        sum:                               299936.33\n";
+    let values = WorkloadFormat::Sysbench.parse(output).unwrap();
+    assert_eq!(values.len(), 9);
+    assert_eq!(get(&values, None, "throughput_ops_per_second"), 333.28);
+    assert_eq!(
+        get(&values, Some("queries"), "throughput_ops_per_second"),
+        6665.58
+    );
+    assert_eq!(get(&values, None, "errors"), 2.0);
+    assert_eq!(get(&values, None, "operations"), 10000.0);
+    assert_eq!(get(&values, None, "runtime_seconds"), 30.0025);
+    assert_eq!(get(&values, None, "latency_mean_ms"), 29.99);
+    assert_eq!(get(&values, None, "latency_p95_ms"), 44.17);
+}
+
+#[test]
+fn parse_wrk() {
+    let output = "Running 1m test @ http://server:8080/\n\
+                  \x20 2 threads and 100 connections\n\
+                  \x20 Thread Stats   Avg      Stdev     Max   +/- Stdev\n\
+                  \x20   Latency   635.91us    0.89ms  12.92ms   93.69%\n\
+                  \x20   Req/Sec    56.20k     8.07k   62.00k    86.54%\n\
+                  \x20 Latency Distribution (HdrHistogram - Recorded Latency)\n\
+                  \x2050.000%  250.00us\n\
+                  \x2099.900%    5.80ms\n\
+                  \n\
+                  \x20 Latency Distribution (HdrHistogram - Uncorrected Latency)\n\
+                  \x2050.000%  200.00us\n\
+                  \x20 22464657 requests in 1.00m, 17.76GB read\n\
+                  \x20 Non-2xx or 3xx responses: 12\n\
+                  Requests/sec: 373810.90\n\
+                  Transfer/sec:    302.60MB\n";
+    let values = WorkloadFormat::Wrk.parse(output).unwrap();
+    assert_eq!(values.len(), 9);
+    assert_eq!(get(&values, None, "latency_mean_ms"), 0.63591);
+    assert_eq!(get(&values, None, "latency_max_ms"), 12.92);
+    assert_eq!(get(&values, None, "latency_p50_ms"), 0.25);
+    assert_eq!(get(&values, None, "latency_p99_9_ms"), 5.8);
+    assert_eq!(get(&values, None, "operations"), 22464657.0);
+    assert_eq!(get(&values, None, "runtime_seconds"), 60.0);
+    assert_eq!(get(&values, None, "errors"), 12.0);
+    assert_eq!(get(&values, None, "throughput_ops_per_second"), 373810.9);
+    assert_eq!(
+        get(&values, None, "throughput_bytes_per_second"),
+        302.6 * 1024.0 * 1024.0
+    );
+}
+
+#[test]
+fn extract_fio() {
+    let dir = std::env::temp_dir().join("exp-workload-test").join("abc-1");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(dir.join("logs")).unwrap();
+    write(
+        dir.join("logs").join("docker-fio.log"),
+        "2022-06-01T12:00:00.000000000Z note: both iodepth >= 1 and synchronous I/O engine\n\
+         2022-06-01T12:00:30.000000000Z {\n\
+         2022-06-01T12:00:30.000000000Z   \"jobs\" : [{\"jobname\" : \"randread\",\n\
+         2022-06-01T12:00:30.000000000Z     \"read\" : {\"io_bytes\" : 4096000, \"bw_bytes\" : 136533, \"iops\" : 33.3, \"total_ios\" : 1000,\n\
+         2022-06-01T12:00:30.000000000Z       \"clat_ns\" : {\"percentile\" : {\"50.000000\" : 1000000, \"99.900000\" : 9000000}},\n\
+         2022-06-01T12:00:30.000000000Z       \"lat_ns\" : {\"min\" : 500000, \"max\" : 10000000, \"mean\" : 2000000.0}},\n\
+         2022-06-01T12:00:30.000000000Z     \"write\" : {\"io_bytes\" : 0}}]\n\
+         2022-06-01T12:00:30.000000000Z }\n",
+    )
+    .unwrap();
+
+    let extractor = WorkloadExtractor {
+        outputs: vec![WorkloadOutput {
+            container: "fio".to_owned(),
+            format: WorkloadFormat::Fio,
+        }],
+    };
+    let written = extractor.extract(&dir).unwrap();
+    assert_eq!(written, vec![dir.join("metrics").join("workload-fio.csv")]);
+
+    let metrics = WorkloadMetric::from_configuration(&dir).unwrap();
+    assert_eq!(metrics.len(), 8);
+    assert!(metrics
+        .iter()
+        .all(|metric| metric.configuration == "abc" && metric.repeat == 1));
+    assert!(metrics
+        .iter()
+        .all(|metric| metric.operation.as_deref() == Some("read")));
+
+    let metrics = workload_metrics(&dir).unwrap();
+    assert_eq!(metrics["fio.read.throughput_ops_per_second"], vec![33.3]);
+    assert_eq!(metrics["fio.read.latency_mean_ms"], vec![2.0]);
+    assert_eq!(metrics["fio.read.latency_p99_9_ms"], vec![9.0]);
+
+    let extractor = WorkloadExtractor {
+        outputs: vec![WorkloadOutput {
+            container: "missing".to_owned(),
+            format: WorkloadFormat::Ycsb,
+        }],
+    };
+    assert!(extractor.extract(&dir).is_err());
+}